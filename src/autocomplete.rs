@@ -1,7 +1,54 @@
 use std::collections::HashSet;
 
+/// Everyday words that say little about what a note is about: articles, pronouns,
+/// common verbs, adjectives, prepositions and adverbs. Lowercase.
+pub const STOPWORDS: &[&str] = &[
+    // Articles & Pronouns
+    "the", "a", "an", "this", "that", "these", "those",
+    "i", "you", "he", "she", "it", "we", "they", "me", "him", "her", "us", "them",
+    "my", "your", "his", "her", "its", "our", "their",
+    
+    // Common Verbs
+    "is", "are", "was", "were", "be", "been", "being",
+    "have", "has", "had", "do", "does", "did", "done",
+    "can", "could", "will", "would", "shall", "should", "may", "might", "must",
+    "make", "made", "making", "get", "got", "getting", "give", "gave", "given",
+    "take", "took", "taken", "come", "came", "coming", "go", "went", "gone",
+    "see", "saw", "seen", "know", "knew", "known", "think", "thought",
+    "want", "wanted", "need", "needed", "use", "used", "using",
+    "work", "worked", "working", "find", "found", "tell", "told",
+    "become", "became", "feel", "felt", "try", "tried", "leave", "left",
+    "call", "called", "ask", "asked", "keep", "kept", "show", "showed",
+    "write", "wrote", "written", "read", "reading", "create", "created",
+    
+    // Common Adjectives
+    "good", "better", "best", "new", "newer", "newest", "first", "last",
+    "long", "longer", "longest", "great", "greater", "greatest",
+    "little", "less", "least", "own", "other", "others", "old", "older",
+    "right", "big", "bigger", "biggest", "high", "higher", "highest",
+    "different", "small", "smaller", "smallest", "large", "larger", "largest",
+    "next", "early", "earlier", "earliest", "young", "younger", "youngest",
+    "important", "few", "fewer", "public", "bad", "worse", "worst",
+    "same", "able", "recent", "current", "previous", "possible",
+    
+    // Prepositions & Conjunctions
+    "of", "to", "in", "for", "on", "with", "at", "by", "from", "up", "about",
+    "into", "through", "during", "before", "after", "above", "below",
+    "between", "under", "since", "without", "and", "but", "or", "if",
+    "because", "as", "until", "while", "so", "than", "when", "where",
+    
+    // Common Adverbs
+    "not", "only", "just", "also", "very", "even", "back", "there", "down",
+    "still", "now", "then", "here", "well", "out", "up", "over", "again",
+    "more", "most", "never", "always", "often", "sometimes", "usually",
+    "really", "actually", "probably", "perhaps", "maybe", "however",
+    "therefore", "furthermore", "moreover", "nevertheless",
+];
+
 pub struct Autocomplete {
+    #[allow(dead_code)]
     words: HashSet<String>,
+    #[allow(dead_code)]
     enabled: bool,
 }
 
@@ -9,26 +56,8 @@ impl Autocomplete {
     pub fn new() -> Self {
         let mut words = HashSet::new();
         
-        // Common English words for autocomplete
+        // Common English words for autocomplete, on top of the stopwords
         let common_words = vec![
-            // Articles & Pronouns
-            "the", "a", "an", "this", "that", "these", "those",
-            "I", "you", "he", "she", "it", "we", "they", "me", "him", "her", "us", "them",
-            "my", "your", "his", "her", "its", "our", "their",
-            
-            // Common Verbs
-            "is", "are", "was", "were", "be", "been", "being",
-            "have", "has", "had", "do", "does", "did", "done",
-            "can", "could", "will", "would", "shall", "should", "may", "might", "must",
-            "make", "made", "making", "get", "got", "getting", "give", "gave", "given",
-            "take", "took", "taken", "come", "came", "coming", "go", "went", "gone",
-            "see", "saw", "seen", "know", "knew", "known", "think", "thought",
-            "want", "wanted", "need", "needed", "use", "used", "using",
-            "work", "worked", "working", "find", "found", "tell", "told",
-            "become", "became", "feel", "felt", "try", "tried", "leave", "left",
-            "call", "called", "ask", "asked", "keep", "kept", "show", "showed",
-            "write", "wrote", "written", "read", "reading", "create", "created",
-            
             // Common Nouns
             "time", "person", "people", "year", "years", "way", "ways", "day", "days",
             "thing", "things", "man", "men", "woman", "women", "child", "children",
@@ -41,29 +70,6 @@ impl Autocomplete {
             "project", "projects", "task", "tasks", "meeting", "meetings",
             "idea", "ideas", "plan", "plans", "goal", "goals",
             
-            // Common Adjectives
-            "good", "better", "best", "new", "newer", "newest", "first", "last",
-            "long", "longer", "longest", "great", "greater", "greatest",
-            "little", "less", "least", "own", "other", "others", "old", "older",
-            "right", "big", "bigger", "biggest", "high", "higher", "highest",
-            "different", "small", "smaller", "smallest", "large", "larger", "largest",
-            "next", "early", "earlier", "earliest", "young", "younger", "youngest",
-            "important", "few", "fewer", "public", "bad", "worse", "worst",
-            "same", "able", "recent", "current", "previous", "possible",
-            
-            // Prepositions & Conjunctions
-            "of", "to", "in", "for", "on", "with", "at", "by", "from", "up", "about",
-            "into", "through", "during", "before", "after", "above", "below",
-            "between", "under", "since", "without", "and", "but", "or", "if",
-            "because", "as", "until", "while", "so", "than", "when", "where",
-            
-            // Common Adverbs
-            "not", "only", "just", "also", "very", "even", "back", "there", "down",
-            "still", "now", "then", "here", "well", "out", "up", "over", "again",
-            "more", "most", "never", "always", "often", "sometimes", "usually",
-            "really", "actually", "probably", "perhaps", "maybe", "however",
-            "therefore", "furthermore", "moreover", "nevertheless",
-            
             // Business & Work
             "project", "management", "business", "market", "product", "service",
             "customer", "client", "team", "manager", "employee", "department",
@@ -89,7 +95,7 @@ impl Autocomplete {
             "August", "September", "October", "November", "December",
         ];
         
-        for word in common_words {
+        for word in STOPWORDS.iter().chain(&common_words) {
             words.insert(word.to_string());
         }
         
@@ -99,16 +105,19 @@ impl Autocomplete {
         }
     }
     
+    #[allow(dead_code)]
     pub fn add_word(&mut self, word: String) {
         self.words.insert(word.to_lowercase());
     }
     
+    #[allow(dead_code)]
     pub fn add_words(&mut self, words: Vec<String>) {
         for word in words {
             self.add_word(word);
         }
     }
     
+    #[allow(dead_code)]
     pub fn get_suggestions(&self, prefix: &str) -> Vec<String> {
         if !self.enabled || prefix.is_empty() || prefix.len() < 2 {
             return Vec::new();
@@ -126,14 +135,17 @@ impl Autocomplete {
         suggestions
     }
     
+    #[allow(dead_code)]
     pub fn toggle(&mut self) {
         self.enabled = !self.enabled;
     }
     
+    #[allow(dead_code)]
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
     
+    #[allow(dead_code)]
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
     }
@@ -0,0 +1,180 @@
+use crate::docx_export::DocxExporter;
+use crate::export::Exporter;
+use crate::html_export::HtmlExporter;
+use crate::pdf_export::PdfExporter;
+use crate::search::{has_property, FuzzySearch, SearchQuery};
+use crate::storage::Storage;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+
+/// Command-line arguments. Without a command, the app opens its window as usual.
+#[derive(Debug, Parser)]
+#[command(
+    name = "notetaking",
+    about = "Markdown notes, with a headless mode for scripts"
+)]
+pub struct Cli {
+    /// Vault to use instead of the one open in the app.
+    #[arg(long, global = true)]
+    pub vault: Option<PathBuf>,
+    /// Open the app without letting anything in the vault be created, edited or deleted.
+    #[arg(long)]
+    pub read_only: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Export one note, found by title.
+    Export {
+        #[arg(long)]
+        note: String,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Pdf)]
+        format: ExportFormat,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Print the titles of notes matching a query, best first.
+    Search {
+        query: String,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    Pdf,
+    Docx,
+    Html,
+}
+
+/// Run `command` against the vault at `vault`, returning the lines to print.
+pub fn run(command: &Command, vault: &Path) -> Result<Vec<String>, String> {
+    // Storage creates a missing vault, which a typo shouldn't do
+    if !vault.is_dir() {
+        return Err(format!("No vault at {}", vault.display()));
+    }
+    let mut storage = Storage::new(vault.to_string_lossy().to_string());
+    storage.ensure_all_loaded();
+
+    match command {
+        Command::Export { note, format, out } => {
+            export_note(&storage, note, *format, out)?;
+            Ok(vec![format!("Exported \"{}\" to {}", note, out.display())])
+        }
+        Command::Search { query, limit } => Ok(search_titles(&storage, query, *limit)),
+    }
+}
+
+fn export_note(
+    storage: &Storage,
+    title: &str,
+    format: ExportFormat,
+    out: &Path,
+) -> Result<(), String> {
+    let note = storage
+        .folders
+        .iter()
+        .flat_map(|f| f.notes.iter())
+        .find(|n| n.title.eq_ignore_ascii_case(title))
+        .ok_or_else(|| format!("No note titled \"{}\"", title))?;
+
+    let exporter: &dyn Exporter = match format {
+        ExportFormat::Pdf => &PdfExporter,
+        ExportFormat::Docx => &DocxExporter,
+        ExportFormat::Html => &HtmlExporter,
+    };
+    exporter.export(note, out).map_err(|e| e.to_string())
+}
+
+fn search_titles(storage: &Storage, query: &str, limit: Option<usize>) -> Vec<String> {
+    let mut search = FuzzySearch::new();
+    search.set_max_results(limit);
+    let query = SearchQuery::parse_with(query, |key| has_property(&storage.folders, key));
+    search
+        .search_query(&storage.folders, &query)
+        .hits
+        .into_iter()
+        .map(|(folder_idx, note_idx)| storage.folders[folder_idx].notes[note_idx].title.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        storage.create_folder("Work").unwrap();
+        storage
+            .create_note(0, "Quarterly Report", "# Report\n\nRevenue is up.\n")
+            .unwrap();
+        storage
+            .create_note(0, "Groceries", "Milk and eggs\n")
+            .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_search_command() {
+        let dir = vault();
+        let cli = Cli::try_parse_from(["notetaking", "search", "revenue"]).unwrap();
+        assert_eq!(
+            run(cli.command.as_ref().unwrap(), dir.path()).unwrap(),
+            vec!["Quarterly Report"]
+        );
+
+        let cli = Cli::try_parse_from(["notetaking", "search", "nothing like this"]).unwrap();
+        assert!(run(cli.command.as_ref().unwrap(), dir.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_export_command() {
+        let dir = vault();
+        let out = dir.path().join("report.html");
+        let cli = Cli::try_parse_from([
+            "notetaking",
+            "--vault",
+            &dir.path().to_string_lossy(),
+            "export",
+            "--note",
+            "quarterly report",
+            "--format",
+            "html",
+            "--out",
+            &out.to_string_lossy(),
+        ])
+        .unwrap();
+        assert_eq!(cli.vault.as_deref(), Some(dir.path()));
+
+        run(cli.command.as_ref().unwrap(), dir.path()).unwrap();
+        assert!(std::fs::read_to_string(&out)
+            .unwrap()
+            .contains("Revenue is up."));
+
+        let missing = Cli::try_parse_from([
+            "notetaking",
+            "export",
+            "--note",
+            "Nope",
+            "--out",
+            "nope.pdf",
+        ])
+        .unwrap();
+        assert!(run(missing.command.as_ref().unwrap(), dir.path()).is_err());
+        assert!(run(cli.command.as_ref().unwrap(), &dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_read_only_flag_opens_app() {
+        let cli = Cli::try_parse_from(["notetaking", "--read-only"]).unwrap();
+        assert!(cli.read_only);
+        assert!(cli.command.is_none());
+        assert!(!Cli::try_parse_from(["notetaking"]).unwrap().read_only);
+    }
+}
@@ -0,0 +1,415 @@
+use crate::export::DEFAULT_FILENAME_TEMPLATE;
+use crate::search::{SavedSearch, DEFAULT_TITLE_WEIGHT};
+use crate::spellcheck::DEFAULT_LANGUAGE;
+use crate::storage::{FilenamePolicy, LineEnding};
+use crate::version_control::CommitPolicy;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Most search hits the sidebar lists unless configured otherwise.
+pub const DEFAULT_SEARCH_RESULT_LIMIT: usize = 100;
+
+/// Folder that takes new notes when none is selected.
+pub const DEFAULT_INBOX_FOLDER: &str = "Inbox";
+
+/// Folder that holds the daily notes unless configured otherwise.
+pub const DEFAULT_JOURNAL_FOLDER: &str = "Journal";
+
+/// Backups kept unless configured otherwise.
+pub const DEFAULT_BACKUP_COUNT: usize = 5;
+
+/// Minutes between scheduled backups unless configured otherwise.
+pub const DEFAULT_BACKUP_INTERVAL_MINUTES: u64 = 30;
+
+/// Where notes lived before the location was configurable.
+pub const DEFAULT_VAULT_PATH: &str = "./notes_data";
+
+/// How much of the window must stay on the monitor for a saved position to be kept.
+const MIN_VISIBLE: f32 = 64.0;
+
+/// Window size and placement and sidebar layout, restored on the next launch.
+/// Sizes and positions are in egui points.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub size: [f32; 2],
+    /// Outer top-left corner, if the platform reported one.
+    pub position: Option<[f32; 2]>,
+    /// Size of the monitor the window was on when it was saved.
+    pub monitor_size: Option<[f32; 2]>,
+    pub sidebar_width: f32,
+    pub sidebar_open: bool,
+}
+
+impl Default for WindowGeometry {
+    fn default() -> Self {
+        Self {
+            size: [1200.0, 800.0],
+            position: None,
+            monitor_size: None,
+            sidebar_width: 220.0,
+            sidebar_open: true,
+        }
+    }
+}
+
+impl WindowGeometry {
+    /// Where to move a window restored at `position` so it isn't lost off-screen,
+    /// or `None` if it can stay. `monitor_size` is the monitor the window opened on,
+    /// or `None` if it isn't on any. If that monitor is the same size as the saved one,
+    /// the setup is taken to be unchanged and the position is trusted.
+    pub fn off_screen_correction(&self, monitor_size: Option<[f32; 2]>) -> Option<[f32; 2]> {
+        let [x, y] = self.position?;
+        let Some([monitor_w, monitor_h]) = monitor_size else {
+            return Some([0.0, 0.0]);
+        };
+        if self.monitor_size == monitor_size {
+            return None;
+        }
+
+        let visible = x + self.size[0] >= MIN_VISIBLE
+            && x <= monitor_w - MIN_VISIBLE
+            && y >= 0.0
+            && y <= monitor_h - MIN_VISIBLE;
+        if visible {
+            None
+        } else {
+            Some([
+                x.clamp(0.0, (monitor_w - self.size[0]).max(0.0)),
+                y.clamp(0.0, (monitor_h - self.size[1]).max(0.0)),
+            ])
+        }
+    }
+}
+
+/// Settings kept in `config.json` under the platform config directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppConfig {
+    /// The vault currently open.
+    pub vault_path: PathBuf,
+    /// Every vault offered in the switcher, in the order they were added.
+    #[serde(default)]
+    pub vaults: Vec<PathBuf>,
+    /// Open vaults for browsing only: nothing in them is created, edited or deleted.
+    #[serde(default)]
+    pub read_only: bool,
+    /// When saves are committed to version history.
+    #[serde(default)]
+    pub commit_policy: CommitPolicy,
+    /// Wrap long lines in the editor instead of scrolling sideways.
+    #[serde(default = "default_soft_wrap")]
+    pub soft_wrap: bool,
+    /// Show the rendered note beside the editor while editing.
+    #[serde(default)]
+    pub split_view: bool,
+    #[serde(default)]
+    pub window: WindowGeometry,
+    /// Most search hits listed in the sidebar.
+    #[serde(default = "default_search_result_limit")]
+    pub search_result_limit: usize,
+    /// How much title matches count against content matches in search ranking;
+    /// see `FuzzySearch::set_title_weight`.
+    #[serde(default = "default_title_weight")]
+    pub title_weight: f64,
+    /// Folder new notes go into when no folder is selected; created on first use.
+    #[serde(default = "default_inbox_folder")]
+    pub inbox_folder: String,
+    /// Folder today's daily note is opened or created in.
+    #[serde(default = "default_journal_folder")]
+    pub journal_folder: String,
+    /// Copy the vault to a timestamped backup directory on each launch.
+    #[serde(default)]
+    pub backup_on_startup: bool,
+    /// Also back the vault up every `backup_interval_minutes` while the app runs.
+    #[serde(default)]
+    pub scheduled_backups: bool,
+    #[serde(default = "default_backup_interval_minutes")]
+    pub backup_interval_minutes: u64,
+    /// How many backups to keep, startup and scheduled alike; older ones are deleted.
+    #[serde(default = "default_backup_count")]
+    pub backup_count: usize,
+    /// Spell-check language; falls back to English if its dictionary is missing.
+    #[serde(default = "default_spell_language")]
+    pub spell_language: String,
+    /// Searches shown as smart folders in the sidebar.
+    #[serde(default)]
+    pub saved_searches: Vec<SavedSearch>,
+    /// Line endings notes are saved with.
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    /// End every saved note with exactly one newline.
+    #[serde(default)]
+    pub trailing_newline: bool,
+    /// Which characters of a title a new note's file name keeps.
+    #[serde(default)]
+    pub filename_policy: FilenamePolicy,
+    /// Path of an exported note inside `export_dir`; see `ExportName::expand`.
+    #[serde(default = "default_export_template")]
+    pub export_template: String,
+    /// Where exports are written.
+    #[serde(default = "default_export_dir")]
+    pub export_dir: PathBuf,
+}
+
+fn default_export_template() -> String {
+    DEFAULT_FILENAME_TEMPLATE.to_string()
+}
+
+fn default_export_dir() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_spell_language() -> String {
+    DEFAULT_LANGUAGE.to_string()
+}
+
+fn default_backup_count() -> usize {
+    DEFAULT_BACKUP_COUNT
+}
+
+fn default_backup_interval_minutes() -> u64 {
+    DEFAULT_BACKUP_INTERVAL_MINUTES
+}
+
+fn default_inbox_folder() -> String {
+    DEFAULT_INBOX_FOLDER.to_string()
+}
+
+fn default_journal_folder() -> String {
+    DEFAULT_JOURNAL_FOLDER.to_string()
+}
+
+fn default_search_result_limit() -> usize {
+    DEFAULT_SEARCH_RESULT_LIMIT
+}
+
+fn default_title_weight() -> f64 {
+    DEFAULT_TITLE_WEIGHT
+}
+
+fn default_soft_wrap() -> bool {
+    true
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            vault_path: PathBuf::from(DEFAULT_VAULT_PATH),
+            vaults: vec![PathBuf::from(DEFAULT_VAULT_PATH)],
+            read_only: false,
+            commit_policy: CommitPolicy::default(),
+            soft_wrap: default_soft_wrap(),
+            split_view: false,
+            window: WindowGeometry::default(),
+            search_result_limit: DEFAULT_SEARCH_RESULT_LIMIT,
+            title_weight: DEFAULT_TITLE_WEIGHT,
+            inbox_folder: default_inbox_folder(),
+            journal_folder: default_journal_folder(),
+            backup_on_startup: false,
+            scheduled_backups: false,
+            backup_interval_minutes: DEFAULT_BACKUP_INTERVAL_MINUTES,
+            backup_count: DEFAULT_BACKUP_COUNT,
+            spell_language: default_spell_language(),
+            saved_searches: Vec::new(),
+            line_ending: LineEnding::AsIs,
+            trailing_newline: false,
+            filename_policy: FilenamePolicy::default(),
+            export_template: default_export_template(),
+            export_dir: default_export_dir(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// `config.json` in the platform config directory, e.g.
+    /// `~/.config/notetaking/config.json` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "notetaking").map(|dirs| dirs.config_dir().join("config.json"))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let mut config: Self = serde_json::from_str(&json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // Configs written before the vault list only name the active vault
+        let active = config.vault_path.clone();
+        config.set_active_vault(active);
+        Ok(config)
+    }
+
+    /// Make `path` the open vault, adding it to the list if it's new.
+    pub fn set_active_vault(&mut self, path: PathBuf) {
+        if !self.vaults.contains(&path) {
+            self.vaults.push(path.clone());
+        }
+        self.vault_path = path;
+    }
+
+    /// Drop `path` from the list. The open vault can't be forgotten.
+    pub fn forget_vault(&mut self, path: &Path) -> bool {
+        if self.vault_path == path {
+            return false;
+        }
+        let before = self.vaults.len();
+        self.vaults.retain(|v| v != path);
+        self.vaults.len() != before
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchScope;
+
+    #[test]
+    fn test_config_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("config.json");
+
+        let mut config = AppConfig::default();
+        config.set_active_vault(PathBuf::from("/home/me/Dropbox/Notes"));
+        config.commit_policy = CommitPolicy::OnInterval(std::time::Duration::from_secs(600));
+        config.soft_wrap = false;
+        config.line_ending = LineEnding::CrLf;
+        config.trailing_newline = true;
+        config.filename_policy = FilenamePolicy {
+            ascii_only: true,
+            allowed: "-_ &".to_string(),
+        };
+        config.save(&path).unwrap();
+
+        assert_eq!(AppConfig::load(&path).unwrap(), config);
+    }
+
+    #[test]
+    fn test_saved_search_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+
+        let mut config = AppConfig::default();
+        config.saved_searches.push(SavedSearch {
+            name: "Open work".to_string(),
+            query: "status:open report".to_string(),
+            scope: SearchScope::Folder("Work".to_string()),
+            tag_filter: Some("urgent".to_string()),
+        });
+        config.saved_searches.push(SavedSearch {
+            name: "Everything".to_string(),
+            query: "meeting".to_string(),
+            scope: SearchScope::AllFolders,
+            tag_filter: None,
+        });
+        config.save(&path).unwrap();
+
+        let loaded = AppConfig::load(&path).unwrap();
+        assert_eq!(loaded.saved_searches, config.saved_searches);
+    }
+
+    #[test]
+    fn test_window_geometry_round_trip() {
+        let config = AppConfig {
+            window: WindowGeometry {
+                size: [1440.0, 900.0],
+                position: Some([120.0, 80.0]),
+                monitor_size: Some([2560.0, 1440.0]),
+                sidebar_width: 310.5,
+                sidebar_open: false,
+            },
+            ..AppConfig::default()
+        };
+
+        let json = serde_json::to_string(&config).unwrap();
+        let loaded: AppConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.window, config.window);
+
+        // Configs from before geometry was saved get the defaults
+        let old: AppConfig = serde_json::from_str(r#"{ "vault_path": "/notes" }"#).unwrap();
+        assert_eq!(old.window, WindowGeometry::default());
+    }
+
+    #[test]
+    fn test_off_screen_window_is_moved_back() {
+        let geometry = WindowGeometry {
+            size: [1200.0, 800.0],
+            position: Some([3000.0, 200.0]),
+            monitor_size: Some([3840.0, 2160.0]),
+            ..WindowGeometry::default()
+        };
+
+        // Same monitor as when it was saved
+        assert_eq!(geometry.off_screen_correction(Some([3840.0, 2160.0])), None);
+        // Back on a smaller laptop screen
+        assert_eq!(
+            geometry.off_screen_correction(Some([1920.0, 1080.0])),
+            Some([720.0, 200.0])
+        );
+        // Not on any monitor
+        assert_eq!(geometry.off_screen_correction(None), Some([0.0, 0.0]));
+
+        let visible = WindowGeometry {
+            position: Some([100.0, 100.0]),
+            ..geometry
+        };
+        assert_eq!(visible.off_screen_correction(Some([1920.0, 1080.0])), None);
+        assert_eq!(WindowGeometry::default().off_screen_correction(None), None);
+    }
+
+    #[test]
+    fn test_vault_list() {
+        let mut config = AppConfig::default();
+        config.set_active_vault(PathBuf::from("/work"));
+        config.set_active_vault(PathBuf::from("/personal"));
+        config.set_active_vault(PathBuf::from("/work"));
+        assert_eq!(
+            config.vaults,
+            vec![
+                PathBuf::from(DEFAULT_VAULT_PATH),
+                PathBuf::from("/work"),
+                PathBuf::from("/personal")
+            ]
+        );
+
+        assert!(!config.forget_vault(Path::new("/work")));
+        assert!(config.forget_vault(Path::new("/personal")));
+        assert_eq!(config.vaults.len(), 2);
+    }
+
+    #[test]
+    fn test_old_config_lists_its_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, r#"{ "vault_path": "/home/me/Notes" }"#).unwrap();
+
+        let config = AppConfig::load(&path).unwrap();
+        assert_eq!(config.vaults, vec![PathBuf::from("/home/me/Notes")]);
+        assert_eq!(config.commit_policy, CommitPolicy::EveryChange);
+        assert!(config.soft_wrap);
+        assert_eq!(config.search_result_limit, DEFAULT_SEARCH_RESULT_LIMIT);
+        assert_eq!(config.title_weight, DEFAULT_TITLE_WEIGHT);
+        assert_eq!(config.export_template, DEFAULT_FILENAME_TEMPLATE);
+        assert_eq!(config.inbox_folder, DEFAULT_INBOX_FOLDER);
+        assert_eq!(config.journal_folder, DEFAULT_JOURNAL_FOLDER);
+        assert!(!config.backup_on_startup);
+        assert!(!config.scheduled_backups);
+        assert_eq!(
+            config.backup_interval_minutes,
+            DEFAULT_BACKUP_INTERVAL_MINUTES
+        );
+        assert_eq!(config.backup_count, DEFAULT_BACKUP_COUNT);
+        assert_eq!(config.spell_language, DEFAULT_LANGUAGE);
+        assert!(config.saved_searches.is_empty());
+        assert_eq!(config.line_ending, LineEnding::AsIs);
+        assert!(!config.trailing_newline);
+        assert_eq!(config.filename_policy, FilenamePolicy::default());
+    }
+}
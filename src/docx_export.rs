@@ -0,0 +1,330 @@
+use crate::images::resolve_image_path;
+use docx_rs::{
+    AbstractNumbering, BreakType, Docx, IndentLevel, Level, LevelJc, LevelOverride, LevelText,
+    NumberFormat, Numbering, NumberingId, Paragraph, Pic, Run, RunFonts, SpecialIndentType, Start,
+    Style, StyleType,
+};
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::fs::{self, File};
+use std::io::Cursor;
+use std::path::Path;
+
+const BULLET_NUMBERING: usize = 2;
+const ORDERED_NUMBERING: usize = 3;
+const LIST_LEVELS: usize = 6;
+// Widest an embedded image may be, in pixels; larger ones are scaled down
+const MAX_IMAGE_WIDTH: u32 = 600;
+const EMU_PER_PX: u32 = 9525;
+
+pub struct DocxExporter;
+
+impl DocxExporter {
+    /// Convert a note's Markdown to a Word document. Relative image paths are
+    /// resolved against `note_path`; images that can't be read are left out.
+    pub fn export_note(
+        title: &str,
+        content: &str,
+        note_path: &Path,
+        output_path: &Path,
+    ) -> Result<(), String> {
+        let file = File::create(output_path).map_err(|e| format!("File creation error: {}", e))?;
+        build_document(title, content, note_path)
+            .build()
+            .pack(file)
+            .map_err(|e| format!("DOCX save error: {}", e))
+    }
+}
+
+fn build_document(title: &str, content: &str, note_path: &Path) -> Docx {
+    let mut writer = DocxWriter::new(note_path);
+    writer.docx = writer.docx.add_paragraph(
+        Paragraph::new()
+            .style("Title")
+            .add_run(Run::new().add_text(title)),
+    );
+
+    for event in Parser::new(content) {
+        writer.handle(event);
+    }
+    writer.flush();
+    writer.docx
+}
+
+/// Markdown event state while building the document.
+struct DocxWriter<'a> {
+    docx: Docx,
+    note_path: &'a Path,
+    paragraph: Option<Paragraph>,
+    // Whether the open paragraph has any runs yet
+    paragraph_empty: bool,
+    // Numbering instance of each open list, innermost last
+    lists: Vec<usize>,
+    next_numbering: usize,
+    bold: bool,
+    italic: bool,
+    code_block: bool,
+    in_image: bool,
+}
+
+impl<'a> DocxWriter<'a> {
+    fn new(note_path: &'a Path) -> Self {
+        let docx = Docx::new()
+            .add_style(heading_style("Title", "Title", 56))
+            .add_style(heading_style("Heading1", "Heading 1", 40))
+            .add_style(heading_style("Heading2", "Heading 2", 32))
+            .add_style(heading_style("Heading3", "Heading 3", 28))
+            .add_style(heading_style("Heading4", "Heading 4", 24))
+            .add_style(heading_style("Heading5", "Heading 5", 22))
+            .add_style(heading_style("Heading6", "Heading 6", 22))
+            .add_abstract_numbering(list_numbering(BULLET_NUMBERING, false))
+            .add_abstract_numbering(list_numbering(ORDERED_NUMBERING, true));
+
+        Self {
+            docx,
+            note_path,
+            paragraph: None,
+            paragraph_empty: true,
+            lists: Vec::new(),
+            next_numbering: ORDERED_NUMBERING + 1,
+            bold: false,
+            italic: false,
+            code_block: false,
+            in_image: false,
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                self.start_paragraph(Paragraph::new().style(heading_style_id(level)))
+            }
+            // A loose list item's text arrives as a paragraph inside the item
+            Event::Start(Tag::Paragraph) if self.paragraph.is_none() || !self.paragraph_empty => {
+                self.start_paragraph(Paragraph::new())
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.code_block = true;
+                self.start_paragraph(Paragraph::new());
+            }
+            Event::Start(Tag::List(start)) => {
+                self.flush();
+                let numbering = match start {
+                    None => BULLET_NUMBERING,
+                    // Each ordered list gets its own instance so numbering restarts
+                    Some(start) => {
+                        let id = self.next_numbering;
+                        self.next_numbering += 1;
+                        self.docx = std::mem::take(&mut self.docx).add_numbering(
+                            Numbering::new(id, ORDERED_NUMBERING)
+                                .add_override(LevelOverride::new(0).start(start as usize)),
+                        );
+                        id
+                    }
+                };
+                self.lists.push(numbering);
+            }
+            Event::Start(Tag::Item) => {
+                let numbering = self.lists.last().copied().unwrap_or(BULLET_NUMBERING);
+                let level = self.lists.len().saturating_sub(1).min(LIST_LEVELS - 1);
+                self.start_paragraph(
+                    Paragraph::new()
+                        .numbering(NumberingId::new(numbering), IndentLevel::new(level)),
+                );
+            }
+            Event::Start(Tag::Strong) => self.bold = true,
+            Event::Start(Tag::Emphasis) => self.italic = true,
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.in_image = true;
+                if let Some(pic) = self.load_image(&dest_url) {
+                    self.push_run(Run::new().add_image(pic));
+                }
+            }
+
+            Event::End(TagEnd::Heading(_) | TagEnd::Paragraph | TagEnd::Item) => self.flush(),
+            Event::End(TagEnd::CodeBlock) => {
+                self.code_block = false;
+                self.flush();
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.flush();
+                self.lists.pop();
+            }
+            Event::End(TagEnd::Strong) => self.bold = false,
+            Event::End(TagEnd::Emphasis) => self.italic = false,
+            Event::End(TagEnd::Image) => self.in_image = false,
+
+            // Alt text is already represented by the image
+            Event::Text(_) if self.in_image => {}
+            Event::Text(text) if self.code_block => {
+                let mut lines = text.trim_end_matches('\n').split('\n').peekable();
+                while let Some(line) = lines.next() {
+                    let mut run = code_run(line);
+                    if lines.peek().is_some() {
+                        run = run.add_break(BreakType::TextWrapping);
+                    }
+                    self.push_run(run);
+                }
+            }
+            Event::Text(text) => {
+                let run = self.styled_run(&text);
+                self.push_run(run);
+            }
+            Event::Code(text) => self.push_run(code_run(&text)),
+            Event::SoftBreak => {
+                let run = self.styled_run(" ");
+                self.push_run(run);
+            }
+            Event::HardBreak => self.push_run(Run::new().add_break(BreakType::TextWrapping)),
+            Event::TaskListMarker(done) => {
+                self.push_run(Run::new().add_text(if done { "☑ " } else { "☐ " }))
+            }
+            _ => {}
+        }
+    }
+
+    fn start_paragraph(&mut self, paragraph: Paragraph) {
+        self.flush();
+        self.paragraph = Some(paragraph);
+        self.paragraph_empty = true;
+    }
+
+    fn push_run(&mut self, run: Run) {
+        let paragraph = self.paragraph.take().unwrap_or_default();
+        self.paragraph = Some(paragraph.add_run(run));
+        self.paragraph_empty = false;
+    }
+
+    fn flush(&mut self) {
+        if let Some(paragraph) = self.paragraph.take() {
+            if !self.paragraph_empty {
+                self.docx = std::mem::take(&mut self.docx).add_paragraph(paragraph);
+            }
+        }
+        self.paragraph_empty = true;
+    }
+
+    fn styled_run(&self, text: &str) -> Run {
+        let mut run = Run::new().add_text(text);
+        if self.bold {
+            run = run.bold();
+        }
+        if self.italic {
+            run = run.italic();
+        }
+        run
+    }
+
+    /// Decode an image and re-encode it as PNG, the only format docx-rs embeds.
+    fn load_image(&self, reference: &str) -> Option<Pic> {
+        let path = resolve_image_path(self.note_path, reference);
+        let image = image::load_from_memory(&fs::read(path).ok()?).ok()?;
+        let (width, height) = (image.width(), image.height());
+
+        let mut png = Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).ok()?;
+
+        let scale = (MAX_IMAGE_WIDTH as f32 / width as f32).min(1.0);
+        let (w, h) = (
+            (width as f32 * scale) as u32,
+            (height as f32 * scale) as u32,
+        );
+        Some(
+            Pic::new_with_dimensions(png.into_inner(), width, height)
+                .size(w * EMU_PER_PX, h * EMU_PER_PX),
+        )
+    }
+}
+
+fn heading_style_id(level: HeadingLevel) -> &'static str {
+    match level {
+        HeadingLevel::H1 => "Heading1",
+        HeadingLevel::H2 => "Heading2",
+        HeadingLevel::H3 => "Heading3",
+        HeadingLevel::H4 => "Heading4",
+        HeadingLevel::H5 => "Heading5",
+        HeadingLevel::H6 => "Heading6",
+    }
+}
+
+/// `size` is in half-points, like Word's own style sheet.
+fn heading_style(id: &str, name: &str, size: usize) -> Style {
+    Style::new(id, StyleType::Paragraph)
+        .name(name)
+        .size(size)
+        .bold()
+}
+
+fn code_run(text: &str) -> Run {
+    let mono = RunFonts::new().ascii("Courier New").hi_ansi("Courier New");
+    Run::new().add_text(text).fonts(mono)
+}
+
+fn list_numbering(id: usize, ordered: bool) -> AbstractNumbering {
+    let mut numbering = AbstractNumbering::new(id);
+    for level in 0..LIST_LEVELS {
+        let (format, text) = if ordered {
+            ("decimal", format!("%{}.", level + 1))
+        } else {
+            ("bullet", "•".to_string())
+        };
+        let indent = 720 * (level as i32 + 1);
+        numbering = numbering.add_level(
+            Level::new(
+                level,
+                Start::new(1),
+                NumberFormat::new(format),
+                LevelText::new(text),
+                LevelJc::new("left"),
+            )
+            .indent(
+                Some(indent),
+                Some(SpecialIndentType::Hanging(360)),
+                None,
+                None,
+            ),
+        );
+    }
+    numbering
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_export_produces_docx_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let note_path = dir.path().join("Report.md");
+        let images = dir.path().join("images");
+        fs::create_dir_all(&images).unwrap();
+        image::RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255]))
+            .save(images.join("chart.png"))
+            .unwrap();
+
+        let content = "# Summary\n\nSome **bold** and *italic* text.\n\n- one\n- two\n  1. nested\n\n![Chart](images/chart.png)\n\n![Gone](images/missing.png)\n";
+        let output = dir.path().join("Report.docx");
+        DocxExporter::export_note("Report", content, &note_path, &output).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let mut document = String::new();
+        archive
+            .by_name("word/document.xml")
+            .unwrap()
+            .read_to_string(&mut document)
+            .unwrap();
+
+        assert!(document.contains("Summary"));
+        assert!(document.contains("w:val=\"Heading1\""));
+        assert!(document.contains("<w:b />") || document.contains("<w:b/>"));
+        assert!(document.contains("w:numId"));
+        assert!(document.contains("<w:drawing>"));
+        assert!((0..archive.len()).any(|i| {
+            archive
+                .by_index(i)
+                .unwrap()
+                .name()
+                .starts_with("word/media/")
+        }));
+    }
+}
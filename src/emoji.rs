@@ -0,0 +1,81 @@
+/// Emoji offered by the editor's picker, each with the words it can be found by.
+/// Status markers come first since notes are mostly annotated with those.
+pub const EMOJI: &[(&str, &str)] = &[
+    ("✅", "check done complete yes"),
+    ("❌", "cross no cancel wrong"),
+    ("⚠️", "warning caution"),
+    ("❗", "exclamation important"),
+    ("❓", "question unsure"),
+    ("🚧", "construction wip in progress"),
+    ("⏳", "hourglass waiting pending"),
+    ("🔥", "fire hot urgent"),
+    ("⭐", "star favorite"),
+    ("📌", "pin pinned"),
+    ("🎯", "target goal"),
+    ("🐛", "bug issue"),
+    ("💡", "idea bulb"),
+    ("📝", "memo note write"),
+    ("📅", "calendar date"),
+    ("⏰", "alarm clock deadline"),
+    ("🔒", "lock private"),
+    ("🔗", "link"),
+    ("📎", "paperclip attachment"),
+    ("📚", "books reading"),
+    ("🚀", "rocket launch ship"),
+    ("🎉", "party celebrate"),
+    ("👍", "thumbs up yes good"),
+    ("👎", "thumbs down no bad"),
+    ("👀", "eyes look review"),
+    ("🙂", "smile happy"),
+    ("😀", "grin happy"),
+    ("😂", "laugh joy"),
+    ("😢", "cry sad"),
+    ("😡", "angry"),
+    ("🤔", "thinking hmm"),
+    ("❤️", "heart love"),
+    ("🟢", "green circle ok"),
+    ("🟡", "yellow circle"),
+    ("🔴", "red circle blocked"),
+    ("🔵", "blue circle"),
+    ("⬆️", "up arrow increase"),
+    ("⬇️", "down arrow decrease"),
+    ("➡️", "right arrow next"),
+    ("🏠", "home house"),
+    ("💼", "work briefcase"),
+    ("💰", "money budget"),
+    ("🛒", "cart shopping"),
+    ("✈️", "travel plane"),
+    ("🍽️", "food meal"),
+    ("☕", "coffee"),
+    ("🏃", "run exercise"),
+    ("🎵", "music"),
+];
+
+/// The emoji whose keywords contain every word of `query`, in table order. An
+/// empty query matches them all.
+pub fn search_emoji(query: &str) -> Vec<&'static str> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    EMOJI
+        .iter()
+        .filter(|(emoji, keywords)| {
+            words.iter().all(|word| {
+                *emoji == word || keywords.split(' ').any(|k| k.starts_with(word.as_str()))
+            })
+        })
+        .map(|(emoji, _)| *emoji)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_emoji() {
+        assert_eq!(search_emoji("").len(), EMOJI.len());
+        assert_eq!(search_emoji("Done"), vec!["✅"]);
+        assert_eq!(search_emoji("thumbs  up"), vec!["👍"]);
+        assert!(search_emoji("circle").contains(&"🔴"));
+        assert!(search_emoji("zzz").is_empty());
+    }
+}
@@ -7,14 +7,22 @@ use argon2::password_hash::{SaltString, rand_core::RngCore};
 use base64::{Engine as _, engine::general_purpose};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What an encrypted note's body holds in place of its text.
+pub const ENCRYPTED_PLACEHOLDER: &str = "[ENCRYPTED]";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EncryptedData {
     pub ciphertext: String,
     pub nonce: String,
     pub salt: String,
+    /// Reminder of the password, shown when asking for it. Stored in the clear,
+    /// beside the ciphertext rather than in it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
 }
 
 pub struct Encryption {
+    #[allow(dead_code)]
     password_hash: Option<String>,
 }
 
@@ -25,6 +33,7 @@ impl Encryption {
         }
     }
     
+    #[allow(dead_code)]
     pub fn set_password(&mut self, password: &str) -> Result<(), String> {
         let salt = SaltString::generate(&mut OsRng);
         let argon2 = Argon2::default();
@@ -38,6 +47,7 @@ impl Encryption {
         Ok(())
     }
     
+    #[allow(dead_code)]
     pub fn verify_password(&self, password: &str) -> bool {
         if let Some(hash) = &self.password_hash {
             if let Ok(parsed_hash) = PasswordHash::new(hash) {
@@ -49,6 +59,7 @@ impl Encryption {
         false
     }
     
+    #[allow(dead_code)]
     pub fn is_password_set(&self) -> bool {
         self.password_hash.is_some()
     }
@@ -92,8 +103,9 @@ impl Encryption {
         
         Ok(EncryptedData {
             ciphertext: general_purpose::STANDARD.encode(&ciphertext),
-            nonce: general_purpose::STANDARD.encode(&nonce_bytes),
-            salt: general_purpose::STANDARD.encode(&salt),
+            nonce: general_purpose::STANDARD.encode(nonce_bytes),
+            salt: general_purpose::STANDARD.encode(salt),
+            hint: None,
         })
     }
     
@@ -157,4 +169,31 @@ mod tests {
         
         assert!(result.is_err());
     }
+    
+    #[test]
+    fn test_password_hint_round_trips_outside_ciphertext() {
+        use crate::note::NoteMetadata;
+        
+        let encryption = Encryption::new();
+        let hint = "First dog, then the year";
+        let mut encrypted = encryption.encrypt("PIN 4321", "rex2010").unwrap();
+        encrypted.hint = Some(hint.to_string());
+        let mut metadata = NoteMetadata::new();
+        metadata.is_encrypted = true;
+        metadata.encrypted_data = Some(encrypted.clone());
+        
+        let json = serde_json::to_string(&metadata).unwrap();
+        let loaded: NoteMetadata = serde_json::from_str(&json).unwrap();
+        let sealed = loaded.encrypted_data.unwrap();
+        assert_eq!(sealed.hint.as_deref(), Some(hint));
+        assert_eq!(sealed, encrypted);
+        
+        // Only the note's text is sealed, and the hint doesn't affect opening it
+        assert!(!sealed.ciphertext.contains(hint));
+        let plain = encryption.decrypt(&sealed, "rex2010").unwrap();
+        assert_eq!(plain, "PIN 4321");
+        let unhinted = EncryptedData { hint: None, ..sealed };
+        assert_eq!(encryption.decrypt(&unhinted, "rex2010").unwrap(), plain);
+        assert!(!serde_json::to_string(&unhinted).unwrap().contains("hint"));
+    }
 }
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Why a storage operation failed.
+#[derive(Debug)]
+pub enum Error {
+    /// No folder at this index.
+    FolderNotFound(usize),
+    /// No note at `(folder_idx, note_idx)`.
+    NoteNotFound(usize, usize),
+    /// A title or folder name that can't be used: blank, or a path rather than a name.
+    InvalidName(String),
+    /// Another folder already has this name.
+    FolderExists(String),
+    /// The note is already in the folder it was asked to move to.
+    #[allow(dead_code)]
+    SameFolder,
+    /// The note's front-matter couldn't be written back.
+    FrontMatter(String),
+    /// A `.meta` file couldn't be encoded or decoded.
+    Metadata(serde_json::Error),
+    /// The folder encrypts its notes, but no session password has been given.
+    FolderLocked(String),
+    /// A note couldn't be encrypted.
+    Encryption(String),
+    /// The path isn't a note in the vault's archive.
+    NotArchived(PathBuf),
+    /// The vault was opened read-only.
+    ReadOnly,
+    /// An image couldn't be imported or saved.
+    Image(String),
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::FolderNotFound(idx) => write!(f, "Folder {} not found", idx),
+            Error::NoteNotFound(folder_idx, note_idx) => {
+                write!(f, "Note {} in folder {} not found", note_idx, folder_idx)
+            }
+            Error::InvalidName(name) => write!(f, "\"{}\" can't be used as a name", name),
+            Error::FolderExists(name) => write!(f, "A folder named \"{}\" already exists", name),
+            Error::SameFolder => write!(f, "The note is already in that folder"),
+            Error::FrontMatter(e) => write!(f, "Invalid front-matter: {}", e),
+            Error::Metadata(e) => write!(f, "Invalid note metadata: {}", e),
+            Error::FolderLocked(name) => {
+                write!(
+                    f,
+                    "\"{}\" is encrypted; unlock it with its password first",
+                    name
+                )
+            }
+            Error::Encryption(e) => write!(f, "Encryption failed: {}", e),
+            Error::NotArchived(path) => write!(f, "{} isn't an archived note", path.display()),
+            Error::ReadOnly => write!(f, "The vault is open read-only"),
+            Error::Image(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Metadata(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Metadata(e)
+    }
+}
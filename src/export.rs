@@ -0,0 +1,335 @@
+use crate::docx_export::DocxExporter;
+use crate::html_export::HtmlExporter;
+use crate::markdown_export::MarkdownExporter;
+use crate::note::Note;
+use crate::pdf_export::PdfExporter;
+use crate::storage::{sanitize_filename, FilenamePolicy};
+use chrono::NaiveDate;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where an exported note goes, inside the export directory, unless configured otherwise.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{title}.{ext}";
+
+/// Why a note couldn't be exported.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportError {
+    /// The note is locked, so there's only ciphertext to write.
+    Encrypted(String),
+    /// The format's writer failed; the message says why.
+    Failed(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Encrypted(title) => write!(f, "\"{}\" is encrypted", title),
+            ExportError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<String> for ExportError {
+    fn from(e: String) -> Self {
+        ExportError::Failed(e)
+    }
+}
+
+/// A file format a single note can be exported to.
+pub trait Exporter {
+    /// Shown in the export dialog.
+    fn name(&self) -> &str;
+    /// File extension without the dot, also used to pick the format.
+    fn extension(&self) -> &str;
+    fn export(&self, note: &Note, out: &Path) -> Result<(), ExportError>;
+}
+
+impl Exporter for PdfExporter {
+    fn name(&self) -> &str {
+        "PDF"
+    }
+
+    fn extension(&self) -> &str {
+        "pdf"
+    }
+
+    fn export(&self, note: &Note, out: &Path) -> Result<(), ExportError> {
+        unlocked(note)?;
+        Ok(PdfExporter::export_note(&note.title, &note.content, out)?)
+    }
+}
+
+impl Exporter for DocxExporter {
+    fn name(&self) -> &str {
+        "Word (DOCX)"
+    }
+
+    fn extension(&self) -> &str {
+        "docx"
+    }
+
+    fn export(&self, note: &Note, out: &Path) -> Result<(), ExportError> {
+        unlocked(note)?;
+        Ok(DocxExporter::export_note(
+            &note.title,
+            &note.content,
+            Path::new(&note.file_path),
+            out,
+        )?)
+    }
+}
+
+impl Exporter for HtmlExporter {
+    fn name(&self) -> &str {
+        "HTML"
+    }
+
+    fn extension(&self) -> &str {
+        "html"
+    }
+
+    fn export(&self, note: &Note, out: &Path) -> Result<(), ExportError> {
+        unlocked(note)?;
+        Ok(HtmlExporter::export_note(&note.title, &note.content, out)?)
+    }
+}
+
+/// Tags come from the note's front-matter; fold app tags into it first to keep them.
+impl Exporter for MarkdownExporter {
+    fn name(&self) -> &str {
+        "Markdown"
+    }
+
+    fn extension(&self) -> &str {
+        "md"
+    }
+
+    fn export(&self, note: &Note, out: &Path) -> Result<(), ExportError> {
+        unlocked(note)?;
+        Ok(MarkdownExporter::export_note(note, &[], out)?)
+    }
+}
+
+fn unlocked(note: &Note) -> Result<(), ExportError> {
+    if note.is_encrypted {
+        return Err(ExportError::Encrypted(note.title.clone()));
+    }
+    Ok(())
+}
+
+/// What a filename template's `{title}`, `{folder}`, `{date}` and `{ext}` stand for.
+pub struct ExportName<'a> {
+    pub title: &'a str,
+    pub folder: &'a str,
+    pub date: NaiveDate,
+    pub extension: &'a str,
+}
+
+impl ExportName<'_> {
+    /// The path `template`, e.g. `{folder}/{title}-{date}.{ext}`, names inside
+    /// `output_dir`. A `/` in the template makes a subdirectory, but the title and
+    /// folder are sanitized like note file names, so they can't. Unknown
+    /// placeholders are kept as written, and `..` is dropped so the path stays
+    /// inside `output_dir`.
+    pub fn expand(&self, template: &str, output_dir: &Path, policy: &FilenamePolicy) -> PathBuf {
+        let components: Vec<String> = template
+            .split(['/', '\\'])
+            .map(|component| self.substitute(component, policy))
+            .filter(|component| {
+                !component.trim().is_empty() && component != "." && component != ".."
+            })
+            .collect();
+        if components.is_empty() {
+            return output_dir.join(self.substitute(DEFAULT_FILENAME_TEMPLATE, policy));
+        }
+        components
+            .iter()
+            .fold(output_dir.to_path_buf(), |path, c| path.join(c))
+    }
+
+    fn substitute(&self, component: &str, policy: &FilenamePolicy) -> String {
+        let mut out = String::new();
+        let mut rest = component;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+                break;
+            };
+            match &rest[start + 1..end] {
+                "title" => out.push_str(&sanitize_filename(self.title, policy)),
+                "folder" => out.push_str(&sanitize_filename(self.folder, policy)),
+                "date" => out.push_str(&self.date.format("%Y-%m-%d").to_string()),
+                "ext" => out.push_str(self.extension),
+                _ => out.push_str(&rest[start..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// `path`, or `stem (2).ext`, `stem (3).ext`, ... beside it if an earlier export in
+/// the same batch already `used` it. Notes with the same title, or titles that
+/// sanitize alike, would otherwise overwrite each other.
+pub fn unique_export_path(path: PathBuf, used: &HashSet<PathBuf>) -> PathBuf {
+    if !used.contains(&path) {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !used.contains(candidate))
+        .unwrap_or(path)
+}
+
+/// The formats on offer, in the order the export dialog lists them.
+pub struct ExporterRegistry {
+    exporters: Vec<Box<dyn Exporter>>,
+}
+
+impl ExporterRegistry {
+    pub fn new() -> Self {
+        Self {
+            exporters: Vec::new(),
+        }
+    }
+
+    /// Add a format. One with the same extension as an earlier format replaces it.
+    pub fn register(&mut self, exporter: Box<dyn Exporter>) {
+        match self
+            .exporters
+            .iter()
+            .position(|e| e.extension() == exporter.extension())
+        {
+            Some(idx) => self.exporters[idx] = exporter,
+            None => self.exporters.push(exporter),
+        }
+    }
+
+    pub fn get(&self, extension: &str) -> Option<&dyn Exporter> {
+        self.exporters
+            .iter()
+            .find(|e| e.extension().eq_ignore_ascii_case(extension))
+            .map(|e| e.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Exporter> {
+        self.exporters.iter().map(|e| e.as_ref())
+    }
+}
+
+impl Default for ExporterRegistry {
+    /// Every built-in format.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(PdfExporter));
+        registry.register(Box::new(DocxExporter));
+        registry.register(Box::new(MarkdownExporter));
+        registry.register(Box::new(HtmlExporter));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    struct TextExporter;
+
+    impl Exporter for TextExporter {
+        fn name(&self) -> &str {
+            "Plain Text"
+        }
+
+        fn extension(&self) -> &str {
+            "txt"
+        }
+
+        fn export(&self, note: &Note, out: &Path) -> Result<(), ExportError> {
+            unlocked(note)?;
+            fs::write(out, format!("{}\n\n{}", note.title, note.content))
+                .map_err(|e| ExportError::Failed(e.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_registered_exporter_runs_through_trait() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = ExporterRegistry::default();
+        registry.register(Box::new(TextExporter));
+        let extensions: Vec<&str> = registry.iter().map(|e| e.extension()).collect();
+        assert_eq!(extensions, vec!["pdf", "docx", "md", "html", "txt"]);
+
+        let mut note = Note::new("Groceries".to_string(), String::new());
+        note.content = "Milk".to_string();
+        let out = dir.path().join("groceries.txt");
+        let exporter = registry.get("TXT").unwrap();
+        exporter.export(&note, &out).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "Groceries\n\nMilk");
+
+        note.is_encrypted = true;
+        assert_eq!(
+            exporter.export(&note, &out),
+            Err(ExportError::Encrypted("Groceries".to_string()))
+        );
+        assert!(registry.get("odt").is_none());
+    }
+
+    #[test]
+    fn test_filename_template_expansion() {
+        let name = ExportName {
+            title: "Q3: Plans/Ideas?",
+            folder: "Work",
+            date: NaiveDate::from_ymd_opt(2024, 3, 9).unwrap(),
+            extension: "pdf",
+        };
+        let policy = FilenamePolicy::default();
+        let out = Path::new("exports");
+        assert_eq!(
+            name.expand("{folder}/{title}-{date}.{ext}", out, &policy),
+            Path::new("exports/Work/Q3_ Plans_Ideas-2024-03-09.pdf")
+        );
+        assert_eq!(
+            name.expand(DEFAULT_FILENAME_TEMPLATE, out, &policy),
+            Path::new("exports/Q3_ Plans_Ideas.pdf")
+        );
+        // Unknown placeholders stay, and the path can't climb out of the directory
+        assert_eq!(
+            name.expand("../{author}/{title}.{ext}", out, &policy),
+            Path::new("exports/{author}/Q3_ Plans_Ideas.pdf")
+        );
+        assert_eq!(
+            name.expand("/", out, &policy),
+            Path::new("exports/Q3_ Plans_Ideas.pdf")
+        );
+
+        // Titles sanitizing to the same name in one batch get a suffix each
+        let twin = ExportName {
+            title: "Q3/ Plans:Ideas",
+            ..name
+        };
+        let mut used = HashSet::new();
+        for (note, expected) in [
+            (&name, "exports/Q3_ Plans_Ideas.pdf"),
+            (&twin, "exports/Q3_ Plans_Ideas (2).pdf"),
+            (&name, "exports/Q3_ Plans_Ideas (3).pdf"),
+        ] {
+            let path =
+                unique_export_path(note.expand(DEFAULT_FILENAME_TEMPLATE, out, &policy), &used);
+            assert_eq!(path, Path::new(expected));
+            used.insert(path);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Which sidebar folders are expanded, keyed by folder name. Kept by the app
+/// rather than left to egui's id memory so "Expand all" / "Collapse all" can set
+/// every folder at once and the layout survives a restart.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FolderTreeState {
+    expanded: BTreeMap<String, bool>,
+}
+
+impl FolderTreeState {
+    /// Folders start collapsed until they're opened.
+    pub fn is_expanded(&self, folder: &str) -> bool {
+        self.expanded.get(folder).copied().unwrap_or(false)
+    }
+
+    pub fn set_expanded(&mut self, folder: &str, expanded: bool) {
+        self.expanded.insert(folder.to_string(), expanded);
+    }
+
+    pub fn toggle(&mut self, folder: &str) {
+        let expanded = self.is_expanded(folder);
+        self.set_expanded(folder, !expanded);
+    }
+
+    /// Expand or collapse every folder in `folders`.
+    pub fn set_all<'a>(&mut self, folders: impl IntoIterator<Item = &'a str>, expanded: bool) {
+        for folder in folders {
+            self.set_expanded(folder, expanded);
+        }
+    }
+
+    /// Drop entries for folders that no longer exist.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.expanded.retain(|folder, _| keep(folder));
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_and_collapse_all() {
+        let mut state = FolderTreeState::default();
+        assert!(!state.is_expanded("Work"));
+
+        state.toggle("Work");
+        assert!(state.is_expanded("Work"));
+        state.toggle("Work");
+        assert!(!state.is_expanded("Work"));
+
+        let folders = ["Work", "Home", "Journal"];
+        state.set_all(folders, true);
+        assert!(folders.iter().all(|f| state.is_expanded(f)));
+
+        state.toggle("Home");
+        assert!(!state.is_expanded("Home"));
+        assert!(state.is_expanded("Journal"));
+
+        state.set_all(folders, false);
+        assert!(folders.iter().all(|f| !state.is_expanded(f)));
+    }
+
+    #[test]
+    fn test_folder_tree_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("folders.json");
+
+        let mut state = FolderTreeState::default();
+        state.set_expanded("Work", true);
+        state.set_expanded("Old", false);
+        state.retain(|folder| folder != "Old");
+        state.save(&path).unwrap();
+
+        let loaded = FolderTreeState::load(&path).unwrap();
+        assert_eq!(loaded, state);
+        assert!(loaded.is_expanded("Work"));
+    }
+}
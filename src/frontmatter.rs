@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// YAML block at the top of a note, delimited by `---` lines (Obsidian/Jekyll style).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrontMatter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// When the note was created and last updated, as written by Markdown export.
+    /// A note without a `.meta` file takes its timestamps from these.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "scalar_string"
+    )]
+    pub created: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "scalar_string"
+    )]
+    pub updated: Option<String>,
+    // Any other keys are preserved untouched so they survive a save
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl FrontMatter {
+    /// Scalar keys other than `title`, `tags` and the timestamps (strings, numbers, booleans) as text.
+    /// These are the note's properties; lists and maps aren't.
+    pub fn properties(&self) -> BTreeMap<String, String> {
+        self.extra
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), scalar_text(value)?)))
+            .collect()
+    }
+
+    /// Make the scalar keys match `properties`. Lists and maps are left alone, and a
+    /// value whose text hasn't changed keeps its YAML type, so `priority: 2` stays a number.
+    pub fn set_properties(&mut self, properties: &BTreeMap<String, String>) {
+        self.extra
+            .retain(|key, value| scalar_text(value).is_none() || properties.contains_key(key));
+        for (key, text) in properties {
+            if self.extra.get(key).and_then(scalar_text).as_ref() != Some(text) {
+                self.extra
+                    .insert(key.clone(), serde_yaml::Value::String(text.clone()));
+            }
+        }
+    }
+}
+
+fn scalar_text(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(text) => Some(text.clone()),
+        serde_yaml::Value::Number(number) => Some(number.to_string()),
+        serde_yaml::Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// Accept a date written as a number or a bare string, rather than failing the
+/// whole block over it.
+fn scalar_string<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let value = Option::<serde_yaml::Value>::deserialize(deserializer)?;
+    Ok(value.as_ref().and_then(scalar_text))
+}
+
+/// Split a raw note file into its front-matter and the remaining body.
+/// Files without a well-formed front-matter block are returned unchanged.
+pub fn split_front_matter(raw: &str) -> (Option<FrontMatter>, &str) {
+    let rest = match raw
+        .strip_prefix("---\n")
+        .or_else(|| raw.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return (None, raw),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let yaml = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+
+            if yaml.trim().is_empty() {
+                return (Some(FrontMatter::default()), body);
+            }
+            return match serde_yaml::from_str::<FrontMatter>(yaml) {
+                Ok(front_matter) => (Some(front_matter), body),
+                Err(_) => (None, raw),
+            };
+        }
+        offset += line.len();
+    }
+
+    (None, raw)
+}
+
+/// Prepend a front-matter block to `body`.
+pub fn join_front_matter(front_matter: &FrontMatter, body: &str) -> Result<String, String> {
+    if *front_matter == FrontMatter::default() {
+        return Ok(format!("---\n---\n{}", body));
+    }
+
+    let yaml = serde_yaml::to_string(front_matter)
+        .map_err(|e| format!("Failed to serialize front-matter: {}", e))?;
+    Ok(format!("---\n{}---\n{}", yaml, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_without_front_matter() {
+        let raw = "# Heading\n\nBody text";
+        let (front_matter, body) = split_front_matter(raw);
+        assert!(front_matter.is_none());
+        assert_eq!(body, raw);
+    }
+
+    #[test]
+    fn test_split_and_rejoin_front_matter() {
+        let raw =
+            "---\ntitle: Custom Title\ntags:\n- rust\n- notes\nstatus: draft\n---\n# Heading\n";
+        let (front_matter, body) = split_front_matter(raw);
+        let front_matter = front_matter.unwrap();
+
+        assert_eq!(front_matter.title.as_deref(), Some("Custom Title"));
+        assert_eq!(front_matter.tags, vec!["rust", "notes"]);
+        assert!(front_matter.extra.contains_key("status"));
+        assert_eq!(body, "# Heading\n");
+
+        let rejoined = join_front_matter(&front_matter, body).unwrap();
+        let (reparsed, rebody) = split_front_matter(&rejoined);
+        assert_eq!(reparsed.unwrap(), front_matter);
+        assert_eq!(rebody, body);
+    }
+
+    #[test]
+    fn test_properties_round_trip() {
+        let raw = "---\nstatus: draft\npriority: 2\naliases:\n- Plan\n---\nBody\n";
+        let mut front_matter = split_front_matter(raw).0.unwrap();
+        let mut properties = front_matter.properties();
+        assert_eq!(
+            properties,
+            BTreeMap::from([
+                ("priority".to_string(), "2".to_string()),
+                ("status".to_string(), "draft".to_string()),
+            ])
+        );
+
+        properties.insert("status".to_string(), "done".to_string());
+        properties.insert("author".to_string(), "Sam".to_string());
+        front_matter.set_properties(&properties);
+        let rejoined = join_front_matter(&front_matter, "Body\n").unwrap();
+        assert!(rejoined.contains("status: done\n"));
+        assert!(rejoined.contains("priority: 2\n"));
+        assert!(rejoined.contains("author: Sam\n"));
+
+        // Removing a property drops the key; lists aren't properties and stay
+        properties.remove("priority");
+        front_matter.set_properties(&properties);
+        let reparsed = split_front_matter(&join_front_matter(&front_matter, "").unwrap())
+            .0
+            .unwrap();
+        assert_eq!(reparsed.properties(), properties);
+        assert!(reparsed.extra.contains_key("aliases"));
+    }
+
+    #[test]
+    fn test_unterminated_block_is_content() {
+        let raw = "---\ntitle: Oops\nno closing line";
+        let (front_matter, body) = split_front_matter(raw);
+        assert!(front_matter.is_none());
+        assert_eq!(body, raw);
+    }
+}
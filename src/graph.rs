@@ -0,0 +1,195 @@
+use crate::links::LinkManager;
+use crate::note::Folder;
+
+/// Notes as nodes and the links between them as edges, for the graph view.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: Vec<(usize, usize)>, // (folder_idx, note_idx)
+    pub titles: Vec<String>,
+    /// Pairs of indices into `nodes`, smaller first. A link in both directions is one edge.
+    pub edges: Vec<(usize, usize)>,
+    /// Number of edges at each node.
+    pub degree: Vec<usize>,
+}
+
+impl Graph {
+    /// Every note in `folders`, joined where `links` has a link between them in
+    /// either direction. Links to notes that no longer exist and links from a
+    /// note to itself are left out.
+    pub fn build(folders: &[Folder], links: &LinkManager) -> Self {
+        let mut graph = Graph::default();
+        let mut index = std::collections::HashMap::new();
+        for (folder_idx, folder) in folders.iter().enumerate() {
+            for (note_idx, note) in folder.notes.iter().enumerate() {
+                index.insert((folder_idx, note_idx), graph.nodes.len());
+                graph.nodes.push((folder_idx, note_idx));
+                graph.titles.push(note.title.clone());
+            }
+        }
+
+        let mut edges = std::collections::BTreeSet::new();
+        for (source, targets) in &links.outgoing_links {
+            let Some(&from) = index.get(source) else {
+                continue;
+            };
+            for target in targets {
+                match index.get(target) {
+                    Some(&to) if to != from => {
+                        edges.insert((from.min(to), from.max(to)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        graph.degree = vec![0; graph.nodes.len()];
+        for &(a, b) in &edges {
+            graph.degree[a] += 1;
+            graph.degree[b] += 1;
+        }
+        graph.edges = edges.into_iter().collect();
+        graph
+    }
+}
+
+/// Positions of a graph's nodes, moved towards a readable layout one step at a
+/// time: every node pushes the others away and each edge pulls its ends together
+/// like a spring. Steps get smaller as the layout cools, so it settles.
+#[derive(Debug, Clone)]
+pub struct GraphLayout {
+    pub positions: Vec<[f32; 2]>,
+    temperature: f32,
+}
+
+/// Largest distance a node may move in the first step.
+const START_TEMPERATURE: f32 = 0.1;
+const COOLING: f32 = 0.95;
+/// Pull towards the centre, so unlinked notes don't drift off.
+const GRAVITY: f32 = 0.1;
+
+impl GraphLayout {
+    /// Nodes spread over a spiral around the origin, the same way every time.
+    pub fn new(node_count: usize) -> Self {
+        let positions = (0..node_count)
+            .map(|i| {
+                let radius = ((i as f32 + 0.5) / node_count as f32).sqrt();
+                let angle = i as f32 * 2.399_963; // The golden angle
+                [radius * angle.cos(), radius * angle.sin()]
+            })
+            .collect();
+        Self {
+            positions,
+            temperature: START_TEMPERATURE,
+        }
+    }
+
+    /// Whether further steps would hardly move anything.
+    pub fn is_settled(&self) -> bool {
+        self.temperature < 0.001
+    }
+
+    pub fn step(&mut self, graph: &Graph) {
+        let n = self.positions.len();
+        if n == 0 {
+            return;
+        }
+        // Ideal edge length for n nodes sharing the unit area
+        let k = 1.0 / (n as f32).sqrt();
+        let mut moves = vec![[0.0f32; 2]; n];
+
+        for i in 0..n {
+            for j in i + 1..n {
+                let (d, dist) = self.offset(i, j);
+                let push = k * k / dist;
+                for axis in 0..2 {
+                    moves[i][axis] += d[axis] / dist * push;
+                    moves[j][axis] -= d[axis] / dist * push;
+                }
+            }
+        }
+        for &(a, b) in &graph.edges {
+            if a >= n || b >= n {
+                continue;
+            }
+            let (d, dist) = self.offset(a, b);
+            let pull = dist * dist / k;
+            for axis in 0..2 {
+                moves[a][axis] -= d[axis] / dist * pull;
+                moves[b][axis] += d[axis] / dist * pull;
+            }
+        }
+
+        for (position, movement) in self.positions.iter_mut().zip(&mut moves) {
+            for axis in 0..2 {
+                movement[axis] -= position[axis] * GRAVITY;
+            }
+            let length = movement[0].hypot(movement[1]);
+            if length > 0.0 {
+                let scale = length.min(self.temperature) / length;
+                position[0] += movement[0] * scale;
+                position[1] += movement[1] * scale;
+            }
+        }
+        self.temperature *= COOLING;
+    }
+
+    /// The vector from node `j` to node `i`, and its length, never quite zero.
+    fn offset(&self, i: usize, j: usize) -> ([f32; 2], f32) {
+        let d = [
+            self.positions[i][0] - self.positions[j][0],
+            self.positions[i][1] - self.positions[j][1],
+        ];
+        (d, d[0].hypot(d[1]).max(0.001))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::Note;
+
+    fn folder(name: &str, titles: &[&str]) -> Folder {
+        let mut folder = Folder::new(name.to_string(), name.to_string());
+        for title in titles {
+            folder.add_note(Note::new(title.to_string(), String::new()));
+        }
+        folder
+    }
+
+    #[test]
+    fn test_graph_built_from_link_manager() {
+        let folders = vec![folder("Work", &["A", "B"]), folder("Home", &["C", "D"])];
+        let mut links = LinkManager::new();
+        links.add_link((0, 0), (0, 1));
+        links.add_link((0, 1), (0, 0)); // Same edge the other way
+        links.add_link((0, 0), (1, 0));
+        links.add_link((1, 0), (1, 0)); // To itself
+        links.add_link((1, 0), (5, 5)); // To a note that's gone
+
+        let graph = Graph::build(&folders, &links);
+        assert_eq!(graph.nodes, vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+        assert_eq!(graph.titles, vec!["A", "B", "C", "D"]);
+        assert_eq!(graph.edges, vec![(0, 1), (0, 2)]);
+        assert_eq!(graph.degree, vec![2, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_layout_pulls_linked_notes_together() {
+        let folders = vec![folder("Work", &["A", "B", "C", "D", "E", "F"])];
+        let mut links = LinkManager::new();
+        links.add_link((0, 0), (0, 5));
+        let graph = Graph::build(&folders, &links);
+
+        let mut layout = GraphLayout::new(graph.nodes.len());
+        while !layout.is_settled() {
+            layout.step(&graph);
+        }
+        let distance = |a: usize, b: usize| {
+            let (pa, pb) = (layout.positions[a], layout.positions[b]);
+            (pa[0] - pb[0]).hypot(pa[1] - pb[1])
+        };
+        for other in 1..5 {
+            assert!(distance(0, 5) < distance(0, other));
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::links::{replace_wiki_links, LinkResolver};
+use crate::note::Folder;
+use pulldown_cmark::{html, Options, Parser};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// What a site export wrote, for the status message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteSummary {
+    pub pages: usize,
+    pub images: usize,
+}
+
+pub struct HtmlExporter;
+
+impl HtmlExporter {
+    /// Render a note's Markdown as a standalone HTML page.
+    pub fn note_to_html(title: &str, content: &str) -> String {
+        let mut body = String::new();
+        html::push_html(&mut body, Parser::new_ext(content, markdown_options()));
+        page(title, &body)
+    }
+
+    pub fn export_note(title: &str, content: &str, output_path: &Path) -> Result<(), String> {
+        fs::write(output_path, Self::note_to_html(title, content))
+            .map_err(|e| format!("Failed to write {}: {}", output_path.display(), e))
+    }
+
+    /// Write the whole vault to `output_dir`: `index.html`, one page per note at
+    /// `<folder>/<note>.html` with `[[wiki links]]` pointing at each other (resolved
+    /// as the app does, so `[[Folder/Title]]` works and the same folder wins), and each
+    /// folder's `images/` copied alongside so relative image paths keep working.
+    /// Note bodies must already be loaded. Encrypted notes are left out.
+    pub fn export_site(folders: &[Folder], output_dir: &Path) -> Result<SiteSummary, String> {
+        let mut summary = SiteSummary {
+            pages: 0,
+            images: 0,
+        };
+
+        let resolver = LinkResolver::new(folders);
+
+        let mut index = String::from("<h1>Notes</h1>\n");
+        for (folder_idx, folder) in folders.iter().enumerate() {
+            let folder_dir = output_dir.join(dir_name(&folder.path));
+            fs::create_dir_all(&folder_dir)
+                .map_err(|e| format!("Failed to create {}: {}", folder_dir.display(), e))?;
+
+            index.push_str(&format!("<h2>{}</h2>\n<ul>\n", escape(&folder.name)));
+            for note in folder.notes.iter().filter(|n| !n.is_encrypted) {
+                let path = page_path(folder, &note.file_path);
+                // Pages sit one folder deep, so links between them go through the root
+                let content = link_wiki_links(&note.content, |name| {
+                    let (f, n) = resolver.resolve(name, folder_idx)?;
+                    let target_folder = &folders[f];
+                    let target = &target_folder.notes[n];
+                    (!target.is_encrypted)
+                        .then(|| format!("../{}", page_path(target_folder, &target.file_path)))
+                });
+                Self::export_note(&note.title, &content, &output_dir.join(&path))?;
+                index.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    encode_href(&path),
+                    escape(&note.title)
+                ));
+                summary.pages += 1;
+            }
+            index.push_str("</ul>\n");
+
+            summary.images += copy_images(&Path::new(&folder.path).join("images"), &folder_dir)?;
+        }
+
+        let index_path = output_dir.join("index.html");
+        fs::write(&index_path, page("Notes", &index))
+            .map_err(|e| format!("Failed to write {}: {}", index_path.display(), e))?;
+
+        Ok(summary)
+    }
+}
+
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body {{ max-width: 48em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; line-height: 1.5; }} \
+         img {{ max-width: 100%; }} pre {{ background: #f4f4f4; padding: 0.5em; overflow-x: auto; }}</style>\n\
+         </head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        body
+    )
+}
+
+fn dir_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "notes".to_string())
+}
+
+/// `<folder>/<note file stem>.html`, so page names match the files in the vault.
+fn page_path(folder: &Folder, file_path: &str) -> String {
+    let stem = Path::new(file_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "untitled".to_string());
+    format!("{}/{}.html", dir_name(&folder.path), stem)
+}
+
+/// Turn `[[Note]]` into a Markdown link when `resolve` knows the note; unknown
+/// targets are left as plain text.
+fn link_wiki_links(content: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+    replace_wiki_links(content, |name| {
+        resolve(name).map(|href| format!("[{}]({})", name, encode_href(&href)))
+    })
+}
+
+/// Percent-encode everything except unreserved characters and path separators.
+fn encode_href(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Copy `images_dir` into `dest_parent/images`, returning the number of files copied.
+fn copy_images(images_dir: &Path, dest_parent: &Path) -> Result<usize, String> {
+    if !images_dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut copied = 0;
+    for entry in WalkDir::new(images_dir).into_iter().flatten() {
+        let relative = match entry.path().strip_prefix(images_dir) {
+            Ok(relative) => relative,
+            Err(_) => continue,
+        };
+        let dest = dest_parent.join("images").join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest)
+                .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+        } else {
+            fs::copy(entry.path(), &dest)
+                .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn test_wiki_links_resolved() {
+        let linked = link_wiki_links("See [[Other Note]] and [[Missing]].", |name| {
+            (name == "Other Note").then(|| "../Work/Other Note.html".to_string())
+        });
+        assert_eq!(
+            linked,
+            "See [Other Note](../Work/Other%20Note.html) and Missing."
+        );
+    }
+
+    #[test]
+    fn test_export_linked_vault() {
+        let vault = tempfile::tempdir().unwrap();
+        let site = tempfile::tempdir().unwrap();
+
+        let mut storage = Storage::new(vault.path().to_string_lossy().to_string());
+        storage.create_folder("Work").unwrap();
+        storage.create_folder("Home").unwrap();
+        let work = storage
+            .folders
+            .iter()
+            .position(|f| f.name == "Work")
+            .unwrap();
+        let home = storage
+            .folders
+            .iter()
+            .position(|f| f.name == "Home")
+            .unwrap();
+        storage
+            .create_note(
+                work,
+                "Plan",
+                "# Plan\n\nSee [[Groceries]] and [[Home/Plan]].\n",
+            )
+            .unwrap();
+        storage
+            .create_note(home, "Plan", "Weekend [[Plan]]\n")
+            .unwrap();
+        storage
+            .create_note(
+                home,
+                "Groceries",
+                "Back to [[Work/Plan]]\n\n![Cart](images/cart.png)\n",
+            )
+            .unwrap();
+        let images = vault.path().join("Home").join("images");
+        fs::create_dir_all(&images).unwrap();
+        fs::write(images.join("cart.png"), b"png").unwrap();
+
+        let summary = HtmlExporter::export_site(&storage.folders, site.path()).unwrap();
+        assert_eq!(
+            summary,
+            SiteSummary {
+                pages: 3,
+                images: 1
+            }
+        );
+
+        let plan = fs::read_to_string(site.path().join("Work/Plan.html")).unwrap();
+        assert!(plan.contains("<a href=\"../Home/Groceries.html\">Groceries</a>"));
+        assert!(plan.contains("<a href=\"../Home/Plan.html\">Home/Plan</a>"));
+        let home_plan = fs::read_to_string(site.path().join("Home/Plan.html")).unwrap();
+        assert!(home_plan.contains("<a href=\"../Home/Plan.html\">Plan</a>"));
+        let groceries = fs::read_to_string(site.path().join("Home/Groceries.html")).unwrap();
+        assert!(groceries.contains("<a href=\"../Work/Plan.html\">Work/Plan</a>"));
+        assert!(groceries.contains("src=\"images/cart.png\""));
+        assert!(site.path().join("Home/images/cart.png").exists());
+
+        let index = fs::read_to_string(site.path().join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"Work/Plan.html\">Plan</a>"));
+        assert!(index.contains("<a href=\"Home/Groceries.html\">Groceries</a>"));
+    }
+}
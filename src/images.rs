@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::time::SystemTime;
 use image::DynamicImage;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,11 +24,13 @@ impl EmbeddedImage {
         }
     }
     
+    #[allow(dead_code)]
     pub fn with_caption(mut self, caption: String) -> Self {
         self.caption = caption;
         self
     }
     
+    #[allow(dead_code)]
     pub fn with_size(mut self, width: u32, height: u32) -> Self {
         self.width = Some(width);
         self.height = Some(height);
@@ -45,28 +50,35 @@ impl ImageManager {
         }
     }
     
+    #[allow(dead_code)]
     pub fn add_image(&mut self, path: String) -> usize {
         let image = EmbeddedImage::new(path);
         self.images.push(image);
         self.images.len() - 1
     }
     
+    #[allow(dead_code)]
     pub fn add_image_with_caption(&mut self, path: String, caption: String) -> usize {
         let image = EmbeddedImage::new(path).with_caption(caption);
         self.images.push(image);
         self.images.len() - 1
     }
     
+    #[allow(dead_code)]
     pub fn get_image(&self, index: usize) -> Option<&EmbeddedImage> {
         self.images.get(index)
     }
     
+    #[allow(dead_code)]
     pub fn remove_image(&mut self, index: usize) {
         if index < self.images.len() {
             self.images.remove(index);
         }
     }
     
+    /// Copy `source_path` into the note folder's `images/` directory, renaming it if
+    /// an image of that name is already there. A file already in `images/` is used
+    /// where it is.
     pub fn copy_image_to_note_folder(
         &self,
         source_path: &Path,
@@ -76,12 +88,22 @@ impl ImageManager {
         let images_dir = note_folder.join("images");
         fs::create_dir_all(&images_dir)?;
         
+        let already_there = source_path
+            .parent()
+            .and_then(|dir| dir.canonicalize().ok())
+            .is_some_and(|dir| images_dir.canonicalize().is_ok_and(|images| images == dir));
+        if already_there {
+            return Ok(source_path.to_path_buf());
+        }
+        
         // Get filename
-        let filename = source_path
-            .file_name()
-            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid filename"))?;
+        let stem = source_path
+            .file_stem()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid filename"))?
+            .to_string_lossy();
+        let extension = source_path.extension().unwrap_or_default().to_string_lossy();
         
-        let dest_path = images_dir.join(filename);
+        let dest_path = images_dir.join(unique_image_name(&images_dir, &stem, &extension));
         
         // Copy file
         fs::copy(source_path, &dest_path)?;
@@ -89,9 +111,69 @@ impl ImageManager {
         Ok(dest_path)
     }
     
+    /// Copy an image file (e.g. one dropped onto the editor) into the note folder and
+    /// return the Markdown reference to it. Files that aren't images are rejected.
+    pub fn import_image(&self, source_path: &Path, note_folder: &Path) -> Result<String, String> {
+        if !is_image_file(source_path) {
+            return Err(format!("{} is not an image", source_path.display()));
+        }
+        
+        let dest_path = self.copy_image_to_note_folder(source_path, note_folder)
+            .map_err(|e| format!("Failed to copy image: {}", e))?;
+        
+        let filename = dest_path.file_name().unwrap_or_default().to_string_lossy();
+        Ok(markdown_image_syntax(&EmbeddedImage::new(format!("images/{}", filename))))
+    }
+    
+    #[allow(dead_code)]
     pub fn load_image(&self, path: &Path) -> Result<DynamicImage, String> {
         image::open(path).map_err(|e| format!("Failed to load image: {}", e))
     }
+    
+    /// Save raw RGBA pixels (e.g. from the clipboard) as a PNG in the note folder's
+    /// `images/` directory. Returns the Markdown reference to insert into the note.
+    pub fn save_pasted_image(
+        &self,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        note_folder: &Path,
+    ) -> Result<String, String> {
+        let buffer = image::RgbaImage::from_raw(width, height, rgba.to_vec())
+            .ok_or_else(|| "Clipboard image has the wrong size".to_string())?;
+        
+        let images_dir = note_folder.join("images");
+        fs::create_dir_all(&images_dir)
+            .map_err(|e| format!("Failed to create images folder: {}", e))?;
+        
+        // Timestamped name, with a counter if several pastes land in the same second
+        let stem = format!("pasted-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        let filename = unique_image_name(&images_dir, &stem, "png");
+        
+        buffer.save(images_dir.join(&filename))
+            .map_err(|e| format!("Failed to save image: {}", e))?;
+        
+        Ok(markdown_image_syntax(&EmbeddedImage::new(format!("images/{}", filename))))
+    }
+}
+
+/// `stem.extension`, or `stem-1.extension`, `stem-2.extension`, ... if that name is
+/// already taken in `images_dir`.
+fn unique_image_name(images_dir: &Path, stem: &str, extension: &str) -> String {
+    let mut filename = format!("{}.{}", stem, extension);
+    let mut counter = 1;
+    while images_dir.join(&filename).exists() {
+        filename = format!("{}-{}.{}", stem, counter, extension);
+        counter += 1;
+    }
+    filename
+}
+
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| matches!(ext.to_lowercase().as_str(), "png" | "jpg" | "jpeg" | "gif" | "bmp" | "webp"))
+        .unwrap_or(false)
 }
 
 pub fn markdown_image_syntax(image: &EmbeddedImage) -> String {
@@ -102,45 +184,251 @@ pub fn markdown_image_syntax(image: &EmbeddedImage) -> String {
     }
 }
 
+/// An `![alt](path)` reference and the byte range it occupies in the note.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageRef {
+    pub alt: String,
+    pub path: String,
+    pub range: Range<usize>,
+}
+
+pub fn find_image_refs(content: &str) -> Vec<ImageRef> {
+    let mut refs = Vec::new();
+    let mut pos = 0;
+    
+    while let Some(offset) = content[pos..].find("![") {
+        let start = pos + offset;
+        let alt_start = start + 2;
+        
+        let Some(alt_len) = content[alt_start..].find(']') else {
+            break;
+        };
+        let alt_end = alt_start + alt_len;
+        
+        if !content[alt_end + 1..].starts_with('(') {
+            pos = alt_start;
+            continue;
+        }
+        let path_start = alt_end + 2;
+        
+        let Some(path_len) = content[path_start..].find(')') else {
+            break;
+        };
+        let end = path_start + path_len + 1;
+        
+        refs.push(ImageRef {
+            alt: content[alt_start..alt_end].to_string(),
+            path: content[path_start..end - 1].to_string(),
+            range: start..end,
+        });
+        pos = end;
+    }
+    
+    refs
+}
+
+#[allow(dead_code)]
 pub fn extract_images_from_markdown(content: &str) -> Vec<(String, String)> {
-    // Simple regex-like extraction of ![alt](path) patterns
-    let mut images = Vec::new();
-    let mut chars = content.chars().peekable();
-    
-    while let Some(ch) = chars.next() {
-        if ch == '!' {
-            if chars.peek() == Some(&'[') {
-                chars.next(); // consume '['
-                
-                // Extract alt text
-                let mut alt = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c == ']' {
-                        chars.next();
-                        break;
-                    }
-                    alt.push(chars.next().unwrap());
-                }
-                
-                // Check for '('
-                if chars.peek() == Some(&'(') {
-                    chars.next(); // consume '('
-                    
-                    // Extract path
-                    let mut path = String::new();
-                    while let Some(&c) = chars.peek() {
-                        if c == ')' {
-                            chars.next();
-                            break;
-                        }
-                        path.push(chars.next().unwrap());
-                    }
-                    
-                    images.push((alt, path));
-                }
-            }
+    find_image_refs(content)
+        .into_iter()
+        .map(|r| (r.alt, r.path))
+        .collect()
+}
+
+/// A note split for the preview: Markdown runs go to the Markdown viewer and
+/// images are drawn between them from the texture cache.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviewSegment<'a> {
+    Markdown(&'a str),
+    Image(ImageRef),
+}
+
+pub fn preview_segments(content: &str) -> Vec<PreviewSegment<'_>> {
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    
+    for image in find_image_refs(content) {
+        let text = &content[pos..image.range.start];
+        if !text.trim().is_empty() {
+            segments.push(PreviewSegment::Markdown(text));
         }
+        pos = image.range.end;
+        segments.push(PreviewSegment::Image(image));
     }
     
-    images
+    let rest = &content[pos..];
+    if !rest.trim().is_empty() {
+        segments.push(PreviewSegment::Markdown(rest));
+    }
+    
+    segments
+}
+
+/// Resolve an image reference relative to the folder of the note that contains it,
+/// never the directory the app was started from. Absolute paths and `file://` URLs
+/// are used as they are.
+pub fn resolve_image_path(note_path: &Path, reference: &str) -> PathBuf {
+    let reference = reference_path(reference);
+    let reference = Path::new(&reference);
+    if reference.is_absolute() {
+        return reference.to_path_buf();
+    }
+    note_path
+        .parent()
+        .map(|dir| dir.join(reference))
+        .unwrap_or_else(|| reference.to_path_buf())
+}
+
+/// The file an image reference's destination names: `<a b.png>` and
+/// `a%20b.png "Title"` both give `a b.png`.
+fn reference_path(reference: &str) -> String {
+    let reference = reference.trim();
+    let path = match reference.strip_prefix('<') {
+        Some(rest) => rest.split('>').next().unwrap_or(rest),
+        None if reference.ends_with('"') => reference
+            .rfind(" \"")
+            .map_or(reference, |title| reference[..title].trim_end()),
+        None => reference,
+    };
+    let path = path.strip_prefix("file://").unwrap_or(path);
+    path.replace("%20", " ")
+}
+
+/// Decoded images uploaded as textures, keyed by path. An entry is reused until the
+/// file's modification time changes, so the preview never re-decodes on a repaint.
+#[derive(Default)]
+pub struct ImageCache {
+    textures: HashMap<PathBuf, (SystemTime, Option<egui::TextureHandle>)>,
+}
+
+impl ImageCache {
+    /// The texture for `path`, or `None` if the file is missing or can't be decoded.
+    pub fn texture(&mut self, ctx: &egui::Context, path: &Path) -> Option<&egui::TextureHandle> {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        
+        let stale = self.textures.get(path).is_none_or(|(cached, _)| *cached != modified);
+        if stale {
+            let texture = image::open(path).ok().map(|image| {
+                let rgba = image.to_rgba8();
+                let size = [rgba.width() as usize, rgba.height() as usize];
+                let color_image = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+                ctx.load_texture(path.to_string_lossy(), color_image, egui::TextureOptions::LINEAR)
+            });
+            self.textures.insert(path.to_path_buf(), (modified, texture));
+        }
+        
+        self.textures.get(path).and_then(|(_, texture)| texture.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_find_image_refs() {
+        let content = "Intro ![Diagram](images/a.png) and ![](b.jpg)\n[not an image](c.png)";
+        let refs = find_image_refs(content);
+        
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0].alt, "Diagram");
+        assert_eq!(refs[0].path, "images/a.png");
+        assert_eq!(&content[refs[0].range.clone()], "![Diagram](images/a.png)");
+        assert_eq!(refs[1].alt, "");
+        assert_eq!(refs[1].path, "b.jpg");
+    }
+    
+    #[test]
+    fn test_save_pasted_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ImageManager::new();
+        let pixels = [255u8, 0, 0, 255].repeat(4);
+        
+        let first = manager.save_pasted_image(&pixels, 2, 2, dir.path()).unwrap();
+        let second = manager.save_pasted_image(&pixels, 2, 2, dir.path()).unwrap();
+        assert_ne!(first, second);
+        
+        let refs = find_image_refs(&first);
+        assert_eq!(refs.len(), 1);
+        assert!(refs[0].path.starts_with("images/pasted-"));
+        
+        let saved = manager.load_image(&resolve_image_path(&dir.path().join("note.md"), &refs[0].path)).unwrap();
+        assert_eq!((saved.width(), saved.height()), (2, 2));
+        
+        assert!(manager.save_pasted_image(&pixels, 3, 3, dir.path()).is_err());
+    }
+    
+    #[test]
+    fn test_import_dropped_image() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let note_dir = tempfile::tempdir().unwrap();
+        let manager = ImageManager::new();
+        
+        let source = source_dir.path().join("Screenshot.PNG");
+        fs::write(&source, b"not decoded here").unwrap();
+        let markdown = manager.import_image(&source, note_dir.path()).unwrap();
+        assert_eq!(markdown, "![Image](images/Screenshot.PNG)");
+        assert!(note_dir.path().join("images").join("Screenshot.PNG").exists());
+        
+        // Same name again keeps the first copy; dropping the copy itself reuses it
+        let again = manager.import_image(&source, note_dir.path()).unwrap();
+        assert_eq!(again, "![Image](images/Screenshot-1.PNG)");
+        let existing = note_dir.path().join("images").join("Screenshot.PNG");
+        assert_eq!(manager.import_image(&existing, note_dir.path()).unwrap(), markdown);
+        assert_eq!(fs::read_dir(note_dir.path().join("images")).unwrap().count(), 2);
+        
+        let text_file = source_dir.path().join("notes.txt");
+        fs::write(&text_file, "hello").unwrap();
+        assert!(manager.import_image(&text_file, note_dir.path()).is_err());
+        assert!(!note_dir.path().join("images").join("notes.txt").exists());
+    }
+    
+    #[test]
+    fn test_preview_segments_split_around_images() {
+        let content = "# Title\n\n![Chart](chart.png)\n\nAfter the chart.";
+        let segments = preview_segments(content);
+        
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], PreviewSegment::Markdown("# Title\n\n"));
+        match &segments[1] {
+            PreviewSegment::Image(image) => assert_eq!(image.path, "chart.png"),
+            other => panic!("expected image, got {:?}", other),
+        }
+        assert_eq!(segments[2], PreviewSegment::Markdown("\n\nAfter the chart."));
+        
+    }
+    
+    #[test]
+    fn test_resolve_relative_and_absolute_image_paths() {
+        let note = Path::new("notes_data/Work/report.md");
+        assert_eq!(
+            resolve_image_path(note, "images/chart.png"),
+            Path::new("notes_data/Work/images/chart.png")
+        );
+        assert_eq!(
+            resolve_image_path(note, "../Shared/logo.png"),
+            Path::new("notes_data/Work/../Shared/logo.png")
+        );
+        assert_eq!(
+            resolve_image_path(note, "<images/my chart.png>"),
+            Path::new("notes_data/Work/images/my chart.png")
+        );
+        assert_eq!(
+            resolve_image_path(note, "images/my%20chart.png \"Q3 chart\""),
+            Path::new("notes_data/Work/images/my chart.png")
+        );
+        
+        let absolute = std::env::temp_dir().join("chart.png");
+        let reference = absolute.to_string_lossy();
+        assert_eq!(resolve_image_path(note, &reference), absolute);
+        assert_eq!(resolve_image_path(note, &format!("file://{}", reference)), absolute);
+        
+        // The same note opened from anywhere finds its images in its own folder
+        let vault = tempfile::tempdir().unwrap();
+        let note = vault.path().join("Work").join("report.md");
+        assert_eq!(
+            resolve_image_path(&note, "images/chart.png"),
+            vault.path().join("Work").join("images").join("chart.png")
+        );
+    }
 }
@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::note::Folder;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
 pub struct NoteLink {
     pub source_folder: usize,
     pub source_note: usize,
@@ -34,16 +41,17 @@ impl LinkManager {
         // Add to outgoing links
         self.outgoing_links
             .entry(source)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(target);
         
         // Add to incoming links (backlinks)
         self.incoming_links
             .entry(target)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(source);
     }
     
+    #[allow(dead_code)]
     pub fn remove_link(
         &mut self,
         source: (usize, usize),
@@ -73,45 +81,14 @@ impl LinkManager {
     }
     
     pub fn scan_note_for_links(&mut self, content: &str, _source: (usize, usize)) -> Vec<String> {
-        // Extract [[Note Name]] style links
-        let mut link_names = Vec::new();
-        let mut chars = content.chars().peekable();
-        
-        while let Some(ch) = chars.next() {
-            if ch == '[' {
-                if chars.peek() == Some(&'[') {
-                    chars.next(); // consume second '['
-                    
-                    // Extract link text
-                    let mut link_text = String::new();
-                    let mut closed = false;
-                    
-                    while let Some(c) = chars.next() {
-                        if c == ']' {
-                            if chars.peek() == Some(&']') {
-                                chars.next(); // consume second ']'
-                                closed = true;
-                                break;
-                            }
-                        }
-                        link_text.push(c);
-                    }
-                    
-                    if closed && !link_text.is_empty() {
-                        link_names.push(link_text);
-                    }
-                }
-            }
-        }
-        
-        link_names
+        wiki_link_names(content)
     }
     
     pub fn rebuild_links_for_note(
         &mut self,
         source: (usize, usize),
         content: &str,
-        note_name_to_id: &HashMap<String, (usize, usize)>,
+        resolver: &LinkResolver,
     ) {
         // Clear existing outgoing links for this note
         if let Some(old_targets) = self.outgoing_links.remove(&source) {
@@ -128,7 +105,7 @@ impl LinkManager {
         
         // Add new links
         for link_name in link_names {
-            if let Some(&target) = note_name_to_id.get(&link_name) {
+            if let Some(target) = resolver.resolve(&link_name, source.0) {
                 self.add_link(source, target);
             }
         }
@@ -139,21 +116,301 @@ impl LinkManager {
         let incoming = self.get_backlinks(note).len();
         (outgoing, incoming)
     }
+    
+    /// Links between every note in the vault, resolved as `LinkResolver` does. Note bodies
+    /// must be loaded; encrypted notes can be linked to but their links aren't read.
+    pub fn build(folders: &[Folder]) -> Self {
+        let mut names = Vec::new();
+        for (folder_idx, folder) in folders.iter().enumerate() {
+            for (note_idx, note) in folder.notes.iter().enumerate() {
+                if !note.is_encrypted {
+                    names.push(((folder_idx, note_idx), wiki_link_names(&note.content)));
+                }
+            }
+        }
+        Self::from_link_names(folders, &names)
+    }
+    
+    /// Links from each source note to the notes its `[[link]]` names resolve to.
+    /// Names with no note are dropped.
+    pub fn from_link_names(folders: &[Folder], names: &[((usize, usize), Vec<String>)]) -> Self {
+        let resolver = LinkResolver::new(folders);
+        let mut manager = Self::new();
+        for (source, links) in names {
+            for link in links {
+                if let Some(target) = resolver.resolve(link, source.0) {
+                    manager.add_link(*source, target);
+                }
+            }
+        }
+        manager
+    }
+}
+
+/// Finds the note a `[[link]]` names. Titles match exactly and can repeat across
+/// folders, so `[[Folder/Title]]` names the folder too.
+#[derive(Debug, Clone, Default)]
+pub struct LinkResolver {
+    titles: HashMap<String, Vec<(usize, usize)>>, // In vault order
+    folders: HashMap<String, usize>,
+}
+
+impl LinkResolver {
+    pub fn new(folders: &[Folder]) -> Self {
+        let mut resolver = Self::default();
+        for (folder_idx, folder) in folders.iter().enumerate() {
+            resolver.folders.insert(folder.name.clone(), folder_idx);
+            for (note_idx, note) in folder.notes.iter().enumerate() {
+                resolver.titles.entry(note.title.clone()).or_default().push((folder_idx, note_idx));
+            }
+        }
+        resolver
+    }
+    
+    /// Every note `link` could mean, in vault order. A `Folder/Title` link means only
+    /// the note with that title in that folder, if there is one; otherwise the whole
+    /// link is taken as a title, since titles may hold a `/`.
+    pub fn candidates(&self, link: &str) -> Vec<(usize, usize)> {
+        let link = link.trim();
+        if let Some((folder, title)) = link.split_once('/') {
+            if let Some(&folder_idx) = self.folders.get(folder.trim()) {
+                let qualified: Vec<_> = self.matches(title.trim())
+                    .filter(|&(f, _)| f == folder_idx)
+                    .collect();
+                if !qualified.is_empty() {
+                    return qualified;
+                }
+            }
+        }
+        self.matches(link).collect()
+    }
+    
+    /// The note `link` means when written in a note in `source_folder`: the only
+    /// candidate, else one in the same folder, else the first in vault order.
+    pub fn resolve(&self, link: &str, source_folder: usize) -> Option<(usize, usize)> {
+        let candidates = self.candidates(link);
+        candidates.iter()
+            .find(|&&(f, _)| f == source_folder)
+            .or(candidates.first())
+            .copied()
+    }
+    
+    /// The folder a `Folder/Title` link names and the title in it, if that folder exists.
+    pub fn split_qualified<'a>(&self, link: &'a str) -> Option<(usize, &'a str)> {
+        let (folder, title) = link.trim().split_once('/')?;
+        Some((*self.folders.get(folder.trim())?, title.trim()))
+    }
+    
+    fn matches(&self, title: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.titles.get(title).into_iter().flatten().copied()
+    }
+}
+
+/// The `[[link]]` names each note held when it was last read, keyed by file path
+/// with the file's modification time then, so a startup scan only has to reread
+/// notes changed since. Kept in the vault as `links.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkIndex {
+    notes: HashMap<String, IndexedLinks>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct IndexedLinks {
+    mtime: SystemTime,
+    links: Vec<String>,
+}
+
+impl LinkIndex {
+    /// What the note at `file_path` linked to, unless it's been modified since.
+    pub fn get(&self, file_path: &str, mtime: Option<SystemTime>) -> Option<&[String]> {
+        let entry = self.notes.get(file_path)?;
+        (Some(entry.mtime) == mtime).then_some(entry.links.as_slice())
+    }
+    
+    /// Record the links read from `file_path` as it was at `mtime`. Without a
+    /// modification time there is nothing to check the entry against later.
+    pub fn insert(&mut self, file_path: &str, mtime: Option<SystemTime>, links: Vec<String>) {
+        match mtime {
+            Some(mtime) => {
+                self.notes.insert(file_path.to_string(), IndexedLinks { mtime, links });
+            }
+            None => {
+                self.notes.remove(file_path);
+            }
+        }
+    }
+    
+    /// Drop entries for notes that no longer exist.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.notes.retain(|file_path, _| keep(file_path));
+    }
+    
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string(self)?;
+        fs::write(path, json)
+    }
+    
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// The names in each `[[Note Name]]` link in `content`, in order.
+pub fn wiki_link_names(content: &str) -> Vec<String> {
+    // Extract [[Note Name]] style links
+    let mut link_names = Vec::new();
+    let mut chars = content.chars().peekable();
+    
+    while let Some(ch) = chars.next() {
+        if ch == '[' && chars.peek() == Some(&'[') {
+            chars.next(); // consume second '['
+            
+            // Extract link text
+            let mut link_text = String::new();
+            let mut closed = false;
+            
+            while let Some(c) = chars.next() {
+                if c == ']' && chars.peek() == Some(&']') {
+                    chars.next(); // consume second ']'
+                    closed = true;
+                    break;
+                }
+                link_text.push(c);
+            }
+            
+            if closed && !link_text.is_empty() {
+                link_names.push(link_text);
+            }
+        }
+    }
+    
+    link_names
+}
+
+/// Notes with no links in or out, in vault order. Encrypted notes are left out
+/// since their links can't be read.
+pub fn find_orphan_notes(folders: &[Folder]) -> Vec<(usize, usize)> {
+    let links = LinkManager::build(folders);
+    let mut orphans = Vec::new();
+    for (folder_idx, folder) in folders.iter().enumerate() {
+        for (note_idx, note) in folder.notes.iter().enumerate() {
+            if !note.is_encrypted && links.get_link_count((folder_idx, note_idx)) == (0, 0) {
+                orphans.push((folder_idx, note_idx));
+            }
+        }
+    }
+    orphans
+}
+
+/// Replace each `[[Note]]` with `replace(name)`, or with the bare name when that
+/// returns `None`. Links spanning lines and empty `[[]]` are left untouched.
+pub fn replace_wiki_links(content: &str, replace: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    
+    while let Some(start) = rest.find("[[") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("]]") {
+            Some(end) if !after[..end].is_empty() && !after[..end].contains('\n') => {
+                let name = &after[..end];
+                match replace(name) {
+                    Some(replacement) => out.push_str(&replacement),
+                    None => out.push_str(name),
+                }
+                rest = &after[end + 2..];
+            }
+            _ => {
+                out.push_str("[[");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `content` with each `[[Note]]` as a Markdown link to `wiki:<n>`, and the linked
+/// names in order of `n`, so the preview can tell which one was clicked.
+pub fn wiki_links_as_markdown(content: &str) -> (String, Vec<String>) {
+    let names = std::cell::RefCell::new(Vec::new());
+    let markdown = replace_wiki_links(content, |name| {
+        let mut names = names.borrow_mut();
+        names.push(name.to_string());
+        Some(format!("[{}](wiki:{})", name, names.len() - 1))
+    });
+    (markdown, names.into_inner())
 }
 
 pub fn format_wiki_link(note_title: &str) -> String {
     format!("[[{}]]", note_title)
 }
 
-pub fn insert_wiki_link_at_cursor(content: &mut String, cursor_pos: usize, note_title: &str) {
+/// Insert `[[note_title]]` at character offset `cursor` (the end if past it) and
+/// return the character offset just after the link, where the cursor goes next.
+pub fn insert_wiki_link_at_cursor(content: &mut String, cursor: usize, note_title: &str) -> usize {
     let link = format_wiki_link(note_title);
-    content.insert_str(cursor_pos, &link);
+    let at = content.char_indices().nth(cursor).map_or(content.len(), |(i, _)| i);
+    content.insert_str(at, &link);
+    content[..at].chars().count() + link.chars().count()
+}
+
+/// Move `content[range]` out into a note called `title`. Returns the original with
+/// the selection replaced by `[[title]]`, and the new note's content. Whitespace
+/// around the selection stays where it was, so the link sits on the selection's
+/// line. `None` if the range is out of bounds, splits a character, or holds only
+/// whitespace.
+pub fn extract_selection(content: &str, range: std::ops::Range<usize>, title: &str) -> Option<(String, String)> {
+    let selected = content.get(range.clone())?;
+    let trimmed = selected.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let start = range.start + (selected.len() - selected.trim_start().len());
+    let end = start + trimmed.len();
+    
+    let original = format!("{}{}{}", &content[..start], format_wiki_link(title), &content[end..]);
+    Some((original, format!("{}\n", trimmed)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_wiki_links_as_markdown() {
+        let (markdown, names) = wiki_links_as_markdown("See [[Idea]] and [[Other Note]], not [[]].");
+        assert_eq!(markdown, "See [Idea](wiki:0) and [Other Note](wiki:1), not [[]].");
+        assert_eq!(names, vec!["Idea", "Other Note"]);
+    }
+    
+    #[test]
+    fn test_insert_wiki_link_at_cursor() {
+        let mut content = String::from("Café notes: see");
+        assert_eq!(insert_wiki_link_at_cursor(&mut content, 4, "Menu"), 12);
+        assert_eq!(content, "Café[[Menu]] notes: see");
+        
+        let end = insert_wiki_link_at_cursor(&mut content, 100, "Other Note");
+        assert_eq!(content, "Café[[Menu]] notes: see[[Other Note]]");
+        assert_eq!(end, content.chars().count());
+    }
+    
+    #[test]
+    fn test_extract_selection() {
+        let content = "# Ideas\n\nIntro.\n\n  Spaced repetition works.\nIt beats cramming.\n\nOutro.\n";
+        let start = content.find("  Spaced").unwrap();
+        let end = content.find("Outro").unwrap();
+        
+        let (original, extracted) = extract_selection(content, start..end, "Spaced Repetition").unwrap();
+        assert_eq!(original, "# Ideas\n\nIntro.\n\n  [[Spaced Repetition]]\n\nOutro.\n");
+        assert_eq!(extracted, "Spaced repetition works.\nIt beats cramming.\n");
+        
+        assert_eq!(extract_selection(content, 7..9, "Empty"), None);
+        assert_eq!(extract_selection(content, 0..500, "Too far"), None);
+        assert_eq!(extract_selection("café", 0..4, "Split"), None);
+    }
+    
     #[test]
     fn test_link_extraction() {
         let mut manager = LinkManager::new();
@@ -166,6 +423,60 @@ mod tests {
         assert_eq!(links[1], "Note B");
     }
     
+    #[test]
+    fn test_build_populates_outgoing_and_incoming() {
+        use crate::note::Note;
+        
+        let note = |title: &str, content: &str| {
+            let mut note = Note::new(title.to_string(), format!("{}.md", title));
+            note.content = content.to_string();
+            note
+        };
+        let mut work = Folder::new("Work".to_string(), "Work".to_string());
+        work.add_note(note("Plan", "See [[Budget]] and [[Team]]."));
+        work.add_note(note("Budget", "Part of [[Plan]]. Also [[Missing]]."));
+        let mut home = Folder::new("Home".to_string(), "Home".to_string());
+        home.add_note(note("Team", "Back to [[Plan]]"));
+        home.add_note(note("Loose", "No links here"));
+        
+        let links = LinkManager::build(&[work, home]);
+        assert_eq!(links.outgoing_links[&(0, 0)], vec![(0, 1), (1, 0)]);
+        assert_eq!(links.outgoing_links[&(0, 1)], vec![(0, 0)]);
+        assert_eq!(links.outgoing_links[&(1, 0)], vec![(0, 0)]);
+        assert_eq!(links.incoming_links[&(0, 0)], vec![(0, 1), (1, 0)]);
+        assert_eq!(links.incoming_links[&(0, 1)], vec![(0, 0)]);
+        assert_eq!(links.incoming_links[&(1, 0)], vec![(0, 0)]);
+        assert_eq!(links.get_link_count((1, 1)), (0, 0));
+    }
+    
+    #[test]
+    fn test_same_title_links_resolve_by_folder() {
+        use crate::note::Note;
+        
+        let mut work = Folder::new("Work".to_string(), "Work".to_string());
+        let mut home = Folder::new("Home".to_string(), "Home".to_string());
+        for folder in [&mut work, &mut home] {
+            let path = format!("{}/Notes.md", folder.path);
+            folder.add_note(Note::new("Notes".to_string(), path));
+        }
+        work.add_note(Note::new("Plan".to_string(), "Work/Plan.md".to_string()));
+        let folders = [work, home];
+        let resolver = LinkResolver::new(&folders);
+        
+        assert_eq!(resolver.candidates("Notes"), vec![(0, 0), (1, 0)]);
+        assert_eq!(resolver.candidates("Home/Notes"), vec![(1, 0)]);
+        assert_eq!(resolver.resolve("Home/Notes", 0), Some((1, 0)));
+        assert_eq!(resolver.resolve(" Work/Notes ", 1), Some((0, 0)));
+        // A bare title prefers the linking note's own folder
+        assert_eq!(resolver.resolve("Notes", 1), Some((1, 0)));
+        assert_eq!(resolver.resolve("Home/Plan", 1), None);
+        assert_eq!(resolver.resolve("Nowhere/Plan", 1), None);
+        
+        let links = LinkManager::from_link_names(&folders, &[((0, 1), vec!["Home/Notes".to_string()])]);
+        assert_eq!(links.get_backlinks((1, 0)), vec![(0, 1)]);
+        assert!(links.get_backlinks((0, 0)).is_empty());
+    }
+    
     #[test]
     fn test_backlinks() {
         let mut manager = LinkManager::new();
@@ -178,4 +489,22 @@ mod tests {
         assert!(backlinks.contains(&(0, 0)));
         assert!(backlinks.contains(&(0, 1)));
     }
+    
+    #[test]
+    fn test_find_orphan_notes() {
+        use crate::note::Note;
+        
+        let mut folder = Folder::new("Zettel".to_string(), "Zettel".to_string());
+        for (title, content) in [
+            ("Idea", "Builds on [[Source]]"),
+            ("Source", "Original reading notes"),
+            ("Loose", "Mentions [[Nowhere]] only"),
+        ] {
+            let mut note = Note::new(title.to_string(), format!("Zettel/{}.md", title));
+            note.content = content.to_string();
+            folder.add_note(note);
+        }
+        
+        assert_eq!(find_orphan_notes(&[folder]), vec![(0, 2)]);
+    }
 }
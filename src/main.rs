@@ -3,28 +3,57 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 mod autocomplete;
+mod cli;
+mod config;
+mod docx_export;
+mod emoji;
 mod encryption;
+mod error;
+mod export;
+mod folder_tree;
+mod frontmatter;
+mod graph;
+mod html_export;
 mod images;
 mod links;
+mod markdown_edit;
+mod markdown_export;
+mod markdown_format;
+mod markdown_refs;
 mod note;
 mod pdf_export;
+mod recent;
+mod scroll_sync;
 mod search;
 mod spellcheck;
 mod storage;
+mod sync;
 mod tags;
+mod tasks;
+mod templates;
 mod theme;
+mod toast;
 mod version_control;
+mod watcher;
 
 use autocomplete::Autocomplete;
-use encryption::Encryption;
-use links::LinkManager;
-use search::FuzzySearch;
-use spellcheck::SpellChecker;
+use config::AppConfig;
+use encryption::{Encryption, ENCRYPTED_PLACEHOLDER};
+use export::ExporterRegistry;
+use folder_tree::FolderTreeState;
+use links::{LinkIndex, LinkManager, LinkResolver};
+use note::SortOrder;
+use recent::RecentNotes;
+use search::{FuzzySearch, SavedSearch, SearchScope, SearchWorker};
+use spellcheck::{Misspelling, SpellCache, SpellChecker};
 use std::path::PathBuf;
-use storage::Storage;
+use std::time::SystemTime;
+use storage::{ArchivedNote, Storage};
 use tags::TagManager;
-use theme::ThemeManager;
-use version_control::VersionControl;
+use templates::TemplateManager;
+use theme::{Theme, ThemeManager};
+use toast::{ToastLevel, Toasts};
+use version_control::{CommitJob, CommitPolicy, CommitWorker, VersionControl};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum EditorFont {
@@ -50,7 +79,7 @@ impl EditorFont {
         ]
     }
 
-    fn to_text_style(&self) -> egui::TextStyle {
+    fn to_text_style(self) -> egui::TextStyle {
         match self {
             EditorFont::Monospace => egui::TextStyle::Monospace,
             EditorFont::Proportional => egui::TextStyle::Body,
@@ -59,24 +88,160 @@ impl EditorFont {
     }
 }
 
+const THEME_PREFERENCES_PATH: &str = "./theme.json";
+const TAGS_FILE: &str = "tags.json";
+const RECENT_FILE: &str = "recent.json";
+const FOLDER_TREE_FILE: &str = "folders.json";
+const LINKS_FILE: &str = "links.json";
+/// How often the open note's unsaved edits are copied to its draft.
+const DRAFT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Icons offered for notes in the editor header's picker.
+const NOTE_ICONS: [&str; 12] = [
+    "⭐", "📌", "🔥", "✅", "❗", "💡", "📚", "🚀", "📝", "🐛", "❤", "🎯",
+];
+/// Sidebar swatch colors offered in the picker.
+const NOTE_COLORS: [(&str, [u8; 3]); 6] = [
+    ("Red", [220, 70, 70]),
+    ("Orange", [230, 145, 50]),
+    ("Yellow", [220, 200, 60]),
+    ("Green", [80, 180, 90]),
+    ("Blue", [70, 130, 220]),
+    ("Purple", [160, 100, 210]),
+];
+
+/// Short label for a vault in the switcher: its directory name.
+fn vault_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+/// The selected folder, or the inbox (created if missing) when none is selected.
+fn new_note_folder(
+    storage: &mut Storage,
+    selected: Option<usize>,
+    inbox: &str,
+) -> Result<usize, String> {
+    if let Some(folder_idx) = selected {
+        return Ok(folder_idx);
+    }
+    let inbox = inbox.trim();
+    if inbox.is_empty() {
+        return Err("No folder selected and no inbox folder set".to_string());
+    }
+    storage
+        .find_or_create_folder(inbox)
+        .map_err(|e| format!("Failed to create inbox folder: {}", e))
+}
+
+/// Insert `text` into `content` at byte offset `at`, returning the offset just past
+/// it. An offset inside a multibyte character moves back to that character's
+/// start, and one past the end appends.
+fn insert_at_byte(content: &mut String, at: usize, text: &str) -> usize {
+    let mut at = at.min(content.len());
+    while !content.is_char_boundary(at) {
+        at -= 1;
+    }
+    content.insert_str(at, text);
+    at + text.len()
+}
+
+/// Whether the editor's `buffer` has edits not in `saved`, the note's content as
+/// last loaded or saved. A note that no longer exists can't hold them either.
+fn is_dirty(buffer: &str, saved: Option<&str>) -> bool {
+    saved.is_none_or(|saved| saved != buffer)
+}
+
+/// Where words added to the spell-check dictionary are kept, beside the config file.
+fn user_dictionary_path(config_path: Option<&std::path::Path>) -> Option<PathBuf> {
+    Some(
+        config_path?
+            .parent()?
+            .join(spellcheck::USER_DICTIONARY_FILE),
+    )
+}
+
+/// A small filled circle in a note's color.
+fn color_swatch(ui: &mut egui::Ui, rgb: [u8; 3], size: f32, sense: egui::Sense) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), sense);
+    let [r, g, b] = rgb;
+    ui.painter()
+        .circle_filled(rect.center(), size / 2.0, egui::Color32::from_rgb(r, g, b));
+    if response.hovered() && sense.interactive() {
+        ui.painter()
+            .circle_stroke(rect.center(), size / 2.0, ui.visuals().selection.stroke);
+    }
+    response
+}
+
+/// Two-column `name | count` rows for the statistics panel.
+fn stat_grid(ui: &mut egui::Ui, id: &str, rows: &[(String, usize)]) {
+    egui::Grid::new(id)
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (name, count) in rows {
+                ui.label(name);
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+}
+
 fn main() -> Result<(), eframe::Error> {
+    let config_path = AppConfig::default_path();
+    let config = config_path
+        .as_deref()
+        .and_then(|path| AppConfig::load(path).ok())
+        .unwrap_or_default();
+
+    // A command runs headless, e.g. from a script or cron job
+    let mut read_only = false;
+    if std::env::args_os().len() > 1 {
+        let cli = <cli::Cli as clap::Parser>::parse();
+        read_only = cli.read_only;
+        if let Some(command) = &cli.command {
+            let vault = cli.vault.clone().unwrap_or(config.vault_path.clone());
+            match cli::run(command, &vault) {
+                Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+                Err(e) => {
+                    eprintln!("✗ {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(config.window.size)
+        .with_min_inner_size([800.0, 600.0]);
+    if let Some(position) = config.window.position {
+        viewport = viewport.with_position(position);
+    }
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1200.0, 800.0])
-            .with_min_inner_size([800.0, 600.0]),
+        viewport,
         ..Default::default()
     };
 
     eframe::run_native(
         "Notetaking App",
         options,
-        Box::new(|_cc| Ok(Box::new(NoteTakingApp::new()))),
+        Box::new(|cc| {
+            Ok(Box::new(NoteTakingApp::new(
+                &cc.egui_ctx,
+                config,
+                config_path,
+                read_only,
+            )))
+        }),
     )
 }
 
 struct NoteTakingApp {
     storage: Arc<Mutex<Storage>>,
-    search: FuzzySearch,
+    read_only_launch: bool, // Launched with --read-only; unlike the setting, not saved
 
     // Enhanced features
     theme_manager: ThemeManager,
@@ -84,15 +249,30 @@ struct NoteTakingApp {
     tag_manager: TagManager,
     link_manager: LinkManager,
     version_control: Option<VersionControl>,
+    #[allow(dead_code)]
     autocomplete: Autocomplete,
     spellcheck: SpellChecker,
 
     // UI State
     selected_folder: Option<usize>,
     selected_note: Option<usize>,
+    multi_selection: Vec<(usize, usize)>, // Ctrl/Shift-clicked notes, in the order picked
+    selection_anchor: Option<(usize, usize)>, // Where a Shift-click range starts
     current_note_content: String,
     search_query: String,
     search_results: Vec<(usize, usize)>, // (folder_idx, note_idx)
+    search_total: usize,                 // matches before the result cap
+    search_worker: SearchWorker,
+    // Failures and confirmations shown briefly in the corner
+    toasts: Toasts,
+    emoji_filter: String,
+
+    // Smart folders: the open one's index in `config.saved_searches` and its results
+    open_smart_folder: Option<(usize, Vec<(usize, usize)>)>,
+    show_save_search_dialog: bool,
+    saved_search_name: String,
+    saved_search_scope: SearchScope,
+    saved_search_tag: Option<String>,
 
     // Folder management
     new_folder_name: String,
@@ -101,30 +281,49 @@ struct NoteTakingApp {
     // Note management
     new_note_title: String,
     show_new_note_dialog: bool,
+    template_manager: TemplateManager,
+    selected_template: Option<usize>,
 
     // UI flags
     sidebar_open: bool,
+    window_checked: bool, // restored position checked against the monitor
     show_markdown_preview: bool,
+    scroll_sync: scroll_sync::ScrollSync, // Lines up the split editor and preview
+    presenting: bool,                     // read-only full-screen preview (F11)
+    was_fullscreen: bool,                 // window state to restore when presenting ends
 
     // Theme management
     show_theme_dialog: bool,
+    show_theme_editor: bool,
+    theme_draft: Theme,
+    window_focused: bool,
 
     // Tag management
     show_tag_dialog: bool,
     new_tag_name: String,
     selected_tag_filter: Option<usize>,
+    #[allow(dead_code)]
     show_tag_editor: bool,
+    renaming_tag: Option<usize>,
+    rename_tag_input: String,
+    rename_tag_error: Option<String>,
 
     // Encryption
     show_encryption_dialog: bool,
     encryption_password: String,
     confirm_password: String,
+    password_hint: String, // Given when encrypting a note; not secret
+    show_unlock_dialog: bool,
+    unlocked_notes: std::collections::HashMap<String, UnlockedNote>, // by file path
+    folder_password: Option<(usize, bool)>, // Folder asking for a password; true to encrypt it
 
     // Export
     show_export_dialog: bool,
-    export_format: ExportFormat,
+    exporters: ExporterRegistry,
+    export_format: String, // Extension of the chosen exporter
 
     // Links panel
+    #[allow(dead_code)]
     show_links_panel: bool,
 
     // Version history
@@ -132,77 +331,376 @@ struct NoteTakingApp {
     note_versions: Vec<version_control::Version>,
     selected_version: Option<usize>,
     version_timeline_position: f32, // 0.0 to 1.0 for slider
+    version_diff: Option<String>,
+    save_conflict: Option<SaveConflict>,
+    commit_worker: Option<CommitWorker>,
+    sync_worker: Option<sync::SyncWorker>, // The cloud sync in progress
+    last_commit_error: Option<String>,
+    uncommitted_notes: Vec<(PathBuf, String)>, // (file path, title) saved since the last commit
+    uncommitted_since: Option<std::time::Instant>,
+    image_cache: images::ImageCache,
+    editor_id: Option<egui::Id>,
+    note_watcher: Option<watcher::NoteWatcher>,
+    open_tabs: Vec<NoteTab>,
+    active_tab: Option<usize>,
+    tab_close_prompt: Option<usize>,
+    // Quitting or switching vaults while tabs have unsaved edits
+    leave_prompt: Option<LeaveAction>,
+    quit_confirmed: bool,
+    show_outline: bool,
+    show_tasks_panel: bool,
+    show_properties_panel: bool,
+    new_property_key: String,
+    new_property_value: String,
+    show_task_dashboard: bool,
+    open_tasks: Vec<OpenTask>,
+    scroll_to_offset: Option<usize>,
+    recent_notes: RecentNotes,
+    folder_tree: FolderTreeState,
+
+    // In-note find (Ctrl+F in the editor)
+    find_open: bool,
+    find_focus_requested: bool,
+    find_query: String,
+    find_case_sensitive: bool,
+    find_current: usize,
+    find_jump: Option<(usize, usize)>, // byte range to select and scroll to
+    find_cache: search::FindCache,
+    note_sort_order: SortOrder,
 
     // Images
+    #[allow(dead_code)]
     show_image_dialog: bool,
 
     // Statistics
     show_statistics: bool,
+    statistics: Option<NoteStatistics>, // Computed when the panel opens
+    orphan_notes: Option<Vec<(usize, usize)>>, // Shown while Some
+    duplicate_notes: Option<Vec<Vec<(usize, usize)>>>, // Shown while Some
+    archived_notes: Option<Vec<ArchivedNote>>, // Shown while Some
+    graph_view: Option<(graph::Graph, graph::GraphLayout)>, // Shown while Some
 
     // Settings
     show_settings: bool,
+    config: AppConfig,
+    config_path: Option<PathBuf>,
+    vault_path_input: String,
     auto_save_enabled: bool,
     auto_save_interval: f32,
     last_save_time: std::time::Instant,
+    last_draft_time: std::time::Instant,
+    last_backup_time: std::time::Instant,
+    backup_job: Option<std::thread::JoinHandle<std::io::Result<Option<PathBuf>>>>, // Scheduled backup in progress
+    last_draft: Option<((usize, usize), String)>, // Note and text last written to a draft
+    draft_recovery: Option<Vec<(usize, usize)>>,  // Shown while Some
+    #[allow(dead_code)]
     autocomplete_enabled: bool,
     spellcheck_enabled: bool,
 
     // Font and zoom settings
     current_font: EditorFont,
+    #[allow(dead_code)]
     font_size: f32,
     zoom_level: f32, // 1.0 = 100%, 1.5 = 150%, etc.
 
     // Autocomplete state
+    #[allow(dead_code)]
     autocomplete_suggestions: Vec<String>,
+    #[allow(dead_code)]
     show_autocomplete: bool,
 
     // Spell check state
-    misspelled_words: Vec<(usize, usize, String)>,
+    spelling: SpellCache,
+    spell_menu: Option<(Misspelling, Vec<String>)>, // Right-clicked word and its suggestions
+    extract_selection: Option<(std::ops::Range<usize>, String)>, // Byte range and text to extract
+    extract_title: String,
+    // A clicked `[[link]]` with no note yet, the note it was clicked in, and the
+    // folder picked for the new note
+    link_note: Option<(String, (usize, usize))>,
+    link_choices: Option<(String, Vec<(usize, usize)>)>, // A link several notes match, to pick from
+    link_note_folder: usize,
 
     // Favorites
     favorite_notes: Vec<(usize, usize)>,
+    #[allow(dead_code)]
     show_favorites: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum ExportFormat {
-    PDF,
-    Markdown,
-    PlainText,
+/// Everything tied to one notes directory; rebuilt when the vault changes.
+struct VaultState {
+    storage: Storage,
+    tag_manager: TagManager,
+    recent_notes: RecentNotes,
+    folder_tree: FolderTreeState,
+    link_manager: LinkManager,
+    version_control: Option<VersionControl>,
+    commit_worker: Option<CommitWorker>,
+    note_watcher: Option<watcher::NoteWatcher>,
+    problems: Vec<String>, // Failures while opening, for the app to show
+}
+
+impl VaultState {
+    /// Load the vault at `path`, creating the directory if it doesn't exist.
+    /// A `read_only` vault gets no new files: its tag list and link index aren't
+    /// written back, and version history is only read if it already has some.
+    fn open(path: &std::path::Path, ctx: &egui::Context, read_only: bool) -> Self {
+        let mut storage = Storage::new(path.to_string_lossy().to_string());
+
+        let tags_path = storage.base_path().join(TAGS_FILE);
+        let mut tag_manager = TagManager::load(&tags_path).unwrap_or_default();
+        let known_tags = tag_manager.tags.len();
+        for note in storage.folders.iter_mut().flat_map(|f| f.notes.iter_mut()) {
+            tag_manager.register_front_matter_tags(note);
+        }
+        if tag_manager.tags.len() != known_tags && !read_only {
+            tag_manager.save(&tags_path).ok();
+        }
+        // Forget recent entries whose note was deleted while the app was closed
+        let mut recent_notes =
+            RecentNotes::load(&storage.base_path().join(RECENT_FILE)).unwrap_or_default();
+        recent_notes.retain(|path| storage.find_note_by_path(path).is_some());
+        let mut folder_tree =
+            FolderTreeState::load(&storage.base_path().join(FOLDER_TREE_FILE)).unwrap_or_default();
+        folder_tree.retain(|name| storage.folders.iter().any(|f| f.name == name));
+        // Backlinks for every note from the start; only notes changed since the
+        // index was saved are read
+        let links_path = storage.base_path().join(LINKS_FILE);
+        let mut link_index = LinkIndex::load(&links_path).unwrap_or_default();
+        let link_manager = storage.link_graph(&mut link_index);
+        let mut problems = Vec::new();
+        if !read_only {
+            if let Err(e) = link_index.save(&links_path) {
+                problems.push(format!("Failed to save link index: {}", e));
+            }
+        }
+
+        // Initialize version control
+        let version_control = VersionControl::new(path.to_path_buf()).ok().and_then(|vc| {
+            if read_only {
+                return path.join(".git").exists().then_some(vc);
+            }
+            vc.init().ok()?;
+            Some(vc)
+        });
+        // Pick up edits made by other editors or a git pull
+        let repaint_ctx = ctx.clone();
+        let note_watcher =
+            watcher::NoteWatcher::new(storage.base_path(), move || repaint_ctx.request_repaint())
+                .map_err(|e| problems.push(format!("Not watching for outside changes: {}", e)))
+                .ok();
+
+        // Commits run on their own thread; the handle above is kept for history reads
+        let commit_worker = version_control
+            .as_ref()
+            .and_then(|_| VersionControl::new(path.to_path_buf()).ok())
+            .map(CommitWorker::spawn);
+
+        Self {
+            storage,
+            tag_manager,
+            recent_notes,
+            folder_tree,
+            link_manager,
+            version_control,
+            commit_worker,
+            note_watcher,
+            problems,
+        }
+    }
+}
+
+/// What to do once the user has dealt with unsaved edits.
+enum LeaveAction {
+    Quit,
+    OpenVault(PathBuf),
+}
+
+/// Drag-and-drop payload for a sidebar note: its folder and position in the manual order,
+/// and its title for linking when dropped on the editor.
+struct NoteDrag {
+    folder_idx: usize,
+    position: usize,
+    title: String,
+}
+
+/// An encrypted note opened for editing with its password. The note stays encrypted
+/// on disk; saves re-encrypt the editor's text with the same password.
+struct UnlockedNote {
+    password: String,
+    saved: String, // Plaintext as last decrypted or saved, for change detection
+}
+
+/// An unchecked task and the `(folder_idx, note_idx)` of its note.
+type OpenTask = (usize, usize, tasks::Task);
+
+/// A note open in the editor's tab strip.
+struct NoteTab {
+    folder_idx: usize,
+    note_idx: usize,
+    file_path: String, // Survives index shifts when notes are added or removed
+    content: String,   // Edit buffer; the active tab's lives in `current_note_content`
+    base_mtime: Option<SystemTime>, // File's mtime when the buffer last matched disk
+}
+
+/// Save a note the app changed itself (tags, encryption, a restored version) and
+/// move its tabs' `base_mtime` up to the write, so their next save doesn't take
+/// the app's own change for one made outside it.
+fn save_note_for_tabs(
+    storage: &mut Storage,
+    tabs: &mut [NoteTab],
+    folder_idx: usize,
+    note_idx: usize,
+) -> error::Result<()> {
+    storage.save_note(folder_idx, note_idx)?;
+    note_written_for_tabs(storage, tabs, folder_idx, note_idx);
+    Ok(())
+}
+
+/// Move `base_mtime` up to the note's file for every tab showing it.
+fn note_written_for_tabs(
+    storage: &Storage,
+    tabs: &mut [NoteTab],
+    folder_idx: usize,
+    note_idx: usize,
+) {
+    let Some(note) = storage
+        .folders
+        .get(folder_idx)
+        .and_then(|f| f.notes.get(note_idx))
+    else {
+        return;
+    };
+    for tab in tabs.iter_mut().filter(|t| t.file_path == note.file_path) {
+        tab.base_mtime = note.disk_mtime;
+    }
+}
+
+/// Where the note at `file_path` is now and what its file holds. Found by path, as
+/// a reload while a save conflict waits can shift or drop notes.
+fn find_disk_version(
+    storage: &Storage,
+    file_path: &str,
+) -> Result<((usize, usize), note::Note), String> {
+    let (folder_idx, note_idx) = storage
+        .find_note_by_path(file_path)
+        .ok_or_else(|| format!("{} is no longer in the vault", file_path))?;
+    let disk = storage
+        .disk_version(folder_idx, note_idx)
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    Ok(((folder_idx, note_idx), disk))
+}
+
+/// A save held back because the note's file changed on disk after it was opened.
+struct SaveConflict {
+    file_path: String,
+    theirs: String, // What's on disk now
+    diff: Option<String>,
 }
 
 struct NoteStatistics {
-    total_notes: usize,
+    vault: storage::VaultStats,
     total_folders: usize,
-    total_words: usize,
     total_chars: usize,
     encrypted_count: usize,
-    total_tags: usize,
     favorite_count: usize,
 }
 
 impl NoteTakingApp {
-    fn new() -> Self {
-        let storage = Storage::new("./notes_data".to_string());
-        let search = FuzzySearch::new();
-        let theme_manager = ThemeManager::new();
+    fn new(
+        ctx: &egui::Context,
+        config: AppConfig,
+        config_path: Option<PathBuf>,
+        read_only_launch: bool,
+    ) -> Self {
+        let read_only = read_only_launch || config.read_only;
+        let VaultState {
+            mut storage,
+            tag_manager,
+            recent_notes,
+            folder_tree,
+            link_manager,
+            version_control,
+            commit_worker,
+            note_watcher,
+            problems,
+        } = VaultState::open(&config.vault_path, ctx, read_only);
+        // Startup failures show once the window is up
+        let mut toasts = Toasts::default();
+        for problem in problems {
+            toasts.notify(ToastLevel::Error, problem);
+        }
+        storage.set_save_format(config.line_ending, config.trailing_newline);
+        storage.set_filename_policy(config.filename_policy.clone());
+        storage.set_read_only(read_only);
+        let draft_recovery =
+            Some(storage.notes_with_drafts()).filter(|d| !d.is_empty() && !read_only);
+        if config.backup_on_startup {
+            match storage.create_backup(&storage.default_backup_root(), config.backup_count) {
+                Ok(path) => toasts.notify(
+                    ToastLevel::Success,
+                    format!("Backed up vault to {}", path.display()),
+                ),
+                Err(e) => {
+                    toasts.notify(ToastLevel::Error, format!("Failed to back up vault: {}", e))
+                }
+            }
+        }
+        let mut theme_manager = ThemeManager::with_user_themes(std::path::Path::new("./themes"));
+        theme_manager.load_preferences(std::path::Path::new(THEME_PREFERENCES_PATH));
+        if theme_manager.follow_system {
+            theme_manager.apply_system_preference();
+        }
         let encryption = Encryption::new();
-        let tag_manager = TagManager::new();
-        let link_manager = LinkManager::new();
         let autocomplete = Autocomplete::new();
-        let spellcheck = SpellChecker::new();
-
-        // Initialize version control
-        let version_control = VersionControl::new(PathBuf::from("./notes_data"))
-            .ok()
-            .and_then(|vc| {
-                vc.init().ok()?;
-                Some(vc)
+        let mut spellcheck = SpellChecker::new();
+        if let Err(e) =
+            spellcheck.load_dictionaries(std::path::Path::new(spellcheck::DICTIONARIES_DIR))
+        {
+            toasts.notify(
+                ToastLevel::Error,
+                format!("Failed to load dictionaries: {}", e),
+            );
+        }
+        if !spellcheck.set_language(&config.spell_language) {
+            toasts.notify(
+                ToastLevel::Warning,
+                format!(
+                    "No \"{}\" dictionary; spell checking in English",
+                    config.spell_language
+                ),
+            );
+        }
+        if let Some(path) = user_dictionary_path(config_path.as_deref()) {
+            if let Err(e) = spellcheck.load_user_dictionary(&path) {
+                toasts.notify(
+                    ToastLevel::Error,
+                    format!("Failed to load dictionary: {}", e),
+                );
+            }
+        }
+        let template_manager = TemplateManager::load(std::path::Path::new("./templates"))
+            .unwrap_or_else(|e| {
+                toasts.notify(
+                    ToastLevel::Error,
+                    format!("Failed to load templates: {}", e),
+                );
+                TemplateManager {
+                    templates: Vec::new(),
+                }
             });
 
+        let storage = Arc::new(Mutex::new(storage));
+        // The search thread loads its own copy of the notes, off the UI thread
+        let search_storage = storage.clone();
+        let search_worker = SearchWorker::spawn(move || {
+            let mut folders = search_storage.lock().unwrap().folders.clone();
+            storage::load_all_bodies(&mut folders);
+            folders
+        });
+
         Self {
-            storage: Arc::new(Mutex::new(storage)),
-            search,
+            storage,
+            read_only_launch,
             theme_manager,
             encryption,
             tag_manager,
@@ -212,41 +710,121 @@ impl NoteTakingApp {
             spellcheck,
             selected_folder: None,
             selected_note: None,
+            multi_selection: Vec::new(),
+            selection_anchor: None,
             current_note_content: String::new(),
             search_query: String::new(),
             search_results: Vec::new(),
+            search_total: 0,
+            search_worker,
+            toasts,
+            emoji_filter: String::new(),
+            open_smart_folder: None,
+            show_save_search_dialog: false,
+            saved_search_name: String::new(),
+            saved_search_scope: SearchScope::AllFolders,
+            saved_search_tag: None,
             new_folder_name: String::new(),
             show_new_folder_dialog: false,
             new_note_title: String::new(),
             show_new_note_dialog: false,
-            sidebar_open: true,
+            template_manager,
+            selected_template: None,
+            sidebar_open: config.window.sidebar_open,
+            window_checked: false,
             show_markdown_preview: false,
+            scroll_sync: scroll_sync::ScrollSync::default(),
+            presenting: false,
+            was_fullscreen: false,
             show_theme_dialog: false,
+            show_theme_editor: false,
+            theme_draft: Theme::default(),
+            window_focused: false,
             show_tag_dialog: false,
             new_tag_name: String::new(),
             selected_tag_filter: None,
             show_tag_editor: false,
+            renaming_tag: None,
+            rename_tag_input: String::new(),
+            rename_tag_error: None,
             show_encryption_dialog: false,
             encryption_password: String::new(),
             confirm_password: String::new(),
+            password_hint: String::new(),
+            show_unlock_dialog: false,
+            folder_password: None,
+            unlocked_notes: std::collections::HashMap::new(),
             show_export_dialog: false,
-            export_format: ExportFormat::PDF,
+            exporters: ExporterRegistry::default(),
+            export_format: "pdf".to_string(),
             show_links_panel: false,
             show_version_history: false,
             note_versions: Vec::new(),
             selected_version: None,
             version_timeline_position: 1.0, // Start at most recent (1.0 = latest)
+            version_diff: None,
+            save_conflict: None,
+            commit_worker,
+            sync_worker: None,
+            last_commit_error: None,
+            uncommitted_notes: Vec::new(),
+            uncommitted_since: None,
+            image_cache: images::ImageCache::default(),
+            editor_id: None,
+            note_watcher,
+            open_tabs: Vec::new(),
+            active_tab: None,
+            tab_close_prompt: None,
+            leave_prompt: None,
+            quit_confirmed: false,
+            show_outline: false,
+            show_properties_panel: false,
+            new_property_key: String::new(),
+            new_property_value: String::new(),
+            show_tasks_panel: false,
+            show_task_dashboard: false,
+            open_tasks: Vec::new(),
+            scroll_to_offset: None,
+            find_open: false,
+            find_focus_requested: false,
+            find_query: String::new(),
+            find_case_sensitive: false,
+            find_cache: search::FindCache::default(),
+            find_current: 0,
+            find_jump: None,
+            recent_notes,
+            folder_tree,
+            note_sort_order: SortOrder::default(),
             show_image_dialog: false,
             show_statistics: false,
+            statistics: None,
+            orphan_notes: None,
+            duplicate_notes: None,
+            archived_notes: None,
+            graph_view: None,
             show_settings: false,
+            vault_path_input: config.vault_path.to_string_lossy().to_string(),
+            config,
+            config_path,
             auto_save_enabled: true,
             auto_save_interval: 30.0,
             last_save_time: std::time::Instant::now(),
+            last_draft_time: std::time::Instant::now(),
+            last_backup_time: std::time::Instant::now(),
+            backup_job: None,
+            last_draft: None,
+            draft_recovery,
             autocomplete_enabled: true,
             spellcheck_enabled: true,
             autocomplete_suggestions: Vec::new(),
             show_autocomplete: false,
-            misspelled_words: Vec::new(),
+            spelling: SpellCache::default(),
+            spell_menu: None,
+            extract_selection: None,
+            extract_title: String::new(),
+            link_note: None,
+            link_choices: None,
+            link_note_folder: 0,
             favorite_notes: Vec::new(),
             show_favorites: false,
             current_font: EditorFont::Monospace,
@@ -256,11 +834,14 @@ impl NoteTakingApp {
     }
 
     fn save_current_note(&mut self) {
+        if self.is_read_only() {
+            return;
+        }
         if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
             // First, build the note name map and update links
-            let (note_name_map, file_path_string) = {
+            let (link_resolver, file_path_string) = {
                 let storage = self.storage.lock().unwrap();
-                let map = self.build_note_name_map(&storage);
+                let map = LinkResolver::new(&storage.folders);
                 let path = storage
                     .folders
                     .get(folder_idx)
@@ -270,1369 +851,7245 @@ impl NoteTakingApp {
                 (map, path)
             };
 
+            let is_encrypted = self
+                .storage
+                .lock()
+                .unwrap()
+                .folders
+                .get(folder_idx)
+                .and_then(|f| f.notes.get(note_idx))
+                .is_some_and(|n| n.is_encrypted);
+            if is_encrypted {
+                self.save_encrypted_note(folder_idx, note_idx, &file_path_string);
+                return;
+            }
+
+            // Never overwrite changes made on disk since the note was opened; ask instead.
+            // Other notes still save while the dialog is open.
+            if self
+                .save_conflict
+                .as_ref()
+                .is_some_and(|conflict| conflict.file_path == file_path_string)
+            {
+                return;
+            }
+            if let Some(theirs) = self.disk_conflict(folder_idx, note_idx) {
+                self.save_conflict = Some(SaveConflict {
+                    file_path: file_path_string,
+                    theirs,
+                    diff: None,
+                });
+                return;
+            }
+
             // Update the note content and links
             {
                 let mut storage = self.storage.lock().unwrap();
                 if let Some(folder) = storage.folders.get_mut(folder_idx) {
                     if let Some(note) = folder.notes.get_mut(note_idx) {
-                        note.content = self.current_note_content.clone();
-                        note.update_timestamp();
+                        if note.content != self.current_note_content {
+                            note.set_content(&self.current_note_content);
+                            note.update_timestamp();
+                        }
 
                         // Update links after releasing the mutable borrow
                         self.link_manager.rebuild_links_for_note(
                             (folder_idx, note_idx),
                             &note.content,
-                            &note_name_map,
+                            &link_resolver,
                         );
                     }
                 }
             }
 
-            // Save to disk
-            {
-                let mut storage = self.storage.lock().unwrap();
-                storage.save_note(folder_idx, note_idx).ok();
-            }
+            // Save to disk; an unchanged note isn't rewritten or committed
+            let written = {
+                let storage_handle = self.storage.clone();
+                let mut storage = storage_handle.lock().unwrap();
+                let written = match storage.write_note(folder_idx, note_idx) {
+                    Ok(written) => written,
+                    Err(e) => {
+                        self.notify(ToastLevel::Error, format!("Failed to save note: {}", e));
+                        return;
+                    }
+                };
+                if let Err(e) = storage.clear_draft(folder_idx, note_idx) {
+                    self.notify(
+                        ToastLevel::Warning,
+                        format!("Failed to remove draft: {}", e),
+                    );
+                }
+                self.last_draft = None;
+                let note = &storage.folders[folder_idx].notes[note_idx];
+                // Saving into an encrypted folder sealed the note; keep it open for editing
+                if let (true, Some(password)) = (note.is_encrypted, storage.session_password()) {
+                    self.unlocked_notes.insert(
+                        note.file_path.clone(),
+                        UnlockedNote {
+                            password: password.to_string(),
+                            saved: self.current_note_content.clone(),
+                        },
+                    );
+                }
+                let saved_mtime = note.disk_mtime;
+                if let Some(tab) = self.active_tab.and_then(|idx| self.open_tabs.get_mut(idx)) {
+                    tab.base_mtime = saved_mtime;
+                }
+                self.search_worker
+                    .update_note(&storage.folders, folder_idx, note_idx);
+                if self.show_task_dashboard {
+                    self.open_tasks = storage.collect_open_tasks();
+                }
+                written
+            };
 
             // Commit to version control
-            if let Some(ref vc) = self.version_control {
-                if !file_path_string.is_empty() {
-                    let file_path = PathBuf::from(&file_path_string);
-                    let storage = self.storage.lock().unwrap();
-                    let title = storage
-                        .folders
-                        .get(folder_idx)
-                        .and_then(|f| f.notes.get(note_idx))
-                        .map(|n| n.title.clone())
-                        .unwrap_or_default();
-                    drop(storage);
-                    vc.commit_note(&file_path, &format!("Updated: {}", title))
-                        .ok();
-                }
+            if written && !file_path_string.is_empty() {
+                let title = self
+                    .storage
+                    .lock()
+                    .unwrap()
+                    .folders
+                    .get(folder_idx)
+                    .and_then(|f| f.notes.get(note_idx))
+                    .map(|n| n.title.clone())
+                    .unwrap_or_default();
+                self.record_saved_note(PathBuf::from(&file_path_string), title);
             }
 
             self.last_save_time = std::time::Instant::now();
-            println!("✓ Note saved and versioned");
         }
     }
 
-    fn build_note_name_map(
-        &self,
-        storage: &Storage,
-    ) -> std::collections::HashMap<String, (usize, usize)> {
-        let mut map = std::collections::HashMap::new();
-        for (folder_idx, folder) in storage.folders.iter().enumerate() {
-            for (note_idx, note) in folder.notes.iter().enumerate() {
-                map.insert(note.title.clone(), (folder_idx, note_idx));
-            }
+    /// Save for Ctrl+S and the Save button, confirming it worked.
+    fn save_current_note_with_feedback(&mut self) {
+        let last_save = self.last_save_time;
+        self.save_current_note();
+        if self.last_save_time != last_save {
+            self.notify(ToastLevel::Success, "Note saved");
         }
-        map
     }
 
-    fn perform_search(&mut self) {
-        self.search_results.clear();
-        if self.search_query.is_empty() {
-            return;
-        }
+    /// Show `message` as a toast.
+    fn notify(&mut self, level: ToastLevel, message: impl Into<String>) {
+        self.toasts.notify(level, message);
+    }
 
+    /// What's on disk, if the note's file changed since the active tab last matched it
+    /// and now differs from the editor's text.
+    fn disk_conflict(&mut self, folder_idx: usize, note_idx: usize) -> Option<String> {
+        let tab = self
+            .active_tab
+            .and_then(|idx| self.open_tabs.get_mut(idx))?;
         let storage = self.storage.lock().unwrap();
-        self.search_results = self.search.search(&storage.folders, &self.search_query);
+        if !storage.changed_on_disk(folder_idx, note_idx, tab.base_mtime) {
+            return None;
+        }
+        // A deleted file is simply written again
+        let theirs = storage.disk_version(folder_idx, note_idx).ok()?;
+        if theirs.content == self.current_note_content {
+            tab.base_mtime = theirs.disk_mtime;
+            return None;
+        }
+        Some(theirs.content)
     }
 
-    fn create_folder(&mut self) {
-        if !self.new_folder_name.is_empty() {
+    /// Settle a held-back save: overwrite the disk version with the editor's text, or
+    /// drop the edits and load what's on disk.
+    fn resolve_save_conflict(&mut self, keep_mine: bool) {
+        let Some(conflict) = self.save_conflict.take() else {
+            return;
+        };
+        let found = find_disk_version(&self.storage.lock().unwrap(), &conflict.file_path);
+        let ((folder_idx, note_idx), disk) = match found {
+            Ok(found) => found,
+            Err(e) => {
+                self.notify(ToastLevel::Error, e);
+                return;
+            }
+        };
+        let Some(tab_idx) = self
+            .open_tabs
+            .iter()
+            .position(|t| t.file_path == conflict.file_path)
+        else {
+            return;
+        };
+
+        self.activate_tab(tab_idx);
+        self.open_tabs[tab_idx].base_mtime = disk.disk_mtime;
+        if keep_mine {
+            self.save_current_note();
+        } else {
+            self.current_note_content = disk.content.clone();
+            self.open_tabs[tab_idx].content = disk.content.clone();
             let mut storage = self.storage.lock().unwrap();
-            storage.create_folder(&self.new_folder_name).ok();
-            self.new_folder_name.clear();
-            self.show_new_folder_dialog = false;
+            storage.folders[folder_idx].notes[note_idx] = disk;
+            self.search_worker.invalidate();
+            drop(storage);
+            self.notify(ToastLevel::Success, "Loaded the version on disk");
         }
     }
 
-    fn create_note(&mut self) {
-        if let Some(folder_idx) = self.selected_folder {
-            if !self.new_note_title.is_empty() {
-                let mut storage = self.storage.lock().unwrap();
-                match storage.create_note(folder_idx, &self.new_note_title) {
-                    Ok(note_idx) => {
-                        println!(
-                            "✓ Note created: {} in folder {}",
-                            self.new_note_title, folder_idx
-                        );
+    /// Save an encrypted note by re-encrypting the editor's text, if it was unlocked.
+    /// A locked note is left alone: the editor only shows its placeholder.
+    fn save_encrypted_note(&mut self, folder_idx: usize, note_idx: usize, file_path: &str) {
+        let Some(unlocked) = self.unlocked_notes.get(file_path) else {
+            return;
+        };
+        if unlocked.saved == self.current_note_content {
+            return;
+        }
+        let mut sealed = match self
+            .encryption
+            .encrypt(&self.current_note_content, &unlocked.password)
+        {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                self.notify(ToastLevel::Error, format!("Encryption failed: {}", e));
+                return;
+            }
+        };
 
-                        // Auto-select and open the newly created note
-                        self.selected_note = Some(note_idx);
-                        if let Some(folder) = storage.folders.get(folder_idx) {
-                            if let Some(note) = folder.notes.get(note_idx) {
-                                self.current_note_content = note.content.clone();
-                            }
-                        }
+        let title = {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            let Some(note) = storage
+                .folders
+                .get_mut(folder_idx)
+                .and_then(|f| f.notes.get_mut(note_idx))
+            else {
+                return;
+            };
+            // Same password, so the same hint
+            sealed.hint = note.encrypted_data.take().and_then(|old| old.hint);
+            note.encrypted_data = Some(sealed);
+            note.update_timestamp();
+            let title = note.title.clone();
+            if let Err(e) =
+                save_note_for_tabs(&mut storage, &mut self.open_tabs, folder_idx, note_idx)
+            {
+                self.notify(ToastLevel::Error, format!("Failed to save note: {}", e));
+                return;
+            }
+            title
+        };
+        if let Some(unlocked) = self.unlocked_notes.get_mut(file_path) {
+            unlocked.saved = self.current_note_content.clone();
+        }
 
-                        self.new_note_title.clear();
-                        self.show_new_note_dialog = false;
-                    }
-                    Err(e) => {
-                        eprintln!("✗ Failed to create note: {}", e);
-                    }
+        self.record_saved_note(PathBuf::from(file_path), title);
+        self.last_save_time = std::time::Instant::now();
+    }
+
+    /// Commit a saved note now or hold it for later, depending on the commit policy.
+    fn record_saved_note(&mut self, file_path: PathBuf, title: String) {
+        if self.config.commit_policy == CommitPolicy::EveryChange {
+            if self.commit_worker.is_none() {
+                return;
+            }
+            // Commit what was just saved, whatever the file holds by the time it runs
+            let content = match std::fs::read(&file_path) {
+                Ok(content) => content,
+                Err(e) => {
+                    self.notify(
+                        ToastLevel::Error,
+                        format!("Couldn't commit {}: {}", file_path.display(), e),
+                    );
+                    return;
                 }
-            } else {
-                println!("⚠ Note title is empty");
+            };
+            if let Some(worker) = &mut self.commit_worker {
+                worker.queue(CommitJob::Note {
+                    file_path,
+                    content,
+                    message: format!("Updated: {}", title),
+                });
             }
-        } else {
-            println!("⚠ No folder selected");
+            return;
         }
+        if !self.uncommitted_notes.iter().any(|(p, _)| *p == file_path) {
+            self.uncommitted_notes.push((file_path, title));
+        }
+        self.uncommitted_since
+            .get_or_insert_with(std::time::Instant::now);
     }
 
-    fn delete_current_note(&mut self) {
-        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
-            let mut storage = self.storage.lock().unwrap();
-            if let Some(folder) = storage.folders.get_mut(folder_idx) {
-                if note_idx < folder.notes.len() {
-                    let note = &folder.notes[note_idx];
-                    // Delete the note file
-                    if let Ok(_) = std::fs::remove_file(&note.file_path) {
-                        // Delete metadata file
-                        let meta_path =
-                            std::path::Path::new(&note.file_path).with_extension("meta");
-                        let _ = std::fs::remove_file(meta_path);
+    /// Squash every held-back save into a single commit.
+    fn commit_uncommitted_notes(&mut self) {
+        self.uncommitted_since = None;
+        let notes = std::mem::take(&mut self.uncommitted_notes);
+        if notes.is_empty() {
+            return;
+        }
+        let Some(worker) = &mut self.commit_worker else {
+            return;
+        };
+        let message = match notes.as_slice() {
+            [(_, title)] => format!("Updated: {}", title),
+            _ => format!(
+                "Updated {} notes: {}",
+                notes.len(),
+                notes
+                    .iter()
+                    .map(|(_, title)| title.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        // The files as they are now; a note that's gone is committed as deleted
+        let mut snapshots = Vec::new();
+        let mut unreadable = Vec::new();
+        for (path, _) in notes {
+            match std::fs::read(&path) {
+                Ok(content) => snapshots.push((path, Some(content))),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => snapshots.push((path, None)),
+                Err(e) => unreadable.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        worker.queue(CommitJob::Notes {
+            notes: snapshots,
+            message,
+        });
+        for error in unreadable {
+            self.notify(ToastLevel::Error, format!("Couldn't commit {}", error));
+        }
+    }
 
-                        // Remove from folder
-                        folder.notes.remove(note_idx);
+    /// Commit held-back saves once the policy says they've waited long enough.
+    fn poll_commit_policy(&mut self, ctx: &egui::Context) {
+        let pending_for = self.uncommitted_since.map(|since| since.elapsed());
+        if self.config.commit_policy.should_commit(pending_for) {
+            self.commit_uncommitted_notes();
+        } else if let (CommitPolicy::OnInterval(interval), Some(waited)) =
+            (self.config.commit_policy, pending_for)
+        {
+            // Wake up when the interval runs out, even if the user is idle
+            ctx.request_repaint_after(interval.saturating_sub(waited));
+        }
+    }
 
-                        // Clear selection
-                        self.selected_note = None;
-                        self.current_note_content.clear();
+    /// Decrypt the selected note into the editor without decrypting it on disk.
+    fn unlock_current_note(&mut self) {
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        let note = {
+            let storage = self.storage.lock().unwrap();
+            storage
+                .folders
+                .get(folder_idx)
+                .and_then(|f| f.notes.get(note_idx))
+                .map(|n| (n.file_path.clone(), n.encrypted_data.clone()))
+        };
+        let Some((file_path, Some(encrypted_data))) = note else {
+            return;
+        };
 
-                        println!("✓ Note deleted");
+        match self
+            .encryption
+            .decrypt(&encrypted_data, &self.encryption_password)
+        {
+            Ok(plaintext) => {
+                // The first note opened in an encrypted folder unlocks the rest of it
+                {
+                    let mut storage = self.storage.lock().unwrap();
+                    let folder_encrypted = storage.folders[folder_idx].encrypted;
+                    if folder_encrypted && storage.session_password().is_none() {
+                        storage.set_session_password(Some(self.encryption_password.clone()));
                     }
                 }
+                self.current_note_content = plaintext.clone();
+                self.unlocked_notes.insert(
+                    file_path,
+                    UnlockedNote {
+                        password: std::mem::take(&mut self.encryption_password),
+                        saved: plaintext,
+                    },
+                );
+                self.stash_active_tab();
+                self.show_unlock_dialog = false;
+                self.notify(ToastLevel::Success, "Note unlocked");
             }
+            Err(e) => self.notify(ToastLevel::Error, format!("Unlock failed: {}", e)),
         }
+        self.encryption_password.clear();
     }
 
-    fn delete_current_folder(&mut self) {
-        if let Some(folder_idx) = self.selected_folder {
-            let mut storage = self.storage.lock().unwrap();
-            if folder_idx < storage.folders.len() {
-                let folder = &storage.folders[folder_idx];
+    /// Decrypt a note in an encrypted folder with the session password so it opens
+    /// ready to edit. Returns its text, or `None` if it can't be opened that way.
+    fn unlock_with_session_password(
+        &mut self,
+        folder_idx: usize,
+        note_idx: usize,
+    ) -> Option<String> {
+        let (file_path, encrypted_data, password) = {
+            let storage = self.storage.lock().unwrap();
+            let folder = storage.folders.get(folder_idx)?;
+            let note = folder.notes.get(note_idx)?;
+            if !folder.encrypted || self.unlocked_notes.contains_key(&note.file_path) {
+                return None;
+            }
+            (
+                note.file_path.clone(),
+                note.encrypted_data.clone()?,
+                storage.session_password()?.to_string(),
+            )
+        };
+        let plaintext = self.encryption.decrypt(&encrypted_data, &password).ok()?;
+        self.unlocked_notes.insert(
+            file_path,
+            UnlockedNote {
+                password,
+                saved: plaintext.clone(),
+            },
+        );
+        Some(plaintext)
+    }
 
-                // Delete all notes in the folder
-                for note in &folder.notes {
-                    let _ = std::fs::remove_file(&note.file_path);
-                    let meta_path = std::path::Path::new(&note.file_path).with_extension("meta");
-                    let _ = std::fs::remove_file(meta_path);
+    /// Encrypt a folder's notes, now and whenever one is saved, with `password`, which
+    /// becomes the session password.
+    fn encrypt_folder(&mut self, folder_idx: usize, password: String) {
+        self.save_all_tabs();
+        self.stash_active_tab();
+        let storage_handle = self.storage.clone();
+        let mut storage = storage_handle.lock().unwrap();
+        // One session password seals every encrypted folder, so a second folder
+        // must use the same one or the first could no longer be opened
+        let matches_existing = match storage.session_password() {
+            Some(current) => current == password,
+            None => storage
+                .folders
+                .iter()
+                .filter(|f| f.encrypted)
+                .find_map(|f| f.notes.iter().find_map(|n| n.encrypted_data.as_ref()))
+                .is_none_or(|sample| self.encryption.decrypt(sample, &password).is_ok()),
+        };
+        if !matches_existing {
+            drop(storage);
+            self.notify(
+                ToastLevel::Error,
+                "Use the password your other encrypted folders are sealed with",
+            );
+            return;
+        }
+        storage.set_session_password(Some(password.clone()));
+        match storage.set_folder_encrypted(folder_idx, true) {
+            Ok(sealed) => {
+                for &note_idx in &sealed {
+                    note_written_for_tabs(&storage, &mut self.open_tabs, folder_idx, note_idx);
                 }
-
-                // Delete the folder directory
-                let _ = std::fs::remove_dir_all(&folder.path);
-
-                // Remove from storage
-                storage.folders.remove(folder_idx);
-
-                // Clear selection
-                self.selected_folder = None;
-                self.selected_note = None;
-                self.current_note_content.clear();
-
-                println!("✓ Folder deleted");
+                // Open tabs of the notes just encrypted stay editable
+                for tab in &self.open_tabs {
+                    if tab.folder_idx == folder_idx && sealed.contains(&tab.note_idx) {
+                        self.unlocked_notes.insert(
+                            tab.file_path.clone(),
+                            UnlockedNote {
+                                password: password.clone(),
+                                saved: tab.content.clone(),
+                            },
+                        );
+                    }
+                }
+                self.notify(
+                    ToastLevel::Success,
+                    format!("Folder encrypted ({} note(s))", sealed.len()),
+                );
             }
+            Err(e) => self.notify(
+                ToastLevel::Error,
+                format!("Failed to encrypt folder: {}", e),
+            ),
         }
+        // Encrypted notes must not stay findable by their plaintext
+        self.search_worker.invalidate();
     }
 
-    fn sync_to_cloud(&mut self) {
-        let storage = self.storage.lock().unwrap();
-        match storage.export_to_cloud() {
-            Ok(path) => {
-                println!("Synced to: {}", path);
+    /// Take `password` as the session password if it opens the folder's notes, and
+    /// unlock the ones open in tabs.
+    fn unlock_folder(&mut self, folder_idx: usize, password: String) {
+        let sample = self
+            .storage
+            .lock()
+            .unwrap()
+            .folders
+            .get(folder_idx)
+            .and_then(|f| f.notes.iter().find_map(|n| n.encrypted_data.clone()));
+        if let Some(sample) = sample {
+            if self.encryption.decrypt(&sample, &password).is_err() {
+                self.notify(ToastLevel::Error, "Unlock failed: wrong password?");
+                return;
             }
-            Err(e) => {
-                eprintln!("Sync failed: {}", e);
+        }
+        self.storage
+            .lock()
+            .unwrap()
+            .set_session_password(Some(password));
+
+        for idx in 0..self.open_tabs.len() {
+            let (tab_folder, tab_note) =
+                (self.open_tabs[idx].folder_idx, self.open_tabs[idx].note_idx);
+            if tab_folder != folder_idx {
+                continue;
+            }
+            if let Some(plaintext) = self.unlock_with_session_password(tab_folder, tab_note) {
+                if self.active_tab == Some(idx) {
+                    self.current_note_content = plaintext.clone();
+                }
+                self.open_tabs[idx].content = plaintext;
             }
         }
+        self.notify(ToastLevel::Success, "Folder unlocked");
     }
 
-    // Theme management
-    fn apply_theme(&mut self, ctx: &egui::Context) {
-        self.theme_manager.current_theme.apply_to_egui(ctx);
+    /// Stop encrypting notes saved in the folder. Notes already encrypted stay so.
+    fn stop_encrypting_folder(&mut self, folder_idx: usize) {
+        let result = self
+            .storage
+            .lock()
+            .unwrap()
+            .set_folder_encrypted(folder_idx, false);
+        match result {
+            Ok(_) => self.notify(
+                ToastLevel::Info,
+                "New notes in this folder won't be encrypted",
+            ),
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to update folder: {}", e)),
+        }
     }
 
-    fn toggle_dark_mode(&mut self) {
-        self.theme_manager.toggle_dark_mode();
+    /// Save and put the selected note's placeholder back in the editor.
+    fn lock_current_note(&mut self) {
+        self.save_current_note();
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        let note = {
+            let storage = self.storage.lock().unwrap();
+            storage
+                .folders
+                .get(folder_idx)
+                .and_then(|f| f.notes.get(note_idx))
+                .map(|n| (n.file_path.clone(), n.content.clone()))
+        };
+        if let Some((file_path, placeholder)) = note {
+            self.unlocked_notes.remove(&file_path);
+            self.current_note_content = placeholder;
+            self.stash_active_tab();
+        }
     }
 
-    // Tag management
-    fn add_tag(&mut self) {
-        if !self.new_tag_name.is_empty() {
-            self.tag_manager.add_tag(self.new_tag_name.clone());
-            self.new_tag_name.clear();
+    /// Switch to the vault at `path`, creating it if needed, and remember it in the
+    /// config. Unsaved tabs are written to the old vault first.
+    fn open_vault(&mut self, path: PathBuf, ctx: &egui::Context) {
+        self.save_all_tabs();
+        if self.config.commit_policy != CommitPolicy::Manual {
+            self.commit_uncommitted_notes();
+        }
+        self.uncommitted_notes.clear();
+        self.uncommitted_since = None;
+        // Dropping the worker waits for queued commits to land in the old repo
+        self.commit_worker = None;
+        self.note_watcher = None;
+
+        let VaultState {
+            storage,
+            tag_manager,
+            recent_notes,
+            folder_tree,
+            link_manager,
+            version_control,
+            commit_worker,
+            note_watcher,
+            problems,
+        } = VaultState::open(&path, ctx, self.is_read_only());
+        *self.storage.lock().unwrap() = storage;
+        self.apply_save_format();
+        self.tag_manager = tag_manager;
+        self.recent_notes = recent_notes;
+        self.folder_tree = folder_tree;
+        self.link_manager = link_manager;
+        self.version_control = version_control;
+        self.commit_worker = commit_worker;
+        self.note_watcher = note_watcher;
+        for problem in problems {
+            self.notify(ToastLevel::Error, problem);
         }
-    }
 
-    fn assign_tag_to_note(&mut self, tag_idx: usize) {
-        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+        // Anything indexing into the old vault's folders is meaningless now
+        self.search_worker.invalidate();
+        self.search_worker.cancel();
+        self.search_results.clear();
+        self.open_smart_folder = None;
+        self.orphan_notes = None;
+        self.duplicate_notes = None;
+        self.archived_notes = None;
+        self.graph_view = None;
+        self.link_note = None;
+        self.link_choices = None;
+        self.multi_selection.clear();
+        self.selection_anchor = None;
+        self.open_tabs.clear();
+        self.active_tab = None;
+        self.tab_close_prompt = None;
+        self.selected_folder = None;
+        self.selected_note = None;
+        self.current_note_content.clear();
+        self.favorite_notes.clear();
+        self.unlocked_notes.clear();
+        self.folder_password = None;
+        self.open_tasks.clear();
+        self.note_versions.clear();
+        self.version_diff = None;
+        self.selected_tag_filter = None;
+        self.last_commit_error = None;
+        self.last_draft = None;
+        self.draft_recovery = {
             let mut storage = self.storage.lock().unwrap();
-            if let Some(folder) = storage.folders.get_mut(folder_idx) {
-                if let Some(note) = folder.notes.get_mut(note_idx) {
-                    note.add_tag(tag_idx);
-                    storage.save_note(folder_idx, note_idx).ok();
-                }
-            }
-        }
+            Some(storage.notes_with_drafts()).filter(|d| !d.is_empty() && !storage.is_read_only())
+        };
+
+        self.config.set_active_vault(path);
+        self.vault_path_input = self.config.vault_path.to_string_lossy().to_string();
+        self.save_config();
+        self.notify(
+            ToastLevel::Success,
+            format!("Opened vault {}", self.config.vault_path.display()),
+        );
     }
 
-    // Encryption
-    fn toggle_encryption(&mut self) {
-        if self.encryption_password != self.confirm_password {
-            eprintln!("✗ Passwords don't match!");
+    /// A window restored onto a monitor that's gone or smaller would open off-screen;
+    /// pull it back once the viewport reports which monitor it landed on.
+    fn check_window_position(&mut self, ctx: &egui::Context) {
+        let Some(monitor_size) = ctx.input(|i| {
+            let viewport = i.viewport();
+            // Wait for the first report of where the window is
+            viewport
+                .outer_rect
+                .map(|_| viewport.monitor_size.map(|size| [size.x, size.y]))
+        }) else {
             return;
+        };
+        self.window_checked = true;
+
+        if let Some([x, y]) = self.config.window.off_screen_correction(monitor_size) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(x, y)));
         }
+    }
 
-        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
-            let mut storage = self.storage.lock().unwrap();
-            if let Some(folder) = storage.folders.get_mut(folder_idx) {
-                if let Some(note) = folder.notes.get_mut(note_idx) {
-                    if note.is_encrypted {
-                        // Decrypt
-                        if let Some(ref encrypted_data) = note.encrypted_data {
-                            match self
-                                .encryption
-                                .decrypt(encrypted_data, &self.encryption_password)
-                            {
-                                Ok(decrypted) => {
-                                    note.content = decrypted;
-                                    note.is_encrypted = false;
-                                    note.encrypted_data = None;
-                                    self.current_note_content = note.content.clone();
-                                    println!("✓ Note decrypted");
-                                }
-                                Err(e) => eprintln!("✗ Decryption failed: {}", e),
-                            }
-                        }
-                    } else {
-                        // Encrypt
-                        match self
-                            .encryption
-                            .encrypt(&note.content, &self.encryption_password)
-                        {
-                            Ok(encrypted_data) => {
-                                note.encrypted_data = Some(encrypted_data);
-                                note.is_encrypted = true;
-                                note.content = "[ENCRYPTED]".to_string();
-                                self.current_note_content =
-                                    "[ENCRYPTED - Enter password to decrypt]".to_string();
-                                println!("✓ Note encrypted");
-                            }
-                            Err(e) => eprintln!("✗ Encryption failed: {}", e),
-                        }
-                    }
+    /// Remember the window and sidebar layout for the next launch.
+    fn save_window_geometry(&mut self, ctx: &egui::Context) {
+        ctx.input(|i| {
+            let viewport = i.viewport();
+            let window = &mut self.config.window;
+            if let Some(rect) = viewport.inner_rect {
+                window.size = [rect.width(), rect.height()];
+            }
+            window.position = viewport.outer_rect.map(|rect| [rect.min.x, rect.min.y]);
+            window.monitor_size = viewport.monitor_size.map(|size| [size.x, size.y]);
+        });
+        self.config.window.sidebar_open = self.sidebar_open;
+        self.save_config();
+    }
 
-                    storage.save_note(folder_idx, note_idx).ok();
+    fn render_language_picker(&mut self, ui: &mut egui::Ui) {
+        let languages: Vec<String> = self.spellcheck.languages().map(String::from).collect();
+        let mut picked = None;
+        egui::ComboBox::from_id_salt("spell_language")
+            .selected_text(self.spellcheck.language())
+            .width(48.0)
+            .show_ui(ui, |ui| {
+                for language in &languages {
+                    let selected = language == self.spellcheck.language();
+                    if ui.selectable_label(selected, language).clicked() {
+                        picked = Some(language.clone());
+                    }
                 }
+            })
+            .response
+            .on_hover_text("Spell-check language");
+
+        if let Some(language) = picked {
+            if self.spellcheck.set_language(&language) {
+                self.spelling
+                    .check_now(&mut self.spellcheck, &self.current_note_content);
+                self.config.spell_language = language;
+                self.save_config();
             }
         }
+    }
 
-        self.encryption_password.clear();
-        self.confirm_password.clear();
-        self.show_encryption_dialog = false;
+    fn add_to_dictionary(&mut self, word: &str) {
+        self.spellcheck.add_to_dictionary(word.to_string());
+        match user_dictionary_path(self.config_path.as_deref()) {
+            Some(path) => match spellcheck::save_user_word(&path, word) {
+                Ok(()) => self.notify(
+                    ToastLevel::Success,
+                    format!("Added \"{}\" to the dictionary", word),
+                ),
+                Err(e) => self.notify(
+                    ToastLevel::Error,
+                    format!("Failed to save dictionary: {}", e),
+                ),
+            },
+            None => self.notify(
+                ToastLevel::Warning,
+                "No config directory on this platform; the word won't persist",
+            ),
+        }
     }
 
-    // Export
-    fn export_note_to_pdf(&self) {
-        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
-            let storage = self.storage.lock().unwrap();
-            if let Some(folder) = storage.folders.get(folder_idx) {
-                if let Some(note) = folder.notes.get(note_idx) {
-                    let output_path = PathBuf::from(format!("{}.pdf", note.title));
-                    match pdf_export::PdfExporter::export_note(
-                        &note.title,
-                        &note.content,
-                        &output_path,
-                    ) {
-                        Ok(_) => println!("✓ Exported to PDF: {:?}", output_path),
-                        Err(e) => eprintln!("✗ PDF export failed: {}", e),
-                    }
-                }
+    fn apply_save_format(&self) {
+        let mut storage = self.storage.lock().unwrap();
+        storage.set_save_format(self.config.line_ending, self.config.trailing_newline);
+        storage.set_filename_policy(self.config.filename_policy.clone());
+        storage.set_read_only(self.is_read_only());
+    }
+
+    /// Whether the vault may be changed: not if the app was launched with
+    /// `--read-only` or the setting is on.
+    fn is_read_only(&self) -> bool {
+        self.read_only_launch || self.config.read_only
+    }
+
+    fn save_config(&mut self) {
+        let Some(path) = &self.config_path else {
+            self.notify(
+                ToastLevel::Warning,
+                "No config directory on this platform; settings won't persist",
+            );
+            return;
+        };
+        if let Err(e) = self.config.save(path) {
+            self.notify(ToastLevel::Error, format!("Failed to save settings: {}", e));
+        }
+    }
+
+    /// Copy the current vault to `dest` and continue working from there.
+    fn move_vault(&mut self, dest: PathBuf, ctx: &egui::Context) {
+        self.save_all_tabs();
+        let source = self.storage.lock().unwrap().base_path().to_path_buf();
+        match storage::copy_vault(&source, &dest) {
+            Ok(count) => {
+                self.notify(
+                    ToastLevel::Success,
+                    format!("Copied {} files to {:?}", count, dest),
+                );
+                self.open_vault(dest, ctx);
             }
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to copy vault: {}", e)),
         }
     }
 
-    fn export_folder_to_pdf(&self) {
-        if let Some(folder_idx) = self.selected_folder {
-            let storage = self.storage.lock().unwrap();
-            if let Some(folder) = storage.folders.get(folder_idx) {
-                let notes: Vec<(String, String)> = folder
-                    .notes
-                    .iter()
-                    .map(|n| (n.title.clone(), n.content.clone()))
-                    .collect();
+    /// Commit every note in the vault at once, e.g. after a bulk edit or import.
+    fn snapshot_vault(&mut self) {
+        if self.is_read_only() {
+            self.notify(ToastLevel::Warning, "The vault is open read-only");
+            return;
+        }
+        self.save_current_note();
+        // The snapshot covers anything held back by the commit policy
+        self.uncommitted_notes.clear();
+        self.uncommitted_since = None;
+        if let Some(worker) = &mut self.commit_worker {
+            let message = format!(
+                "Snapshot: {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M")
+            );
+            worker.queue(CommitJob::All { message });
+        }
+    }
 
-                let output_path = PathBuf::from(format!("{}_folder.pdf", folder.name));
-                match pdf_export::PdfExporter::export_multiple_notes(&notes, &output_path) {
-                    Ok(_) => println!("✓ Exported folder to PDF: {:?}", output_path),
-                    Err(e) => eprintln!("✗ PDF export failed: {}", e),
-                }
-            }
+    /// Reload notes that changed on disk outside the app.
+    fn poll_note_watcher(&mut self, ctx: &egui::Context) {
+        let Some(watcher) = &mut self.note_watcher else {
+            return;
+        };
+        let changed = watcher.poll();
+        if watcher.has_pending() {
+            ctx.request_repaint_after(watcher::DEBOUNCE);
+        }
+        if changed.is_empty() {
+            return;
+        }
+
+        let storage_handle = self.storage.clone();
+        let mut storage = storage_handle.lock().unwrap();
+
+        // Remember what each tab last saved, so tabs without local edits can follow disk
+        let saved: Vec<Option<String>> = self
+            .open_tabs
+            .iter()
+            .map(|t| {
+                storage
+                    .folders
+                    .get(t.folder_idx)
+                    .and_then(|f| f.notes.get(t.note_idx))
+                    .map(|n| n.content.clone())
+            })
+            .collect();
+
+        let mut reloaded = false;
+        for path in &changed {
+            reloaded |= storage.reload_path(path);
         }
+        if !reloaded {
+            return;
+        }
+        self.search_worker.invalidate();
+        for note in storage.folders.iter_mut().flat_map(|f| f.notes.iter_mut()) {
+            self.tag_manager.register_front_matter_tags(note);
+        }
+
+        self.retarget_tabs(&storage, &saved);
+        drop(storage);
+        self.notify(ToastLevel::Info, "Reloaded external changes");
     }
 
-    // Version history
-    fn load_version_history(&mut self) {
-        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
-            let storage = self.storage.lock().unwrap();
-            if let Some(folder) = storage.folders.get(folder_idx) {
-                if let Some(note) = folder.notes.get(note_idx) {
-                    if let Some(ref vc) = self.version_control {
-                        let file_path = PathBuf::from(&note.file_path);
-                        match vc.get_file_history(&file_path) {
-                            Ok(versions) => {
-                                self.note_versions = versions;
-                                println!("✓ Loaded {} versions", self.note_versions.len());
-                            }
-                            Err(e) => eprintln!("✗ Failed to load versions: {}", e),
-                        }
-                    }
+    /// Pick up finished background commits.
+    fn poll_commit_worker(&mut self, ctx: &egui::Context) {
+        let Some(worker) = &mut self.commit_worker else {
+            return;
+        };
+
+        let finished = worker.poll();
+        let pending = worker.pending();
+        for result in &finished {
+            match result {
+                Ok(commit_id) => {
+                    self.notify(ToastLevel::Info, format!("Committed {}", &commit_id[..7]));
+                    self.last_commit_error = None;
+                }
+                Err(e) => {
+                    self.notify(ToastLevel::Error, format!("Failed to commit: {}", e));
+                    self.last_commit_error = Some(e.clone());
                 }
             }
         }
+
+        // Keep polling while commits are outstanding, even if the user is idle
+        if pending > 0 {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        if !finished.is_empty() && self.show_version_history {
+            self.load_version_history();
+        }
     }
 
-    fn restore_version(&mut self, version_idx: usize) {
-        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
-            if let Some(version) = self.note_versions.get(version_idx) {
-                let storage = self.storage.lock().unwrap();
-                if let Some(folder) = storage.folders.get(folder_idx) {
-                    if let Some(note) = folder.notes.get(note_idx) {
-                        if let Some(ref vc) = self.version_control {
-                            let file_path = PathBuf::from(&note.file_path);
-                            match vc.restore_version(&file_path, &version.commit_id) {
-                                Ok(content) => {
-                                    self.current_note_content = content;
-                                    println!("✓ Restored version from {}", version.timestamp);
-                                }
-                                Err(e) => eprintln!("✗ Failed to restore: {}", e),
-                            }
-                        }
-                    }
-                }
+    /// Show a note in the editor, activating its tab or opening a new one.
+    fn open_note(&mut self, folder_idx: usize, note_idx: usize) {
+        if let Some(idx) = self
+            .open_tabs
+            .iter()
+            .position(|t| (t.folder_idx, t.note_idx) == (folder_idx, note_idx))
+        {
+            let file_path = self.open_tabs[idx].file_path.clone();
+            self.record_recent(&file_path);
+            self.activate_tab(idx);
+            return;
+        }
+
+        let (file_path, content, base_mtime) = {
+            let mut storage = self.storage.lock().unwrap();
+            if let Err(e) = storage.ensure_loaded(folder_idx, note_idx) {
+                drop(storage);
+                self.notify(ToastLevel::Error, format!("Failed to load note: {}", e));
+                return;
             }
+            let note = &storage.folders[folder_idx].notes[note_idx];
+            (
+                note.file_path.clone(),
+                note.content.clone(),
+                note.disk_mtime,
+            )
+        };
+        let content = self
+            .unlock_with_session_password(folder_idx, note_idx)
+            .unwrap_or(content);
+
+        let file_path_for_recent = file_path.clone();
+        self.open_tabs.push(NoteTab {
+            folder_idx,
+            note_idx,
+            file_path,
+            content,
+            base_mtime,
+        });
+        self.record_recent(&file_path_for_recent);
+        self.activate_tab(self.open_tabs.len() - 1);
+    }
+
+    fn record_recent(&mut self, file_path: &str) {
+        self.recent_notes.record(file_path);
+        if self.is_read_only() {
+            return;
+        }
+        let path = self.storage.lock().unwrap().base_path().join(RECENT_FILE);
+        if let Err(e) = self.recent_notes.save(&path) {
+            self.notify(
+                ToastLevel::Error,
+                format!("Failed to save recent notes: {}", e),
+            );
         }
     }
 
-    // Favorites
-    fn toggle_favorite(&mut self) {
-        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
-            let note_id = (folder_idx, note_idx);
-            if let Some(pos) = self.favorite_notes.iter().position(|&id| id == note_id) {
-                self.favorite_notes.remove(pos);
-                println!("✓ Removed from favorites");
-            } else {
-                self.favorite_notes.push(note_id);
-                println!("✓ Added to favorites");
-            }
+    fn reorder_note(&mut self, folder_idx: usize, from: usize, to: usize) {
+        let result = self
+            .storage
+            .lock()
+            .unwrap()
+            .reorder_note(folder_idx, from, to);
+        if let Err(e) = result {
+            self.notify(ToastLevel::Error, format!("Failed to reorder notes: {}", e));
         }
     }
 
-    fn is_favorite(&self) -> bool {
-        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
-            self.favorite_notes.contains(&(folder_idx, note_idx))
-        } else {
-            false
+    fn save_folder_tree(&mut self) {
+        if self.is_read_only() {
+            return;
+        }
+        let path = self
+            .storage
+            .lock()
+            .unwrap()
+            .base_path()
+            .join(FOLDER_TREE_FILE);
+        if let Err(e) = self.folder_tree.save(&path) {
+            self.notify(
+                ToastLevel::Error,
+                format!("Failed to save folder layout: {}", e),
+            );
         }
     }
 
-    // Statistics
-    fn calculate_statistics(&self) -> NoteStatistics {
-        let storage = self.storage.lock().unwrap();
-        let total_notes: usize = storage.folders.iter().map(|f| f.notes.len()).sum();
-        let total_folders = storage.folders.len();
+    /// Keep the editor's buffer with its tab before switching away from it.
+    fn stash_active_tab(&mut self) {
+        if let Some(tab) = self.active_tab.and_then(|idx| self.open_tabs.get_mut(idx)) {
+            tab.content = self.current_note_content.clone();
+        }
+    }
 
-        let total_words: usize = storage
-            .folders
-            .iter()
-            .flat_map(|f| &f.notes)
-            .map(|n| n.content.split_whitespace().count())
-            .sum();
+    fn activate_tab(&mut self, idx: usize) {
+        if self.active_tab == Some(idx) {
+            return;
+        }
+        self.stash_active_tab();
+        let Some(tab) = self.open_tabs.get(idx) else {
+            return;
+        };
 
-        let total_chars: usize = storage
-            .folders
-            .iter()
-            .flat_map(|f| &f.notes)
-            .map(|n| n.content.len())
-            .sum();
+        self.active_tab = Some(idx);
+        self.selected_folder = Some(tab.folder_idx);
+        self.selected_note = Some(tab.note_idx);
+        self.current_note_content = tab.content.clone();
 
-        let encrypted_count: usize = storage
-            .folders
-            .iter()
-            .flat_map(|f| &f.notes)
-            .filter(|n| n.is_encrypted)
-            .count();
+        if self.spellcheck_enabled {
+            self.spelling
+                .check_now(&mut self.spellcheck, &self.current_note_content);
+        }
+    }
 
-        let total_tags = self.tag_manager.all_tags().len();
+    /// Leave the tabs open but show no note.
+    fn deactivate_tab(&mut self) {
+        self.stash_active_tab();
+        self.active_tab = None;
+        self.selected_note = None;
+        self.current_note_content.clear();
+    }
 
-        NoteStatistics {
-            total_notes,
-            total_folders,
-            total_words,
-            total_chars,
-            encrypted_count,
-            total_tags,
-            favorite_count: self.favorite_notes.len(),
+    fn tab_has_unsaved_changes(&self, idx: usize) -> bool {
+        let Some(tab) = self.open_tabs.get(idx) else {
+            return false;
+        };
+        let content = if self.active_tab == Some(idx) {
+            &self.current_note_content
+        } else {
+            &tab.content
+        };
+        if let Some(unlocked) = self.unlocked_notes.get(&tab.file_path) {
+            return is_dirty(content, Some(&unlocked.saved));
         }
+        let storage = self.storage.lock().unwrap();
+        let saved = storage
+            .folders
+            .get(tab.folder_idx)
+            .and_then(|f| f.notes.get(tab.note_idx))
+            .map(|note| note.content.as_str());
+        is_dirty(content, saved)
     }
 
-    // Auto-save
-    fn check_auto_save(&mut self) {
-        if self.auto_save_enabled && !self.show_markdown_preview {
-            // Only auto-save if we have a note selected and we're in edit mode (not preview)
-            if self.selected_folder.is_some() && self.selected_note.is_some() {
-                let elapsed = self.last_save_time.elapsed().as_secs_f32();
-                if elapsed >= self.auto_save_interval {
-                    self.save_current_note();
-                    println!("✓ Auto-saved");
+    fn has_unsaved_changes(&self) -> bool {
+        (0..self.open_tabs.len()).any(|idx| self.tab_has_unsaved_changes(idx))
+    }
+
+    /// Put every tab back to its note's saved content.
+    fn discard_unsaved_changes(&mut self) {
+        {
+            let storage = self.storage.lock().unwrap();
+            for tab in &mut self.open_tabs {
+                // Fails harmlessly when read-only, where no drafts are written
+                storage.clear_draft(tab.folder_idx, tab.note_idx).ok();
+                let saved = match self.unlocked_notes.get(&tab.file_path) {
+                    Some(unlocked) => Some(unlocked.saved.clone()),
+                    None => storage
+                        .folders
+                        .get(tab.folder_idx)
+                        .and_then(|f| f.notes.get(tab.note_idx))
+                        .map(|note| note.content.clone()),
+                };
+                if let Some(saved) = saved {
+                    tab.content = saved;
                 }
             }
         }
+        if let Some(tab) = self.active_tab.and_then(|idx| self.open_tabs.get(idx)) {
+            self.current_note_content = tab.content.clone();
+        }
     }
-}
-
-impl eframe::App for NoteTakingApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Apply theme every frame
-        self.apply_theme(ctx);
-
-        // Check auto-save
-        self.check_auto_save();
 
-        // Keyboard shortcuts
-        ctx.input(|i| {
-            // Ctrl/Cmd + S to save
-            if i.modifiers.command && i.key_pressed(egui::Key::S) {
-                self.save_current_note();
-            }
+    /// Switch vaults from the UI, asking what to do with unsaved edits first.
+    fn switch_vault(&mut self, path: PathBuf, ctx: &egui::Context) {
+        if self.has_unsaved_changes() {
+            self.leave_prompt = Some(LeaveAction::OpenVault(path));
+        } else {
+            self.open_vault(path, ctx);
+        }
+    }
 
-            // Ctrl/Cmd + P to toggle preview
-            if i.modifiers.command && i.key_pressed(egui::Key::P) {
-                if self.selected_note.is_some() {
-                    self.show_markdown_preview = !self.show_markdown_preview;
-                }
-            }
+    /// Close a tab, asking first if it has unsaved changes unless `discard` is set.
+    fn close_tab(&mut self, idx: usize, discard: bool) {
+        if idx >= self.open_tabs.len() {
+            return;
+        }
+        if !discard && self.tab_has_unsaved_changes(idx) {
+            self.tab_close_prompt = Some(idx);
+            return;
+        }
+        if discard {
+            let tab = &self.open_tabs[idx];
+            let storage = self.storage.lock().unwrap();
+            storage.clear_draft(tab.folder_idx, tab.note_idx).ok();
+        }
 
-            // Ctrl/Cmd + N for new note
-            if i.modifiers.command && i.key_pressed(egui::Key::N) {
-                if self.selected_folder.is_some() {
-                    self.show_new_note_dialog = true;
+        self.open_tabs.remove(idx);
+        match self.active_tab {
+            Some(active) if active == idx => {
+                self.active_tab = None;
+                if self.open_tabs.is_empty() {
+                    self.selected_note = None;
+                    self.current_note_content.clear();
+                } else {
+                    self.activate_tab(idx.min(self.open_tabs.len() - 1));
                 }
             }
+            Some(active) if active > idx => self.active_tab = Some(active - 1),
+            _ => {}
+        }
+    }
 
-            // Ctrl/Cmd + F for search (focus search bar)
-            if i.modifiers.command && i.key_pressed(egui::Key::F) {
-                // Search bar will be auto-focused
+    /// Save every tab with unsaved edits, e.g. before the window closes.
+    fn save_all_tabs(&mut self) {
+        let active = self.active_tab;
+        for idx in 0..self.open_tabs.len() {
+            if self.tab_has_unsaved_changes(idx) {
+                self.activate_tab(idx);
+                self.save_current_note();
             }
+        }
+        match active {
+            Some(idx) => self.activate_tab(idx),
+            None => self.deactivate_tab(),
+        }
+    }
 
-            // Ctrl/Cmd + E to encrypt/decrypt
-            if i.modifiers.command && i.key_pressed(egui::Key::E) {
-                if self.selected_note.is_some() {
-                    self.show_encryption_dialog = true;
+    /// Re-point tabs at their notes by path after notes moved or disappeared, closing
+    /// tabs whose note is gone. `saved` holds each tab's last-saved content from before
+    /// a reload; tabs without local edits take the new content from disk. The sidebar's
+    /// multi-selection holds bare indices, so it is dropped.
+    fn retarget_tabs(&mut self, storage: &Storage, saved: &[Option<String>]) {
+        self.stash_active_tab();
+        self.multi_selection.clear();
+        self.selection_anchor = None;
+        let mut kept = Vec::new();
+        let mut active = None;
+
+        for (idx, mut tab) in std::mem::take(&mut self.open_tabs).into_iter().enumerate() {
+            let Some((folder_idx, note_idx)) = storage.find_note_by_path(&tab.file_path) else {
+                continue;
+            };
+            tab.folder_idx = folder_idx;
+            tab.note_idx = note_idx;
+
+            let note = &storage.folders[folder_idx].notes[note_idx];
+            let disk_content = &note.content;
+            match saved.get(idx).cloned().flatten() {
+                Some(old) if tab.content == old => {
+                    tab.content = disk_content.clone();
+                    tab.base_mtime = note.disk_mtime;
                 }
+                Some(old) if *disk_content != old => self.notify(
+                    ToastLevel::Warning,
+                    format!(
+                        "{} changed on disk; keeping your unsaved edits",
+                        tab.file_path
+                    ),
+                ),
+                _ => {}
             }
 
-            // Ctrl/Cmd + Plus/Equals to zoom in
-            if i.modifiers.command && i.key_pressed(egui::Key::Plus) {
-                self.zoom_level = (self.zoom_level + 0.1).min(3.0);
+            if self.active_tab == Some(idx) {
+                active = Some(kept.len());
             }
+            kept.push(tab);
+        }
 
-            // Ctrl/Cmd + Minus to zoom out
-            if i.modifiers.command && i.key_pressed(egui::Key::Minus) {
-                self.zoom_level = (self.zoom_level - 0.1).max(0.5);
+        self.open_tabs = kept;
+        self.active_tab = active;
+        match active.and_then(|idx| self.open_tabs.get(idx)) {
+            Some(tab) => {
+                self.selected_folder = Some(tab.folder_idx);
+                self.selected_note = Some(tab.note_idx);
+                self.current_note_content = tab.content.clone();
             }
-
-            // Ctrl/Cmd + 0 to reset zoom
-            if i.modifiers.command && i.key_pressed(egui::Key::Num0) {
-                self.zoom_level = 1.0;
+            None => {
+                self.selected_note = None;
+                self.current_note_content.clear();
             }
-        });
+        }
+        if self
+            .selected_folder
+            .is_some_and(|f| f >= storage.folders.len())
+        {
+            self.selected_folder = None;
+        }
+    }
 
-        // Minimalist top panel
-        egui::TopBottomPanel::top("top_panel")
+    /// Start searching for `search_query` in the background; results arrive in
+    /// `poll_search_worker`. The previous results stay up until then.
+    fn perform_search(&mut self) {
+        if self.search_query.is_empty() {
+            self.search_worker.cancel();
+            self.search_results.clear();
+            return;
+        }
+
+        // "status:done report" filters on a property, for keys some note actually has
+        let query = {
+            let storage = self.storage.lock().unwrap();
+            search::SearchQuery::parse_with(&self.search_query, |key| {
+                search::has_property(&storage.folders, key)
+            })
+        };
+        self.search_worker.submit(
+            query,
+            Some(self.config.search_result_limit),
+            self.config.title_weight,
+        );
+    }
+
+    /// Show the newest background search results once they arrive.
+    fn poll_search_worker(&mut self, ctx: &egui::Context) {
+        if let Some(results) = self.search_worker.poll() {
+            self.search_total = results.total;
+            self.search_results = results.hits;
+        }
+        if self.search_worker.is_searching() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(30));
+        }
+    }
+
+    /// Collect the current search results into a new note of `[[links]]` with snippets.
+    fn save_search_results_as_note(&mut self) {
+        let query = self.search_query.trim().to_string();
+        let target = self.new_note_folder();
+        let Some(folder_idx) = target else {
+            return;
+        };
+
+        let created = {
+            let mut storage = self.storage.lock().unwrap();
+            let text = search::SearchQuery::parse_with(&query, |key| {
+                search::has_property(&storage.folders, key)
+            })
+            .text;
+            let results: Vec<(String, String)> = self
+                .search_results
+                .iter()
+                .filter_map(|&(f, n)| {
+                    storage.ensure_loaded(f, n).ok()?;
+                    let note = &storage.folders[f].notes[n];
+                    Some((note.title.clone(), search::snippet(&note.content, &text)))
+                })
+                .collect();
+            let body = search::results_to_markdown(&query, &results);
+            let title = storage.unique_title(folder_idx, &format!("Search {}", query));
+            storage.create_note(folder_idx, &title, &body)
+        };
+        match created {
+            Ok(note_idx) => {
+                self.search_worker.invalidate();
+                self.notify(
+                    ToastLevel::Success,
+                    format!("Saved search results for \"{}\"", query),
+                );
+                self.open_note(folder_idx, note_idx);
+            }
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to create note: {}", e)),
+        }
+    }
+
+    /// Where a new note goes: see `new_note_folder`.
+    fn new_note_folder(&mut self) -> Option<usize> {
+        let target = new_note_folder(
+            &mut self.storage.lock().unwrap(),
+            self.selected_folder,
+            &self.config.inbox_folder,
+        );
+        target.map_err(|e| self.notify(ToastLevel::Warning, e)).ok()
+    }
+
+    fn create_folder(&mut self) {
+        if !self.new_folder_name.is_empty() {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            match storage.create_folder(self.new_folder_name.trim()) {
+                Ok(()) => {
+                    self.new_folder_name.clear();
+                    self.show_new_folder_dialog = false;
+                }
+                Err(e) => self.notify(ToastLevel::Error, format!("Failed to create folder: {}", e)),
+            }
+        }
+    }
+
+    fn create_note(&mut self) {
+        if self.new_note_title.is_empty() {
+            self.notify(ToastLevel::Warning, "Note title is empty");
+            return;
+        }
+        let target = self.new_note_folder();
+        if let Some(folder_idx) = target {
+            let initial_content = self
+                .selected_template
+                .and_then(|idx| self.template_manager.templates.get(idx))
+                .map(|t| t.render(&self.new_note_title))
+                .unwrap_or_default();
+
+            let created = self.storage.lock().unwrap().create_note(
+                folder_idx,
+                &self.new_note_title,
+                &initial_content,
+            );
+            match created {
+                Ok(note_idx) => {
+                    self.search_worker.invalidate();
+                    self.notify(
+                        ToastLevel::Success,
+                        format!("Created note: {}", self.new_note_title),
+                    );
+
+                    // Auto-select and open the newly created note
+                    self.open_note(folder_idx, note_idx);
+
+                    self.new_note_title.clear();
+                    self.show_new_note_dialog = false;
+                }
+                Err(e) => self.notify(ToastLevel::Error, format!("Failed to create note: {}", e)),
+            }
+        }
+    }
+
+    /// Open today's note in the journal folder, creating the folder and note as needed.
+    fn open_daily_note(&mut self) {
+        let folder_name = self.config.journal_folder.trim().to_string();
+        if folder_name.is_empty() {
+            self.notify(ToastLevel::Warning, "Journal folder name is empty");
+            return;
+        }
+        let template = self.template_manager.daily_template();
+
+        let (opened, created) = {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            let folder_idx = match storage.find_or_create_folder(&folder_name) {
+                Ok(idx) => idx,
+                Err(e) => {
+                    self.notify(
+                        ToastLevel::Error,
+                        format!("Failed to create journal folder: {}", e),
+                    );
+                    return;
+                }
+            };
+            let notes_before = storage.folders[folder_idx].notes.len();
+            let opened = storage
+                .get_or_create_daily_note(folder_idx, &template)
+                .map(|note_idx| (folder_idx, note_idx));
+            let created = storage.folders[folder_idx].notes.len() != notes_before;
+            (opened, created)
+        };
+
+        match opened {
+            Ok((folder_idx, note_idx)) => {
+                if created {
+                    self.search_worker.invalidate();
+                    self.notify(
+                        ToastLevel::Success,
+                        format!("Created today's note in {}", folder_name),
+                    );
+                }
+                self.open_note(folder_idx, note_idx);
+            }
+            Err(e) => self.notify(
+                ToastLevel::Error,
+                format!("Failed to open today's note: {}", e),
+            ),
+        }
+    }
+
+    fn delete_current_note(&mut self) {
+        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            match storage.delete_note(folder_idx, note_idx) {
+                Ok(_) => {
+                    self.search_worker.invalidate();
+
+                    // Drop its tab and clear selection
+                    if let Some(tab) = self.active_tab {
+                        self.open_tabs.remove(tab);
+                        self.active_tab = None;
+                    }
+                    self.selected_note = None;
+                    self.current_note_content.clear();
+                    self.retarget_tabs(&storage, &[]);
+
+                    self.notify(ToastLevel::Success, "Note deleted");
+                }
+                Err(e) => self.notify(ToastLevel::Error, format!("Failed to delete note: {}", e)),
+            }
+        }
+    }
+
+    /// Put the note away in the archive: gone from the sidebar and search, but kept.
+    fn archive_current_note(&mut self) {
+        // Archiving closes the tab, so its edits must be on disk first
+        self.save_current_note();
+        if self
+            .active_tab
+            .is_some_and(|idx| self.tab_has_unsaved_changes(idx))
+        {
+            self.notify(
+                ToastLevel::Warning,
+                "Not archived: the note's changes couldn't be saved",
+            );
+            return;
+        }
+        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            match storage.archive_note(folder_idx, note_idx) {
+                Ok(note) => {
+                    self.search_worker.invalidate();
+
+                    if let Some(tab) = self.active_tab {
+                        self.open_tabs.remove(tab);
+                        self.active_tab = None;
+                    }
+                    self.selected_note = None;
+                    self.current_note_content.clear();
+                    self.retarget_tabs(&storage, &[]);
+                    if self.archived_notes.is_some() {
+                        self.archived_notes = Some(storage.list_archived());
+                    }
+
+                    self.notify(ToastLevel::Success, format!("Archived \"{}\"", note.title));
+                }
+                Err(e) => self.notify(ToastLevel::Error, format!("Failed to archive note: {}", e)),
+            }
+        }
+    }
+
+    fn show_graph_view(&mut self) {
+        let graph = graph::Graph::build(&self.storage.lock().unwrap().folders, &self.link_manager);
+        let layout = graph::GraphLayout::new(graph.nodes.len());
+        self.graph_view = Some((graph, layout));
+    }
+
+    fn show_archived_notes(&mut self) {
+        self.archived_notes = Some(self.storage.lock().unwrap().list_archived());
+    }
+
+    fn restore_archived_note(&mut self, path: &std::path::Path) {
+        let restored = {
+            let mut storage = self.storage.lock().unwrap();
+            let restored = storage.unarchive(path);
+            self.archived_notes = Some(storage.list_archived());
+            restored
+        };
+        match restored {
+            Ok((folder_idx, note_idx)) => {
+                self.search_worker.invalidate();
+                self.notify(ToastLevel::Success, "Note restored");
+                self.open_note(folder_idx, note_idx);
+            }
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to restore note: {}", e)),
+        }
+    }
+
+    fn delete_current_folder(&mut self) {
+        if let Some(folder_idx) = self.selected_folder {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            match storage.delete_folder(folder_idx) {
+                Ok(_) => {
+                    self.search_worker.invalidate();
+
+                    // Clear selection; tabs for the folder's notes close with it
+                    self.selected_folder = None;
+                    self.retarget_tabs(&storage, &[]);
+                    drop(storage);
+                    self.deactivate_tab();
+
+                    self.notify(ToastLevel::Success, "Folder deleted");
+                }
+                Err(e) => self.notify(ToastLevel::Error, format!("Failed to delete folder: {}", e)),
+            }
+        }
+    }
+
+    /// Start mirroring the vault into its cloud sync folder in the background.
+    fn sync_to_cloud(&mut self) {
+        if self.sync_worker.is_some() {
+            return;
+        }
+        let plan = self.storage.lock().unwrap().cloud_sync_plan();
+        self.sync_worker = Some(sync::SyncWorker::spawn(plan));
+    }
+
+    /// Pick up the cloud sync's progress, and its result once it's done.
+    fn poll_sync_worker(&mut self, ctx: &egui::Context) {
+        let Some(worker) = &mut self.sync_worker else {
+            return;
+        };
+        let Some(result) = worker.poll() else {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        };
+        let cancelled = worker.is_cancelled();
+        self.sync_worker = None;
+        match result {
+            Err(_) if cancelled => self.notify(ToastLevel::Warning, "Sync cancelled"),
+            Ok(summary) => self.notify(
+                ToastLevel::Success,
+                format!(
+                    "Synced to {} ({} added, {} updated, {} deleted)",
+                    summary.path, summary.added, summary.updated, summary.deleted
+                ),
+            ),
+            Err(e) => self.notify(ToastLevel::Error, format!("Sync failed: {}", e)),
+        }
+    }
+
+    fn render_sync_progress(&mut self, ctx: &egui::Context) {
+        let Some(worker) = &self.sync_worker else {
+            return;
+        };
+        let (done, total) = worker.progress();
+        let mut cancel = false;
+        egui::Window::new("☁ Syncing")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-12.0, -40.0])
+            .show(ctx, |ui| {
+                let fraction = if total == 0 {
+                    0.0
+                } else {
+                    done as f32 / total as f32
+                };
+                ui.add(
+                    egui::ProgressBar::new(fraction)
+                        .desired_width(220.0)
+                        .text(format!("{} / {} files", done, total)),
+                );
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        if cancel {
+            worker.cancel();
+        }
+    }
+
+    // Theme management
+    fn apply_theme(&mut self, ctx: &egui::Context) {
+        if self.show_theme_editor {
+            // Live preview of the theme being edited
+            self.theme_draft.apply_to_egui(ctx);
+        } else {
+            self.theme_manager.current_theme.apply_to_egui(ctx);
+        }
+    }
+
+    fn select_theme(&mut self, theme: Theme) {
+        self.theme_manager.set_theme(theme);
+        // An explicit choice overrides following the OS appearance
+        self.theme_manager.follow_system = false;
+        self.save_theme_preferences();
+    }
+
+    fn save_theme_preferences(&mut self) {
+        if let Err(e) = self
+            .theme_manager
+            .save_preferences(std::path::Path::new(THEME_PREFERENCES_PATH))
+        {
+            self.notify(
+                ToastLevel::Error,
+                format!("Failed to save theme preferences: {}", e),
+            );
+        }
+    }
+
+    fn open_theme_editor(&mut self) {
+        self.theme_draft = self.theme_manager.current_theme.clone();
+        self.theme_draft.name = format!("{} (Custom)", self.theme_draft.name);
+        self.show_theme_editor = true;
+    }
+
+    fn save_custom_theme(&mut self) {
+        match self.theme_manager.save_user_theme(self.theme_draft.clone()) {
+            Ok(path) => {
+                self.notify(ToastLevel::Success, format!("Theme saved: {:?}", path));
+                self.select_theme(self.theme_draft.clone());
+                self.show_theme_editor = false;
+            }
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to save theme: {}", e)),
+        }
+    }
+
+    fn toggle_dark_mode(&mut self) {
+        self.theme_manager.toggle_dark_mode();
+        self.theme_manager.follow_system = false;
+        self.save_theme_preferences();
+    }
+
+    // Tag management
+    fn add_tag(&mut self) {
+        if !self.new_tag_name.is_empty() {
+            self.tag_manager.add_tag(self.new_tag_name.clone());
+            self.new_tag_name.clear();
+            self.save_tags();
+        }
+    }
+
+    fn remove_tag(&mut self, name: &str) {
+        let Some(removed) = self.tag_manager.remove_tag(name) else {
+            return;
+        };
+
+        {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            let mut changed = tags::reindex_notes(&mut storage.folders, removed);
+
+            // Drop the name from front-matter too, or it would be re-registered on restart
+            for (folder_idx, folder) in storage.folders.iter_mut().enumerate() {
+                for (note_idx, note) in folder.notes.iter_mut().enumerate() {
+                    if let Some(front_matter) = note.front_matter.as_mut() {
+                        if front_matter.tags.iter().any(|t| t == name) {
+                            front_matter.tags.retain(|t| t != name);
+                            if !changed.contains(&(folder_idx, note_idx)) {
+                                changed.push((folder_idx, note_idx));
+                            }
+                        }
+                    }
+                }
+            }
+
+            for (folder_idx, note_idx) in changed {
+                if let Err(e) =
+                    save_note_for_tabs(&mut storage, &mut self.open_tabs, folder_idx, note_idx)
+                {
+                    self.notify(ToastLevel::Error, format!("Failed to save note: {}", e));
+                }
+            }
+        }
+
+        self.selected_tag_filter = match self.selected_tag_filter {
+            Some(idx) if idx == removed => None,
+            Some(idx) if idx > removed => Some(idx - 1),
+            other => other,
+        };
+        self.save_tags();
+    }
+
+    /// Rename a tag everywhere. With `merge`, a name collision folds this tag into the existing one.
+    fn rename_tag(&mut self, idx: usize, merge: bool) {
+        let new_name = self.rename_tag_input.trim().to_string();
+        let Some(old_name) = self
+            .tag_manager
+            .get_tag_by_index(idx)
+            .map(|t| t.name.clone())
+        else {
+            return;
+        };
+
+        let existing = self
+            .tag_manager
+            .all_tags()
+            .iter()
+            .position(|t| t.name == new_name)
+            .filter(|&existing| existing != idx);
+
+        let storage_handle = self.storage.clone();
+        let mut storage = storage_handle.lock().unwrap();
+        let result = match existing {
+            Some(into) if merge => self
+                .tag_manager
+                .merge_tag(&mut storage.folders, idx, into)
+                .map(|mut changed| {
+                    if self.selected_tag_filter == Some(idx) {
+                        self.selected_tag_filter = Some(if into > idx { into - 1 } else { into });
+                    } else if let Some(filter) = self.selected_tag_filter.filter(|&f| f > idx) {
+                        self.selected_tag_filter = Some(filter - 1);
+                    }
+                    changed.extend(tags::rename_front_matter_tag(
+                        &mut storage.folders,
+                        &old_name,
+                        &new_name,
+                    ));
+                    changed
+                }),
+            _ => self
+                .tag_manager
+                .rename_tag(idx, &new_name)
+                .map(|_| tags::rename_front_matter_tag(&mut storage.folders, &old_name, &new_name)),
+        };
+
+        match result {
+            Ok(changed) => {
+                for (folder_idx, note_idx) in changed {
+                    if let Err(e) =
+                        save_note_for_tabs(&mut storage, &mut self.open_tabs, folder_idx, note_idx)
+                    {
+                        self.notify(ToastLevel::Error, format!("Failed to save note: {}", e));
+                    }
+                }
+                drop(storage);
+                self.renaming_tag = None;
+                self.rename_tag_error = None;
+                self.save_tags();
+            }
+            Err(e) => self.rename_tag_error = Some(e),
+        }
+    }
+
+    fn save_tags(&mut self) {
+        if self.is_read_only() {
+            return;
+        }
+        let path = self.storage.lock().unwrap().base_path().join(TAGS_FILE);
+        if let Err(e) = self.tag_manager.save(&path) {
+            self.notify(ToastLevel::Error, format!("Failed to save tags: {}", e));
+        }
+    }
+
+    fn assign_tag_to_note(&mut self, tag_idx: usize) {
+        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            if let Some(folder) = storage.folders.get_mut(folder_idx) {
+                if let Some(note) = folder.notes.get_mut(note_idx) {
+                    note.add_tag(tag_idx);
+
+                    // Keep the front-matter tag list in sync so it's written back on save
+                    if let (Some(front_matter), Some(tag)) = (
+                        note.front_matter.as_mut(),
+                        self.tag_manager.get_tag_by_index(tag_idx),
+                    ) {
+                        if !front_matter.tags.contains(&tag.name) {
+                            front_matter.tags.push(tag.name.clone());
+                        }
+                    }
+
+                    if let Err(e) =
+                        save_note_for_tabs(&mut storage, &mut self.open_tabs, folder_idx, note_idx)
+                    {
+                        self.notify(ToastLevel::Error, format!("Failed to save note: {}", e));
+                    }
+                }
+            }
+        }
+    }
+
+    // Encryption
+    fn toggle_encryption(&mut self) {
+        if self.encryption_password != self.confirm_password {
+            self.notify(ToastLevel::Error, "Passwords don't match!");
+            return;
+        }
+        // Encrypt what's in the editor, and don't decrypt an older copy over new edits
+        self.save_current_note();
+
+        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            if let Some(folder) = storage.folders.get_mut(folder_idx) {
+                if let Some(note) = folder.notes.get_mut(note_idx) {
+                    if note.is_encrypted {
+                        // Decrypt
+                        if let Some(ref encrypted_data) = note.encrypted_data {
+                            match self
+                                .encryption
+                                .decrypt(encrypted_data, &self.encryption_password)
+                            {
+                                Ok(decrypted) => {
+                                    note.content = decrypted;
+                                    note.is_encrypted = false;
+                                    note.encrypted_data = None;
+                                    self.unlocked_notes.remove(&note.file_path);
+                                    self.current_note_content = note.content.clone();
+                                    self.notify(ToastLevel::Success, "Note decrypted");
+                                }
+                                Err(e) => self
+                                    .notify(ToastLevel::Error, format!("Decryption failed: {}", e)),
+                            }
+                        }
+                    } else {
+                        // Encrypt
+                        match self
+                            .encryption
+                            .encrypt(&note.content, &self.encryption_password)
+                        {
+                            Ok(mut encrypted_data) => {
+                                let hint = self.password_hint.trim();
+                                encrypted_data.hint = (!hint.is_empty()).then(|| hint.to_string());
+                                note.encrypted_data = Some(encrypted_data);
+                                note.is_encrypted = true;
+                                note.content = ENCRYPTED_PLACEHOLDER.to_string();
+                                self.current_note_content =
+                                    "[ENCRYPTED - Enter password to decrypt]".to_string();
+                                self.notify(ToastLevel::Success, "Note encrypted");
+                            }
+                            Err(e) => {
+                                self.notify(ToastLevel::Error, format!("Encryption failed: {}", e))
+                            }
+                        }
+                    }
+
+                    if let Err(e) =
+                        save_note_for_tabs(&mut storage, &mut self.open_tabs, folder_idx, note_idx)
+                    {
+                        self.notify(ToastLevel::Error, format!("Failed to save note: {}", e));
+                    }
+                    // Encrypted notes must not stay findable by their plaintext
+                    self.search_worker
+                        .update_note(&storage.folders, folder_idx, note_idx);
+                }
+            }
+        }
+
+        self.encryption_password.clear();
+        self.confirm_password.clear();
+        self.password_hint.clear();
+        self.show_encryption_dialog = false;
+    }
+
+    /// Whether the selected note is encrypted, and the hint to its password if
+    /// it was given one.
+    fn selected_note_encryption(&self) -> (bool, Option<String>) {
+        let storage = self.storage.lock().unwrap();
+        let note = self
+            .selected_folder
+            .zip(self.selected_note)
+            .and_then(|(f, n)| storage.folders.get(f)?.notes.get(n));
+        (
+            note.is_some_and(|note| note.is_encrypted),
+            note.and_then(|note| note.encrypted_data.as_ref()?.hint.clone()),
+        )
+    }
+
+    // Export
+    /// Where a note exported as `extension` goes, by the configured template and
+    /// directory. Directories on the way are created.
+    fn export_path(&self, title: &str, folder: &str, extension: &str) -> std::io::Result<PathBuf> {
+        let name = export::ExportName {
+            title,
+            folder,
+            date: chrono::Local::now().date_naive(),
+            extension,
+        };
+        let path = name.expand(
+            &self.config.export_template,
+            &self.config.export_dir,
+            &self.config.filename_policy,
+        );
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+
+    /// A vault-wide or combined export's file, straight in the export directory.
+    fn export_dir_path(&self, file_name: &str) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.config.export_dir)?;
+        Ok(self.config.export_dir.join(file_name))
+    }
+
+    /// Export one note in the chosen format, its app tags folded into the front-matter.
+    /// `used` holds the paths already written in this batch, which it won't overwrite.
+    fn export_note(
+        &self,
+        folder_idx: usize,
+        note_idx: usize,
+        used: &mut std::collections::HashSet<PathBuf>,
+    ) -> Result<PathBuf, String> {
+        let exporter = self
+            .exporters
+            .get(&self.export_format)
+            .ok_or_else(|| format!("No exporter for .{}", self.export_format))?;
+        let storage = self.storage.lock().unwrap();
+        let folder = storage
+            .folders
+            .get(folder_idx)
+            .ok_or_else(|| "Folder not found".to_string())?;
+        let mut note = folder
+            .notes
+            .get(note_idx)
+            .ok_or_else(|| "Note not found".to_string())?
+            .clone();
+        let front_matter = note.front_matter.get_or_insert_with(Default::default);
+        for tag in note.tags.get_tags(&self.tag_manager) {
+            if !front_matter.tags.contains(&tag.name) {
+                front_matter.tags.push(tag.name.clone());
+            }
+        }
+
+        let output_path = self
+            .export_path(&note.title, &folder.name, exporter.extension())
+            .map_err(|e| e.to_string())?;
+        let output_path = export::unique_export_path(output_path, used);
+        used.insert(output_path.clone());
+        exporter
+            .export(&note, &output_path)
+            .map_err(|e| e.to_string())?;
+        Ok(output_path)
+    }
+
+    /// Export the selected note in the format chosen in the export dialog. Its app
+    /// tags go into the front-matter so Markdown exports keep them.
+    fn export_selected_note(&mut self) {
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        let Some(format_name) = self
+            .exporters
+            .get(&self.export_format)
+            .map(|e| e.name().to_string())
+        else {
+            return;
+        };
+        match self.export_note(folder_idx, note_idx, &mut Default::default()) {
+            Ok(output_path) => self.notify(
+                ToastLevel::Success,
+                format!("Exported to {}: {:?}", format_name, output_path),
+            ),
+            Err(e) => self.notify(
+                ToastLevel::Error,
+                format!("{} export failed: {}", format_name, e),
+            ),
+        }
+    }
+
+    /// Export each note in the selected folder to a file of its own. Encrypted
+    /// notes are skipped.
+    fn export_folder_notes(&mut self) {
+        let Some(folder_idx) = self.selected_folder else {
+            return;
+        };
+        let note_count = {
+            let mut storage = self.storage.lock().unwrap();
+            storage.ensure_all_loaded();
+            storage.folders.get(folder_idx).map_or(0, |f| f.notes.len())
+        };
+        let mut exported = 0;
+        let mut failed = Vec::new();
+        let mut used = std::collections::HashSet::new();
+        for note_idx in 0..note_count {
+            match self.export_note(folder_idx, note_idx, &mut used) {
+                Ok(_) => exported += 1,
+                Err(e) => failed.push(e),
+            }
+        }
+        let mut message = format!(
+            "Exported {} notes to {}",
+            exported,
+            self.config.export_dir.display()
+        );
+        if !failed.is_empty() {
+            message.push_str(&format!(" ({} skipped: {})", failed.len(), failed[0]));
+        }
+        let level = if failed.is_empty() {
+            ToastLevel::Success
+        } else {
+            ToastLevel::Warning
+        };
+        self.notify(level, message);
+    }
+
+    fn export_folder_to_pdf(&mut self) {
+        if let Some(folder_idx) = self.selected_folder {
+            let storage_handle = self.storage.clone();
+            let mut storage = storage_handle.lock().unwrap();
+            storage.ensure_all_loaded();
+            if let Some(folder) = storage.folders.get(folder_idx) {
+                let notes: Vec<(String, String)> = folder
+                    .notes
+                    .iter()
+                    .map(|n| (n.title.clone(), n.content.clone()))
+                    .collect();
+
+                let output_path = self.export_dir_path(&format!("{}_folder.pdf", folder.name));
+                match output_path
+                    .map_err(|e| e.to_string())
+                    .and_then(|output_path| {
+                        pdf_export::PdfExporter::export_multiple_notes(&notes, &output_path)
+                            .map(|_| output_path)
+                    }) {
+                    Ok(output_path) => self.notify(
+                        ToastLevel::Success,
+                        format!("Exported folder to PDF: {:?}", output_path),
+                    ),
+                    Err(e) => self.notify(ToastLevel::Error, format!("PDF export failed: {}", e)),
+                }
+            }
+        }
+    }
+
+    /// Combine a folder's notes, or the whole vault's, into one Markdown file.
+    /// Encrypted notes are left out.
+    fn export_combined_markdown(&mut self, folder_idx: Option<usize>) {
+        let storage_handle = self.storage.clone();
+        let mut storage = storage_handle.lock().unwrap();
+        storage.ensure_all_loaded();
+        let folders: Vec<_> = match folder_idx {
+            Some(idx) => storage.folders.get(idx).into_iter().collect(),
+            None => storage.folders.iter().collect(),
+        };
+        let notes: Vec<(String, String)> = folders
+            .iter()
+            .flat_map(|folder| {
+                folder
+                    .sorted_note_indices(self.note_sort_order)
+                    .into_iter()
+                    .map(|note_idx| &folder.notes[note_idx])
+            })
+            .filter(|n| !n.is_encrypted)
+            .map(|n| (n.title.clone(), n.content.clone()))
+            .collect();
+
+        let file_name = match folder_idx.and_then(|idx| storage.folders.get(idx)) {
+            Some(folder) => format!("{}_combined.md", folder.name),
+            None => "vault_combined.md".to_string(),
+        };
+        match self
+            .export_dir_path(&file_name)
+            .map_err(|e| e.to_string())
+            .and_then(|output_path| {
+                markdown_export::MarkdownExporter::export_combined(&notes, &output_path)
+                    .map(|_| output_path)
+            }) {
+            Ok(output_path) => self.notify(
+                ToastLevel::Success,
+                format!("Combined {} notes into {:?}", notes.len(), output_path),
+            ),
+            Err(e) => self.notify(ToastLevel::Error, format!("Markdown export failed: {}", e)),
+        }
+    }
+
+    fn export_vault_to_site(&mut self) {
+        let storage_handle = self.storage.clone();
+        let mut storage = storage_handle.lock().unwrap();
+        storage.ensure_all_loaded();
+        let output_dir = self.config.export_dir.join("site");
+        match html_export::HtmlExporter::export_site(&storage.folders, &output_dir) {
+            Ok(summary) => self.notify(
+                ToastLevel::Success,
+                format!(
+                    "Exported {} pages and {} images to {:?}",
+                    summary.pages, summary.images, output_dir
+                ),
+            ),
+            Err(e) => self.notify(ToastLevel::Error, format!("Site export failed: {}", e)),
+        }
+    }
+
+    fn export_vault_to_json(&mut self) {
+        let output = self.config.export_dir.join("vault.json");
+        let result = std::fs::create_dir_all(&self.config.export_dir)
+            .map_err(Into::into)
+            .and_then(|_| {
+                self.storage
+                    .lock()
+                    .unwrap()
+                    .export_json_to(&self.tag_manager, &output)
+            });
+        match result {
+            Ok(count) => self.notify(
+                ToastLevel::Success,
+                format!("Exported {} notes to {:?}", count, output),
+            ),
+            Err(e) => self.notify(ToastLevel::Error, format!("JSON export failed: {}", e)),
+        }
+    }
+
+    fn import_json_vault(&mut self, path: &std::path::Path, ctx: &egui::Context) {
+        self.save_all_tabs();
+        let result = std::fs::read_to_string(path)
+            .map_err(Into::into)
+            .and_then(|json| {
+                self.storage
+                    .lock()
+                    .unwrap()
+                    .import_json(&json, &mut self.tag_manager)
+            });
+        let count = match result {
+            Ok(count) => count,
+            Err(e) => {
+                self.notify(ToastLevel::Error, format!("JSON import failed: {}", e));
+                return;
+            }
+        };
+        self.save_tags();
+        self.notify(
+            ToastLevel::Success,
+            format!("Imported {} notes from {:?}", count, path),
+        );
+
+        // Reopen so the sidebar, links and tags pick up the new notes
+        self.open_vault(self.config.vault_path.clone(), ctx);
+        if let Some(worker) = &mut self.commit_worker {
+            worker.queue(CommitJob::All {
+                message: format!("Imported notes from {}", path.display()),
+            });
+        }
+    }
+
+    fn import_obsidian_vault(&mut self, vault_path: &std::path::Path, ctx: &egui::Context) {
+        self.save_all_tabs();
+        let result = self.storage.lock().unwrap().import_obsidian(vault_path);
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                self.notify(ToastLevel::Error, format!("Obsidian import failed: {}", e));
+                return;
+            }
+        };
+        self.notify(
+            ToastLevel::Success,
+            format!(
+                "Imported {} notes and {} attachments from {:?}",
+                report.notes, report.attachments, vault_path
+            ),
+        );
+        for (title, link) in &report.unresolved_links {
+            self.notify(
+                ToastLevel::Warning,
+                format!("{}: no note for [[{}]]", title, link),
+            );
+        }
+
+        // Folders were reloaded, so reopen to reset every index into them
+        self.open_vault(self.config.vault_path.clone(), ctx);
+        if let Some(worker) = &mut self.commit_worker {
+            worker.queue(CommitJob::All {
+                message: format!("Imported Obsidian vault: {}", vault_path.display()),
+            });
+        }
+    }
+
+    // Version history
+    fn load_version_history(&mut self) {
+        self.version_diff = None;
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        let history = {
+            let storage = self.storage.lock().unwrap();
+            let note = storage
+                .folders
+                .get(folder_idx)
+                .and_then(|f| f.notes.get(note_idx));
+            match (note, &self.version_control) {
+                (Some(note), Some(vc)) => {
+                    Some(vc.get_file_history(&PathBuf::from(&note.file_path)))
+                }
+                _ => None,
+            }
+        };
+        match history {
+            Some(Ok(versions)) => self.note_versions = versions,
+            Some(Err(e)) => {
+                self.notify(ToastLevel::Error, format!("Failed to load versions: {}", e))
+            }
+            None => {}
+        }
+    }
+
+    fn restore_version(&mut self, version_idx: usize) {
+        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+            if let Some(version) = self.note_versions.get(version_idx).cloned() {
+                let storage_handle = self.storage.clone();
+                let mut storage = storage_handle.lock().unwrap();
+                if let Some(folder) = storage.folders.get_mut(folder_idx) {
+                    if let Some(note) = folder.notes.get_mut(note_idx) {
+                        if let Some(ref vc) = self.version_control {
+                            match vc.restore_note(note, &version.commit_id) {
+                                Ok(_) => {
+                                    self.current_note_content = note.content.clone();
+                                    // Persist the refreshed timestamp alongside the restored file
+                                    if let Err(e) = save_note_for_tabs(
+                                        &mut storage,
+                                        &mut self.open_tabs,
+                                        folder_idx,
+                                        note_idx,
+                                    ) {
+                                        self.notify(
+                                            ToastLevel::Error,
+                                            format!("Failed to save note: {}", e),
+                                        );
+                                    }
+                                    self.search_worker.update_note(
+                                        &storage.folders,
+                                        folder_idx,
+                                        note_idx,
+                                    );
+                                    self.notify(
+                                        ToastLevel::Success,
+                                        format!("Restored version from {}", version.timestamp),
+                                    );
+                                }
+                                Err(e) => self
+                                    .notify(ToastLevel::Error, format!("Failed to restore: {}", e)),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.spellcheck_enabled {
+            self.spelling
+                .check_now(&mut self.spellcheck, &self.current_note_content);
+        }
+        self.load_version_history();
+    }
+
+    /// Diff the version at `version_idx` against the one before it.
+    fn load_version_diff(&mut self, version_idx: usize) {
+        self.version_diff = None;
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        let (Some(newer), Some(older)) = (
+            self.note_versions.get(version_idx),
+            self.note_versions.get(version_idx + 1),
+        ) else {
+            self.version_diff = Some("This is the first version.".to_string());
+            return;
+        };
+
+        let file_path = {
+            let storage = self.storage.lock().unwrap();
+            storage
+                .folders
+                .get(folder_idx)
+                .and_then(|f| f.notes.get(note_idx))
+                .map(|n| PathBuf::from(&n.file_path))
+        };
+
+        if let (Some(vc), Some(file_path)) = (&self.version_control, file_path) {
+            match vc.get_diff(&file_path, &older.commit_id, &newer.commit_id) {
+                Ok(diff) if diff.is_empty() => {
+                    self.version_diff = Some("No changes to this note.".to_string())
+                }
+                Ok(diff) => self.version_diff = Some(diff),
+                Err(e) => {
+                    let message = format!("Failed to diff versions: {}", e);
+                    self.notify(ToastLevel::Error, message);
+                }
+            }
+        }
+    }
+
+    /// Diff the version at `version_idx` against the editor's current content.
+    fn load_working_diff(&mut self, version_idx: usize) {
+        self.version_diff = None;
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        let Some(version) = self.note_versions.get(version_idx) else {
+            return;
+        };
+
+        let note_file = {
+            let storage = self.storage.lock().unwrap();
+            storage
+                .folders
+                .get(folder_idx)
+                .and_then(|f| f.notes.get(note_idx))
+                .map(|n| (PathBuf::from(&n.file_path), n.front_matter.clone()))
+        };
+        let Some((file_path, front_matter)) = note_file else {
+            return;
+        };
+
+        // Committed blobs include the front-matter block, so compare like with like
+        let current = match &front_matter {
+            Some(fm) => match frontmatter::join_front_matter(fm, &self.current_note_content) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    self.notify(ToastLevel::Error, e.to_string());
+                    return;
+                }
+            },
+            None => self.current_note_content.clone(),
+        };
+
+        if let Some(vc) = &self.version_control {
+            match vc.diff_against_working(&file_path, &version.commit_id, &current) {
+                Ok(diff) if diff.is_empty() => {
+                    self.version_diff = Some("Current content matches this version.".to_string())
+                }
+                Ok(diff) => self.version_diff = Some(diff),
+                Err(e) => {
+                    let message = format!("Failed to diff against current content: {}", e);
+                    self.notify(ToastLevel::Error, message);
+                }
+            }
+        }
+    }
+
+    /// Move the selected text into a new note in the same folder, link to it in its
+    /// place, and save both.
+    fn extract_to_new_note(&mut self) {
+        let Some((range, text)) = self.extract_selection.take() else {
+            return;
+        };
+        let (Some(folder_idx), Some(_)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        if self.current_note_content.get(range.clone()) != Some(text.as_str()) {
+            self.notify(
+                ToastLevel::Warning,
+                "The note changed; select the text to extract again",
+            );
+            return;
+        }
+        let title = self.extract_title.trim().to_string();
+        if self.storage.lock().unwrap().title_taken(folder_idx, &title) {
+            self.notify(
+                ToastLevel::Warning,
+                format!("A note called \"{}\" is already in this folder", title),
+            );
+            // Keep the dialog open for another title
+            self.extract_selection = Some((range, text));
+            return;
+        }
+        let Some((original, extracted)) =
+            links::extract_selection(&self.current_note_content, range, &title)
+        else {
+            self.notify(ToastLevel::Warning, "Nothing to extract");
+            return;
+        };
+
+        let created = self
+            .storage
+            .lock()
+            .unwrap()
+            .create_note(folder_idx, &title, &extracted);
+        match created {
+            Ok(_) => {
+                self.search_worker.invalidate();
+                self.current_note_content = original;
+                self.save_current_note();
+                if self.spellcheck_enabled {
+                    self.spelling
+                        .check_now(&mut self.spellcheck, &self.current_note_content);
+                }
+                self.notify(
+                    ToastLevel::Success,
+                    format!("Extracted to new note: {}", title),
+                );
+            }
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to create note: {}", e)),
+        }
+    }
+
+    /// Normalize the open note's Markdown in the editor. Saving is left to the user.
+    fn format_current_note(&mut self) {
+        if self.selected_note.is_none() {
+            return;
+        }
+        let formatted = markdown_format::format_markdown(&self.current_note_content);
+        if formatted == self.current_note_content {
+            self.notify(ToastLevel::Info, "Note is already formatted");
+            return;
+        }
+        self.current_note_content = formatted;
+        if self.spellcheck_enabled {
+            self.spelling
+                .check_now(&mut self.spellcheck, &self.current_note_content);
+        }
+        self.notify(ToastLevel::Success, "Formatted note");
+    }
+
+    /// Write the selected note's history as a Markdown timeline, newest first.
+    fn export_history_timeline(&mut self) {
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        let note_file = {
+            let storage = self.storage.lock().unwrap();
+            storage
+                .folders
+                .get(folder_idx)
+                .and_then(|f| f.notes.get(note_idx))
+                .map(|n| (PathBuf::from(&n.file_path), n.title.clone()))
+        };
+        let (Some(vc), Some((file_path, title))) = (&self.version_control, note_file) else {
+            return;
+        };
+
+        let result = self
+            .export_dir_path(&format!("{}_history.md", title))
+            .map_err(|e| e.to_string())
+            .and_then(|output_path| {
+                let timeline = vc.export_history_markdown(&file_path)?;
+                std::fs::write(&output_path, timeline)
+                    .map_err(|e| format!("Failed to write {}: {}", output_path.display(), e))?;
+                Ok(output_path)
+            });
+        match result {
+            Ok(output_path) => self.notify(
+                ToastLevel::Success,
+                format!("Exported history to {:?}", output_path),
+            ),
+            Err(e) => self.notify(ToastLevel::Error, format!("History export failed: {}", e)),
+        }
+    }
+
+    // Favorites
+    #[allow(dead_code)]
+    fn toggle_favorite(&mut self) {
+        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+            let note_id = (folder_idx, note_idx);
+            if let Some(pos) = self.favorite_notes.iter().position(|&id| id == note_id) {
+                self.favorite_notes.remove(pos);
+                self.notify(ToastLevel::Success, "Removed from favorites");
+            } else {
+                self.favorite_notes.push(note_id);
+                self.notify(ToastLevel::Success, "Added to favorites");
+            }
+        }
+    }
+
+    fn is_favorite(&self) -> bool {
+        if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+            self.favorite_notes.contains(&(folder_idx, note_idx))
+        } else {
+            false
+        }
+    }
+
+    // Statistics
+    fn find_orphan_notes(&mut self) {
+        let mut storage = self.storage.lock().unwrap();
+        storage.ensure_all_loaded();
+        self.orphan_notes = Some(links::find_orphan_notes(&storage.folders));
+    }
+
+    fn find_duplicate_notes(&mut self) {
+        self.duplicate_notes = Some(self.storage.lock().unwrap().find_duplicates());
+    }
+
+    /// Merge the other notes of a duplicate group into `keep`. Tabs follow the notes
+    /// that remain, and tabs of the kept note and of notes whose links were re-pointed
+    /// show the new content unless they have unsaved edits.
+    fn resolve_duplicates(&mut self, keep: (usize, usize), others: &[(usize, usize)]) {
+        self.stash_active_tab();
+        let storage_handle = self.storage.clone();
+        let mut storage = storage_handle.lock().unwrap();
+        let saved: Vec<Option<String>> = self
+            .open_tabs
+            .iter()
+            .map(|t| {
+                storage
+                    .folders
+                    .get(t.folder_idx)
+                    .and_then(|f| f.notes.get(t.note_idx))
+                    .map(|n| n.content.clone())
+            })
+            .collect();
+
+        match storage.merge_notes(keep, others) {
+            Ok(_) => {
+                self.notify(
+                    ToastLevel::Success,
+                    format!("Merged {} duplicate(s)", others.len()),
+                );
+            }
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to merge notes: {}", e)),
+        }
+        self.search_worker.invalidate();
+        self.link_manager = LinkManager::build(&storage.folders);
+        self.retarget_tabs(&storage, &saved);
+        self.duplicate_notes = Some(storage.find_duplicates());
+    }
+
+    fn delete_duplicate(&mut self, note: (usize, usize)) {
+        let storage_handle = self.storage.clone();
+        let mut storage = storage_handle.lock().unwrap();
+        match storage.delete_note(note.0, note.1) {
+            Ok(_) => self.notify(ToastLevel::Success, "Duplicate deleted"),
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to delete note: {}", e)),
+        }
+        self.search_worker.invalidate();
+        self.retarget_tabs(&storage, &[]);
+        self.duplicate_notes = Some(storage.find_duplicates());
+    }
+
+    fn calculate_statistics(&self) -> NoteStatistics {
+        let mut storage = self.storage.lock().unwrap();
+        let vault = storage.compute_stats(&self.tag_manager);
+        let notes = || storage.folders.iter().flat_map(|f| &f.notes);
+
+        NoteStatistics {
+            vault,
+            total_folders: storage.folders.len(),
+            total_chars: notes().map(|n| n.content.len()).sum(),
+            encrypted_count: notes().filter(|n| n.is_encrypted).count(),
+            favorite_count: self.favorite_notes.len(),
+        }
+    }
+
+    // Auto-save
+    /// Save the clipboard image into the note's `images/` folder and insert a reference to it.
+    fn paste_clipboard_image(&mut self, ctx: &egui::Context) {
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+
+        let image = match arboard::Clipboard::new().and_then(|mut c| c.get_image()) {
+            Ok(image) => image,
+            Err(e) => {
+                self.notify(
+                    ToastLevel::Warning,
+                    format!("No image on the clipboard: {}", e),
+                );
+                return;
+            }
+        };
+
+        let result = self.storage.lock().unwrap().save_pasted_image(
+            folder_idx,
+            note_idx,
+            &image.bytes,
+            image.width as u32,
+            image.height as u32,
+        );
+        match result {
+            Ok(markdown) => {
+                self.insert_at_cursor(ctx, &markdown);
+                self.notify(ToastLevel::Success, "Pasted image");
+            }
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to paste image: {}", e)),
+        }
+    }
+
+    /// Copy dropped image files into the note's `images/` folder and append references to them.
+    fn import_dropped_files(&mut self, paths: &[PathBuf]) {
+        if self.show_markdown_preview {
+            return;
+        }
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+
+        for path in paths {
+            let result = self
+                .storage
+                .lock()
+                .unwrap()
+                .import_image(folder_idx, note_idx, path);
+            match result {
+                Ok(markdown) => {
+                    if !self.current_note_content.is_empty()
+                        && !self.current_note_content.ends_with('\n')
+                    {
+                        self.current_note_content.push('\n');
+                    }
+                    self.current_note_content.push_str(&markdown);
+                    self.current_note_content.push('\n');
+                    self.notify(
+                        ToastLevel::Success,
+                        format!("Added image {}", path.display()),
+                    );
+                }
+                Err(e) => self.notify(ToastLevel::Warning, format!("Ignored dropped file: {}", e)),
+            }
+        }
+    }
+
+    /// Insert `text` at the editor's cursor (or the end of the note) and move the cursor past it.
+    fn insert_at_cursor(&mut self, ctx: &egui::Context, text: &str) {
+        let state = self
+            .editor_id
+            .and_then(|id| egui::TextEdit::load_state(ctx, id));
+        let char_idx = state
+            .as_ref()
+            .and_then(|s| s.cursor.char_range())
+            .map(|range| range.primary.index)
+            .unwrap_or_else(|| self.current_note_content.chars().count());
+        let byte_idx = self
+            .current_note_content
+            .char_indices()
+            .nth(char_idx)
+            .map_or(self.current_note_content.len(), |(i, _)| i);
+        insert_at_byte(&mut self.current_note_content, byte_idx, text);
+
+        if let (Some(id), Some(mut state)) = (self.editor_id, state) {
+            let cursor = egui::text::CCursor::new(char_idx + text.chars().count());
+            state
+                .cursor
+                .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+            state.store(ctx, id);
+        }
+    }
+
+    /// Insert a `[[link]]` to a note dropped from the sidebar at character `char_idx`,
+    /// or at the editor's cursor (or the end of the note) when `None`.
+    fn drop_note_link(&mut self, ctx: &egui::Context, char_idx: Option<usize>, title: &str) {
+        let state = self
+            .editor_id
+            .and_then(|id| egui::TextEdit::load_state(ctx, id));
+        let char_idx = char_idx
+            .or_else(|| {
+                state
+                    .as_ref()
+                    .and_then(|s| s.cursor.char_range())
+                    .map(|range| range.primary.index)
+            })
+            .unwrap_or_else(|| self.current_note_content.chars().count());
+        let after =
+            links::insert_wiki_link_at_cursor(&mut self.current_note_content, char_idx, title);
+
+        if let Some(id) = self.editor_id {
+            let mut state = state.unwrap_or_default();
+            let cursor = egui::text::CCursor::new(after);
+            state
+                .cursor
+                .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+            state.store(ctx, id);
+            ctx.memory_mut(|m| m.request_focus(id));
+        }
+    }
+
+    /// Copy the open note's unsaved edits to its draft every `DRAFT_INTERVAL`, so a
+    /// crash loses at most a few seconds of typing.
+    fn check_draft(&mut self) {
+        if self.last_draft_time.elapsed() < DRAFT_INTERVAL || self.is_read_only() {
+            return;
+        }
+        self.last_draft_time = std::time::Instant::now();
+        let (Some(idx), Some(folder_idx), Some(note_idx)) =
+            (self.active_tab, self.selected_folder, self.selected_note)
+        else {
+            return;
+        };
+        let note = (folder_idx, note_idx);
+        let drafted = self
+            .last_draft
+            .as_ref()
+            .is_some_and(|(id, text)| *id == note && *text == self.current_note_content);
+        if drafted || !self.tab_has_unsaved_changes(idx) {
+            return;
+        }
+        let result = self.storage.lock().unwrap().write_draft(
+            folder_idx,
+            note_idx,
+            &self.current_note_content,
+        );
+        match result {
+            Ok(()) => self.last_draft = Some((note, self.current_note_content.clone())),
+            Err(e) => self.notify(ToastLevel::Warning, format!("Failed to write draft: {}", e)),
+        }
+    }
+
+    /// Back the vault up on a background thread every `backup_interval_minutes` when
+    /// scheduled backups are on, and report how the last one went once it's done.
+    fn check_scheduled_backup(&mut self, ctx: &egui::Context) {
+        if self
+            .backup_job
+            .as_ref()
+            .is_some_and(|job| job.is_finished())
+        {
+            match self.backup_job.take().map(|job| job.join()) {
+                Some(Ok(Ok(Some(path)))) => self.notify(
+                    ToastLevel::Info,
+                    format!("Backed up vault to {}", path.display()),
+                ),
+                Some(Ok(Ok(None))) => {}
+                Some(Ok(Err(e))) => {
+                    self.notify(ToastLevel::Error, format!("Scheduled backup failed: {}", e))
+                }
+                _ => self.notify(ToastLevel::Error, "Scheduled backup stopped unexpectedly"),
+            }
+        }
+        if !self.config.scheduled_backups {
+            return;
+        }
+        let interval =
+            std::time::Duration::from_secs(self.config.backup_interval_minutes.max(1) * 60);
+        let elapsed = self.last_backup_time.elapsed();
+        if elapsed < interval || self.backup_job.is_some() {
+            // Wake up for it even if nothing else happens meanwhile
+            ctx.request_repaint_after(interval.saturating_sub(elapsed));
+            return;
+        }
+        self.last_backup_time = std::time::Instant::now();
+        let (source, backup_root) = {
+            let storage = self.storage.lock().unwrap();
+            (
+                storage.base_path().to_path_buf(),
+                storage.default_backup_root(),
+            )
+        };
+        let keep_n = self.config.backup_count;
+        self.backup_job = Some(std::thread::spawn(move || {
+            // Nothing new to keep if the vault hasn't changed since the last one
+            storage::backup_vault_if_changed(&source, &backup_root, keep_n)
+        }));
+    }
+
+    fn check_auto_save(&mut self) {
+        if self.auto_save_enabled && !self.show_markdown_preview {
+            // Only auto-save if we have a note selected and we're in edit mode (not preview)
+            if self.selected_folder.is_some() && self.selected_note.is_some() {
+                let elapsed = self.last_save_time.elapsed().as_secs_f32();
+                if elapsed >= self.auto_save_interval {
+                    self.save_current_note();
+                }
+            }
+        }
+    }
+}
+
+impl eframe::App for NoteTakingApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Re-check the OS appearance whenever the window regains focus
+        let focused = ctx.input(|i| i.focused);
+        if focused && !self.window_focused && self.theme_manager.follow_system {
+            self.theme_manager.apply_system_preference();
+        }
+        self.window_focused = focused;
+
+        // Apply theme every frame
+        self.apply_theme(ctx);
+
+        // Check auto-save
+        self.check_auto_save();
+        self.check_draft();
+        self.check_scheduled_backup(ctx);
+        if !self.window_checked {
+            self.check_window_position(ctx);
+        }
+        if ctx.input(|i| i.viewport().close_requested()) {
+            if !self.quit_confirmed && self.has_unsaved_changes() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                self.leave_prompt = Some(LeaveAction::Quit);
+            } else {
+                self.save_window_geometry(ctx);
+                if self.config.commit_policy != CommitPolicy::Manual {
+                    self.commit_uncommitted_notes();
+                }
+            }
+        }
+        self.poll_commit_policy(ctx);
+        self.poll_commit_worker(ctx);
+        self.poll_sync_worker(ctx);
+        self.poll_note_watcher(ctx);
+        self.poll_search_worker(ctx);
+        // Keep the "N minutes ago" labels current while the app sits idle
+        ctx.request_repaint_after(std::time::Duration::from_secs(60));
+
+        // Keyboard shortcuts
+        let mut paste_image = false;
+        let mut toggle_presentation = false;
+        let editor_focused = self
+            .editor_id
+            .is_some_and(|id| ctx.memory(|m| m.has_focus(id)));
+        ctx.input(|i| {
+            // Ctrl/Cmd + S to save
+            if i.modifiers.command && i.key_pressed(egui::Key::S) {
+                self.save_current_note_with_feedback();
+            }
+
+            // Ctrl/Cmd + P to toggle preview
+            if i.modifiers.command && i.key_pressed(egui::Key::P) && self.selected_note.is_some() {
+                self.show_markdown_preview = !self.show_markdown_preview;
+            }
+
+            // F11 presents the note; F11 or Escape goes back
+            if i.key_pressed(egui::Key::F11)
+                || (self.presenting && i.key_pressed(egui::Key::Escape))
+            {
+                toggle_presentation = true;
+            }
+
+            // Ctrl/Cmd + Shift + I to paste a clipboard image. Plain Ctrl+V never reaches the
+            // app when the clipboard holds only an image, so it needs its own shortcut.
+            if i.modifiers.command
+                && i.modifiers.shift
+                && i.key_pressed(egui::Key::I)
+                && self.selected_note.is_some()
+                && !self.show_markdown_preview
+            {
+                paste_image = true;
+            }
+
+            // Ctrl/Cmd + N for new note, in the inbox if no folder is selected
+            if i.modifiers.command && i.key_pressed(egui::Key::N) {
+                self.show_new_note_dialog = true;
+            }
+
+            // Ctrl/Cmd + F in the editor to find within the note
+            if i.modifiers.command
+                && i.key_pressed(egui::Key::F)
+                && editor_focused
+                && !self.show_markdown_preview
+            {
+                self.find_open = true;
+                self.find_focus_requested = true;
+            }
+
+            // Ctrl/Cmd + E to encrypt/decrypt
+            if i.modifiers.command && i.key_pressed(egui::Key::E) && self.selected_note.is_some() {
+                self.show_encryption_dialog = true;
+            }
+
+            // Ctrl/Cmd + Plus/Equals to zoom in
+            if i.modifiers.command && i.key_pressed(egui::Key::Plus) {
+                self.zoom_level = (self.zoom_level + 0.1).min(3.0);
+            }
+
+            // Ctrl/Cmd + Minus to zoom out
+            if i.modifiers.command && i.key_pressed(egui::Key::Minus) {
+                self.zoom_level = (self.zoom_level - 0.1).max(0.5);
+            }
+
+            // Ctrl/Cmd + 0 to reset zoom
+            if i.modifiers.command && i.key_pressed(egui::Key::Num0) {
+                self.zoom_level = 1.0;
+            }
+        });
+        if paste_image {
+            self.paste_clipboard_image(ctx);
+        }
+        if toggle_presentation {
+            self.set_presenting(!self.presenting, ctx);
+        }
+
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|f| f.path.clone())
+                .collect()
+        });
+        if !dropped.is_empty() {
+            self.import_dropped_files(&dropped);
+        }
+
+        // Presenting replaces the whole layout; the panels' own flags are left alone
+        // so everything comes back as it was
+        if self.presenting {
+            self.render_presentation(ctx);
+            return;
+        }
+
+        // Minimalist top panel
+        egui::TopBottomPanel::top("top_panel")
             .frame(egui::Frame::none().inner_margin(egui::Margin::symmetric(12.0, 8.0)))
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
-                    // Sidebar toggle
+                    // Sidebar toggle
+                    if ui
+                        .small_button(if self.sidebar_open { "◀" } else { "▶" })
+                        .clicked()
+                    {
+                        self.sidebar_open = !self.sidebar_open;
+                    }
+
+                    ui.add_space(8.0);
+                    self.render_vault_switcher(ui);
+                    ui.add_space(8.0);
+
+                    // Clean search bar
+                    let search_response = ui
+                        .add(
+                            egui::TextEdit::singleline(&mut self.search_query)
+                                .hint_text("Search...")
+                                .desired_width(200.0),
+                        )
+                        .on_hover_text("Add key:value, e.g. status:done, to filter by property");
+                    if search_response.changed() {
+                        self.perform_search();
+                    }
+
+                    ui.add_space(8.0);
+
+                    // Minimalist menu buttons
+                    let note_hint = match self.selected_folder {
+                        Some(_) => "New note in the selected folder".to_string(),
+                        None => format!("New note in {}", self.config.inbox_folder),
+                    };
+                    let read_only = self.is_read_only();
+                    if read_only {
+                        ui.label(egui::RichText::new("🔒 Read-only").small().weak())
+                            .on_hover_text("Notes can be browsed but not changed");
+                    } else {
+                        if ui.small_button("+ Note").on_hover_text(note_hint).clicked() {
+                            self.show_new_note_dialog = true;
+                        }
+
+                        if ui.small_button("+ Folder").clicked() {
+                            self.show_new_folder_dialog = true;
+                        }
+
+                        if ui
+                            .small_button("📅 Today")
+                            .on_hover_text(format!(
+                                "Open today's note in {}",
+                                self.config.journal_folder
+                            ))
+                            .clicked()
+                        {
+                            self.open_daily_note();
+                        }
+                    }
+
+                    ui.separator();
+
+                    // Prominent Preview/Edit toggle button
+                    let preview_enabled = self.selected_note.is_some();
+                    ui.add_enabled_ui(preview_enabled, |ui| {
+                        let button_text = if self.show_markdown_preview {
+                            "✏ Edit (Ctrl+P)"
+                        } else {
+                            "👁 Preview (Ctrl+P)"
+                        };
+                        if ui.button(button_text).clicked() {
+                            self.show_markdown_preview = !self.show_markdown_preview;
+                            if !self.show_markdown_preview {
+                                // Switching back to edit mode, save first
+                                self.save_current_note();
+                            }
+                        }
+                    });
+
+                    let paste_enabled =
+                        self.selected_note.is_some() && !self.show_markdown_preview && !read_only;
+                    ui.add_enabled_ui(paste_enabled, |ui| {
+                        if ui
+                            .button("🖼 Paste Image")
+                            .on_hover_text("Ctrl+Shift+I")
+                            .clicked()
+                        {
+                            self.paste_clipboard_image(ui.ctx());
+                        }
+                    });
+
+                    ui.separator();
+
+                    // Compact menus
+                    ui.menu_button("View", |ui| {
+                        if ui.button("Statistics").clicked() {
+                            self.show_statistics = !self.show_statistics;
+                            self.statistics =
+                                self.show_statistics.then(|| self.calculate_statistics());
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button("Orphan Notes")
+                            .on_hover_text("Notes with no links in or out")
+                            .clicked()
+                        {
+                            self.find_orphan_notes();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button("Duplicate Notes")
+                            .on_hover_text("Notes with the same or nearly the same content")
+                            .clicked()
+                        {
+                            self.find_duplicate_notes();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button("Graph View")
+                            .on_hover_text("Notes and the links between them")
+                            .clicked()
+                        {
+                            self.show_graph_view();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button("Archived Notes")
+                            .on_hover_text("Notes put away in the archive, to read or restore")
+                            .clicked()
+                        {
+                            self.show_archived_notes();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.selected_note.is_some(),
+                                egui::Button::new("Present (F11)"),
+                            )
+                            .on_hover_text("Show the note read-only and full screen")
+                            .clicked()
+                        {
+                            self.set_presenting(true, ui.ctx());
+                            ui.close_menu();
+                        }
+                        ui.checkbox(&mut self.show_outline, "Outline");
+                        ui.checkbox(&mut self.show_properties_panel, "Properties");
+                        ui.checkbox(&mut self.show_tasks_panel, "Tasks");
+                        if ui
+                            .checkbox(&mut self.config.split_view, "Split Preview")
+                            .on_hover_text("Show the rendered note beside the editor")
+                            .changed()
+                        {
+                            self.save_config();
+                        }
+                        if ui
+                            .checkbox(&mut self.config.soft_wrap, "Soft Wrap")
+                            .on_hover_text(
+                                "Wrap long lines in the editor instead of scrolling sideways",
+                            )
+                            .changed()
+                        {
+                            self.save_config();
+                        }
+                        if ui.button("Task Dashboard").clicked() {
+                            self.open_tasks = self.storage.lock().unwrap().collect_open_tasks();
+                            self.show_task_dashboard = true;
+                            ui.close_menu();
+                        }
+                    });
+
+                    ui.menu_button("Tools", |ui| {
+                        if ui.button("Manage Tags").clicked() {
+                            self.show_tag_dialog = true;
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.selected_note.is_some() && !read_only,
+                                egui::Button::new("Format Note"),
+                            )
+                            .on_hover_text(
+                                "Tidy blank lines, headings, bullets and trailing spaces",
+                            )
+                            .clicked()
+                        {
+                            self.format_current_note();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(!self.is_read_only(), egui::Button::new("Snapshot Vault"))
+                            .clicked()
+                        {
+                            self.snapshot_vault();
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.sync_worker.is_none(),
+                                egui::Button::new("Sync to Cloud Folder"),
+                            )
+                            .on_hover_text(
+                                "Copy new and changed notes to the folder your sync client uploads",
+                            )
+                            .clicked()
+                        {
+                            self.sync_to_cloud();
+                            ui.close_menu();
+                        }
+                        if ui.button("Export").clicked() {
+                            self.show_export_dialog = true;
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(!read_only, egui::Button::new("Import Obsidian Vault…"))
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            if let Some(path) = rfd::FileDialog::new()
+                                .set_title("Choose an Obsidian vault")
+                                .pick_folder()
+                            {
+                                self.import_obsidian_vault(&path, ctx);
+                            }
+                        }
+                        if ui
+                            .add_enabled(!read_only, egui::Button::new("Import JSON…"))
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            if let Some(path) = rfd::FileDialog::new()
+                                .set_title("Choose a vault exported as JSON")
+                                .add_filter("JSON", &["json"])
+                                .pick_file()
+                            {
+                                self.import_json_vault(&path, ctx);
+                            }
+                        }
+                        if ui
+                            .add_enabled(!read_only, egui::Button::new("Encrypt"))
+                            .clicked()
+                        {
+                            self.show_encryption_dialog = true;
+                            ui.close_menu();
+                        }
+                    });
+
+                    ui.menu_button("⚙", |ui| {
+                        if ui.button("Settings").clicked() {
+                            self.show_settings = true;
+                            ui.close_menu();
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Journal folder:");
+                            if ui
+                                .add(
+                                    egui::TextEdit::singleline(&mut self.config.journal_folder)
+                                        .desired_width(120.0),
+                                )
+                                .changed()
+                            {
+                                self.save_config();
+                            }
+                        });
+                        if ui.button("Theme").clicked() {
+                            self.show_theme_dialog = true;
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button(if self.theme_manager.current_theme.is_dark {
+                                "Light Mode"
+                            } else {
+                                "Dark Mode"
+                            })
+                            .clicked()
+                        {
+                            self.toggle_dark_mode();
+                            ui.close_menu();
+                        }
+                        let mut follow_system = self.theme_manager.follow_system;
+                        if ui
+                            .checkbox(&mut follow_system, "Follow system theme")
+                            .changed()
+                        {
+                            self.theme_manager.set_follow_system(follow_system);
+                            self.save_theme_preferences();
+                        }
+                        ui.separator();
+
+                        // Font selection
+                        ui.label("Font:");
+                        for font in EditorFont::all() {
+                            let is_current = font == self.current_font;
+                            let text = if is_current {
+                                format!("✓ {}", font.name())
+                            } else {
+                                font.name().to_string()
+                            };
+                            if ui.button(text).clicked() {
+                                self.current_font = font;
+                            }
+                        }
+
+                        ui.separator();
+                        ui.checkbox(&mut self.auto_save_enabled, "Auto-save");
+                        if ui
+                            .checkbox(&mut self.spellcheck_enabled, "Spell Check")
+                            .changed()
+                            && self.spellcheck_enabled
+                        {
+                            self.spelling
+                                .check_now(&mut self.spellcheck, &self.current_note_content);
+                        }
+                        if self.spellcheck_enabled && self.spellcheck.languages().count() > 1 {
+                            self.render_language_picker(ui);
+                        }
+                    });
+
+                    // Right-aligned current note with save button
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if self.selected_note.is_some() {
+                            if !read_only && ui.small_button("💾 Save").clicked() {
+                                self.save_current_note_with_feedback();
+                            }
+
+                            if let (Some(folder_idx), Some(note_idx)) =
+                                (self.selected_folder, self.selected_note)
+                            {
+                                let storage = self.storage.lock().unwrap();
+                                if let Some(folder) = storage.folders.get(folder_idx) {
+                                    if let Some(note) = folder.notes.get(note_idx) {
+                                        ui.add_space(8.0);
+                                        let mut title_text = note.title.clone();
+                                        if note.is_encrypted {
+                                            title_text = format!("🔒 {}", title_text);
+                                        }
+                                        if self.is_favorite() {
+                                            title_text = format!("⭐ {}", title_text);
+                                        }
+                                        ui.label(title_text);
+                                    }
+                                }
+                            }
+                        }
+                    });
+                });
+            });
+
+        // Rest of the UI remains the same but continues in sidebar and central panel
+        if self.sidebar_open {
+            self.render_sidebar(ctx);
+        }
+
+        // Bottom panel with zoom controls
+        egui::TopBottomPanel::bottom("bottom_panel")
+            .frame(
+                egui::Frame::none()
+                    .inner_margin(egui::Margin::symmetric(12.0, 6.0))
+                    .fill(egui::Color32::from_gray(25)),
+            )
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    // Delete buttons (left)
+                    if self.selected_folder.is_some() && !self.is_read_only() {
+                        if self.selected_note.is_some() {
+                            if ui
+                                .small_button("📦 Archive Note")
+                                .on_hover_text("Move the note out of the vault without deleting it")
+                                .clicked()
+                            {
+                                self.archive_current_note();
+                            }
+                            ui.separator();
+                            if ui
+                                .small_button("🗑 Delete Note")
+                                .on_hover_text("Delete current note")
+                                .clicked()
+                            {
+                                self.delete_current_note();
+                            }
+                            ui.separator();
+                        }
+                        if ui
+                            .small_button("🗑 Delete Folder")
+                            .on_hover_text("Delete current folder and all its notes")
+                            .clicked()
+                        {
+                            self.delete_current_folder();
+                        }
+                        ui.separator();
+                    }
+
+                    // Zoom controls (center-left)
+                    ui.label(egui::RichText::new("Zoom:").weak().small());
+                    if ui
+                        .small_button("🔍−")
+                        .on_hover_text("Zoom out (Ctrl+−)")
+                        .clicked()
+                    {
+                        self.zoom_level = (self.zoom_level - 0.1).max(0.5);
+                    }
+                    ui.label(
+                        egui::RichText::new(format!("{}%", (self.zoom_level * 100.0) as i32))
+                            .small(),
+                    );
+                    if ui
+                        .small_button("🔍+")
+                        .on_hover_text("Zoom in (Ctrl++)")
+                        .clicked()
+                    {
+                        self.zoom_level = (self.zoom_level + 0.1).min(3.0);
+                    }
+                    if ui
+                        .small_button("↺")
+                        .on_hover_text("Reset zoom (Ctrl+0)")
+                        .clicked()
+                    {
+                        self.zoom_level = 1.0;
+                    }
+
+                    // Version history button (right)
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if self.selected_note.is_some()
+                            && ui
+                                .small_button("📜 Versions")
+                                .on_hover_text("View version history")
+                                .clicked()
+                        {
+                            self.load_version_history();
+                            self.show_version_history = true;
+                        }
+                    });
+                });
+            });
+
+        self.render_outline_panel(ctx);
+        self.render_properties_panel(ctx);
+        self.render_tasks_panel(ctx);
+        self.render_central_panel(ctx);
+        self.render_all_dialogs(ctx);
+        self.toasts.show(ctx);
+    }
+}
+
+impl NoteTakingApp {
+    // Render helper methods
+    fn render_sidebar(&mut self, ctx: &egui::Context) {
+        let response = egui::SidePanel::left("sidebar")
+            .resizable(true)
+            .default_width(self.config.window.sidebar_width)
+            .frame(
+                egui::Frame::none()
+                    .inner_margin(egui::Margin::same(12.0))
+                    .fill(egui::Color32::from_gray(25)),
+            ) // Slightly lighter dark gray
+            .show(ctx, |ui| {
+                // Set text color to be clearly visible
+                ui.style_mut().visuals.override_text_color = Some(egui::Color32::from_gray(220));
+
+                ui.heading("Notes");
+                ui.add_space(4.0);
+                egui::ComboBox::from_id_salt("note_sort_order")
+                    .selected_text(format!("Sort: {}", self.note_sort_order.label()))
+                    .show_ui(ui, |ui| {
+                        for order in SortOrder::ALL {
+                            ui.selectable_value(&mut self.note_sort_order, order, order.label());
+                        }
+                    });
+                ui.add_space(8.0);
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    // Show search results if searching
+                    if !self.search_query.is_empty() && !self.search_results.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new("Search Results")
+                                    .strong()
+                                    .color(egui::Color32::from_gray(200)),
+                            );
+                            if ui
+                                .small_button("💾 Save as note")
+                                .on_hover_text("Create a note linking to every result")
+                                .clicked()
+                            {
+                                self.save_search_results_as_note();
+                            }
+                            if ui
+                                .small_button("⭐ Smart folder")
+                                .on_hover_text("Keep this search in the sidebar")
+                                .clicked()
+                            {
+                                self.saved_search_name = self.search_query.trim().to_string();
+                                self.saved_search_scope = SearchScope::AllFolders;
+                                self.saved_search_tag = None;
+                                self.show_save_search_dialog = true;
+                            }
+                        });
+                        if self.search_total > self.search_results.len() {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "Showing {} of {}",
+                                    self.search_results.len(),
+                                    self.search_total
+                                ))
+                                .small()
+                                .color(egui::Color32::from_gray(150)),
+                            );
+                        }
+                        ui.add_space(4.0);
+
+                        let search_display: Vec<_> = {
+                            let storage = self.storage.lock().unwrap();
+                            self.search_results
+                                .iter()
+                                .filter_map(|(folder_idx, note_idx)| {
+                                    storage.folders.get(*folder_idx).and_then(|folder| {
+                                        folder.notes.get(*note_idx).map(|note| {
+                                            (
+                                                *folder_idx,
+                                                *note_idx,
+                                                note.title.clone(),
+                                                folder.name.clone(),
+                                            )
+                                        })
+                                    })
+                                })
+                                .collect()
+                        };
+
+                        for (folder_idx, note_idx, title, folder_name) in search_display {
+                            let is_selected = self.selected_folder == Some(folder_idx)
+                                && self.selected_note == Some(note_idx);
+
+                            if ui
+                                .selectable_label(
+                                    is_selected,
+                                    egui::RichText::new(&title)
+                                        .color(egui::Color32::from_gray(220)),
+                                )
+                                .clicked()
+                            {
+                                self.open_note(folder_idx, note_idx);
+                            }
+                            ui.label(
+                                egui::RichText::new(&folder_name)
+                                    .small()
+                                    .color(egui::Color32::from_gray(150)),
+                            );
+                            ui.add_space(4.0);
+                        }
+
+                        ui.add_space(8.0);
+                        ui.separator();
+                        ui.add_space(8.0);
+                        ui.label(
+                            egui::RichText::new("All Notes")
+                                .strong()
+                                .color(egui::Color32::from_gray(200)),
+                        );
+                        ui.add_space(4.0);
+                    }
+
+                    if self.search_query.is_empty() {
+                        self.render_recent_notes(ui);
+                    }
+
+                    self.render_smart_folders(ui);
+
+                    // Show folder tree
+                    self.render_folder_tree(ui);
+                });
+            });
+        self.config.window.sidebar_width = response.response.rect.width();
+    }
+
+    fn render_recent_notes(&mut self, ui: &mut egui::Ui) {
+        if self.recent_notes.is_empty() {
+            return;
+        }
+
+        // Resolve paths on every frame so entries survive index shifts and skip deleted notes
+        let recent_display: Vec<_> = {
+            let storage = self.storage.lock().unwrap();
+            self.recent_notes
+                .iter()
+                .filter_map(|path| {
+                    let (folder_idx, note_idx) = storage.find_note_by_path(path)?;
+                    let title = storage.folders[folder_idx].notes[note_idx].title.clone();
+                    Some((folder_idx, note_idx, title))
+                })
+                .collect()
+        };
+        if recent_display.is_empty() {
+            return;
+        }
+
+        ui.label(
+            egui::RichText::new("Recent")
+                .strong()
+                .color(egui::Color32::from_gray(200)),
+        );
+        ui.add_space(4.0);
+
+        for (folder_idx, note_idx, title) in recent_display {
+            let is_selected =
+                self.selected_folder == Some(folder_idx) && self.selected_note == Some(note_idx);
+            if ui
+                .selectable_label(
+                    is_selected,
+                    egui::RichText::new(&title).color(egui::Color32::from_gray(210)),
+                )
+                .clicked()
+            {
+                self.open_note(folder_idx, note_idx);
+            }
+        }
+
+        ui.add_space(8.0);
+        ui.separator();
+        ui.add_space(8.0);
+    }
+
+    fn render_smart_folders(&mut self, ui: &mut egui::Ui) {
+        if self.config.saved_searches.is_empty() {
+            return;
+        }
+
+        let mut toggled = None;
+        let mut deleted = None;
+        let mut opened = None;
+        for (idx, saved) in self.config.saved_searches.iter().enumerate() {
+            let results = match &self.open_smart_folder {
+                Some((open_idx, results)) if *open_idx == idx => Some(results),
+                _ => None,
+            };
+            let header = egui::RichText::new(format!("🔎 {}", saved.name))
+                .strong()
+                .color(egui::Color32::from_gray(230));
+            let header_response = egui::CollapsingHeader::new(header)
+                .id_salt(("smart_folder", idx))
+                .open(Some(results.is_some()))
+                .show(ui, |ui| {
+                    let storage = self.storage.lock().unwrap();
+                    let notes: Vec<_> = results
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|&(f, n)| {
+                            let note = storage.folders.get(f)?.notes.get(n)?;
+                            Some((f, n, note.title.clone()))
+                        })
+                        .collect();
+                    drop(storage);
+                    if notes.is_empty() {
+                        ui.label(egui::RichText::new("No matching notes").small().weak());
+                    }
+                    for (folder_idx, note_idx, title) in notes {
+                        let is_selected = self.selected_folder == Some(folder_idx)
+                            && self.selected_note == Some(note_idx);
+                        if ui.selectable_label(is_selected, title).clicked() {
+                            opened = Some((folder_idx, note_idx));
+                        }
+                    }
+                })
+                .header_response;
+            let hover = match &saved.tag_filter {
+                Some(tag) => format!("{} (tag: {})", saved.query, tag),
+                None => saved.query.clone(),
+            };
+            let header_response = header_response.on_hover_text(hover);
+            if header_response.clicked() {
+                toggled = Some(idx);
+            }
+            header_response.context_menu(|ui| {
+                if ui.button("🗑 Delete Smart Folder").clicked() {
+                    deleted = Some(idx);
+                    ui.close_menu();
+                }
+            });
+        }
+
+        if let Some((folder_idx, note_idx)) = opened {
+            self.open_note(folder_idx, note_idx);
+        }
+        if let Some(idx) = toggled {
+            if matches!(self.open_smart_folder, Some((open_idx, _)) if open_idx == idx) {
+                self.open_smart_folder = None;
+            } else {
+                self.open_smart_folder(idx);
+            }
+        }
+        if let Some(idx) = deleted {
+            let removed = self.config.saved_searches.remove(idx);
+            self.open_smart_folder = None;
+            self.save_config();
+            self.notify(
+                ToastLevel::Success,
+                format!("Deleted smart folder \"{}\"", removed.name),
+            );
+        }
+
+        ui.add_space(8.0);
+        ui.separator();
+        ui.add_space(8.0);
+    }
+
+    /// Re-run a saved search and show its results under its smart folder.
+    fn open_smart_folder(&mut self, idx: usize) {
+        let Some(saved) = self.config.saved_searches.get(idx) else {
+            return;
+        };
+        let mut storage = self.storage.lock().unwrap();
+        storage.ensure_all_loaded();
+        let mut search = FuzzySearch::new();
+        search.set_max_results(Some(self.config.search_result_limit));
+        search.set_title_weight(self.config.title_weight);
+        let results = saved.evaluate(&mut search, &storage.folders, &self.tag_manager);
+        self.open_smart_folder = Some((idx, results.hits));
+    }
+
+    /// Ctrl-click toggles `note` in the multi-selection; Shift-click (`range`) selects
+    /// every note from the last one clicked to it, in `display_order`, the sidebar
+    /// order of `note`'s folder. The open note starts the selection.
+    fn extend_selection(&mut self, note: (usize, usize), display_order: &[usize], range: bool) {
+        if self.multi_selection.is_empty() {
+            if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+                self.multi_selection.push((folder_idx, note_idx));
+            }
+        }
+
+        let position = |n: (usize, usize)| {
+            (n.0 == note.0)
+                .then(|| display_order.iter().position(|&idx| idx == n.1))
+                .flatten()
+        };
+        let anchor = self.selection_anchor.and_then(position);
+        match (range, anchor, position(note)) {
+            (true, Some(from), Some(to)) => {
+                for &note_idx in &display_order[from.min(to)..=from.max(to)] {
+                    if !self.multi_selection.contains(&(note.0, note_idx)) {
+                        self.multi_selection.push((note.0, note_idx));
+                    }
+                }
+            }
+            _ => {
+                match self.multi_selection.iter().position(|&n| n == note) {
+                    Some(idx) => {
+                        self.multi_selection.remove(idx);
+                    }
+                    None => self.multi_selection.push(note),
+                }
+                self.selection_anchor = Some(note);
+            }
+        }
+    }
+
+    /// Export the multi-selected notes, in the order they were picked, into one PDF.
+    fn export_selection_to_pdf(&mut self) {
+        let (notes, failed) = {
+            let mut storage = self.storage.lock().unwrap();
+            let failed: Vec<String> = self
+                .multi_selection
+                .iter()
+                .filter_map(|&(folder_idx, note_idx)| {
+                    storage.ensure_loaded(folder_idx, note_idx).err()
+                })
+                .map(|e| e.to_string())
+                .collect();
+            let notes = note::title_content_pairs(&storage.folders, &self.multi_selection);
+            (notes, failed)
+        };
+        for e in failed {
+            self.notify(ToastLevel::Error, format!("Failed to load note: {}", e));
+        }
+        if notes.is_empty() {
+            self.notify(
+                ToastLevel::Warning,
+                "Nothing to export: the selected notes are encrypted",
+            );
+            return;
+        }
+
+        match self
+            .export_dir_path("selected_notes.pdf")
+            .map_err(|e| e.to_string())
+            .and_then(|output_path| {
+                pdf_export::PdfExporter::export_multiple_notes(&notes, &output_path)
+                    .map(|_| output_path)
+            }) {
+            Ok(output_path) => {
+                let skipped = self.multi_selection.len() - notes.len();
+                let mut message =
+                    format!("Exported {} notes to PDF: {:?}", notes.len(), output_path);
+                if skipped > 0 {
+                    message.push_str(&format!(" ({} encrypted left out)", skipped));
+                }
+                self.notify(ToastLevel::Success, message);
+            }
+            Err(e) => self.notify(ToastLevel::Error, format!("PDF export failed: {}", e)),
+        }
+    }
+
+    fn render_folder_tree(&mut self, ui: &mut egui::Ui) {
+        let folders_display: Vec<_> = {
+            let storage = self.storage.lock().unwrap();
+            storage
+                .folders
+                .iter()
+                .enumerate()
+                .map(|(folder_idx, folder)| {
+                    // Display order only; each entry keeps its real index for selection
+                    let notes: Vec<_> = folder
+                        .sorted_note_indices(self.note_sort_order)
+                        .into_iter()
+                        .map(|note_idx| {
+                            let note = &folder.notes[note_idx];
+                            (
+                                note_idx,
+                                note.title.clone(),
+                                note.is_encrypted,
+                                note.updated(),
+                                note.file_path.clone(),
+                                note.icon.clone(),
+                                note.color,
+                            )
+                        })
+                        .collect();
+                    let count = note::folder_note_count(&storage.folders, folder_idx, false);
+                    (
+                        folder_idx,
+                        folder.name.clone(),
+                        count,
+                        folder.encrypted,
+                        notes,
+                    )
+                })
+                .collect()
+        };
+
+        let mut expand_all = None;
+        ui.horizontal(|ui| {
+            if ui.small_button("⊞ Expand all").clicked() {
+                expand_all = Some(true);
+            }
+            if ui.small_button("⊟ Collapse all").clicked() {
+                expand_all = Some(false);
+            }
+        });
+        if let Some(expanded) = expand_all {
+            self.folder_tree.set_all(
+                folders_display
+                    .iter()
+                    .map(|(_, name, _, _, _)| name.as_str()),
+                expanded,
+            );
+            self.save_folder_tree();
+        }
+
+        // Ctrl/Shift-clicked notes can be exported together
+        if !self.multi_selection.is_empty() {
+            let mut export = false;
+            let mut clear = false;
+            ui.horizontal(|ui| {
+                ui.label(
+                    egui::RichText::new(format!("{} selected", self.multi_selection.len())).small(),
+                );
+                if ui
+                    .small_button("📄 Export selected to PDF")
+                    .on_hover_text("One PDF with a contents page, in the order picked")
+                    .clicked()
+                {
+                    export = true;
+                }
+                if ui
+                    .small_button("✕")
+                    .on_hover_text("Clear selection")
+                    .clicked()
+                {
+                    clear = true;
+                }
+            });
+            if export {
+                self.export_selection_to_pdf();
+            }
+            if clear {
+                self.multi_selection.clear();
+            }
+        }
+
+        let session_unlocked = self.storage.lock().unwrap().session_password().is_some();
+        for (folder_idx, folder_name, count, encrypted, notes) in folders_display {
+            // Empty folders are dimmed so the ones with content stand out
+            let icon = if encrypted { "🔒" } else { "📁" };
+            let header = egui::RichText::new(format!("{} {} ({})", icon, folder_name, count));
+            let header = if count == 0 {
+                header.color(egui::Color32::from_gray(140))
+            } else {
+                header.strong().color(egui::Color32::from_gray(230))
+            };
+            // Keyed by name so the open/closed state survives the count changing
+            let mut dropped = None;
+            let header_response = egui::CollapsingHeader::new(header)
+                .id_salt(&folder_name)
+                .open(Some(self.folder_tree.is_expanded(&folder_name)))
+                .show(ui, |ui| {
+                    if notes.is_empty() {
+                        ui.label(
+                            egui::RichText::new("No notes")
+                                .color(egui::Color32::from_gray(140))
+                                .small(),
+                        );
+                    }
+                    let manual = self.note_sort_order == SortOrder::Manual;
+                    let display_order: Vec<usize> = notes.iter().map(|n| n.0).collect();
+                    for (
+                        position,
+                        (note_idx, title, is_encrypted, updated, note_path, icon, color),
+                    ) in notes.into_iter().enumerate()
+                    {
+                        let mut label_text = title.clone();
+                        if let Some(icon) = icon {
+                            label_text = format!("{} {}", icon, label_text);
+                        }
+                        if is_encrypted {
+                            label_text = format!("🔒 {}", label_text);
+                        }
+
+                        let is_note_selected = (self.selected_folder == Some(folder_idx)
+                            && self.selected_note == Some(note_idx))
+                            || self.multi_selection.contains(&(folder_idx, note_idx));
+
+                        // Create colored text for better visibility
+                        let note_label =
+                            egui::RichText::new(&label_text).color(egui::Color32::from_gray(210));
+
+                        let row = |ui: &mut egui::Ui| {
+                            ui.horizontal(|ui| {
+                                if let Some(color) = color {
+                                    color_swatch(ui, color, 8.0, egui::Sense::hover());
+                                }
+                                let clicked =
+                                    ui.selectable_label(is_note_selected, note_label).clicked();
+                                ui.label(
+                                    egui::RichText::new(note::humanize(updated))
+                                        .small()
+                                        .color(egui::Color32::from_gray(140)),
+                                )
+                                .on_hover_text(format!("Modified {}", note::format_local(updated)));
+                                clicked
+                            })
+                            .inner
+                        };
+                        // Dropped on the editor a note becomes a link; dropped on another
+                        // row it is reordered, while the folder shows its manual order
+                        let drag = NoteDrag {
+                            folder_idx,
+                            position,
+                            title: title.clone(),
+                        };
+                        let row =
+                            ui.dnd_drag_source(egui::Id::new(("note_drag", &note_path)), drag, row);
+                        if row.inner {
+                            let modifiers = ui.input(|i| i.modifiers);
+                            if modifiers.shift || modifiers.command {
+                                self.extend_selection(
+                                    (folder_idx, note_idx),
+                                    &display_order,
+                                    modifiers.shift,
+                                );
+                            } else {
+                                self.multi_selection.clear();
+                                self.selection_anchor = Some((folder_idx, note_idx));
+                                self.open_note(folder_idx, note_idx);
+                            }
+                        }
+
+                        if manual {
+                            let rect = row.response.rect;
+                            if let Some(drag) = row.response.dnd_hover_payload::<NoteDrag>() {
+                                if drag.folder_idx == folder_idx && drag.position != position {
+                                    // Show where the note will land
+                                    let y = if drag.position < position {
+                                        rect.bottom()
+                                    } else {
+                                        rect.top()
+                                    };
+                                    ui.painter().hline(
+                                        rect.x_range(),
+                                        y,
+                                        ui.visuals().selection.stroke,
+                                    );
+                                }
+                            }
+                            if let Some(drag) = row.response.dnd_release_payload::<NoteDrag>() {
+                                if drag.folder_idx == folder_idx && drag.position != position {
+                                    dropped = Some((drag.position, position));
+                                }
+                            }
+                        }
+                    }
+                });
+
+            if let Some((from, to)) = dropped {
+                self.reorder_note(folder_idx, from, to);
+            }
+
+            let mut stop_encrypting = false;
+            header_response.header_response.context_menu(|ui| {
+                if !encrypted {
+                    if ui
+                        .button("🔒 Encrypt Folder…")
+                        .on_hover_text("Encrypt its notes, and every note saved in it")
+                        .clicked()
+                    {
+                        self.folder_password = Some((folder_idx, true));
+                        ui.close_menu();
+                    }
+                    return;
+                }
+                if !session_unlocked && ui.button("🔑 Unlock Folder…").clicked() {
+                    self.folder_password = Some((folder_idx, false));
+                    ui.close_menu();
+                }
+                if ui
+                    .button("🔓 Stop Encrypting")
+                    .on_hover_text("New notes are saved as plain text; encrypted ones stay so")
+                    .clicked()
+                {
+                    stop_encrypting = true;
+                    ui.close_menu();
+                }
+            });
+            if stop_encrypting {
+                self.stop_encrypting_folder(folder_idx);
+            }
+
+            if header_response.header_response.clicked() {
+                self.folder_tree.toggle(&folder_name);
+                self.save_folder_tree();
+                self.deactivate_tab();
+                self.selected_folder = Some(folder_idx);
+            }
+
+            ui.add_space(4.0);
+        }
+    }
+
+    fn render_central_panel(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if !self.open_tabs.is_empty() {
+                self.render_tab_strip(ui);
+            }
+            if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
+                self.render_status_bar(ui);
+                self.render_note_editor(ui, folder_idx, note_idx);
+            } else {
+                self.render_welcome_screen(ui);
+            }
+        });
+    }
+
+    /// Headings of the open note; clicking one scrolls the editor to it.
+    fn render_outline_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_outline || self.selected_note.is_none() {
+            return;
+        }
+
+        // Re-parsed every frame so the outline follows typing
+        let headings = note::extract_headings(&self.current_note_content);
+        egui::SidePanel::right("outline_panel")
+            .resizable(true)
+            .default_width(200.0)
+            .show(ctx, |ui| {
+                ui.heading("Outline");
+                ui.separator();
+                if headings.is_empty() {
+                    ui.label(egui::RichText::new("No headings").weak());
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (level, text, offset) in headings {
+                        ui.horizontal(|ui| {
+                            ui.add_space((level - 1) as f32 * 12.0);
+                            let text = if level == 1 {
+                                egui::RichText::new(text).strong()
+                            } else {
+                                egui::RichText::new(text)
+                            };
+                            if ui.selectable_label(false, text).clicked() {
+                                self.scroll_to_offset = Some(offset);
+                            }
+                        });
+                    }
+                });
+            });
+    }
+
+    /// Key-value properties of the open note. Edits apply as you type and are saved
+    /// when a field loses focus or a property is added or removed.
+    fn render_properties_panel(&mut self, ctx: &egui::Context) {
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        if !self.show_properties_panel {
+            return;
+        }
+        let Some(mut properties) = self
+            .storage
+            .lock()
+            .unwrap()
+            .folders
+            .get(folder_idx)
+            .and_then(|f| f.notes.get(note_idx))
+            .map(|n| n.properties.clone())
+        else {
+            return;
+        };
+
+        let mut changed = false;
+        let mut save = false;
+        egui::SidePanel::right("properties_panel")
+            .resizable(true)
+            .default_width(220.0)
+            .show(ctx, |ui| {
+                ui.heading("Properties");
+                ui.separator();
+                if properties.is_empty() {
+                    ui.label(egui::RichText::new("No properties").weak());
+                }
+
+                let mut removed = None;
+                egui::Grid::new("properties_grid")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for (key, value) in properties.iter_mut() {
+                            ui.label(key.as_str()).on_hover_text(format!(
+                                "Search \"{}:{}\" to find notes with this value",
+                                key, value
+                            ));
+                            let response =
+                                ui.add(egui::TextEdit::singleline(value).desired_width(110.0));
+                            changed |= response.changed();
+                            save |= response.lost_focus();
+                            if ui.small_button("✕").on_hover_text("Remove").clicked() {
+                                removed = Some(key.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(key) = removed {
+                    properties.remove(&key);
+                    changed = true;
+                    save = true;
+                }
+
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_property_key)
+                            .hint_text("status")
+                            .desired_width(70.0),
+                    );
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_property_value)
+                            .hint_text("draft")
+                            .desired_width(70.0),
+                    );
+                    let key = self.new_property_key.trim();
+                    if ui
+                        .add_enabled(!key.is_empty(), egui::Button::new("Add"))
+                        .clicked()
+                    {
+                        properties
+                            .insert(key.to_string(), self.new_property_value.trim().to_string());
+                        self.new_property_key.clear();
+                        self.new_property_value.clear();
+                        changed = true;
+                        save = true;
+                    }
+                });
+            });
+
+        if changed {
+            let mut storage = self.storage.lock().unwrap();
+            if let Some(note) = storage
+                .folders
+                .get_mut(folder_idx)
+                .and_then(|f| f.notes.get_mut(note_idx))
+            {
+                note.properties = properties;
+            }
+        }
+        if save {
+            self.save_current_note();
+        }
+    }
+
+    /// The find bar above the editor. Returns the byte ranges of the current matches.
+    fn render_find_bar(&mut self, ui: &mut egui::Ui) -> Vec<(usize, usize)> {
+        let mut matches = self
+            .find_cache
+            .ranges(
+                &self.current_note_content,
+                &self.find_query,
+                self.find_case_sensitive,
+            )
+            .to_vec();
+        let mut step = None; // Some(true) = next, Some(false) = previous
+        let mut restart = false;
+        let mut close = false;
+
+        ui.horizontal(|ui| {
+            ui.label("🔍");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.find_query)
+                    .hint_text("Find in note")
+                    .desired_width(220.0),
+            );
+            if std::mem::take(&mut self.find_focus_requested) {
+                response.request_focus();
+            }
+            if response.changed() {
+                restart = true;
+            }
+            // Enter / Shift+Enter step through matches; the field gives up focus on Enter
+            if response.lost_focus() {
+                if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    step = Some(!ui.input(|i| i.modifiers.shift));
+                    response.request_focus();
+                } else if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    close = true;
+                }
+            }
+
+            if ui
+                .toggle_value(&mut self.find_case_sensitive, "Aa")
+                .on_hover_text("Match case")
+                .changed()
+            {
+                restart = true;
+            }
+            if ui
+                .small_button("▲")
+                .on_hover_text("Previous (Shift+Enter)")
+                .clicked()
+            {
+                step = Some(false);
+            }
+            if ui.small_button("▼").on_hover_text("Next (Enter)").clicked() {
+                step = Some(true);
+            }
+
+            if !self.find_query.is_empty() {
+                let count = if matches.is_empty() {
+                    "No matches".to_string()
+                } else {
+                    format!(
+                        "{}/{}",
+                        self.find_current.min(matches.len() - 1) + 1,
+                        matches.len()
+                    )
+                };
+                ui.label(egui::RichText::new(count).weak());
+            }
+
+            if ui.small_button("✖").on_hover_text("Close (Esc)").clicked() {
+                close = true;
+            }
+        });
+
+        if close {
+            // Hand focus back to the editor, which keeps the current match selected
+            self.find_open = false;
+            if let Some(id) = self.editor_id {
+                ui.memory_mut(|m| m.request_focus(id));
+            }
+            return Vec::new();
+        }
+
+        if restart {
+            matches = self
+                .find_cache
+                .ranges(
+                    &self.current_note_content,
+                    &self.find_query,
+                    self.find_case_sensitive,
+                )
+                .to_vec();
+            self.find_current = 0;
+        }
+        if matches.is_empty() {
+            self.find_current = 0;
+            return matches;
+        }
+        self.find_current = self.find_current.min(matches.len() - 1);
+
+        match step {
+            Some(true) => self.find_current = (self.find_current + 1) % matches.len(),
+            Some(false) => {
+                self.find_current = (self.find_current + matches.len() - 1) % matches.len()
+            }
+            None => {}
+        }
+        if restart || step.is_some() {
+            self.find_jump = Some(matches[self.find_current]);
+        }
+        matches
+    }
+
+    fn render_tasks_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_tasks_panel || self.selected_note.is_none() {
+            return;
+        }
+
+        let tasks = tasks::parse_tasks(&self.current_note_content);
+        let (open, done) = tasks::task_counts(&tasks);
+        let mut toggled = None;
+        egui::SidePanel::right("tasks_panel")
+            .resizable(true)
+            .default_width(220.0)
+            .show(ctx, |ui| {
+                ui.heading("Tasks");
+                ui.label(format!("{} open · {} done", open, done));
+                ui.separator();
+                if tasks.is_empty() {
+                    ui.label(egui::RichText::new("No tasks").weak());
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for task in &tasks {
+                        let mut checked = task.done;
+                        if ui.checkbox(&mut checked, &task.text).changed() {
+                            toggled = Some(task.line);
+                        }
+                    }
+                });
+            });
+
+        if let Some(line) = toggled {
+            tasks::toggle_task(&mut self.current_note_content, line);
+        }
+    }
+
+    fn render_tab_strip(&mut self, ui: &mut egui::Ui) {
+        let titles: Vec<String> = {
+            let storage = self.storage.lock().unwrap();
+            self.open_tabs
+                .iter()
+                .map(|t| {
+                    storage
+                        .folders
+                        .get(t.folder_idx)
+                        .and_then(|f| f.notes.get(t.note_idx))
+                        .map(|n| n.title.clone())
+                        .unwrap_or_default()
+                })
+                .collect()
+        };
+
+        let mut activate = None;
+        let mut close = None;
+        egui::TopBottomPanel::top("note_tabs")
+            .frame(egui::Frame::none().inner_margin(egui::Margin::symmetric(4.0, 4.0)))
+            .show_inside(ui, |ui| {
+                egui::ScrollArea::horizontal().show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        for (idx, title) in titles.iter().enumerate() {
+                            let label = if self.tab_has_unsaved_changes(idx) {
+                                format!("● {}", title)
+                            } else {
+                                title.clone()
+                            };
+                            if ui
+                                .selectable_label(self.active_tab == Some(idx), label)
+                                .clicked()
+                            {
+                                activate = Some(idx);
+                            }
+                            if ui.small_button("✕").on_hover_text("Close tab").clicked() {
+                                close = Some(idx);
+                            }
+                            ui.separator();
+                        }
+                    });
+                });
+            });
+
+        if let Some(idx) = activate {
+            self.activate_tab(idx);
+        }
+        if let Some(idx) = close {
+            self.close_tab(idx, false);
+        }
+    }
+
+    fn render_status_bar(&self, ui: &mut egui::Ui) {
+        egui::TopBottomPanel::bottom("editor_status_bar")
+            .frame(egui::Frame::none().inner_margin(egui::Margin::symmetric(4.0, 4.0)))
+            .show_inside(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let words = note::word_count(&self.current_note_content);
+                    let minutes = note::reading_time_minutes(&self.current_note_content);
+                    let reading_time = if minutes == 0 && words > 0 {
+                        "< 1 min read".to_string()
+                    } else {
+                        format!("{} min read", minutes)
+                    };
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} word{} · {}",
+                            words,
+                            if words == 1 { "" } else { "s" },
+                            reading_time
+                        ))
+                        .small()
+                        .weak(),
+                    );
+
+                    let pending = self
+                        .commit_worker
+                        .as_ref()
+                        .map_or(0, |worker| worker.pending());
+                    if pending > 0 {
+                        ui.label(egui::RichText::new("⏳ Saving version…").small().weak());
+                    } else if !self.uncommitted_notes.is_empty() {
+                        let count = self.uncommitted_notes.len();
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "● {} note{} not in history yet",
+                                count,
+                                if count == 1 { "" } else { "s" }
+                            ))
+                            .small()
+                            .weak(),
+                        );
+                    } else if let Some(error) = &self.last_commit_error {
+                        ui.label(
+                            egui::RichText::new("✗ Version not saved")
+                                .small()
+                                .color(egui::Color32::from_rgb(220, 80, 80)),
+                        )
+                        .on_hover_text(error);
+                    }
+                });
+            });
+    }
+
+    /// Markdown preview with embedded images drawn from the texture cache.
+    /// Footnotes are listed at the end, and their markers and back-references
+    /// scroll between the two. With `interactive`, clicking a task checkbox
+    /// toggles it in the note and `[[links]]` can be followed. Returns where each
+    /// part of the note was drawn, as (source line, screen y) pairs, ending with
+    /// the end of the note.
+    fn render_preview(
+        &mut self,
+        ui: &mut egui::Ui,
+        note_path: &std::path::Path,
+        interactive: bool,
+    ) -> Vec<(f32, f32)> {
+        let mut commonmark_cache = egui_commonmark::CommonMarkCache::default();
+        let mut toggled_task = None;
+        let mut clicked_link = None;
+        let references = markdown_refs::References::collect(&self.current_note_content);
+        let footnote_hook = |prefix: &str, n: usize| format!("{}{}", prefix, n);
+        let add_footnote_hooks = |cache: &mut egui_commonmark::CommonMarkCache| {
+            for footnote in references.footnotes() {
+                cache.add_link_hook(footnote_hook(markdown_refs::FOOTNOTE_LINK, footnote.number));
+                cache.add_link_hook(footnote_hook(
+                    markdown_refs::FOOTNOTE_BACK_LINK,
+                    footnote.number,
+                ));
+            }
+        };
+        let clicked_footnote = |cache: &egui_commonmark::CommonMarkCache, prefix: &str| {
+            references
+                .footnotes()
+                .iter()
+                .map(|footnote| footnote.number)
+                .find(|&n| cache.get_link_hook(&footnote_hook(prefix, n)) == Some(true))
+        };
+        // Where each footnote is first referenced, and which one was clicked
+        let mut marker_rects = std::collections::HashMap::new();
+        let mut clicked_marker = None;
+        let mut clicked_back = None;
+        let mut anchors = Vec::new();
+        let line_of = |content: &str, byte: usize| content[..byte].matches('\n').count() as f32;
+
+        for segment in images::preview_segments(&self.current_note_content) {
+            // Segments are slices of the note, so this is the segment's byte offset
+            let start = match &segment {
+                images::PreviewSegment::Markdown(text) => {
+                    text.as_ptr() as usize - self.current_note_content.as_ptr() as usize
+                }
+                images::PreviewSegment::Image(image) => image.range.start,
+            };
+            anchors.push((
+                line_of(&self.current_note_content, start),
+                ui.cursor().top(),
+            ));
+            match segment {
+                images::PreviewSegment::Markdown(text) => {
+                    // Wiki links become hooked Markdown links so clicks come back to us
+                    let with_references = references.apply(text);
+                    let (rendered, link_names) = if interactive {
+                        links::wiki_links_as_markdown(&with_references)
+                    } else {
+                        (with_references, Vec::new())
+                    };
+                    let hook = |i: usize| format!("wiki:{}", i);
+                    for i in 0..link_names.len() {
+                        commonmark_cache.add_link_hook(hook(i));
+                    }
+                    add_footnote_hooks(&mut commonmark_cache);
+
+                    // The viewer flips clicked checkboxes in its own copy; work out which
+                    // task changed and toggle that line in the note
+                    let mut edited = rendered.clone();
+                    let rect = ui
+                        .scope(|ui| {
+                            egui_commonmark::CommonMarkViewer::new().show_mut(
+                                ui,
+                                &mut commonmark_cache,
+                                &mut edited,
+                            );
+                        })
+                        .response
+                        .rect;
+                    if let Some(i) = (0..link_names.len())
+                        .find(|&i| commonmark_cache.get_link_hook(&hook(i)) == Some(true))
+                    {
+                        clicked_link = Some(link_names[i].clone());
+                    }
+                    for footnote in references.footnotes() {
+                        let marker = format!(
+                            "({})",
+                            footnote_hook(markdown_refs::FOOTNOTE_LINK, footnote.number)
+                        );
+                        if rendered.contains(&marker) {
+                            marker_rects.entry(footnote.number).or_insert(rect);
+                        }
+                    }
+                    clicked_marker = clicked_marker.or(clicked_footnote(
+                        &commonmark_cache,
+                        markdown_refs::FOOTNOTE_LINK,
+                    ));
+                    commonmark_cache.link_hooks_clear();
+
+                    if interactive && !self.is_read_only() && edited != rendered {
+                        let first_line = line_of(&self.current_note_content, start) as usize;
+                        toggled_task = tasks::parse_tasks(text)
+                            .into_iter()
+                            .zip(tasks::parse_tasks(&edited))
+                            .find(|(before, after)| before.done != after.done)
+                            .map(|(before, _)| first_line + before.line);
+                    }
+                }
+                images::PreviewSegment::Image(image) => {
+                    let path = images::resolve_image_path(note_path, &image.path);
+                    match self.image_cache.texture(ui.ctx(), &path) {
+                        Some(texture) => {
+                            let response = ui.add(
+                                egui::Image::from_texture(egui::load::SizedTexture::from_handle(
+                                    texture,
+                                ))
+                                .fit_to_original_size(1.0)
+                                .max_width(ui.available_width()),
+                            );
+                            if !image.alt.is_empty() {
+                                response.on_hover_text(&image.alt);
+                            }
+                        }
+                        None => {
+                            ui.group(|ui| {
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "🖼 Image not found: {}",
+                                        image.path
+                                    ))
+                                    .weak(),
+                                );
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        let content = &self.current_note_content;
+        anchors.push((line_of(content, content.len()) + 1.0, ui.cursor().top()));
+
+        if let Some(mut section) = references.footnote_section() {
+            add_footnote_hooks(&mut commonmark_cache);
+            let rect = ui
+                .scope(|ui| {
+                    egui_commonmark::CommonMarkViewer::new().show_mut(
+                        ui,
+                        &mut commonmark_cache,
+                        &mut section,
+                    );
+                })
+                .response
+                .rect;
+            clicked_back = clicked_footnote(&commonmark_cache, markdown_refs::FOOTNOTE_BACK_LINK);
+            if clicked_marker.is_some() {
+                ui.scroll_to_rect(rect, Some(egui::Align::TOP));
+            }
+        }
+        if let Some(rect) = clicked_back.and_then(|n| marker_rects.get(&n)) {
+            ui.scroll_to_rect(*rect, Some(egui::Align::TOP));
+        }
+
+        if let Some(line) = toggled_task {
+            tasks::toggle_task(&mut self.current_note_content, line);
+        }
+        if let Some(link) = clicked_link {
+            self.follow_wiki_link(&link);
+        }
+        anchors
+    }
+
+    /// Open the note a `[[link]]` names, or offer to create it if there isn't one.
+    /// When several notes share the title and none is in this note's folder, ask which.
+    fn follow_wiki_link(&mut self, link: &str) {
+        let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) else {
+            return;
+        };
+        let title = link.trim();
+        let (candidates, named_folder) = {
+            let storage = self.storage.lock().unwrap();
+            let resolver = LinkResolver::new(&storage.folders);
+            (
+                resolver.candidates(title),
+                resolver.split_qualified(title).map(|(f, _)| f),
+            )
+        };
+        let same_folder = candidates.iter().find(|&&(f, _)| f == folder_idx);
+        match (candidates.as_slice(), same_folder) {
+            ([], _) => {
+                self.link_note_folder = named_folder.unwrap_or(folder_idx);
+                self.link_note = Some((title.to_string(), (folder_idx, note_idx)));
+            }
+            ([only], _) | (_, Some(only)) => self.open_note(only.0, only.1),
+            _ => self.link_choices = Some((title.to_string(), candidates)),
+        }
+    }
+
+    /// Create the note a dangling link names, register the link to it and open it.
+    fn create_link_note(&mut self) {
+        let Some((title, source)) = self.link_note.take() else {
+            return;
+        };
+        let created = self
+            .storage
+            .lock()
+            .unwrap()
+            .create_note_for_link(self.link_note_folder, &title);
+        match created {
+            Ok((folder_idx, note_idx)) => {
+                self.search_worker.invalidate();
+                {
+                    let storage = self.storage.lock().unwrap();
+                    let link_resolver = LinkResolver::new(&storage.folders);
+                    let is_open = (self.selected_folder, self.selected_note)
+                        == (Some(source.0), Some(source.1));
+                    let content = match storage
+                        .folders
+                        .get(source.0)
+                        .and_then(|f| f.notes.get(source.1))
+                    {
+                        Some(_) if is_open => Some(self.current_note_content.clone()),
+                        Some(note) => Some(note.content.clone()),
+                        None => None,
+                    };
+                    if let Some(content) = content {
+                        self.link_manager
+                            .rebuild_links_for_note(source, &content, &link_resolver);
+                    }
+                }
+                self.notify(ToastLevel::Success, format!("Created note: {}", title));
+                self.open_note(folder_idx, note_idx);
+            }
+            Err(e) => self.notify(ToastLevel::Error, format!("Failed to create note: {}", e)),
+        }
+    }
+
+    fn set_presenting(&mut self, presenting: bool, ctx: &egui::Context) {
+        if presenting == self.presenting || (presenting && self.selected_note.is_none()) {
+            return;
+        }
+        if presenting {
+            self.save_current_note();
+            self.was_fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+        } else if !self.was_fullscreen {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+        }
+        self.presenting = presenting;
+    }
+
+    /// The open note's rendered preview alone, centered in large type. Nothing in it
+    /// edits the note.
+    fn render_presentation(&mut self, ctx: &egui::Context) {
+        let note = match (self.selected_folder, self.selected_note) {
+            (Some(folder_idx), Some(note_idx)) => {
+                let storage = self.storage.lock().unwrap();
+                storage
+                    .folders
+                    .get(folder_idx)
+                    .and_then(|folder| folder.notes.get(note_idx))
+                    .map(|note| {
+                        let locked =
+                            note.is_encrypted && !self.unlocked_notes.contains_key(&note.file_path);
+                        (note.title.clone(), PathBuf::from(&note.file_path), locked)
+                    })
+            }
+            _ => None,
+        };
+        let Some((title, note_path, locked)) = note else {
+            self.set_presenting(false, ctx);
+            return;
+        };
+
+        egui::CentralPanel::default()
+            .frame(
+                egui::Frame::none()
+                    .inner_margin(egui::Margin::same(32.0))
+                    .fill(ctx.style().visuals.panel_fill),
+            )
+            .show(ctx, |ui| {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                    if ui.small_button("✕ Exit (Esc)").clicked() {
+                        self.set_presenting(false, ui.ctx());
+                    }
+                });
+
+                let scale = 1.6 * self.zoom_level;
+                for font in ui.style_mut().text_styles.values_mut() {
+                    font.size *= scale;
+                }
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        let width = ui.available_width().min(900.0 * scale);
+                        let margin = (ui.available_width() - width) / 2.0;
+                        ui.horizontal(|ui| {
+                            ui.add_space(margin);
+                            ui.vertical(|ui| {
+                                ui.set_width(width);
+                                ui.heading(&title);
+                                ui.add_space(16.0);
+                                if locked {
+                                    ui.label(egui::RichText::new("This note is encrypted.").weak());
+                                } else {
+                                    self.render_preview(ui, &note_path, false);
+                                }
+                            });
+                        });
+                    });
+            });
+    }
+
+    /// `Projects / 2024 / Q1 / note` above the editor; clicking a folder selects it.
+    /// Returns true if one was clicked, which closes the note.
+    fn render_breadcrumb(&mut self, ui: &mut egui::Ui, crumbs: &[(String, Option<usize>)]) -> bool {
+        let mut clicked_folder = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 4.0;
+            for (i, (label, folder_idx)) in crumbs.iter().enumerate() {
+                if i > 0 {
+                    ui.label(egui::RichText::new("/").weak());
+                }
+                match folder_idx {
+                    Some(folder_idx) => {
+                        let link = egui::Label::new(egui::RichText::new(label).weak())
+                            .sense(egui::Sense::click());
+                        if ui.add(link).on_hover_text("Select folder").clicked() {
+                            clicked_folder = Some(*folder_idx);
+                        }
+                    }
+                    None => {
+                        ui.label(egui::RichText::new(label).strong());
+                    }
+                }
+            }
+        });
+
+        if let Some(folder_idx) = clicked_folder {
+            let folder_name = self
+                .storage
+                .lock()
+                .unwrap()
+                .folders
+                .get(folder_idx)
+                .map(|f| f.name.clone());
+            if let Some(folder_name) = folder_name {
+                self.folder_tree.set_expanded(&folder_name, true);
+                self.save_folder_tree();
+            }
+            self.deactivate_tab();
+            self.selected_folder = Some(folder_idx);
+        }
+        clicked_folder.is_some()
+    }
+
+    /// Menu for the note's sidebar icon and color swatch.
+    fn render_appearance_picker(
+        &mut self,
+        ui: &mut egui::Ui,
+        folder_idx: usize,
+        note_idx: usize,
+        (icon, color): (Option<String>, Option<[u8; 3]>),
+    ) {
+        let mut picked = None;
+        let button = icon.clone().unwrap_or_else(|| "🎨".to_string());
+        ui.menu_button(button, |ui| {
+            ui.label("Icon");
+            ui.horizontal_wrapped(|ui| {
+                for emoji in NOTE_ICONS {
                     if ui
-                        .small_button(if self.sidebar_open { "◀" } else { "▶" })
+                        .selectable_label(icon.as_deref() == Some(emoji), emoji)
                         .clicked()
                     {
-                        self.sidebar_open = !self.sidebar_open;
+                        picked = Some((Some(emoji.to_string()), color));
+                        ui.close_menu();
+                    }
+                }
+            });
+            ui.label("Color");
+            ui.horizontal(|ui| {
+                for (name, rgb) in NOTE_COLORS {
+                    if color_swatch(ui, rgb, 16.0, egui::Sense::click())
+                        .on_hover_text(name)
+                        .clicked()
+                    {
+                        picked = Some((icon.clone(), Some(rgb)));
+                        ui.close_menu();
+                    }
+                }
+            });
+            ui.separator();
+            if ui.button("Clear").clicked() {
+                picked = Some((None, None));
+                ui.close_menu();
+            }
+        })
+        .response
+        .on_hover_text("Icon and color in the sidebar");
+
+        if let Some((icon, color)) = picked {
+            let result = self
+                .storage
+                .lock()
+                .unwrap()
+                .set_note_appearance(folder_idx, note_idx, icon, color);
+            if let Err(e) = result {
+                self.notify(
+                    ToastLevel::Error,
+                    format!("Failed to save note icon: {}", e),
+                );
+            }
+        }
+    }
+
+    /// Buttons wrapping the selection in Markdown, or taking the markers off again.
+    fn render_format_toolbar(&mut self, ui: &mut egui::Ui) {
+        // (label, tooltip, prefix, suffix); an empty suffix marks a line prefix
+        const BUTTONS: [(&str, &str, &str, &str); 6] = [
+            ("B", "Bold", "**", "**"),
+            ("I", "Italic", "*", "*"),
+            ("H", "Heading", "# ", ""),
+            ("•", "Bullet list", "- ", ""),
+            ("</>", "Code", "`", "`"),
+            ("🔗", "Link", "[", "](url)"),
+        ];
+
+        let mut clicked = None;
+        ui.horizontal(|ui| {
+            for (label, tooltip, prefix, suffix) in BUTTONS {
+                let text = match label {
+                    "B" => egui::RichText::new(label).strong(),
+                    "I" => egui::RichText::new(label).italics(),
+                    _ => egui::RichText::new(label),
+                };
+                if ui.small_button(text).on_hover_text(tooltip).clicked() {
+                    clicked = Some((prefix, suffix));
+                }
+            }
+        });
+
+        if let Some((prefix, suffix)) = clicked {
+            self.edit_selection(ui.ctx(), |content, range| {
+                if suffix.is_empty() {
+                    markdown_edit::toggle_line_prefix(content, range, prefix)
+                } else {
+                    markdown_edit::wrap_selection(content, range, prefix, suffix)
+                }
+            });
+        }
+    }
+
+    /// Replace the note through `edit`, given the editor's selection as a byte range
+    /// (the end of the note without a cursor), then select the range it returns.
+    fn edit_selection(
+        &mut self,
+        ctx: &egui::Context,
+        edit: impl FnOnce(&str, std::ops::Range<usize>) -> Option<(String, std::ops::Range<usize>)>,
+    ) {
+        let state = self
+            .editor_id
+            .and_then(|id| egui::TextEdit::load_state(ctx, id));
+        let content = &self.current_note_content;
+        let [first, last] = state
+            .as_ref()
+            .and_then(|s| s.cursor.char_range())
+            .map(|range| range.sorted().map(|c| c.index))
+            .unwrap_or_else(|| [content.chars().count(); 2]);
+        let byte = |c: usize| {
+            content
+                .char_indices()
+                .nth(c)
+                .map_or(content.len(), |(i, _)| i)
+        };
+        let Some((edited, selected)) = edit(content, byte(first)..byte(last)) else {
+            return;
+        };
+
+        let char_at = |b: usize| edited[..b].chars().count();
+        let cursor = egui::text::CCursorRange::two(
+            egui::text::CCursor::new(char_at(selected.start)),
+            egui::text::CCursor::new(char_at(selected.end)),
+        );
+        self.current_note_content = edited;
+        if let Some(id) = self.editor_id {
+            let mut state = state.unwrap_or_default();
+            state.cursor.set_char_range(Some(cursor));
+            state.store(ctx, id);
+            ctx.memory_mut(|m| m.request_focus(id));
+        }
+    }
+
+    /// Searchable menu of emoji; the one picked goes in at the editor's cursor.
+    fn render_emoji_picker(&mut self, ui: &mut egui::Ui) {
+        let mut picked = None;
+        ui.menu_button("😀", |ui| {
+            ui.set_max_width(240.0);
+            ui.add(
+                egui::TextEdit::singleline(&mut self.emoji_filter)
+                    .hint_text("Search emoji")
+                    .desired_width(f32::INFINITY),
+            );
+            let matches = emoji::search_emoji(&self.emoji_filter);
+            if matches.is_empty() {
+                ui.label(egui::RichText::new("No matches").weak());
+            }
+            ui.horizontal_wrapped(|ui| {
+                for emoji in matches {
+                    if ui.button(emoji).clicked() {
+                        picked = Some(emoji);
+                        ui.close_menu();
+                    }
+                }
+            });
+        })
+        .response
+        .on_hover_text("Insert emoji");
+
+        if let Some(emoji) = picked {
+            self.insert_at_cursor(ui.ctx(), emoji);
+            self.emoji_filter.clear();
+            if let Some(id) = self.editor_id {
+                ui.memory_mut(|m| m.request_focus(id));
+            }
+        }
+    }
+
+    fn render_note_editor(&mut self, ui: &mut egui::Ui, folder_idx: usize, note_idx: usize) {
+        let note_data = {
+            let storage = self.storage.lock().unwrap();
+            storage.folders.get(folder_idx).and_then(|folder| {
+                folder.notes.get(note_idx).map(|note| {
+                    (
+                        note.title.clone(),
+                        note.created(),
+                        note.updated(),
+                        note.is_encrypted,
+                        PathBuf::from(&note.file_path),
+                        note::breadcrumb(&storage.folders, folder_idx, &note.title),
+                        (note.icon.clone(), note.color),
+                    )
+                })
+            })
+        };
+
+        if let Some((_, created, updated, is_encrypted, note_path, crumbs, appearance)) = note_data
+        {
+            ui.add_space(8.0);
+            if self.render_breadcrumb(ui, &crumbs) {
+                // The note was closed to show its folder
+                return;
+            }
+
+            // An encrypted note shows a placeholder until it is unlocked with its password
+            let locked = is_encrypted
+                && !self
+                    .unlocked_notes
+                    .contains_key(note_path.to_string_lossy().as_ref());
+
+            // Minimal header - just when the note was last touched
+            ui.horizontal(|ui| {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "Modified {} · Created {}",
+                        note::humanize(updated),
+                        note::humanize(created)
+                    ))
+                    .small()
+                    .weak(),
+                )
+                .on_hover_text(format!(
+                    "Created {}\nModified {}",
+                    note::format_local(created),
+                    note::format_local(updated)
+                ));
+                self.render_appearance_picker(ui, folder_idx, note_idx, appearance);
+                if !locked && !self.show_markdown_preview && !self.is_read_only() {
+                    self.render_emoji_picker(ui);
+                }
+            });
+
+            if is_encrypted {
+                ui.horizontal(|ui| {
+                    if locked {
+                        ui.label(egui::RichText::new("This note is encrypted.").weak());
+                        if ui.button("🔒 Unlock to edit").clicked() {
+                            self.show_unlock_dialog = true;
+                        }
+                    } else {
+                        ui.label(egui::RichText::new("Unlocked: saves stay encrypted.").weak());
+                        if ui.button("🔓 Lock").clicked() {
+                            self.lock_current_note();
+                        }
+                    }
+                });
+                ui.add_space(4.0);
+            }
+
+            if !locked && !self.show_markdown_preview && !self.is_read_only() {
+                self.render_format_toolbar(ui);
+            }
+
+            let find_matches = if self.find_open && !self.show_markdown_preview {
+                self.render_find_bar(ui)
+            } else {
+                Vec::new()
+            };
+
+            // Jump requested from the outline or the find bar: scroll to the line and select
+            // the range. Outline jumps also move focus to the editor; find keeps it in the bar.
+            // Without soft wrap, long lines scroll sideways; the preview always wraps
+            let mut scroll_area = if self.config.soft_wrap || self.show_markdown_preview {
+                egui::ScrollArea::vertical()
+            } else {
+                egui::ScrollArea::both()
+            };
+            let jump = self
+                .scroll_to_offset
+                .take()
+                .map(|offset| (offset, offset, true))
+                .or_else(|| {
+                    self.find_jump
+                        .take()
+                        .map(|(start, end)| (start, end, false))
+                });
+            if let Some((start, end, focus)) = jump {
+                let content = &self.current_note_content;
+                if let (Some(before), Some(selected)) =
+                    (content.get(..start), content.get(start..end))
+                {
+                    let mut font_id = self.current_font.to_text_style().resolve(ui.style());
+                    font_id.size = 14.0 * self.zoom_level;
+                    let row_height = ui.fonts(|f| f.row_height(&font_id));
+                    let line = before.matches('\n').count();
+                    scroll_area = scroll_area.vertical_scroll_offset(line as f32 * row_height);
+
+                    if let (Some(id), false) = (self.editor_id, self.show_markdown_preview) {
+                        let mut state =
+                            egui::TextEdit::load_state(ui.ctx(), id).unwrap_or_default();
+                        let first = before.chars().count();
+                        let last = first + selected.chars().count();
+                        state
+                            .cursor
+                            .set_char_range(Some(egui::text::CCursorRange::two(
+                                egui::text::CCursor::new(first),
+                                egui::text::CCursor::new(last),
+                            )));
+                        state.store(ui.ctx(), id);
+                        if focus {
+                            ui.memory_mut(|m| m.request_focus(id));
+                        }
+                    }
+                }
+            }
+
+            // Beside the editor, the rendered note scrolled to the same place
+            let split = self.config.split_view && !self.show_markdown_preview;
+            let mut preview_pane = None;
+            if split {
+                let target = self.scroll_sync.take_preview_target();
+                egui::SidePanel::right("split_preview")
+                    .resizable(true)
+                    .default_width(ui.available_width() / 2.0)
+                    .show_inside(ui, |ui| {
+                        let mut preview_area =
+                            egui::ScrollArea::vertical().id_salt("split_preview_scroll");
+                        if let Some(offset) = target {
+                            preview_area = preview_area.vertical_scroll_offset(offset);
+                        }
+                        let output = preview_area.show(ui, |ui| {
+                            self.apply_zoom(ui);
+                            let top = ui.min_rect().top();
+                            let anchors = self.render_preview(ui, &note_path, true);
+                            anchors
+                                .into_iter()
+                                .map(|(line, y)| (line, y - top))
+                                .collect()
+                        });
+                        preview_pane = Some(scroll_sync::Pane {
+                            map: scroll_sync::ScrollMap::new(output.inner),
+                            offset: output.state.offset.y,
+                            max_offset: output.content_size.y - output.inner_rect.height(),
+                        });
+                    });
+                if jump.is_none() {
+                    if let Some(offset) = self.scroll_sync.take_editor_target() {
+                        scroll_area = scroll_area.vertical_scroll_offset(offset);
+                    }
+                }
+            }
+
+            // Main editor area - seamlessly editable or preview
+            let mut editor_anchors = Vec::new();
+            let editor_output = scroll_area.show(ui, |ui| {
+                self.apply_zoom(ui);
+                let content_top = ui.min_rect().top();
+
+                if self.show_markdown_preview {
+                    // Clean preview mode
+                    let preview = ui.scope(|ui| self.render_preview(ui, &note_path, true));
+                    // A note dropped here is linked at the cursor, back in the editor
+                    if let Some(drag) = preview.response.dnd_release_payload::<NoteDrag>() {
+                        if !locked {
+                            self.show_markdown_preview = false;
+                            self.drop_note_link(ui.ctx(), None, &drag.title);
+                        }
+                    }
+                } else {
+                    // Clean edit mode
+                    let soft_wrap = self.config.soft_wrap;
+                    let text_style = self.current_font.to_text_style();
+                    let now = std::time::Instant::now();
+                    if self.spellcheck_enabled {
+                        self.spelling.refresh(
+                            &mut self.spellcheck,
+                            &self.current_note_content,
+                            now,
+                        );
+                    }
+                    let misspelled = if self.spellcheck_enabled {
+                        self.spelling.misspelled()
+                    } else {
+                        &[]
+                    };
+                    let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                        let format = egui::TextFormat::simple(
+                            text_style.resolve(ui.style()),
+                            ui.visuals()
+                                .override_text_color
+                                .unwrap_or_else(|| ui.visuals().widgets.inactive.text_color()),
+                        );
+                        let underline =
+                            egui::Stroke::new(1.5, egui::Color32::from_rgb(255, 80, 80));
+                        let job = spellcheck::spelling_layout_job(
+                            text,
+                            format,
+                            underline,
+                            misspelled,
+                            if soft_wrap { wrap_width } else { f32::INFINITY },
+                        );
+                        ui.fonts(|f| f.layout_job(job))
+                    };
+                    let read_only = self.is_read_only();
+                    let text_edit = egui::TextEdit::multiline(&mut self.current_note_content)
+                        .layouter(&mut layouter)
+                        .interactive(!locked && !read_only)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(35)
+                        .font(self.current_font.to_text_style());
+
+                    let output = text_edit.show(ui);
+                    let response = output.response;
+                    self.editor_id = Some(response.id);
+                    if split {
+                        editor_anchors = scroll_sync::galley_line_anchors(
+                            &output.galley,
+                            output.galley_pos.y - content_top,
+                        );
+                    }
+
+                    // A note dragged from the sidebar is linked where it is dropped
+                    let drop_cursor = |pos: egui::Pos2| {
+                        output
+                            .galley
+                            .cursor_from_pos(pos - output.galley_pos)
+                            .ccursor
+                    };
+                    if !locked && response.dnd_hover_payload::<NoteDrag>().is_some() {
+                        if let Some(pos) = ui.ctx().pointer_hover_pos() {
+                            let at = output
+                                .galley
+                                .pos_from_ccursor(drop_cursor(pos))
+                                .translate(output.galley_pos.to_vec2());
+                            ui.painter().vline(
+                                at.min.x,
+                                at.y_range(),
+                                ui.visuals().selection.stroke,
+                            );
+                        }
+                    }
+                    if let Some(drag) = response.dnd_release_payload::<NoteDrag>() {
+                        if !locked {
+                            let char_idx = ui
+                                .ctx()
+                                .pointer_interact_pos()
+                                .map(|pos| drop_cursor(pos).index);
+                            self.drop_note_link(ui.ctx(), char_idx, &drag.title);
+                        }
+                    }
+
+                    // Right-clicking a misspelled word offers suggestions
+                    if self.spellcheck_enabled && response.secondary_clicked() {
+                        self.spell_menu = response.interact_pointer_pos().and_then(|pos| {
+                            let cursor = output.galley.cursor_from_pos(pos - output.galley_pos);
+                            let byte_idx = self
+                                .current_note_content
+                                .char_indices()
+                                .nth(cursor.ccursor.index)
+                                .map_or(self.current_note_content.len(), |(i, _)| i);
+                            let misspelling =
+                                spellcheck::misspelling_at(self.spelling.misspelled(), byte_idx)?
+                                    .clone();
+                            let suggestions = self.spellcheck.suggest(&misspelling.2);
+                            Some((misspelling, suggestions))
+                        });
+                    }
+                    let mut picked_suggestion = None;
+                    let mut add_to_dictionary = false;
+                    if let Some((misspelling, suggestions)) = &self.spell_menu {
+                        response.context_menu(|ui| {
+                            if suggestions.is_empty() {
+                                ui.label(egui::RichText::new("No suggestions").weak());
+                            }
+                            for suggestion in suggestions {
+                                if ui.button(suggestion).clicked() {
+                                    picked_suggestion = Some(suggestion.clone());
+                                    ui.close_menu();
+                                }
+                            }
+                            ui.separator();
+                            if ui
+                                .button(format!("Add \"{}\" to Dictionary", misspelling.2))
+                                .clicked()
+                            {
+                                add_to_dictionary = true;
+                                ui.close_menu();
+                            }
+                        });
                     }
-
-                    ui.add_space(8.0);
-
-                    // Clean search bar
-                    let search_response = ui.add(
-                        egui::TextEdit::singleline(&mut self.search_query)
-                            .hint_text("Search...")
-                            .desired_width(200.0),
-                    );
-                    if search_response.changed() {
-                        self.perform_search();
+                    if picked_suggestion.is_some() || add_to_dictionary {
+                        if let Some((misspelling, _)) = self.spell_menu.take() {
+                            if let Some(suggestion) = picked_suggestion {
+                                spellcheck::replace_misspelling(
+                                    &mut self.current_note_content,
+                                    &misspelling,
+                                    &suggestion,
+                                );
+                            } else {
+                                self.add_to_dictionary(&misspelling.2);
+                            }
+                            self.spelling
+                                .check_now(&mut self.spellcheck, &self.current_note_content);
+                        }
                     }
 
-                    ui.add_space(8.0);
+                    // Selected text can be moved out into a note of its own
+                    let selection = output.cursor_range.filter(|r| !r.is_empty()).map(|r| {
+                        let chars = r.as_sorted_char_range();
+                        let content = &self.current_note_content;
+                        let byte = |c: usize| {
+                            content
+                                .char_indices()
+                                .nth(c)
+                                .map_or(content.len(), |(i, _)| i)
+                        };
+                        byte(chars.start)..byte(chars.end)
+                    });
+                    if let (Some(range), None, false) = (selection, &self.spell_menu, locked) {
+                        let mut extract = false;
+                        response.context_menu(|ui| {
+                            if ui.button("✂ Extract to New Note…").clicked() {
+                                extract = true;
+                                ui.close_menu();
+                            }
+                        });
+                        if extract {
+                            let text = self.current_note_content[range.clone()].to_string();
+                            self.extract_title = text
+                                .lines()
+                                .map(|line| line.trim().trim_start_matches('#').trim())
+                                .find(|line| !line.is_empty())
+                                .unwrap_or_default()
+                                .to_string();
+                            self.extract_selection = Some((range, text));
+                        }
+                    }
 
-                    // Minimalist menu buttons
-                    let note_button_enabled = self.selected_folder.is_some();
-                    ui.add_enabled_ui(note_button_enabled, |ui| {
-                        if ui.small_button("+ Note").clicked() {
-                            self.show_new_note_dialog = true;
+                    // Highlight find matches; the editor only draws its selection while focused
+                    for (i, &(start, end)) in find_matches.iter().enumerate() {
+                        let content = &self.current_note_content;
+                        let (Some(before), Some(matched)) =
+                            (content.get(..start), content.get(start..end))
+                        else {
+                            continue;
+                        };
+                        let first = before.chars().count();
+                        let last = first + matched.chars().count();
+                        let from = output
+                            .galley
+                            .pos_from_ccursor(egui::text::CCursor::new(first));
+                        let to = output
+                            .galley
+                            .pos_from_ccursor(egui::text::CCursor::new(last));
+                        // Matches spanning a line break aren't highlighted
+                        if from.min.y != to.min.y {
+                            continue;
                         }
-                    });
-                    if !note_button_enabled {
-                        ui.label(egui::RichText::new("(select folder first)").small().weak());
+                        let alpha = if i == self.find_current { 140 } else { 50 };
+                        ui.painter().rect_filled(
+                            egui::Rect::from_min_max(from.min, to.max)
+                                .translate(output.galley_pos.to_vec2()),
+                            2.0,
+                            egui::Color32::from_rgba_unmultiplied(255, 200, 0, alpha),
+                        );
                     }
 
-                    if ui.small_button("+ Folder").clicked() {
-                        self.show_new_folder_dialog = true;
+                    // Recheck once typing pauses rather than on every keystroke
+                    if response.changed() && self.spellcheck_enabled {
+                        self.spelling.schedule(now);
+                    }
+                    if let Some(wait) = self.spelling.pending(now) {
+                        ui.ctx().request_repaint_after(wait);
                     }
 
-                    ui.separator();
+                    // Minimal spell check summary at bottom
+                    let misspelled = self.spelling.misspelled();
+                    if self.spellcheck_enabled && !misspelled.is_empty() {
+                        ui.add_space(8.0);
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "⚠ {} spelling issue{}",
+                                    misspelled.len(),
+                                    if misspelled.len() == 1 { "" } else { "s" }
+                                ))
+                                .small()
+                                .color(egui::Color32::from_rgb(255, 150, 100)),
+                            );
 
-                    // Prominent Preview/Edit toggle button
-                    let preview_enabled = self.selected_note.is_some();
-                    ui.add_enabled_ui(preview_enabled, |ui| {
-                        let button_text = if self.show_markdown_preview {
-                            "✏ Edit (Ctrl+P)"
-                        } else {
-                            "👁 Preview (Ctrl+P)"
-                        };
-                        if ui.button(button_text).clicked() {
-                            self.show_markdown_preview = !self.show_markdown_preview;
-                            if !self.show_markdown_preview {
-                                // Switching back to edit mode, save first
-                                self.save_current_note();
+                            // Show first few misspelled words
+                            let display_words: Vec<String> = misspelled
+                                .iter()
+                                .take(3)
+                                .map(|(_, _, word)| word.clone())
+                                .collect();
+
+                            if !display_words.is_empty() {
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "({}{})",
+                                        display_words.join(", "),
+                                        if misspelled.len() > 3 { "..." } else { "" }
+                                    ))
+                                    .small()
+                                    .weak(),
+                                );
                             }
-                        }
-                    });
+                        });
+                    }
+                }
+            });
 
-                    ui.separator();
+            if let Some(preview) = preview_pane {
+                let editor = scroll_sync::Pane {
+                    map: scroll_sync::ScrollMap::new(editor_anchors),
+                    offset: editor_output.state.offset.y,
+                    max_offset: editor_output.content_size.y - editor_output.inner_rect.height(),
+                };
+                if self.scroll_sync.update(&editor, &preview) {
+                    ui.ctx().request_repaint();
+                }
+            }
+        }
+    }
 
-                    // Compact menus
-                    ui.menu_button("View", |ui| {
-                        if ui.button("Statistics").clicked() {
-                            self.show_statistics = !self.show_statistics;
-                            ui.close_menu();
-                        }
-                    });
+    /// Scale the editor's and preview's text by the zoom level.
+    fn apply_zoom(&self, ui: &mut egui::Ui) {
+        let mut style = (**ui.style()).clone();
+        let base_font_size = 14.0;
+        let zoomed_font_size = base_font_size * self.zoom_level;
+
+        // Update all text styles based on zoom level
+        style.text_styles.insert(
+            egui::TextStyle::Monospace,
+            egui::FontId::monospace(zoomed_font_size),
+        );
+        style.text_styles.insert(
+            egui::TextStyle::Body,
+            egui::FontId::proportional(zoomed_font_size),
+        );
+        style.text_styles.insert(
+            egui::TextStyle::Heading,
+            egui::FontId::proportional(zoomed_font_size * 1.5),
+        );
+        style.text_styles.insert(
+            egui::TextStyle::Button,
+            egui::FontId::proportional(zoomed_font_size),
+        );
+
+        ui.set_style(style);
+    }
 
-                    ui.menu_button("Tools", |ui| {
-                        if ui.button("Tags").clicked() {
-                            self.show_tag_dialog = true;
-                            ui.close_menu();
-                        }
-                        if ui.button("Export").clicked() {
-                            self.show_export_dialog = true;
-                            ui.close_menu();
-                        }
-                        if ui.button("Encrypt").clicked() {
-                            self.show_encryption_dialog = true;
-                            ui.close_menu();
+    fn render_welcome_screen(&self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(150.0);
+
+            ui.heading("Notes");
+            ui.add_space(20.0);
+
+            ui.label("Create a folder to get started");
+            ui.add_space(10.0);
+
+            ui.label(egui::RichText::new("Click '+ Folder' above").small().weak());
+        });
+    }
+
+    fn render_all_dialogs(&mut self, ctx: &egui::Context) {
+        self.render_sync_progress(ctx);
+        self.render_new_folder_dialog(ctx);
+        self.render_new_note_dialog(ctx);
+        self.render_theme_dialog(ctx);
+        self.render_theme_editor_dialog(ctx);
+        self.render_tag_dialog(ctx);
+        self.render_encryption_dialog(ctx);
+        self.render_unlock_dialog(ctx);
+        self.render_folder_password_dialog(ctx);
+        self.render_export_dialog(ctx);
+        self.render_statistics_dialog(ctx);
+        self.render_version_history_dialog(ctx);
+        self.render_tab_close_dialog(ctx);
+        self.render_leave_dialog(ctx);
+        self.render_save_conflict_dialog(ctx);
+        self.render_draft_recovery_dialog(ctx);
+        self.render_save_search_dialog(ctx);
+        self.render_orphan_notes_dialog(ctx);
+        self.render_duplicate_notes_dialog(ctx);
+        self.render_archived_notes_dialog(ctx);
+        self.render_graph_view(ctx);
+        self.render_extract_note_dialog(ctx);
+        self.render_link_note_dialog(ctx);
+        self.render_link_choices_dialog(ctx);
+        self.render_task_dashboard(ctx);
+        self.render_settings_dialog(ctx);
+    }
+
+    fn render_vault_switcher(&mut self, ui: &mut egui::Ui) {
+        let mut switch_to = None;
+        egui::ComboBox::from_id_salt("vault_switcher")
+            .selected_text(format!("🗄 {}", vault_name(&self.config.vault_path)))
+            .show_ui(ui, |ui| {
+                for vault in &self.config.vaults {
+                    let is_open = *vault == self.config.vault_path;
+                    if ui
+                        .selectable_label(is_open, vault_name(vault))
+                        .on_hover_text(vault.to_string_lossy())
+                        .clicked()
+                        && !is_open
+                    {
+                        switch_to = Some(vault.clone());
+                    }
+                }
+                ui.separator();
+                if ui.button("Manage Vaults…").clicked() {
+                    self.show_settings = true;
+                }
+            });
+
+        if let Some(vault) = switch_to {
+            self.switch_vault(vault, ui.ctx());
+        }
+    }
+
+    fn render_settings_dialog(&mut self, ctx: &egui::Context) {
+        if !self.show_settings {
+            return;
+        }
+
+        let mut open = true;
+        let mut reopen = false;
+        let mut relocate = false;
+        let mut forget = None;
+        let mut policy_changed = false;
+        let mut export_changed = false;
+        let mut search_changed = false;
+        let mut inbox_changed = false;
+        let mut backup_changed = false;
+        let mut format_changed = false;
+        let mut read_only_changed = false;
+        egui::Window::new("⚙ Settings")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new("Vaults").strong());
+                for vault in &self.config.vaults {
+                    ui.horizontal(|ui| {
+                        let is_open = *vault == self.config.vault_path;
+                        ui.label(vault.to_string_lossy());
+                        if is_open {
+                            ui.label(egui::RichText::new("(open)").small().weak());
+                        } else if ui.small_button("Remove").clicked() {
+                            forget = Some(vault.clone());
                         }
                     });
+                }
+                read_only_changed |= ui
+                    .add_enabled(
+                        !self.read_only_launch,
+                        egui::Checkbox::new(&mut self.config.read_only, "Open vaults read-only"),
+                    )
+                    .on_hover_text("Browse notes without being able to create, edit or delete them")
+                    .on_disabled_hover_text("Launched with --read-only")
+                    .changed();
+                ui.separator();
+
+                ui.label(egui::RichText::new("Vault location").strong());
+                ui.label(
+                    egui::RichText::new(format!("Current: {}", self.config.vault_path.display()))
+                        .small()
+                        .weak(),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.vault_path_input)
+                        .hint_text("/path/to/notes")
+                        .desired_width(320.0),
+                );
 
-                    ui.menu_button("⚙", |ui| {
-                        if ui.button("Theme").clicked() {
-                            self.show_theme_dialog = true;
-                            ui.close_menu();
+                let target = self.vault_path_input.trim();
+                let changed =
+                    !target.is_empty() && self.config.vault_path != std::path::Path::new(target);
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(changed, |ui| {
+                        if ui
+                            .button("📂 Open Vault")
+                            .on_hover_text(
+                                "Switch to the notes in that folder, creating it if missing",
+                            )
+                            .clicked()
+                        {
+                            reopen = true;
                         }
                         if ui
-                            .button(if self.theme_manager.current_theme.is_dark {
-                                "Light Mode"
-                            } else {
-                                "Dark Mode"
-                            })
+                            .button("📦 Move Notes There")
+                            .on_hover_text(
+                                "Copy this vault into that (empty) folder and switch to it",
+                            )
                             .clicked()
                         {
-                            self.toggle_dark_mode();
-                            ui.close_menu();
+                            relocate = true;
                         }
-                        ui.separator();
+                    });
+                });
+                ui.separator();
+
+                ui.label(egui::RichText::new("Version history").strong());
+                let policy = &mut self.config.commit_policy;
+                if ui
+                    .radio(*policy == CommitPolicy::EveryChange, "Commit every save")
+                    .clicked()
+                {
+                    *policy = CommitPolicy::EveryChange;
+                    policy_changed = true;
+                }
+                ui.horizontal(|ui| {
+                    let interval = match policy {
+                        CommitPolicy::OnInterval(interval) => Some(*interval),
+                        _ => None,
+                    };
+                    if ui
+                        .radio(interval.is_some(), "Combine saves into one commit every")
+                        .clicked()
+                        && interval.is_none()
+                    {
+                        *policy =
+                            CommitPolicy::OnInterval(version_control::DEFAULT_COMMIT_INTERVAL);
+                        policy_changed = true;
+                    }
+                    let mut minutes = interval
+                        .unwrap_or(version_control::DEFAULT_COMMIT_INTERVAL)
+                        .as_secs()
+                        / 60;
+                    let response = ui.add_enabled(
+                        interval.is_some(),
+                        egui::DragValue::new(&mut minutes)
+                            .range(1..=240)
+                            .suffix(" min"),
+                    );
+                    if response.changed() {
+                        *policy =
+                            CommitPolicy::OnInterval(std::time::Duration::from_secs(minutes * 60));
+                        policy_changed = true;
+                    }
+                });
+                if ui
+                    .radio(
+                        *policy == CommitPolicy::Manual,
+                        "Only when I take a snapshot",
+                    )
+                    .clicked()
+                {
+                    *policy = CommitPolicy::Manual;
+                    policy_changed = true;
+                }
+                ui.separator();
 
-                        // Font selection
-                        ui.label("Font:");
-                        for font in EditorFont::all() {
-                            let is_current = font == self.current_font;
-                            let text = if is_current {
-                                format!("✓ {}", font.name())
-                            } else {
-                                font.name().to_string()
-                            };
-                            if ui.button(text).clicked() {
-                                self.current_font = font;
-                            }
-                        }
+                ui.label(egui::RichText::new("Search").strong());
+                ui.horizontal(|ui| {
+                    ui.label("Show at most");
+                    search_changed = ui
+                        .add(
+                            egui::DragValue::new(&mut self.config.search_result_limit)
+                                .range(10..=1000)
+                                .suffix(" results"),
+                        )
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Rank by:");
+                    ui.label(egui::RichText::new("Titles and content").small().weak());
+                    search_changed |= ui
+                        .add(
+                            egui::Slider::new(
+                                &mut self.config.title_weight,
+                                search::MIN_TITLE_WEIGHT..=search::MAX_TITLE_WEIGHT,
+                            )
+                            .show_value(false),
+                        )
+                        .on_hover_text(
+                            "How much a match in a note's title counts over one in its text",
+                        )
+                        .changed();
+                    ui.label(egui::RichText::new("Titles only").small().weak());
+                });
+                ui.separator();
 
-                        ui.separator();
-                        ui.checkbox(&mut self.auto_save_enabled, "Auto-save");
-                        ui.checkbox(&mut self.spellcheck_enabled, "Spell Check");
-                    });
+                ui.label(egui::RichText::new("New notes").strong());
+                ui.horizontal(|ui| {
+                    ui.label("Inbox folder:");
+                    inbox_changed = ui
+                        .add(
+                            egui::TextEdit::singleline(&mut self.config.inbox_folder)
+                                .desired_width(160.0),
+                        )
+                        .on_hover_text("Where new notes go when no folder is selected")
+                        .changed();
+                });
+                let policy = &mut self.config.filename_policy;
+                format_changed |= ui
+                    .checkbox(&mut policy.ascii_only, "ASCII-only file names")
+                    .on_hover_text(
+                        "Otherwise titles like \"Café\" keep their accents; only characters \
+                         no file name may contain are replaced",
+                    )
+                    .changed();
+                ui.horizontal(|ui| {
+                    ui.label("Also allow:");
+                    format_changed |= ui
+                        .add_enabled(
+                            policy.ascii_only,
+                            egui::TextEdit::singleline(&mut policy.allowed).desired_width(80.0),
+                        )
+                        .on_hover_text("Punctuation kept in ASCII-only file names")
+                        .changed();
+                });
+                ui.separator();
 
-                    // Right-aligned current note with save button
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if self.selected_note.is_some() {
-                            if ui.small_button("💾 Save").clicked() {
-                                self.save_current_note();
+                ui.label(egui::RichText::new("Export").strong());
+                ui.horizontal(|ui| {
+                    ui.label("File name:");
+                    export_changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut self.config.export_template)
+                                .desired_width(220.0),
+                        )
+                        .on_hover_text(
+                            "{title}, {folder}, {date} and {ext} are filled in; \
+                             / makes a subfolder",
+                        )
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Export to:");
+                    let mut dir = self.config.export_dir.to_string_lossy().to_string();
+                    if ui
+                        .add(egui::TextEdit::singleline(&mut dir).desired_width(220.0))
+                        .changed()
+                    {
+                        self.config.export_dir = PathBuf::from(dir);
+                        export_changed = true;
+                    }
+                    if ui.button("Browse…").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .set_title("Choose where exports go")
+                            .pick_folder()
+                        {
+                            self.config.export_dir = path;
+                            export_changed = true;
+                        }
+                    }
+                });
+                ui.separator();
+
+                ui.label(egui::RichText::new("Saving").strong());
+                ui.horizontal(|ui| {
+                    ui.label("Line endings:");
+                    egui::ComboBox::from_id_salt("line_ending")
+                        .selected_text(self.config.line_ending.label())
+                        .show_ui(ui, |ui| {
+                            for ending in storage::LineEnding::ALL {
+                                format_changed |= ui
+                                    .selectable_value(
+                                        &mut self.config.line_ending,
+                                        ending,
+                                        ending.label(),
+                                    )
+                                    .changed();
                             }
+                        });
+                });
+                format_changed |= ui
+                    .checkbox(
+                        &mut self.config.trailing_newline,
+                        "End every note with a single newline",
+                    )
+                    .changed();
+                ui.separator();
+
+                ui.label(egui::RichText::new("Backups").strong());
+                let backup_root = self.storage.lock().unwrap().default_backup_root();
+                backup_changed |= ui
+                    .checkbox(
+                        &mut self.config.backup_on_startup,
+                        "Back up the vault on startup",
+                    )
+                    .on_hover_text(format!("Copies go to {}", backup_root.display()))
+                    .changed();
+                ui.horizontal(|ui| {
+                    backup_changed |= ui
+                        .checkbox(&mut self.config.scheduled_backups, "Back up every")
+                        .changed();
+                    backup_changed |= ui
+                        .add_enabled(
+                            self.config.scheduled_backups,
+                            egui::DragValue::new(&mut self.config.backup_interval_minutes)
+                                .range(5..=1440)
+                                .suffix(" min"),
+                        )
+                        .changed();
+                    ui.label("while the app is open");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Keep the last");
+                    backup_changed |= ui
+                        .add_enabled(
+                            self.config.backup_on_startup || self.config.scheduled_backups,
+                            egui::DragValue::new(&mut self.config.backup_count)
+                                .range(1..=50)
+                                .suffix(" backups"),
+                        )
+                        .changed();
+                });
+            });
+        self.show_settings = open;
+
+        if inbox_changed || backup_changed {
+            self.save_config();
+        }
+        if format_changed || read_only_changed {
+            self.apply_save_format();
+            self.save_config();
+        }
+
+        if policy_changed || export_changed {
+            self.save_config();
+        }
+        if search_changed {
+            self.save_config();
+            self.perform_search();
+        }
+
+        if let Some(vault) = forget {
+            // Only drops it from the list; the notes stay on disk
+            if self.config.forget_vault(&vault) {
+                self.save_config();
+            }
+        }
+        let target = PathBuf::from(self.vault_path_input.trim());
+        if reopen {
+            self.switch_vault(target, ctx);
+        } else if relocate {
+            self.move_vault(target, ctx);
+        }
+    }
+
+    fn render_task_dashboard(&mut self, ctx: &egui::Context) {
+        if !self.show_task_dashboard {
+            return;
+        }
+
+        // Group by note, keeping the order tasks were collected in
+        let groups: Vec<(String, Vec<OpenTask>)> = {
+            let storage = self.storage.lock().unwrap();
+            let mut groups: Vec<(String, Vec<OpenTask>)> = Vec::new();
+            for (folder_idx, note_idx, task) in &self.open_tasks {
+                let Some(note) = storage
+                    .folders
+                    .get(*folder_idx)
+                    .and_then(|f| f.notes.get(*note_idx))
+                else {
+                    continue;
+                };
+                let entry = (*folder_idx, *note_idx, task.clone());
+                match groups.last_mut() {
+                    Some((_, tasks)) if tasks[0].0 == *folder_idx && tasks[0].1 == *note_idx => {
+                        tasks.push(entry)
+                    }
+                    _ => groups.push((note.title.clone(), vec![entry])),
+                }
+            }
+            groups
+        };
+
+        let mut open = true;
+        let mut go_to = None;
+        let mut refresh = false;
+        egui::Window::new("✅ Task Dashboard")
+            .collapsible(false)
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} open tasks", self.open_tasks.len()));
+                    if ui.button("🔄 Refresh").clicked() {
+                        refresh = true;
+                    }
+                });
+                ui.separator();
 
-                            if let (Some(folder_idx), Some(note_idx)) =
-                                (self.selected_folder, self.selected_note)
-                            {
-                                let storage = self.storage.lock().unwrap();
-                                if let Some(folder) = storage.folders.get(folder_idx) {
-                                    if let Some(note) = folder.notes.get(note_idx) {
-                                        ui.add_space(8.0);
-                                        let mut title_text = note.title.clone();
-                                        if note.is_encrypted {
-                                            title_text = format!("🔒 {}", title_text);
-                                        }
-                                        if self.is_favorite() {
-                                            title_text = format!("⭐ {}", title_text);
-                                        }
-                                        ui.label(title_text);
-                                    }
+                if groups.is_empty() {
+                    ui.label(egui::RichText::new("Nothing to do").weak());
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for (title, tasks) in &groups {
+                            ui.label(egui::RichText::new(title).strong());
+                            for (folder_idx, note_idx, task) in tasks {
+                                if ui
+                                    .selectable_label(false, format!("☐ {}", task.text))
+                                    .clicked()
+                                {
+                                    go_to = Some((*folder_idx, *note_idx, task.line));
                                 }
                             }
+                            ui.add_space(6.0);
                         }
                     });
-                });
             });
+        self.show_task_dashboard = open;
 
-        // Rest of the UI remains the same but continues in sidebar and central panel
-        if self.sidebar_open {
-            self.render_sidebar(ctx);
+        if let Some((folder_idx, note_idx, line)) = go_to {
+            self.open_note(folder_idx, note_idx);
+            self.scroll_to_offset = tasks::line_offset(&self.current_note_content, line);
+        }
+        if refresh {
+            self.open_tasks = self.storage.lock().unwrap().collect_open_tasks();
         }
+    }
 
-        // Bottom panel with zoom controls
-        egui::TopBottomPanel::bottom("bottom_panel")
-            .frame(
-                egui::Frame::none()
-                    .inner_margin(egui::Margin::symmetric(12.0, 6.0))
-                    .fill(egui::Color32::from_gray(25)),
-            )
+    fn render_tab_close_dialog(&mut self, ctx: &egui::Context) {
+        let Some(idx) = self.tab_close_prompt else {
+            return;
+        };
+        let title = {
+            let storage = self.storage.lock().unwrap();
+            self.open_tabs
+                .get(idx)
+                .and_then(|t| storage.folders.get(t.folder_idx)?.notes.get(t.note_idx))
+                .map(|n| n.title.clone())
+        };
+        let Some(title) = title else {
+            self.tab_close_prompt = None;
+            return;
+        };
+
+        egui::Window::new("💾 Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
             .show(ctx, |ui| {
+                ui.label(format!("\"{}\" has unsaved changes.", title));
                 ui.horizontal(|ui| {
-                    // Delete buttons (left)
-                    if let Some(folder_idx) = self.selected_folder {
-                        if self.selected_note.is_some() {
-                            if ui
-                                .small_button("🗑 Delete Note")
-                                .on_hover_text("Delete current note")
-                                .clicked()
-                            {
-                                self.delete_current_note();
-                            }
-                            ui.separator();
-                        }
-                        if ui
-                            .small_button("🗑 Delete Folder")
-                            .on_hover_text("Delete current folder and all its notes")
-                            .clicked()
-                        {
-                            self.delete_current_folder();
+                    if ui.button("Save").clicked() {
+                        self.activate_tab(idx);
+                        self.save_current_note();
+                        // A conflict keeps the tab open until it's resolved
+                        if self.save_conflict.is_none() {
+                            self.close_tab(idx, true);
                         }
-                        ui.separator();
+                        self.tab_close_prompt = None;
+                    }
+                    if ui.button("Discard").clicked() {
+                        self.close_tab(idx, true);
+                        self.tab_close_prompt = None;
                     }
+                    if ui.button("Cancel").clicked() {
+                        self.tab_close_prompt = None;
+                    }
+                });
+            });
+    }
 
-                    // Zoom controls (center-left)
-                    ui.label(egui::RichText::new("Zoom:").weak().small());
+    fn render_leave_dialog(&mut self, ctx: &egui::Context) {
+        if self.leave_prompt.is_none() {
+            return;
+        }
+        let titles: Vec<String> = {
+            let storage = self.storage.lock().unwrap();
+            (0..self.open_tabs.len())
+                .filter(|&idx| self.tab_has_unsaved_changes(idx))
+                .filter_map(|idx| {
+                    let tab = &self.open_tabs[idx];
+                    storage
+                        .folders
+                        .get(tab.folder_idx)?
+                        .notes
+                        .get(tab.note_idx)
+                        .map(|n| n.title.clone())
+                })
+                .collect()
+        };
+
+        let mut choice = None;
+        egui::Window::new("💾 Discard Unsaved Changes?")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("These notes have changes that haven't been saved:");
+                for title in &titles {
+                    ui.label(format!("• {}", title));
+                }
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        choice = Some(true);
+                    }
+                    if ui.button("Discard").clicked() {
+                        choice = Some(false);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.leave_prompt = None;
+                    }
+                });
+            });
+
+        let Some(save) = choice else {
+            return;
+        };
+        if save {
+            self.save_all_tabs();
+            // A conflict needs resolving before anything is left behind
+            if self.save_conflict.is_some() {
+                self.leave_prompt = None;
+                return;
+            }
+        } else {
+            self.discard_unsaved_changes();
+        }
+        match self.leave_prompt.take() {
+            Some(LeaveAction::Quit) => {
+                self.quit_confirmed = true;
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            Some(LeaveAction::OpenVault(path)) => self.open_vault(path, ctx),
+            None => {}
+        }
+    }
+
+    fn render_save_conflict_dialog(&mut self, ctx: &egui::Context) {
+        let Some(conflict) = &self.save_conflict else {
+            return;
+        };
+        let title = vault_name(std::path::Path::new(&conflict.file_path));
+
+        let mut resolution = None;
+        let mut show_diff = false;
+        egui::Window::new("⚠ Changed on Disk")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "\"{}\" was changed by another program since you opened it.",
+                    title
+                ));
+                ui.label(egui::RichText::new("Your edits haven't been saved yet.").weak());
+                ui.horizontal(|ui| {
                     if ui
-                        .small_button("🔍−")
-                        .on_hover_text("Zoom out (Ctrl+−)")
+                        .button("Keep Mine")
+                        .on_hover_text("Save your version over the one on disk")
                         .clicked()
                     {
-                        self.zoom_level = (self.zoom_level - 0.1).max(0.5);
+                        resolution = Some(true);
                     }
-                    ui.label(
-                        egui::RichText::new(format!("{}%", (self.zoom_level * 100.0) as i32))
-                            .small(),
-                    );
                     if ui
-                        .small_button("🔍+")
-                        .on_hover_text("Zoom in (Ctrl++)")
+                        .button("Keep Theirs")
+                        .on_hover_text("Discard your edits and load the version on disk")
                         .clicked()
                     {
-                        self.zoom_level = (self.zoom_level + 0.1).min(3.0);
+                        resolution = Some(false);
+                    }
+                    if conflict.diff.is_none() && ui.button("View Diff").clicked() {
+                        show_diff = true;
+                    }
+                });
+                if let Some(diff) = &conflict.diff {
+                    ui.separator();
+                    ui.label(egui::RichText::new("From the disk version to yours:").small());
+                    render_diff(ui, diff);
+                }
+            });
+
+        if show_diff {
+            let mine = self.current_note_content.clone();
+            let diff = self.save_conflict.as_ref().map(|conflict| {
+                let path = std::path::Path::new(&conflict.file_path);
+                version_control::diff_text(path, &conflict.theirs, &mine)
+            });
+            match diff {
+                Some(Ok(diff)) => {
+                    if let Some(conflict) = &mut self.save_conflict {
+                        conflict.diff = Some(diff);
                     }
+                }
+                Some(Err(e)) => self.notify(ToastLevel::Error, format!("Failed to diff: {}", e)),
+                None => {}
+            }
+        }
+        if let Some(keep_mine) = resolution {
+            self.resolve_save_conflict(keep_mine);
+        }
+    }
+
+    fn render_save_search_dialog(&mut self, ctx: &egui::Context) {
+        if !self.show_save_search_dialog {
+            return;
+        }
+        let folder_names: Vec<String> = {
+            let storage = self.storage.lock().unwrap();
+            storage.folders.iter().map(|f| f.name.clone()).collect()
+        };
+        let tag_names: Vec<String> = self
+            .tag_manager
+            .all_tags()
+            .iter()
+            .map(|t| t.name.clone())
+            .collect();
+
+        let mut save = false;
+        let mut cancel = false;
+        egui::Window::new("⭐ New Smart Folder")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new(format!("Search: {}", self.search_query)).weak());
+                ui.add_space(5.0);
+                egui::Grid::new("save_search_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Name:");
+                        ui.text_edit_singleline(&mut self.saved_search_name);
+                        ui.end_row();
+
+                        ui.label("Folder:");
+                        let scope_text = match &self.saved_search_scope {
+                            SearchScope::AllFolders => "All folders".to_string(),
+                            SearchScope::Folder(name) => name.clone(),
+                        };
+                        egui::ComboBox::from_id_salt("saved_search_scope")
+                            .selected_text(scope_text)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut self.saved_search_scope,
+                                    SearchScope::AllFolders,
+                                    "All folders",
+                                );
+                                for name in &folder_names {
+                                    ui.selectable_value(
+                                        &mut self.saved_search_scope,
+                                        SearchScope::Folder(name.clone()),
+                                        name,
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Tag:");
+                        egui::ComboBox::from_id_salt("saved_search_tag")
+                            .selected_text(self.saved_search_tag.as_deref().unwrap_or("Any"))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.saved_search_tag, None, "Any");
+                                for name in &tag_names {
+                                    ui.selectable_value(
+                                        &mut self.saved_search_tag,
+                                        Some(name.clone()),
+                                        name,
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                    });
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    let can_save = !self.saved_search_name.trim().is_empty();
                     if ui
-                        .small_button("↺")
-                        .on_hover_text("Reset zoom (Ctrl+0)")
+                        .add_enabled(can_save, egui::Button::new("Save"))
                         .clicked()
                     {
-                        self.zoom_level = 1.0;
+                        save = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
                     }
-
-                    // Version history button (right)
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if self.selected_note.is_some() {
-                            if ui
-                                .small_button("📜 Versions")
-                                .on_hover_text("View version history")
-                                .clicked()
-                            {
-                                self.load_version_history();
-                                self.show_version_history = true;
-                            }
-                        }
-                    });
                 });
             });
 
-        self.render_central_panel(ctx);
-        self.render_all_dialogs(ctx);
+        if save {
+            let saved = SavedSearch {
+                name: self.saved_search_name.trim().to_string(),
+                query: self.search_query.trim().to_string(),
+                scope: self.saved_search_scope.clone(),
+                tag_filter: self.saved_search_tag.clone(),
+            };
+            self.notify(
+                ToastLevel::Success,
+                format!("Saved smart folder \"{}\"", saved.name),
+            );
+            self.config.saved_searches.push(saved);
+            self.save_config();
+        }
+        if save || cancel {
+            self.show_save_search_dialog = false;
+        }
     }
-}
 
-impl NoteTakingApp {
-    // Render helper methods
-    fn render_sidebar(&mut self, ctx: &egui::Context) {
-        egui::SidePanel::left("sidebar")
-            .resizable(true)
-            .default_width(220.0)
-            .frame(
-                egui::Frame::none()
-                    .inner_margin(egui::Margin::same(12.0))
-                    .fill(egui::Color32::from_gray(25)),
-            ) // Slightly lighter dark gray
-            .show(ctx, |ui| {
-                // Set text color to be clearly visible
-                ui.style_mut().visuals.override_text_color = Some(egui::Color32::from_gray(220));
+    fn render_new_folder_dialog(&mut self, ctx: &egui::Context) {
+        if self.show_new_folder_dialog {
+            egui::Window::new("New Folder")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label("Folder name:");
+                    let response = ui.text_edit_singleline(&mut self.new_folder_name);
 
-                ui.heading("Notes");
-                ui.add_space(8.0);
+                    // Auto-focus the text field
+                    response.request_focus();
 
-                egui::ScrollArea::vertical().show(ui, |ui| {
-                    // Show search results if searching
-                    if !self.search_query.is_empty() && !self.search_results.is_empty() {
-                        ui.label(
-                            egui::RichText::new("Search Results")
-                                .strong()
-                                .color(egui::Color32::from_gray(200)),
-                        );
-                        ui.add_space(4.0);
+                    // Support Enter key to create
+                    if ui.input(|i| i.key_pressed(egui::Key::Enter))
+                        && !self.new_folder_name.is_empty()
+                    {
+                        self.create_folder();
+                    }
 
-                        let search_display: Vec<_> = {
-                            let storage = self.storage.lock().unwrap();
-                            self.search_results
-                                .iter()
-                                .filter_map(|(folder_idx, note_idx)| {
-                                    storage.folders.get(*folder_idx).and_then(|folder| {
-                                        folder.notes.get(*note_idx).map(|note| {
-                                            (
-                                                *folder_idx,
-                                                *note_idx,
-                                                note.title.clone(),
-                                                folder.name.clone(),
-                                                note.content.clone(),
-                                            )
-                                        })
-                                    })
-                                })
-                                .collect()
-                        };
+                    ui.add_space(5.0);
 
-                        for (folder_idx, note_idx, title, folder_name, content) in search_display {
-                            let is_selected = self.selected_folder == Some(folder_idx)
-                                && self.selected_note == Some(note_idx);
+                    ui.horizontal(|ui| {
+                        if ui.button("Create").clicked() {
+                            self.create_folder();
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.new_folder_name.clear();
+                            self.show_new_folder_dialog = false;
+                        }
+                    });
+                });
+        }
+    }
 
-                            if ui
-                                .selectable_label(
-                                    is_selected,
-                                    egui::RichText::new(&title)
-                                        .color(egui::Color32::from_gray(220)),
-                                )
-                                .clicked()
-                            {
-                                self.save_current_note();
-                                self.selected_folder = Some(folder_idx);
-                                self.selected_note = Some(note_idx);
-                                self.current_note_content = content.clone();
+    fn render_extract_note_dialog(&mut self, ctx: &egui::Context) {
+        if self.extract_selection.is_none() {
+            return;
+        }
+        let mut extract = false;
+        let mut cancel = false;
+        let taken = self.selected_folder.is_some_and(|folder_idx| {
+            self.storage
+                .lock()
+                .unwrap()
+                .title_taken(folder_idx, &self.extract_title)
+        });
+        egui::Window::new("✂ Extract to New Note")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("The selection moves to a new note and a link to it takes its place.");
+                ui.add_space(5.0);
+                ui.label("Note title:");
+                let response = ui.text_edit_singleline(&mut self.extract_title);
+                response.request_focus();
+                if taken {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "A note with this title is already in this folder",
+                    );
+                }
+                let title_ok = !self.extract_title.trim().is_empty() && !taken;
+                if title_ok && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    extract = true;
+                }
+
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(title_ok, egui::Button::new("Extract"))
+                        .clicked()
+                    {
+                        extract = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if extract {
+            self.extract_to_new_note();
+        } else if cancel {
+            self.extract_selection = None;
+        }
+    }
 
-                                if self.spellcheck_enabled {
-                                    self.misspelled_words = self.spellcheck.check_text(&content);
-                                }
+    fn render_link_note_dialog(&mut self, ctx: &egui::Context) {
+        let Some((title, _)) = &self.link_note else {
+            return;
+        };
+        let title = title.clone();
+        let folders: Vec<String> = self
+            .storage
+            .lock()
+            .unwrap()
+            .folders
+            .iter()
+            .map(|f| f.name.clone())
+            .collect();
+        if folders.is_empty() {
+            self.link_note = None;
+            return;
+        }
+        self.link_note_folder = self.link_note_folder.min(folders.len() - 1);
+        // A [[Folder/Title]] link already says where the note goes
+        let qualified = title
+            .split_once('/')
+            .is_some_and(|(folder, _)| folders.iter().any(|name| name == folder.trim()));
+
+        let mut create = false;
+        let mut cancel = false;
+        egui::Window::new("🔗 Create Linked Note")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("There's no note called \"{}\" yet.", title));
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.label("Create it in:");
+                    if qualified {
+                        ui.label(&folders[self.link_note_folder]);
+                        return;
+                    }
+                    egui::ComboBox::from_id_salt("link_note_folder")
+                        .selected_text(&folders[self.link_note_folder])
+                        .show_ui(ui, |ui| {
+                            for (idx, name) in folders.iter().enumerate() {
+                                ui.selectable_value(&mut self.link_note_folder, idx, name);
                             }
-                            ui.label(
-                                egui::RichText::new(&folder_name)
-                                    .small()
-                                    .color(egui::Color32::from_gray(150)),
-                            );
-                            ui.add_space(4.0);
-                        }
+                        });
+                });
 
-                        ui.add_space(8.0);
-                        ui.separator();
-                        ui.add_space(8.0);
-                        ui.label(
-                            egui::RichText::new("All Notes")
-                                .strong()
-                                .color(egui::Color32::from_gray(200)),
-                        );
-                        ui.add_space(4.0);
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Create").clicked() {
+                        create = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
                     }
-
-                    // Show folder tree
-                    self.render_folder_tree(ui);
                 });
             });
+
+        if create {
+            self.create_link_note();
+        } else if cancel {
+            self.link_note = None;
+        }
     }
 
-    fn render_folder_tree(&mut self, ui: &mut egui::Ui) {
-        let folders_display: Vec<_> = {
+    fn render_link_choices_dialog(&mut self, ctx: &egui::Context) {
+        let Some((title, candidates)) = &self.link_choices else {
+            return;
+        };
+        let choices: Vec<_> = {
             let storage = self.storage.lock().unwrap();
-            storage
-                .folders
+            candidates
                 .iter()
-                .enumerate()
-                .map(|(folder_idx, folder)| {
-                    let notes: Vec<_> = folder
-                        .notes
-                        .iter()
-                        .enumerate()
-                        .map(|(note_idx, note)| {
-                            (
-                                note_idx,
-                                note.title.clone(),
-                                note.content.clone(),
-                                note.is_encrypted,
-                            )
-                        })
-                        .collect();
-                    (folder_idx, folder.name.clone(), notes)
+                .filter_map(|&(f, n)| {
+                    let folder = storage.folders.get(f)?;
+                    Some((
+                        (f, n),
+                        format!("{} / {}", folder.name, folder.notes.get(n)?.title),
+                    ))
                 })
                 .collect()
         };
 
-        for (folder_idx, folder_name, notes) in folders_display {
-            let header_response = ui.collapsing(
-                egui::RichText::new(&folder_name)
-                    .strong()
-                    .color(egui::Color32::from_gray(230)),
-                |ui| {
-                    if notes.is_empty() {
-                        ui.label(
-                            egui::RichText::new("No notes")
-                                .color(egui::Color32::from_gray(140))
-                                .small(),
-                        );
+        let mut opened = None;
+        let mut cancel = false;
+        egui::Window::new("🔗 Which Note?")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Several notes are called \"{}\":", title));
+                ui.add_space(5.0);
+                for (note, label) in &choices {
+                    if ui.link(label).clicked() {
+                        opened = Some(*note);
                     }
-                    for (note_idx, title, content, is_encrypted) in notes {
-                        let mut label_text = title.clone();
-                        if is_encrypted {
-                            label_text = format!("🔒 {}", label_text);
-                        }
+                }
+                ui.add_space(5.0);
+                ui.label(
+                    egui::RichText::new("Write [[Folder/Title]] to link to one of them directly.")
+                        .small()
+                        .weak(),
+                );
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
 
-                        let is_note_selected = self.selected_folder == Some(folder_idx)
-                            && self.selected_note == Some(note_idx);
+        if let Some((folder_idx, note_idx)) = opened {
+            self.link_choices = None;
+            self.open_note(folder_idx, note_idx);
+        } else if cancel {
+            self.link_choices = None;
+        }
+    }
 
-                        // Create colored text for better visibility
-                        let note_label =
-                            egui::RichText::new(&label_text).color(egui::Color32::from_gray(210));
+    fn render_new_note_dialog(&mut self, ctx: &egui::Context) {
+        if self.show_new_note_dialog {
+            egui::Window::new("New Note")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label("Note title:");
+                    let response = ui.text_edit_singleline(&mut self.new_note_title);
 
-                        if ui.selectable_label(is_note_selected, note_label).clicked() {
-                            self.save_current_note();
-                            self.selected_folder = Some(folder_idx);
-                            self.selected_note = Some(note_idx);
-                            self.current_note_content = content.clone();
+                    // Auto-focus the text field
+                    response.request_focus();
 
-                            if self.spellcheck_enabled {
-                                self.misspelled_words = self.spellcheck.check_text(&content);
-                            }
-                        }
+                    // Support Enter key to create
+                    if ui.input(|i| i.key_pressed(egui::Key::Enter))
+                        && !self.new_note_title.is_empty()
+                    {
+                        self.create_note();
                     }
-                },
-            );
 
-            if header_response.header_response.clicked() {
-                self.selected_folder = Some(folder_idx);
-                self.selected_note = None;
-                self.current_note_content.clear();
-            }
+                    ui.add_space(5.0);
 
-            ui.add_space(4.0);
+                    let selected_name = self
+                        .selected_template
+                        .and_then(|idx| self.template_manager.templates.get(idx))
+                        .map(|t| t.name.clone())
+                        .unwrap_or_else(|| "Blank".to_string());
+                    egui::ComboBox::from_label("Template")
+                        .selected_text(selected_name)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.selected_template, None, "Blank");
+                            for (idx, template) in
+                                self.template_manager.templates.iter().enumerate()
+                            {
+                                ui.selectable_value(
+                                    &mut self.selected_template,
+                                    Some(idx),
+                                    &template.name,
+                                );
+                            }
+                        });
+
+                    ui.add_space(5.0);
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Create").clicked() {
+                            self.create_note();
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.new_note_title.clear();
+                            self.show_new_note_dialog = false;
+                        }
+                    });
+
+                    // Show selected folder, or the inbox the note will go to
+                    let folder_name = match self.selected_folder {
+                        Some(folder_idx) => {
+                            let storage = self.storage.lock().unwrap();
+                            storage.folders.get(folder_idx).map(|f| f.name.clone())
+                        }
+                        None => Some(self.config.inbox_folder.clone()),
+                    };
+                    if let Some(folder_name) = folder_name {
+                        ui.add_space(5.0);
+                        ui.label(
+                            egui::RichText::new(format!("In folder: {}", folder_name))
+                                .small()
+                                .weak(),
+                        );
+                    }
+                });
         }
     }
 
-    fn render_central_panel(&mut self, ctx: &egui::Context) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            if let (Some(folder_idx), Some(note_idx)) = (self.selected_folder, self.selected_note) {
-                self.render_note_editor(ui, folder_idx, note_idx);
-            } else {
-                self.render_welcome_screen(ui);
-            }
-        });
-    }
+    fn render_theme_dialog(&mut self, ctx: &egui::Context) {
+        if self.show_theme_dialog {
+            egui::Window::new("Select Theme")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Current: {}",
+                            self.theme_manager.current_theme.name
+                        ))
+                        .weak(),
+                    );
+                    ui.add_space(8.0);
 
-    fn render_note_editor(&mut self, ui: &mut egui::Ui, folder_idx: usize, note_idx: usize) {
-        let note_data = {
-            let storage = self.storage.lock().unwrap();
-            storage.folders.get(folder_idx).and_then(|folder| {
-                folder.notes.get(note_idx).map(|note| {
-                    (
-                        note.title.clone(),
-                        note.created_at.clone(),
-                        note.updated_at.clone(),
-                        note.is_encrypted,
-                    )
-                })
-            })
-        };
+                    for theme in self.theme_manager.available_themes.clone() {
+                        let is_current = theme.name == self.theme_manager.current_theme.name;
+                        let button_text = if is_current {
+                            format!("✓ {}", theme.name)
+                        } else {
+                            theme.name.clone()
+                        };
 
-        if let Some((title, created_at, updated_at, is_encrypted)) = note_data {
-            // Minimal header - just title
-            ui.add_space(8.0);
+                        if ui.button(button_text).clicked() {
+                            self.select_theme(theme);
+                            self.show_theme_dialog = false;
+                        }
+                    }
 
-            // Main editor area - seamlessly editable or preview
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                // Apply zoom to UI style for both modes
-                let mut style = (**ui.style()).clone();
-                let base_font_size = 14.0;
-                let zoomed_font_size = base_font_size * self.zoom_level;
-
-                // Update all text styles based on zoom level
-                style.text_styles.insert(
-                    egui::TextStyle::Monospace,
-                    egui::FontId::monospace(zoomed_font_size),
-                );
-                style.text_styles.insert(
-                    egui::TextStyle::Body,
-                    egui::FontId::proportional(zoomed_font_size),
-                );
-                style.text_styles.insert(
-                    egui::TextStyle::Heading,
-                    egui::FontId::proportional(zoomed_font_size * 1.5),
-                );
-                style.text_styles.insert(
-                    egui::TextStyle::Button,
-                    egui::FontId::proportional(zoomed_font_size),
-                );
+                    ui.add_space(8.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("🎨 Customize...").clicked() {
+                            self.open_theme_editor();
+                            self.show_theme_dialog = false;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_theme_dialog = false;
+                        }
+                    });
+                });
+        }
+    }
 
-                ui.set_style(style);
+    fn render_theme_editor_dialog(&mut self, ctx: &egui::Context) {
+        if self.show_theme_editor {
+            egui::Window::new("🎨 Theme Editor")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Name:");
+                        ui.text_edit_singleline(&mut self.theme_draft.name);
+                    });
+                    ui.checkbox(&mut self.theme_draft.is_dark, "Dark base");
+                    ui.separator();
 
-                if self.show_markdown_preview {
-                    // Clean preview mode
-                    egui_commonmark::CommonMarkViewer::new().show(
-                        ui,
-                        &mut egui_commonmark::CommonMarkCache::default(),
-                        &self.current_note_content,
-                    );
-                } else {
-                    // Clean edit mode
-                    let text_edit = egui::TextEdit::multiline(&mut self.current_note_content)
-                        .desired_width(f32::INFINITY)
-                        .desired_rows(35)
-                        .font(self.current_font.to_text_style());
+                    let draft = &mut self.theme_draft;
+                    egui::Grid::new("theme_editor_colors")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            for (label, color) in [
+                                ("Background", &mut draft.background),
+                                ("Foreground", &mut draft.foreground),
+                                ("Accent", &mut draft.accent),
+                                ("Sidebar", &mut draft.sidebar_bg),
+                                ("Editor", &mut draft.editor_bg),
+                            ] {
+                                ui.label(label);
+                                ui.color_edit_button_srgb(color);
+                                ui.end_row();
+                            }
+                        });
 
-                    let response = ui.add(text_edit);
-
-                    // Draw red underlines for misspelled words
-                    if self.spellcheck_enabled && !self.misspelled_words.is_empty() {
-                        let painter = ui.painter();
-                        let rect = response.rect;
-
-                        // Get font metrics with current zoom
-                        let font_id = self.current_font.to_text_style().resolve(ui.style());
-                        let row_height = ui.fonts(|f| f.row_height(&font_id)) * self.zoom_level;
-
-                        let text = &self.current_note_content;
-
-                        for (start, end, word) in &self.misspelled_words {
-                            if *start < text.len() && *end <= text.len() {
-                                // Calculate which line this word is on
-                                let text_before = &text[..*start];
-                                let line_num = text_before.matches('\n').count();
-
-                                // Find the start of this line
-                                let line_start =
-                                    text_before.rfind('\n').map(|i| i + 1).unwrap_or(0);
-                                let line_end = text[*start..]
-                                    .find('\n')
-                                    .map(|i| *start + i)
-                                    .unwrap_or(text.len());
-                                let line_text = &text[line_start..line_end];
-
-                                // Calculate column position (characters from line start)
-                                let col = start - line_start;
-
-                                // Measure text width up to this point on the line
-                                let text_before_word = &line_text[..col.min(line_text.len())];
-                                let x_offset = ui.fonts(|f| {
-                                    f.layout_no_wrap(
-                                        text_before_word.to_string(),
-                                        font_id.clone(),
-                                        egui::Color32::WHITE,
-                                    )
-                                    .rect
-                                    .width()
-                                });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button("Save theme").clicked() {
+                            self.save_custom_theme();
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.show_theme_editor = false;
+                        }
+                    });
+                });
+        }
+    }
 
-                                // Measure the word width
-                                let word_width = ui.fonts(|f| {
-                                    f.layout_no_wrap(
-                                        word.clone(),
-                                        font_id.clone(),
-                                        egui::Color32::WHITE,
-                                    )
-                                    .rect
-                                    .width()
-                                });
+    fn render_tag_dialog(&mut self, ctx: &egui::Context) {
+        if self.show_tag_dialog {
+            egui::Window::new("🏷 Tag Manager")
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.new_tag_name);
+                        if ui.button("Add Tag").clicked() {
+                            self.add_tag();
+                        }
+                    });
+                    ui.separator();
+
+                    // Words the open note keeps using, offered as tags
+                    let suggestions = {
+                        let storage = self.storage.lock().unwrap();
+                        match self
+                            .selected_folder
+                            .zip(self.selected_note)
+                            .and_then(|(f, n)| storage.folders.get(f)?.notes.get(n))
+                        {
+                            Some(note) if !note.is_encrypted => {
+                                let existing: Vec<_> = note
+                                    .tags
+                                    .get_tags(&self.tag_manager)
+                                    .into_iter()
+                                    .cloned()
+                                    .collect();
+                                tags::suggest_tags(&self.current_note_content, &existing)
+                            }
+                            _ => Vec::new(),
+                        }
+                    };
+                    if !suggestions.is_empty() {
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label(egui::RichText::new("Suggested:").small().weak());
+                            for word in suggestions {
+                                if ui
+                                    .small_button(format!("+ {}", word))
+                                    .on_hover_text("Add this tag to the note")
+                                    .clicked()
+                                {
+                                    let idx = self.tag_manager.add_tag(word);
+                                    self.save_tags();
+                                    self.assign_tag_to_note(idx);
+                                }
+                            }
+                        });
+                        ui.separator();
+                    }
+
+                    // Collect tags first to avoid borrow conflicts
+                    let counts = {
+                        let storage = self.storage.lock().unwrap();
+                        self.tag_manager.usage_counts(&storage.folders)
+                    };
+                    let tags: Vec<_> = self
+                        .tag_manager
+                        .all_tags()
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, t)| {
+                            (
+                                t.name.clone(),
+                                t.color,
+                                counts.get(&idx).copied().unwrap_or(0),
+                            )
+                        })
+                        .collect();
 
-                                // Calculate underline position
-                                let underline_start_x = rect.min.x + 6.0 + x_offset;
-                                let underline_y =
-                                    rect.min.y + 6.0 + (line_num as f32 * row_height) + row_height
-                                        - 2.0;
-
-                                // Draw wavy underline
-                                let num_waves = ((word_width / 4.0) as usize).max(2);
-
-                                for i in 0..num_waves {
-                                    let x1 = underline_start_x
-                                        + (i as f32 * word_width / num_waves as f32);
-                                    let x2 = underline_start_x
-                                        + ((i + 1) as f32 * word_width / num_waves as f32);
-                                    let y1 = underline_y + if i % 2 == 0 { 0.0 } else { 1.0 };
-                                    let y2 = underline_y + if i % 2 == 0 { 1.0 } else { 0.0 };
-
-                                    painter.line_segment(
-                                        [egui::pos2(x1, y1), egui::pos2(x2, y2)],
-                                        egui::Stroke::new(
-                                            1.0,
-                                            egui::Color32::from_rgb(255, 80, 80),
-                                        ),
-                                    );
+                    let mut tag_to_remove = None;
+                    for (idx, (name, color, count)) in tags.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if self.renaming_tag == Some(idx) {
+                                ui.text_edit_singleline(&mut self.rename_tag_input);
+                                if ui.small_button("✓").clicked() {
+                                    self.rename_tag(idx, false);
+                                }
+                                if self.rename_tag_error.is_some()
+                                    && ui
+                                        .small_button("Merge")
+                                        .on_hover_text("Merge into the existing tag")
+                                        .clicked()
+                                {
+                                    self.rename_tag(idx, true);
                                 }
+                                if ui.small_button("✕").clicked() {
+                                    self.renaming_tag = None;
+                                    self.rename_tag_error = None;
+                                }
+                                return;
                             }
-                        }
-                    }
-
-                    // Check for content changes to trigger spell check
-                    if response.changed() && self.spellcheck_enabled {
-                        self.misspelled_words =
-                            self.spellcheck.check_text(&self.current_note_content);
-                    }
 
-                    // Minimal spell check summary at bottom
-                    if self.spellcheck_enabled && !self.misspelled_words.is_empty() {
-                        ui.add_space(8.0);
-                        ui.horizontal(|ui| {
-                            ui.label(
-                                egui::RichText::new(format!(
-                                    "⚠ {} spelling issue{}",
-                                    self.misspelled_words.len(),
-                                    if self.misspelled_words.len() == 1 {
-                                        ""
-                                    } else {
-                                        "s"
-                                    }
-                                ))
-                                .small()
-                                .color(egui::Color32::from_rgb(255, 150, 100)),
+                            ui.colored_label(
+                                egui::Color32::from_rgb(color[0], color[1], color[2]),
+                                name,
                             );
-
-                            // Show first few misspelled words
-                            let display_words: Vec<String> = self
-                                .misspelled_words
-                                .iter()
-                                .take(3)
-                                .map(|(_, _, word)| word.clone())
-                                .collect();
-
-                            if !display_words.is_empty() {
-                                ui.label(
-                                    egui::RichText::new(format!(
-                                        "({}{})",
-                                        display_words.join(", "),
-                                        if self.misspelled_words.len() > 3 {
-                                            "..."
-                                        } else {
-                                            ""
-                                        }
-                                    ))
-                                    .small()
-                                    .weak(),
-                                );
+                            ui.label(egui::RichText::new(format!("({})", count)).small().weak());
+                            if ui.button("Assign").clicked() {
+                                self.assign_tag_to_note(idx);
+                            }
+                            if ui.small_button("✏").on_hover_text("Rename tag").clicked() {
+                                self.renaming_tag = Some(idx);
+                                self.rename_tag_input = name.clone();
+                                self.rename_tag_error = None;
+                            }
+                            if ui.small_button("🗑").on_hover_text("Delete tag").clicked() {
+                                tag_to_remove = Some(name.clone());
                             }
                         });
                     }
-                }
-            });
-        }
-    }
-
-    fn render_welcome_screen(&self, ui: &mut egui::Ui) {
-        ui.vertical_centered(|ui| {
-            ui.add_space(150.0);
-
-            ui.heading("Notes");
-            ui.add_space(20.0);
-
-            ui.label("Create a folder to get started");
-            ui.add_space(10.0);
+                    if let Some(error) = &self.rename_tag_error {
+                        ui.label(
+                            egui::RichText::new(format!("⚠ {}", error))
+                                .small()
+                                .color(egui::Color32::from_rgb(255, 200, 100)),
+                        );
+                    }
+                    if let Some(name) = tag_to_remove {
+                        self.remove_tag(&name);
+                    }
 
-            ui.label(egui::RichText::new("Click '+ Folder' above").small().weak());
-        });
-    }
+                    let unused: Vec<String> = tags
+                        .iter()
+                        .filter(|(_, _, count)| *count == 0)
+                        .map(|(name, _, _)| name.clone())
+                        .collect();
+                    if !unused.is_empty() {
+                        ui.separator();
+                        if ui
+                            .button(format!("🧹 Delete {} unused tag(s)", unused.len()))
+                            .clicked()
+                        {
+                            for name in unused {
+                                self.remove_tag(&name);
+                            }
+                        }
+                    }
 
-    fn render_all_dialogs(&mut self, ctx: &egui::Context) {
-        self.render_new_folder_dialog(ctx);
-        self.render_new_note_dialog(ctx);
-        self.render_theme_dialog(ctx);
-        self.render_tag_dialog(ctx);
-        self.render_encryption_dialog(ctx);
-        self.render_export_dialog(ctx);
-        self.render_statistics_dialog(ctx);
-        self.render_version_history_dialog(ctx);
+                    if ui.button("Close").clicked() {
+                        self.show_tag_dialog = false;
+                    }
+                });
+        }
     }
 
-    fn render_new_folder_dialog(&mut self, ctx: &egui::Context) {
-        if self.show_new_folder_dialog {
-            egui::Window::new("New Folder")
+    fn render_encryption_dialog(&mut self, ctx: &egui::Context) {
+        if self.show_encryption_dialog {
+            let (encrypted, hint) = self.selected_note_encryption();
+            egui::Window::new("🔐 Encrypt/Decrypt Note")
                 .collapsible(false)
-                .resizable(false)
                 .show(ctx, |ui| {
-                    ui.label("Folder name:");
-                    let response = ui.text_edit_singleline(&mut self.new_folder_name);
-
-                    // Auto-focus the text field
-                    response.request_focus();
-
-                    // Support Enter key to create
-                    if ui.input(|i| i.key_pressed(egui::Key::Enter))
-                        && !self.new_folder_name.is_empty()
-                    {
-                        self.create_folder();
+                    if let Some(hint) = &hint {
+                        ui.label(egui::RichText::new(format!("💡 Hint: {}", hint)).weak());
+                    }
+                    ui.label("Password:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.encryption_password).password(true),
+                    );
+                    ui.label("Confirm:");
+                    ui.add(egui::TextEdit::singleline(&mut self.confirm_password).password(true));
+                    if !encrypted {
+                        ui.label("Hint (optional):");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.password_hint)
+                                .hint_text("Shown when asking for the password"),
+                        )
+                        .on_hover_text(
+                            "Stored unencrypted next to the note; don't put the password in it",
+                        );
                     }
-
-                    ui.add_space(5.0);
 
                     ui.horizontal(|ui| {
-                        if ui.button("Create").clicked() {
-                            self.create_folder();
+                        if ui.button("Apply").clicked() {
+                            self.toggle_encryption();
                         }
                         if ui.button("Cancel").clicked() {
-                            self.new_folder_name.clear();
-                            self.show_new_folder_dialog = false;
+                            self.encryption_password.clear();
+                            self.confirm_password.clear();
+                            self.password_hint.clear();
+                            self.show_encryption_dialog = false;
                         }
                     });
                 });
         }
     }
 
-    fn render_new_note_dialog(&mut self, ctx: &egui::Context) {
-        if self.show_new_note_dialog {
-            egui::Window::new("New Note")
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.label("Note title:");
-                    let response = ui.text_edit_singleline(&mut self.new_note_title);
+    fn render_unlock_dialog(&mut self, ctx: &egui::Context) {
+        if !self.show_unlock_dialog {
+            return;
+        }
 
-                    // Auto-focus the text field
-                    response.request_focus();
+        let mut unlock = false;
+        let mut cancel = false;
+        let (_, hint) = self.selected_note_encryption();
+        egui::Window::new("🔒 Unlock Note")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if let Some(hint) = &hint {
+                    ui.label(egui::RichText::new(format!("💡 Hint: {}", hint)).weak());
+                }
+                ui.label("Password:");
+                let response = ui
+                    .add(egui::TextEdit::singleline(&mut self.encryption_password).password(true));
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    unlock = true;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Unlock").clicked() {
+                        unlock = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
 
-                    // Support Enter key to create
-                    if ui.input(|i| i.key_pressed(egui::Key::Enter))
-                        && !self.new_note_title.is_empty()
-                    {
-                        self.create_note();
+        if unlock {
+            self.unlock_current_note();
+        } else if cancel {
+            self.encryption_password.clear();
+            self.show_unlock_dialog = false;
+        }
+    }
+
+    fn render_folder_password_dialog(&mut self, ctx: &egui::Context) {
+        let Some((folder_idx, encrypt)) = self.folder_password else {
+            return;
+        };
+
+        let mut apply = false;
+        let mut cancel = false;
+        let title = if encrypt {
+            "🔒 Encrypt Folder"
+        } else {
+            "🔑 Unlock Folder"
+        };
+        egui::Window::new(title).collapsible(false).show(ctx, |ui| {
+            ui.label("Password:");
+            let response =
+                ui.add(egui::TextEdit::singleline(&mut self.encryption_password).password(true));
+            if encrypt {
+                ui.label("Confirm:");
+                ui.add(egui::TextEdit::singleline(&mut self.confirm_password).password(true));
+                ui.label(
+                    egui::RichText::new("Used for this folder until the app is closed.")
+                        .small()
+                        .weak(),
+                );
+            } else if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                apply = true;
+            }
+            ui.horizontal(|ui| {
+                if ui
+                    .button(if encrypt { "Encrypt" } else { "Unlock" })
+                    .clicked()
+                {
+                    apply = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+        if apply {
+            if self.encryption_password.is_empty() {
+                self.notify(ToastLevel::Error, "Enter a password");
+                return;
+            }
+            if encrypt && self.encryption_password != self.confirm_password {
+                self.notify(ToastLevel::Error, "Passwords don't match!");
+                return;
+            }
+            let password = std::mem::take(&mut self.encryption_password);
+            if encrypt {
+                self.encrypt_folder(folder_idx, password);
+            } else {
+                self.unlock_folder(folder_idx, password);
+            }
+        }
+        if apply || cancel {
+            self.encryption_password.clear();
+            self.confirm_password.clear();
+            self.folder_password = None;
+        }
+    }
+
+    fn render_export_dialog(&mut self, ctx: &egui::Context) {
+        if self.show_export_dialog {
+            egui::Window::new("📄 Export")
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    ui.label("Export format:");
+                    for exporter in self.exporters.iter() {
+                        ui.radio_value(
+                            &mut self.export_format,
+                            exporter.extension().to_string(),
+                            exporter.name(),
+                        );
                     }
 
-                    ui.add_space(5.0);
+                    ui.separator();
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Saved as {} in {}",
+                            self.config.export_template,
+                            self.config.export_dir.display()
+                        ))
+                        .small()
+                        .weak(),
+                    );
 
                     ui.horizontal(|ui| {
-                        if ui.button("Create").clicked() {
-                            self.create_note();
+                        if ui.button("Export Note").clicked() {
+                            self.export_selected_note();
+                            self.show_export_dialog = false;
+                        }
+                        if ui
+                            .button("Export Each Note")
+                            .on_hover_text("Every note in the folder to a file of its own")
+                            .clicked()
+                        {
+                            self.export_folder_notes();
+                            self.show_export_dialog = false;
+                        }
+                        if ui.button("Export Folder").clicked() {
+                            if self.export_format == "md" {
+                                if let Some(folder_idx) = self.selected_folder {
+                                    self.export_combined_markdown(Some(folder_idx));
+                                }
+                            } else {
+                                self.export_folder_to_pdf();
+                            }
+                            self.show_export_dialog = false;
+                        }
+                        if ui
+                            .button("Export Vault as One File")
+                            .on_hover_text("Every note in a single Markdown handout")
+                            .clicked()
+                        {
+                            self.export_combined_markdown(None);
+                            self.show_export_dialog = false;
+                        }
+                        if ui.button("Export Vault as Site").clicked() {
+                            self.export_vault_to_site();
+                            self.show_export_dialog = false;
+                        }
+                        if ui.button("Export Vault as JSON").clicked() {
+                            self.export_vault_to_json();
+                            self.show_export_dialog = false;
                         }
                         if ui.button("Cancel").clicked() {
-                            self.new_note_title.clear();
-                            self.show_new_note_dialog = false;
+                            self.show_export_dialog = false;
                         }
                     });
+                });
+        }
+    }
 
-                    // Show selected folder
-                    if let Some(folder_idx) = self.selected_folder {
-                        let storage = self.storage.lock().unwrap();
-                        if let Some(folder) = storage.folders.get(folder_idx) {
-                            ui.add_space(5.0);
-                            ui.label(
-                                egui::RichText::new(format!("In folder: {}", folder.name))
-                                    .small()
-                                    .weak(),
-                            );
+    /// Notes left with unsaved drafts by a session that ended without saving them.
+    fn render_draft_recovery_dialog(&mut self, ctx: &egui::Context) {
+        let Some(drafts) = &self.draft_recovery else {
+            return;
+        };
+        let display: Vec<_> = {
+            let storage = self.storage.lock().unwrap();
+            drafts
+                .iter()
+                .filter_map(|&(f, n)| {
+                    let folder = storage.folders.get(f)?;
+                    Some((
+                        f,
+                        n,
+                        folder.notes.get(n)?.title.clone(),
+                        folder.name.clone(),
+                    ))
+                })
+                .collect()
+        };
+
+        let mut restore = None;
+        let mut discard = Vec::new();
+        let mut close = false;
+        egui::Window::new("🩹 Recover Unsaved Changes")
+            .collapsible(false)
+            .default_width(340.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(
+                        "These notes had edits that weren't saved when the app last closed.",
+                    )
+                    .small()
+                    .weak(),
+                );
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        for (folder_idx, note_idx, title, folder_name) in &display {
+                            ui.horizontal(|ui| {
+                                ui.label(title);
+                                ui.label(egui::RichText::new(folder_name).small().weak());
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        if ui.button("Discard").clicked() {
+                                            discard.push((*folder_idx, *note_idx));
+                                        }
+                                        if ui.button("Restore").clicked() {
+                                            restore = Some((*folder_idx, *note_idx));
+                                        }
+                                    },
+                                );
+                            });
                         }
-                    } else {
-                        ui.add_space(5.0);
-                        ui.label(
-                            egui::RichText::new("⚠ Please select a folder first")
-                                .small()
-                                .color(egui::Color32::from_rgb(255, 200, 100)),
-                        );
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Discard All").clicked() {
+                        discard.extend(display.iter().map(|&(f, n, ..)| (f, n)));
+                    }
+                    if ui
+                        .button("Later")
+                        .on_hover_text("Keep the drafts and ask again next time")
+                        .clicked()
+                    {
+                        close = true;
                     }
                 });
+            });
+
+        if let Some((folder_idx, note_idx)) = restore {
+            self.restore_draft(folder_idx, note_idx);
+            discard.retain(|&note| note != (folder_idx, note_idx));
+            if let Some(drafts) = &mut self.draft_recovery {
+                drafts.retain(|&note| note != (folder_idx, note_idx));
+            }
+        }
+        if !discard.is_empty() {
+            let failed: Vec<String> = {
+                let storage = self.storage.lock().unwrap();
+                discard
+                    .iter()
+                    .filter_map(|&(folder_idx, note_idx)| {
+                        storage.clear_draft(folder_idx, note_idx).err()
+                    })
+                    .map(|e| e.to_string())
+                    .collect()
+            };
+            for e in failed {
+                self.notify(
+                    ToastLevel::Warning,
+                    format!("Failed to remove draft: {}", e),
+                );
+            }
+            if let Some(drafts) = &mut self.draft_recovery {
+                drafts.retain(|note| !discard.contains(note));
+            }
+        }
+        if close || self.draft_recovery.as_ref().is_some_and(|d| d.is_empty()) {
+            self.draft_recovery = None;
         }
     }
 
-    fn render_theme_dialog(&mut self, ctx: &egui::Context) {
-        if self.show_theme_dialog {
-            egui::Window::new("Select Theme")
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.label(
-                        egui::RichText::new(format!(
-                            "Current: {}",
-                            self.theme_manager.current_theme.name
-                        ))
-                        .weak(),
-                    );
-                    ui.add_space(8.0);
+    /// Open a note with its draft in the editor, unsaved, so saving keeps it.
+    fn restore_draft(&mut self, folder_idx: usize, note_idx: usize) {
+        let draft = self
+            .storage
+            .lock()
+            .unwrap()
+            .recover_draft(folder_idx, note_idx);
+        let draft = match draft {
+            Ok(Some(draft)) => draft,
+            Ok(None) => return,
+            Err(e) => {
+                self.notify(ToastLevel::Error, format!("Couldn't read draft: {}", e));
+                return;
+            }
+        };
+        self.open_note(folder_idx, note_idx);
+        if (self.selected_folder, self.selected_note) != (Some(folder_idx), Some(note_idx)) {
+            return;
+        }
+        self.current_note_content = draft.clone();
+        if let Some(tab) = self.active_tab.and_then(|idx| self.open_tabs.get_mut(idx)) {
+            tab.content = draft;
+        }
+        self.notify(ToastLevel::Info, "Draft restored; save to keep it");
+    }
 
-                    for theme in self.theme_manager.available_themes.clone() {
-                        let is_current = theme.name == self.theme_manager.current_theme.name;
-                        let button_text = if is_current {
-                            format!("✓ {}", theme.name)
-                        } else {
-                            theme.name.clone()
-                        };
+    fn render_orphan_notes_dialog(&mut self, ctx: &egui::Context) {
+        let Some(orphans) = &self.orphan_notes else {
+            return;
+        };
+        let display: Vec<_> = {
+            let storage = self.storage.lock().unwrap();
+            orphans
+                .iter()
+                .filter_map(|&(f, n)| {
+                    let folder = storage.folders.get(f)?;
+                    Some((
+                        f,
+                        n,
+                        folder.notes.get(n)?.title.clone(),
+                        folder.name.clone(),
+                    ))
+                })
+                .collect()
+        };
 
-                        if ui.button(button_text).clicked() {
-                            self.theme_manager.set_theme(theme);
-                            self.show_theme_dialog = false;
+        let mut opened = None;
+        let mut refresh = false;
+        let mut close = false;
+        egui::Window::new("🔗 Orphan Notes")
+            .collapsible(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(
+                        "Notes with no links in or out, ready to connect or delete.",
+                    )
+                    .small()
+                    .weak(),
+                );
+                ui.separator();
+                if display.is_empty() {
+                    ui.label("Every note is linked.");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        for (folder_idx, note_idx, title, folder_name) in &display {
+                            ui.horizontal(|ui| {
+                                if ui.link(title).clicked() {
+                                    opened = Some((*folder_idx, *note_idx));
+                                }
+                                ui.label(egui::RichText::new(folder_name).small().weak());
+                            });
                         }
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} orphan(s)", display.len()));
+                    if ui.button("⟳ Refresh").clicked() {
+                        refresh = true;
                     }
-
-                    ui.add_space(8.0);
                     if ui.button("Close").clicked() {
-                        self.show_theme_dialog = false;
+                        close = true;
                     }
                 });
+            });
+
+        if let Some((folder_idx, note_idx)) = opened {
+            self.open_note(folder_idx, note_idx);
+        }
+        if refresh {
+            self.find_orphan_notes();
+        }
+        if close {
+            self.orphan_notes = None;
         }
     }
 
-    fn render_tag_dialog(&mut self, ctx: &egui::Context) {
-        if self.show_tag_dialog {
-            egui::Window::new("🏷 Tag Manager")
-                .collapsible(false)
-                .show(ctx, |ui| {
-                    ui.horizontal(|ui| {
-                        ui.text_edit_singleline(&mut self.new_tag_name);
-                        if ui.button("Add Tag").clicked() {
-                            self.add_tag();
-                        }
-                    });
-                    ui.separator();
+    /// The link graph, laid out a few steps more each frame until it settles.
+    /// Node size follows link count; clicking a node opens the note.
+    fn render_graph_view(&mut self, ctx: &egui::Context) {
+        let Some((graph, layout)) = &mut self.graph_view else {
+            return;
+        };
+        if !layout.is_settled() {
+            for _ in 0..5 {
+                layout.step(graph);
+            }
+            ctx.request_repaint();
+        }
 
-                    // Collect tags first to avoid borrow conflicts
-                    let tags: Vec<_> = self
-                        .tag_manager
-                        .all_tags()
-                        .iter()
-                        .map(|t| (t.name.clone(), t.color))
-                        .collect();
+        let selected = self.selected_folder.zip(self.selected_note);
+        let mut opened = None;
+        let mut refresh = false;
+        let mut open = true;
+        egui::Window::new("🕸 Graph")
+            .open(&mut open)
+            .default_size([520.0, 420.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} notes, {} links",
+                            graph.nodes.len(),
+                            graph.edges.len()
+                        ))
+                        .small()
+                        .weak(),
+                    );
+                    if ui.small_button("⟳ Refresh").clicked() {
+                        refresh = true;
+                    }
+                });
+                let (response, painter) =
+                    ui.allocate_painter(ui.available_size(), egui::Sense::click());
+                let rect = response.rect.shrink(20.0);
+
+                // Fit the layout to the panel
+                let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+                for p in &layout.positions {
+                    for axis in 0..2 {
+                        min[axis] = min[axis].min(p[axis]);
+                        max[axis] = max[axis].max(p[axis]);
+                    }
+                }
+                let to_screen = |p: [f32; 2]| {
+                    let fit = |axis: usize| {
+                        let span = (max[axis] - min[axis]).max(0.001);
+                        (p[axis] - min[axis]) / span
+                    };
+                    egui::pos2(
+                        rect.left() + fit(0) * rect.width(),
+                        rect.top() + fit(1) * rect.height(),
+                    )
+                };
+                let points: Vec<egui::Pos2> =
+                    layout.positions.iter().map(|&p| to_screen(p)).collect();
+                let radius = |node: usize| 3.0 + 2.0 * (graph.degree[node] as f32).sqrt();
+
+                let visuals = ui.visuals();
+                let edge_stroke = egui::Stroke::new(1.0, visuals.weak_text_color());
+                for &(a, b) in &graph.edges {
+                    painter.line_segment([points[a], points[b]], edge_stroke);
+                }
 
-                    for (idx, (name, color)) in tags.iter().enumerate() {
-                        ui.horizontal(|ui| {
-                            ui.colored_label(
-                                egui::Color32::from_rgb(color[0], color[1], color[2]),
-                                name,
-                            );
-                            if ui.button("Assign").clicked() {
-                                self.assign_tag_to_note(idx);
-                            }
-                        });
+                let hovered = response.hover_pos().and_then(|pointer| {
+                    (0..points.len())
+                        .filter(|&node| points[node].distance(pointer) <= radius(node) + 3.0)
+                        .min_by(|&a, &b| {
+                            points[a]
+                                .distance(pointer)
+                                .total_cmp(&points[b].distance(pointer))
+                        })
+                });
+                for (node, &point) in points.iter().enumerate() {
+                    let color = if Some(graph.nodes[node]) == selected {
+                        visuals.selection.bg_fill
+                    } else if Some(node) == hovered {
+                        visuals.strong_text_color()
+                    } else {
+                        visuals.text_color()
+                    };
+                    painter.circle_filled(point, radius(node), color);
+                    // Label the hubs and the node under the pointer
+                    if graph.degree[node] >= 3 || Some(node) == hovered {
+                        painter.text(
+                            point + egui::vec2(0.0, radius(node) + 2.0),
+                            egui::Align2::CENTER_TOP,
+                            &graph.titles[node],
+                            egui::FontId::proportional(11.0),
+                            visuals.text_color(),
+                        );
                     }
+                }
 
-                    if ui.button("Close").clicked() {
-                        self.show_tag_dialog = false;
+                if let Some(node) = hovered {
+                    if response.clicked() {
+                        opened = Some(graph.nodes[node]);
                     }
-                });
+                }
+            });
+
+        if let Some((folder_idx, note_idx)) = opened {
+            self.open_note(folder_idx, note_idx);
+        }
+        if refresh {
+            self.show_graph_view();
+        } else if !open {
+            self.graph_view = None;
         }
     }
 
-    fn render_encryption_dialog(&mut self, ctx: &egui::Context) {
-        if self.show_encryption_dialog {
-            egui::Window::new("🔐 Encrypt/Decrypt Note")
-                .collapsible(false)
-                .show(ctx, |ui| {
-                    ui.label("Password:");
-                    ui.add(
-                        egui::TextEdit::singleline(&mut self.encryption_password).password(true),
-                    );
-                    ui.label("Confirm:");
-                    ui.add(egui::TextEdit::singleline(&mut self.confirm_password).password(true));
+    fn render_archived_notes_dialog(&mut self, ctx: &egui::Context) {
+        let Some(archived) = &self.archived_notes else {
+            return;
+        };
 
-                    ui.horizontal(|ui| {
-                        if ui.button("Apply").clicked() {
-                            self.toggle_encryption();
-                        }
-                        if ui.button("Cancel").clicked() {
-                            self.encryption_password.clear();
-                            self.confirm_password.clear();
-                            self.show_encryption_dialog = false;
+        let mut restore = None;
+        let mut close = false;
+        egui::Window::new("📦 Archived Notes")
+            .collapsible(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(
+                        "Archived notes are kept out of the sidebar and search. \
+                         Restoring one puts it back in its folder.",
+                    )
+                    .small()
+                    .weak(),
+                );
+                ui.separator();
+                if archived.is_empty() {
+                    ui.label("Nothing archived.");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        let mut last_folder = None;
+                        for entry in archived {
+                            if last_folder != Some(&entry.folder) {
+                                ui.label(egui::RichText::new(&entry.folder).strong());
+                                last_folder = Some(&entry.folder);
+                            }
+                            ui.horizontal(|ui| {
+                                let preview = if entry.note.is_encrypted {
+                                    "🔒 Encrypted".to_string()
+                                } else {
+                                    entry.note.content.chars().take(300).collect()
+                                };
+                                ui.label(&entry.note.title).on_hover_text(preview);
+                                if ui
+                                    .small_button("↩ Restore")
+                                    .on_hover_text(format!("Move back to {}", entry.folder))
+                                    .clicked()
+                                {
+                                    restore = Some(PathBuf::from(&entry.note.file_path));
+                                }
+                            });
                         }
                     });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} note(s)", archived.len()));
+                    if ui.button("Close").clicked() {
+                        close = true;
+                    }
                 });
+            });
+
+        if let Some(path) = restore {
+            self.restore_archived_note(&path);
+        }
+        if close {
+            self.archived_notes = None;
         }
     }
 
-    fn render_export_dialog(&mut self, ctx: &egui::Context) {
-        if self.show_export_dialog {
-            egui::Window::new("📄 Export")
-                .collapsible(false)
-                .show(ctx, |ui| {
-                    ui.label("Export format:");
-                    ui.radio_value(&mut self.export_format, ExportFormat::PDF, "PDF");
-                    ui.radio_value(&mut self.export_format, ExportFormat::Markdown, "Markdown");
-                    ui.radio_value(
-                        &mut self.export_format,
-                        ExportFormat::PlainText,
-                        "Plain Text",
-                    );
-
-                    ui.separator();
+    fn render_duplicate_notes_dialog(&mut self, ctx: &egui::Context) {
+        let Some(groups) = &self.duplicate_notes else {
+            return;
+        };
+        let display: Vec<Vec<_>> = {
+            let storage = self.storage.lock().unwrap();
+            groups
+                .iter()
+                .map(|group| {
+                    group
+                        .iter()
+                        .filter_map(|&(f, n)| {
+                            let folder = storage.folders.get(f)?;
+                            Some((
+                                (f, n),
+                                folder.notes.get(n)?.title.clone(),
+                                folder.name.clone(),
+                            ))
+                        })
+                        .collect()
+                })
+                .collect()
+        };
 
-                    ui.horizontal(|ui| {
-                        if ui.button("Export Note").clicked() {
-                            self.export_note_to_pdf();
-                            self.show_export_dialog = false;
-                        }
-                        if ui.button("Export Folder").clicked() {
-                            self.export_folder_to_pdf();
-                            self.show_export_dialog = false;
-                        }
-                        if ui.button("Cancel").clicked() {
-                            self.show_export_dialog = false;
+        let mut opened = None;
+        let mut merge = None;
+        let mut delete = None;
+        let mut refresh = false;
+        let mut close = false;
+        egui::Window::new("🗐 Duplicate Notes")
+            .collapsible(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(
+                        "Notes with the same or nearly the same content. Merging keeps one \
+                         note with the others' tags and any text it was missing.",
+                    )
+                    .small()
+                    .weak(),
+                );
+                ui.separator();
+                if display.is_empty() {
+                    ui.label("No duplicates found.");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        for group in &display {
+                            for (note, title, folder_name) in group {
+                                ui.horizontal(|ui| {
+                                    if ui.link(title).clicked() {
+                                        opened = Some(*note);
+                                    }
+                                    ui.label(egui::RichText::new(folder_name).small().weak());
+                                    if ui
+                                        .small_button("Keep")
+                                        .on_hover_text("Merge the other copies into this note")
+                                        .clicked()
+                                    {
+                                        let others: Vec<_> = group
+                                            .iter()
+                                            .map(|(other, _, _)| *other)
+                                            .filter(|other| other != note)
+                                            .collect();
+                                        merge = Some((*note, others));
+                                    }
+                                    if ui
+                                        .small_button("🗑")
+                                        .on_hover_text("Delete this copy")
+                                        .clicked()
+                                    {
+                                        delete = Some(*note);
+                                    }
+                                });
+                            }
+                            ui.separator();
                         }
                     });
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} group(s)", display.len()));
+                    if ui.button("⟳ Refresh").clicked() {
+                        refresh = true;
+                    }
+                    if ui.button("Close").clicked() {
+                        close = true;
+                    }
                 });
+            });
+
+        if let Some((folder_idx, note_idx)) = opened {
+            self.open_note(folder_idx, note_idx);
+        }
+        if let Some((keep, others)) = merge {
+            self.resolve_duplicates(keep, &others);
+        } else if let Some(note) = delete {
+            self.delete_duplicate(note);
+        }
+        if refresh {
+            self.find_duplicate_notes();
+        }
+        if close {
+            self.duplicate_notes = None;
         }
     }
 
     fn render_statistics_dialog(&mut self, ctx: &egui::Context) {
-        if self.show_statistics {
-            let stats = self.calculate_statistics();
-            egui::Window::new("📊 Statistics")
-                .collapsible(false)
-                .show(ctx, |ui| {
-                    ui.label(format!("📁 Total Folders: {}", stats.total_folders));
-                    ui.label(format!("📝 Total Notes: {}", stats.total_notes));
-                    ui.label(format!("📝 Total Words: {}", stats.total_words));
-                    ui.label(format!("📝 Total Characters: {}", stats.total_chars));
-                    ui.label(format!("🔒 Encrypted Notes: {}", stats.encrypted_count));
-                    ui.label(format!("🏷 Total Tags: {}", stats.total_tags));
-                    ui.label(format!("⭐ Favorites: {}", stats.favorite_count));
+        if !self.show_statistics {
+            return;
+        }
+        let Some(stats) = &self.statistics else {
+            self.statistics = Some(self.calculate_statistics());
+            return;
+        };
+
+        let mut refresh = false;
+        let mut close = false;
+        egui::Window::new("📊 Statistics")
+            .collapsible(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                let vault = &stats.vault;
+                ui.label(format!("📁 Total Folders: {}", stats.total_folders));
+                ui.label(format!("📝 Total Notes: {}", vault.total_notes));
+                ui.label(format!("📝 Total Words: {}", vault.total_words));
+                ui.label(format!("📝 Total Characters: {}", stats.total_chars));
+                ui.label(format!("🔒 Encrypted Notes: {}", stats.encrypted_count));
+                ui.label(format!("🏷 Total Tags: {}", vault.tag_counts.len()));
+                ui.label(format!("⭐ Favorites: {}", stats.favorite_count));
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        egui::CollapsingHeader::new("Notes per Folder")
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                stat_grid(ui, "stats_folders", &vault.notes_per_folder);
+                            });
+                        egui::CollapsingHeader::new("Most Linked")
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                if vault.most_linked.is_empty() {
+                                    ui.label(egui::RichText::new("No links yet").weak());
+                                }
+                                stat_grid(ui, "stats_links", &vault.most_linked);
+                            });
+                        egui::CollapsingHeader::new("Tags").show(ui, |ui| {
+                            if vault.tag_counts.is_empty() {
+                                ui.label(egui::RichText::new("No tags yet").weak());
+                            }
+                            stat_grid(ui, "stats_tags", &vault.tag_counts);
+                        });
+                        egui::CollapsingHeader::new("Activity by Month").show(ui, |ui| {
+                            egui::Grid::new("stats_activity")
+                                .num_columns(3)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.strong("Month");
+                                    ui.strong("Created");
+                                    ui.strong("Updated");
+                                    ui.end_row();
+                                    for (month, created, updated) in vault.activity.iter().rev() {
+                                        ui.label(month);
+                                        ui.label(created.to_string());
+                                        ui.label(updated.to_string());
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                    });
 
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("⟳ Refresh").clicked() {
+                        refresh = true;
+                    }
                     if ui.button("Close").clicked() {
-                        self.show_statistics = false;
+                        close = true;
                     }
                 });
+            });
+
+        if refresh {
+            self.statistics = Some(self.calculate_statistics());
+        }
+        if close {
+            self.show_statistics = false;
+            self.statistics = None;
         }
     }
 
@@ -1722,6 +8179,18 @@ impl NoteTakingApp {
                                 self.selected_version = Some(current_index);
                             }
 
+                            if ui.button("📝 Show Changes").clicked() {
+                                self.load_version_diff(current_index);
+                            }
+
+                            if ui.button("🔍 Compare with Current").clicked() {
+                                self.load_working_diff(current_index);
+                            }
+
+                            if ui.button("📤 Export Timeline").clicked() {
+                                self.export_history_timeline();
+                            }
+
                             if ui.button("↩ Restore This Version").clicked() {
                                 self.restore_version(current_index);
                                 self.show_version_history = false;
@@ -1733,6 +8202,11 @@ impl NoteTakingApp {
                         });
                     }
 
+                    if let Some(diff) = &self.version_diff {
+                        ui.separator();
+                        render_diff(ui, diff);
+                    }
+
                     ui.separator();
 
                     // Version list (for reference)
@@ -1741,6 +8215,7 @@ impl NoteTakingApp {
                         .max_height(200.0)
                         .show(ui, |ui| {
                             let versions = self.note_versions.clone();
+                            let mut version_to_restore = None;
 
                             for (idx, version) in versions.iter().enumerate().rev() {
                                 let is_current = idx == current_index;
@@ -1770,14 +8245,189 @@ impl NoteTakingApp {
                                                         - (idx as f32 / (version_count - 1) as f32);
                                                     self.selected_version = Some(idx);
                                                 }
+                                                if ui
+                                                    .small_button("Restore")
+                                                    .on_hover_text("Restore this version")
+                                                    .clicked()
+                                                {
+                                                    version_to_restore = Some(idx);
+                                                }
                                             });
                                         },
                                     );
                                 });
                                 ui.add_space(2.0);
                             }
+
+                            if let Some(idx) = version_to_restore {
+                                self.restore_version(idx);
+                            }
                         });
                 });
         }
     }
 }
+
+/// Draw unified-diff text with added lines in green and removed lines in red.
+fn render_diff(ui: &mut egui::Ui, diff: &str) {
+    egui::ScrollArea::vertical()
+        .id_salt("version_diff")
+        .max_height(250.0)
+        .show(ui, |ui| {
+            for line in diff.lines() {
+                let color = if line.starts_with("+++") || line.starts_with("---") {
+                    egui::Color32::from_gray(160)
+                } else if line.starts_with('+') {
+                    egui::Color32::from_rgb(110, 200, 110)
+                } else if line.starts_with('-') {
+                    egui::Color32::from_rgb(230, 100, 100)
+                } else if line.starts_with("@@") {
+                    egui::Color32::from_rgb(100, 180, 255)
+                } else {
+                    egui::Color32::from_gray(200)
+                };
+                ui.label(egui::RichText::new(line).monospace().color(color));
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_buffer_against_stored_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        storage.create_folder("Inbox").unwrap();
+        storage.create_note(0, "Draft", "first line\n").unwrap();
+        let stored = |storage: &Storage| storage.folders[0].notes[0].content.clone();
+
+        let mut buffer = stored(&storage);
+        assert!(!is_dirty(&buffer, Some(&stored(&storage))));
+        buffer.push_str("second line\n");
+        assert!(is_dirty(&buffer, Some(&stored(&storage))));
+
+        storage.folders[0].notes[0].set_content(&buffer);
+        storage.save_note(0, 0).unwrap();
+        assert!(!is_dirty(&buffer, Some(&stored(&storage))));
+        // Gone from the vault: nowhere holds the buffer
+        assert!(is_dirty(&buffer, None));
+    }
+
+    #[test]
+    fn test_own_writes_do_not_look_like_outside_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        storage.create_folder("Inbox").unwrap();
+        storage.create_note(0, "Draft", "body").unwrap();
+        let note = &storage.folders[0].notes[0];
+        let mut tabs = vec![NoteTab {
+            folder_idx: 0,
+            note_idx: 0,
+            file_path: note.file_path.clone(),
+            content: note.content.clone(),
+            base_mtime: None,
+        }];
+        assert!(storage.changed_on_disk(0, 0, tabs[0].base_mtime));
+
+        // A tag edit rewrites the file; the open tab follows it
+        storage.folders[0].notes[0].set_content(
+            "body
+
+#todo",
+        );
+        save_note_for_tabs(&mut storage, &mut tabs, 0, 0).unwrap();
+        assert!(!storage.changed_on_disk(0, 0, tabs[0].base_mtime));
+    }
+
+    #[test]
+    fn test_save_conflict_survives_a_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        storage.create_folder("Inbox").unwrap();
+        storage.create_note(0, "Alpha", "a").unwrap();
+        storage.create_note(0, "Beta", "b").unwrap();
+        let beta = storage.folders[0].notes[1].file_path.clone();
+        std::fs::write(&beta, "b, edited elsewhere").unwrap();
+
+        // While the conflict on Beta waits, Alpha is deleted outside the app
+        let alpha = PathBuf::from(&storage.folders[0].notes[0].file_path);
+        std::fs::remove_file(&alpha).unwrap();
+        assert!(storage.reload_path(&alpha));
+
+        let (at, disk) = find_disk_version(&storage, &beta).unwrap();
+        assert_eq!(at, (0, 0));
+        assert_eq!(disk.content, "b, edited elsewhere");
+
+        std::fs::remove_file(&beta).unwrap();
+        assert!(storage.reload_path(std::path::Path::new(&beta)));
+        assert!(find_disk_version(&storage, &beta).is_err());
+    }
+
+    #[test]
+    fn test_insert_at_byte_multibyte() {
+        let mut content = "café 日記".to_string();
+        // Inside "é" (bytes 3..5): lands before it
+        assert_eq!(insert_at_byte(&mut content, 4, "✅"), 6);
+        assert_eq!(content, "caf✅é 日記");
+
+        let end = content.len();
+        assert_eq!(insert_at_byte(&mut content, end - 1, "!"), end - 2);
+        assert_eq!(content, "caf✅é 日!記");
+        assert_eq!(insert_at_byte(&mut content, 99, "🎉"), content.len());
+        assert!(content.ends_with("記🎉"));
+    }
+
+    #[test]
+    fn test_switching_vaults_keeps_notes_apart() {
+        let work = tempfile::tempdir().unwrap();
+        let personal = tempfile::tempdir().unwrap();
+        let ctx = egui::Context::default();
+
+        let mut vault = VaultState::open(work.path(), &ctx, false);
+        vault.storage.create_folder("Projects").unwrap();
+        vault.storage.create_note(0, "Roadmap", "Q3 plans").unwrap();
+        vault.tag_manager.add_tag("urgent".to_string());
+        vault
+            .tag_manager
+            .save(&work.path().join(TAGS_FILE))
+            .unwrap();
+        vault
+            .recent_notes
+            .record(&vault.storage.folders[0].notes[0].file_path);
+        drop(vault);
+
+        let vault = VaultState::open(personal.path(), &ctx, false);
+        assert!(vault.storage.folders.is_empty());
+        assert!(vault.tag_manager.tags.is_empty());
+        assert!(vault.recent_notes.is_empty());
+        assert!(!personal.path().join("Projects").exists());
+        drop(vault);
+
+        let mut vault = VaultState::open(work.path(), &ctx, false);
+        vault.storage.ensure_all_loaded();
+        assert_eq!(vault.storage.folders.len(), 1);
+        assert_eq!(vault.storage.folders[0].notes[0].content, "Q3 plans");
+        assert_eq!(vault.tag_manager.tags.len(), 1);
+    }
+
+    #[test]
+    fn test_new_note_without_selection_goes_to_inbox() {
+        let vault = tempfile::tempdir().unwrap();
+        let mut storage = Storage::new(vault.path().to_string_lossy().to_string());
+        storage.create_folder("Work").unwrap();
+
+        // A selected folder wins; otherwise the inbox is created once and reused
+        assert_eq!(new_note_folder(&mut storage, Some(0), "Capture"), Ok(0));
+        let inbox = new_note_folder(&mut storage, None, " Capture ").unwrap();
+        storage.create_note(inbox, "Quick thought", "").unwrap();
+        assert_eq!(new_note_folder(&mut storage, None, "Capture"), Ok(inbox));
+        assert!(new_note_folder(&mut storage, None, "  ").is_err());
+
+        assert_eq!(storage.folders.len(), 2);
+        assert_eq!(storage.folders[inbox].name, "Capture");
+        assert_eq!(storage.folders[inbox].notes[0].title, "Quick thought");
+        assert!(vault.path().join("Capture/Quick thought.md").exists());
+    }
+}
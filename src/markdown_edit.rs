@@ -0,0 +1,233 @@
+use std::ops::Range;
+
+/// Put `prefix` and `suffix` around `content[range]`, or take them away if the
+/// selection already has them, either inside its ends or just outside. A marker
+/// that is part of a longer run doesn't count, so italic inside `**bold**` adds
+/// a `*` rather than taking one of the bold's away. Returns the
+/// new content and the byte range to select afterwards: the same text, now with
+/// or without its markers. With nothing selected the markers are inserted and the
+/// cursor goes between them. `None` if the range is out of bounds or splits a
+/// character.
+pub fn wrap_selection(
+    content: &str,
+    range: Range<usize>,
+    prefix: &str,
+    suffix: &str,
+) -> Option<(String, Range<usize>)> {
+    let selected = content.get(range.clone())?;
+
+    let before = &content[..range.start];
+    let after = &content[range.end..];
+
+    // `**text**` selected, markers included
+    if selected.len() >= prefix.len() + suffix.len()
+        && selected.starts_with(prefix)
+        && selected.ends_with(suffix)
+        && !extends_run(prefix, before.chars().next_back())
+        && !extends_run(prefix, selected[prefix.len()..].chars().next())
+        && !extends_run(
+            suffix,
+            selected[..selected.len() - suffix.len()]
+                .chars()
+                .next_back(),
+        )
+        && !extends_run(suffix, after.chars().next())
+    {
+        let inner = &selected[prefix.len()..selected.len() - suffix.len()];
+        let edited = format!(
+            "{}{}{}",
+            &content[..range.start],
+            inner,
+            &content[range.end..]
+        );
+        return Some((edited, range.start..range.start + inner.len()));
+    }
+
+    // `text` selected inside `**text**`
+    if !selected.is_empty()
+        && before.ends_with(prefix)
+        && after.starts_with(suffix)
+        && !extends_run(
+            prefix,
+            before[..before.len() - prefix.len()].chars().next_back(),
+        )
+        && !extends_run(prefix, selected.chars().next())
+        && !extends_run(suffix, selected.chars().next_back())
+        && !extends_run(suffix, after[suffix.len()..].chars().next())
+    {
+        let start = range.start - prefix.len();
+        let edited = format!(
+            "{}{}{}",
+            &content[..start],
+            selected,
+            &after[suffix.len()..]
+        );
+        return Some((edited, start..start + selected.len()));
+    }
+
+    let edited = format!("{}{}{}{}{}", before, prefix, selected, suffix, after);
+    let start = range.start + prefix.len();
+    Some((edited, start..start + selected.len()))
+}
+
+/// Whether `neighbour`, the character next to a marker like `*` or `**`, would
+/// make it part of a longer run of the same character.
+fn extends_run(marker: &str, neighbour: Option<char>) -> bool {
+    let mut chars = marker.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    chars.all(|c| c == first) && neighbour == Some(first)
+}
+
+/// Start every line the selection touches with `prefix` (`# `, `- `), or remove
+/// it if they all have it already. Blank lines are left alone unless there is
+/// nothing else. A heading prefix replaces any heading level the line already
+/// has. Returns the new content and the range of the lines edited. `None` if the
+/// range is out of bounds or splits a character.
+pub fn toggle_line_prefix(
+    content: &str,
+    range: Range<usize>,
+    prefix: &str,
+) -> Option<(String, Range<usize>)> {
+    content.get(range.clone())?;
+    let start = content[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    // A selection ending just after a newline doesn't take in the next line
+    let last = if range.end > range.start && content[..range.end].ends_with('\n') {
+        range.end - 1
+    } else {
+        range.end
+    };
+    let end = content[last..]
+        .find('\n')
+        .map_or(content.len(), |i| last + i);
+
+    let lines: Vec<&str> = content[start..end].split('\n').collect();
+    let skip_blank = lines.iter().any(|line| !line.trim().is_empty());
+    let skipped = |line: &str| skip_blank && line.trim().is_empty();
+    let all_prefixed = lines
+        .iter()
+        .filter(|line| !skipped(line))
+        .all(|line| line.starts_with(prefix));
+    let edited_lines: Vec<String> = lines
+        .iter()
+        .map(|line| {
+            if skipped(line) {
+                line.to_string()
+            } else if all_prefixed {
+                line[prefix.len()..].to_string()
+            } else {
+                format!("{}{}", prefix, strip_marker(line, prefix))
+            }
+        })
+        .collect();
+    let edited_block = edited_lines.join("\n");
+
+    let edited = format!("{}{}{}", &content[..start], edited_block, &content[end..]);
+    Some((edited, start..start + edited_block.len()))
+}
+
+/// `line` without a marker of the same kind as `prefix`: any heading level when
+/// `prefix` is a heading, otherwise `prefix` itself.
+fn strip_marker<'a>(line: &'a str, prefix: &str) -> &'a str {
+    if heading_level(prefix).is_some() {
+        return heading_level(line).map_or(line, |level| &line[level + 1..]);
+    }
+    line.strip_prefix(prefix).unwrap_or(line)
+}
+
+/// The level of the `#`-run heading marker `line` starts with, if any.
+fn heading_level(line: &str) -> Option<usize> {
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    ((1..=6).contains(&hashes) && line[hashes..].starts_with(' ')).then_some(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_selection() {
+        let content = "make this bold";
+        let start = content.find("bold").unwrap();
+        let (edited, selected) = wrap_selection(content, start..start + 4, "**", "**").unwrap();
+        assert_eq!(edited, "make this **bold**");
+        assert_eq!(&edited[selected], "bold");
+
+        let (edited, selected) = wrap_selection("a  b", 2..2, "`", "`").unwrap();
+        assert_eq!(edited, "a `` b");
+        assert_eq!(selected, 3..3);
+
+        let (edited, selected) = wrap_selection("see docs", 4..8, "[", "](url)").unwrap();
+        assert_eq!(edited, "see [docs](url)");
+        assert_eq!(&edited[selected], "docs");
+
+        let content = "日記 note";
+        assert!(wrap_selection(content, 1..3, "*", "*").is_none());
+        assert!(wrap_selection(content, 0..99, "*", "*").is_none());
+    }
+
+    #[test]
+    fn test_wrap_selection_toggles_off() {
+        // Selection inside the markers
+        let content = "make this **bold** now";
+        let start = content.find("bold").unwrap();
+        let (edited, selected) = wrap_selection(content, start..start + 4, "**", "**").unwrap();
+        assert_eq!(edited, "make this bold now");
+        assert_eq!(&edited[selected], "bold");
+
+        // Markers selected too
+        let start = content.find("**").unwrap();
+        let (edited, selected) = wrap_selection(content, start..start + 8, "**", "**").unwrap();
+        assert_eq!(edited, "make this bold now");
+        assert_eq!(&edited[selected], "bold");
+
+        // Wrapping twice gets the original back
+        let (wrapped, selected) = wrap_selection("x *y* z", 3..4, "*", "*").unwrap();
+        assert_eq!(wrapped, "x y z");
+        let (unwrapped, _) = wrap_selection(&wrapped, selected, "*", "*").unwrap();
+        assert_eq!(unwrapped, "x *y* z");
+
+        // Italic inside bold adds markers instead of eating the bold's
+        let content = "a **bold** b";
+        let start = content.find("bold").unwrap();
+        let (edited, selected) = wrap_selection(content, start..start + 4, "*", "*").unwrap();
+        assert_eq!(edited, "a ***bold*** b");
+        assert_eq!(&edited[selected], "bold");
+        let (edited, _) = wrap_selection(content, 2..10, "*", "*").unwrap();
+        assert_eq!(edited, "a ***bold*** b");
+    }
+
+    #[test]
+    fn test_toggle_line_prefix() {
+        let content = "intro\none\ntwo\noutro";
+        let start = content.find("ne").unwrap();
+        let end = content.find("wo").unwrap();
+        let (edited, lines) = toggle_line_prefix(content, start..end, "- ").unwrap();
+        assert_eq!(edited, "intro\n- one\n- two\noutro");
+        assert_eq!(&edited[lines.clone()], "- one\n- two");
+
+        let (edited, _) = toggle_line_prefix(&edited, lines, "- ").unwrap();
+        assert_eq!(edited, content);
+
+        let (edited, _) = toggle_line_prefix("Title\nbody\n", 0..6, "# ").unwrap();
+        assert_eq!(edited, "# Title\nbody\n");
+        let (edited, _) = toggle_line_prefix("", 0..0, "# ").unwrap();
+        assert_eq!(edited, "# ");
+
+        // Blank lines in the selection stay blank, and don't stop toggling off
+        let content = "one\n\ntwo";
+        let (edited, lines) = toggle_line_prefix(content, 0..content.len(), "- ").unwrap();
+        assert_eq!(edited, "- one\n\n- two");
+        let (edited, _) = toggle_line_prefix(&edited, lines, "- ").unwrap();
+        assert_eq!(edited, content);
+
+        // A heading changes level rather than stacking
+        let (edited, _) = toggle_line_prefix("## Title", 0..0, "# ").unwrap();
+        assert_eq!(edited, "# Title");
+        let (edited, _) = toggle_line_prefix("# Title", 0..0, "# ").unwrap();
+        assert_eq!(edited, "Title");
+        let (edited, _) = toggle_line_prefix("#日記", 0..0, "# ").unwrap();
+        assert_eq!(edited, "# #日記");
+    }
+}
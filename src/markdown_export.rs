@@ -0,0 +1,204 @@
+use crate::frontmatter::join_front_matter;
+use crate::links::replace_wiki_links;
+use crate::note::Note;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub struct MarkdownExporter;
+
+impl MarkdownExporter {
+    /// Write one note as a Markdown file that imports back as the same note.
+    pub fn export_note(note: &Note, tags: &[String], output_path: &Path) -> Result<(), String> {
+        fs::write(output_path, Self::note_with_front_matter(note, tags)?)
+            .map_err(|e| format!("Failed to write {}: {}", output_path.display(), e))
+    }
+
+    /// The note's content under a front-matter block with its title, tags and
+    /// timestamps, on top of whatever front-matter it already had. `tags` are the
+    /// names of the tags assigned in the app; front-matter tags are kept as well.
+    pub fn note_with_front_matter(note: &Note, tags: &[String]) -> Result<String, String> {
+        let mut front_matter = note.front_matter.clone().unwrap_or_default();
+        front_matter.title = Some(note.title.clone());
+        for tag in tags {
+            if !front_matter.tags.contains(tag) {
+                front_matter.tags.push(tag.clone());
+            }
+        }
+        front_matter.created = Some(note.created().to_rfc3339());
+        front_matter.updated = Some(note.updated().to_rfc3339());
+        front_matter.set_properties(&note.properties);
+        join_front_matter(&front_matter, &note.content)
+    }
+
+    /// Write `notes` (title, content) into one Markdown file, in order.
+    pub fn export_combined(notes: &[(String, String)], output_path: &Path) -> Result<(), String> {
+        fs::write(output_path, Self::combine(notes))
+            .map_err(|e| format!("Failed to write {}: {}", output_path.display(), e))
+    }
+
+    /// Each note becomes a `# Title` section, separated by horizontal rules. Headings
+    /// inside a note move down a level to sit under its title, and a leading heading
+    /// that just repeats the title is dropped. `[[links]]` to notes in the document
+    /// point at their section's anchor; links to anything else become plain text.
+    pub fn combine(notes: &[(String, String)]) -> String {
+        // Anchors follow GitHub's heading ids, with -1, -2... for repeated titles
+        let mut used: HashMap<String, usize> = HashMap::new();
+        let mut anchors: HashMap<String, String> = HashMap::new();
+        for (title, _) in notes {
+            let base = slugify(title);
+            let count = used.entry(base.clone()).or_insert(0);
+            let anchor = if *count == 0 {
+                base.clone()
+            } else {
+                format!("{}-{}", base, count)
+            };
+            *count += 1;
+            anchors.entry(title.to_lowercase()).or_insert(anchor);
+        }
+
+        let sections: Vec<String> = notes
+            .iter()
+            .map(|(title, content)| {
+                let body = demote_headings(strip_title_heading(content, title));
+                let body = replace_wiki_links(&body, |name| {
+                    anchors
+                        .get(&name.to_lowercase())
+                        .map(|anchor| format!("[{}](#{})", name, anchor))
+                });
+                format!("# {}\n\n{}\n", title, body.trim())
+            })
+            .collect();
+        sections.join("\n---\n\n")
+    }
+}
+
+/// GitHub-style heading id: lowercase, spaces to hyphens, other punctuation dropped.
+fn slugify(title: &str) -> String {
+    title
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+fn strip_title_heading<'a>(content: &'a str, title: &str) -> &'a str {
+    let trimmed = content.trim_start();
+    let (first, rest) = trimmed.split_once('\n').unwrap_or((trimmed, ""));
+    match first.strip_prefix("# ") {
+        Some(heading) if heading.trim().eq_ignore_ascii_case(title.trim()) => rest,
+        _ => content,
+    }
+}
+
+/// Add a `#` to every ATX heading outside fenced code, up to level 6.
+fn demote_headings(content: &str) -> String {
+    let mut out = String::with_capacity(content.len() + 16);
+    let mut in_fence = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let is_heading =
+            !in_fence && (1..6).contains(&level) && trimmed[level..].starts_with([' ', '\t', '\n']);
+        if is_heading {
+            out.push('#');
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::parse_timestamp;
+    use crate::storage::Storage;
+    use crate::tags::TagManager;
+
+    #[test]
+    fn test_combine_two_notes() {
+        let notes = vec![
+            (
+                "Getting Started".to_string(),
+                "# Getting Started\n\nRead [[Advanced Topics]] next.\n\n## Setup\n\n```\n# not a heading\n```\n".to_string(),
+            ),
+            (
+                "Advanced Topics".to_string(),
+                "Back to [[getting started]] or [[Missing]].".to_string(),
+            ),
+        ];
+        let combined = MarkdownExporter::combine(&notes);
+
+        // Titles are headings in order, and the note's own copy of its title is gone
+        assert!(combined.starts_with("# Getting Started\n\nRead"));
+        let second = combined.find("\n# Advanced Topics\n").unwrap();
+        assert!(combined.find("Read [").unwrap() < second);
+        assert_eq!(combined.matches("# Getting Started").count(), 1);
+        assert!(combined.contains("Read [Advanced Topics](#advanced-topics) next."));
+        assert!(combined.contains("Back to [getting started](#getting-started) or Missing."));
+        assert!(combined.contains("\n### Setup\n"));
+        assert!(combined.contains("\n# not a heading\n"));
+        assert_eq!(combined.matches("\n---\n").count(), 1);
+    }
+
+    #[test]
+    fn test_repeated_titles_get_distinct_anchors() {
+        assert_eq!(slugify("What's New? (2024)"), "whats-new-2024");
+
+        let notes = vec![
+            ("Notes".to_string(), "first".to_string()),
+            ("Notes".to_string(), "second".to_string()),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("handout.md");
+        MarkdownExporter::export_combined(&notes, &output).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "# Notes\n\nfirst\n\n---\n\n# Notes\n\nsecond\n"
+        );
+    }
+
+    #[test]
+    fn test_exported_note_imports_with_tags_and_timestamps() {
+        let mut note = Note::new("Trip Plan".to_string(), "trip.md".to_string());
+        note.content = "# Packing\n\n- passport\n".to_string();
+        note.created_at = parse_timestamp("2024-03-01T08:30:00Z").unwrap();
+        note.updated_at = parse_timestamp("2024-03-05T17:45:10+02:00").unwrap();
+        note.properties
+            .insert("status".to_string(), "draft".to_string());
+
+        let vault = tempfile::tempdir().unwrap();
+        fs::create_dir_all(vault.path().join("Imported")).unwrap();
+        let output = vault.path().join("Imported/Trip Plan.md");
+        let tags = vec!["travel".to_string(), "2024".to_string()];
+        MarkdownExporter::export_note(&note, &tags, &output).unwrap();
+
+        let mut storage = Storage::new(vault.path().to_string_lossy().to_string());
+        storage.ensure_loaded(0, 0).unwrap();
+        let imported = &mut storage.folders[0].notes[0];
+        assert_eq!(imported.title, "Trip Plan");
+        assert_eq!(imported.content, note.content);
+        assert_eq!(imported.created(), note.created());
+        assert_eq!(imported.updated(), note.updated());
+        assert_eq!(imported.properties, note.properties);
+
+        let mut tag_manager = TagManager::new();
+        tag_manager.register_front_matter_tags(imported);
+        let names: Vec<&str> = imported
+            .tags
+            .get_tags(&tag_manager)
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"travel") && names.contains(&"2024"));
+    }
+}
@@ -0,0 +1,211 @@
+/// Tidy a note's Markdown: trailing whitespace trimmed, runs of blank lines
+/// collapsed to one, one blank line around headings and one space after their
+/// `#`s, `*` and `+` bullets written as `-`, and a single final newline. Fenced
+/// code blocks, fenced or indented, are copied through untouched, and a
+/// two-space hard line break inside a paragraph is kept. `#tag` lines aren't
+/// headings and are left alone.
+pub fn format_markdown(content: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    // The opening fence while inside a code block
+    let mut fence: Option<String> = None;
+    let mut after_heading = false;
+    // Inside a list, indented lines are continuations rather than code
+    let mut in_list = false;
+    let mut raw = content.lines().peekable();
+
+    while let Some(raw_line) = raw.next() {
+        if let Some(open) = &fence {
+            if closes_fence(raw_line, open) {
+                fence = None;
+            }
+            lines.push(raw_line.to_string());
+            continue;
+        }
+
+        let after_blank = lines.last().is_none_or(|l| l.is_empty());
+        if after_blank && !in_list && !raw_line.trim().is_empty() && is_indented_code(raw_line) {
+            // Copy the block through, blank lines included, up to the first
+            // line that isn't indented
+            lines.push(raw_line.to_string());
+            while let Some(next) = raw.peek() {
+                if is_indented_code(next) {
+                    lines.push(next.to_string());
+                } else if next.trim().is_empty() {
+                    lines.push(String::new());
+                } else {
+                    break;
+                }
+                raw.next();
+            }
+            while lines.last().is_some_and(|l| l.is_empty()) {
+                lines.pop();
+            }
+            lines.push(String::new());
+            after_heading = false;
+            continue;
+        }
+
+        let line = raw_line.trim_end();
+        if line.is_empty() {
+            if lines.last().is_some_and(|last| !last.is_empty()) {
+                lines.push(String::new());
+            }
+            after_heading = false;
+            continue;
+        }
+
+        let heading = heading(line);
+        if (after_heading || heading.is_some()) && lines.last().is_some_and(|l| !l.is_empty()) {
+            lines.push(String::new());
+        }
+        after_heading = heading.is_some();
+        if is_list_item(line) {
+            in_list = true;
+        } else if !line.starts_with([' ', '\t']) {
+            in_list = false;
+        }
+
+        if let Some(open) = fence_marker(line) {
+            fence = Some(open.to_string());
+            lines.push(line.to_string());
+        } else if let Some(heading) = heading {
+            lines.push(heading);
+        } else {
+            let mut line = bullet(line);
+            let hard_break =
+                raw_line.ends_with("  ") && !raw_line.trim_end_matches(' ').ends_with('\t');
+            if hard_break && raw.peek().is_some_and(|next| continues_paragraph(next)) {
+                line.push_str("  ");
+            }
+            lines.push(line);
+        }
+    }
+
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut formatted = lines.join("\n");
+    formatted.push('\n');
+    formatted
+}
+
+/// The run of backticks or tildes opening a fenced code block.
+pub(crate) fn fence_marker(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(marker).len();
+    (len >= 3).then(|| &trimmed[..len])
+}
+
+/// A fence closes with at least as many of the same character and nothing after.
+pub(crate) fn closes_fence(line: &str, open: &str) -> bool {
+    fence_marker(line).is_some_and(|marker| {
+        marker.starts_with(&open[..1])
+            && marker.len() >= open.len()
+            && line.trim()[marker.len()..].trim().is_empty()
+    })
+}
+
+/// Four spaces or a tab of indent, which starts an indented code block after a
+/// blank line.
+fn is_indented_code(line: &str) -> bool {
+    line.starts_with("    ") || line.starts_with('\t')
+}
+
+/// A `-`, `*` or `+` bullet or a `1.`/`1)` numbered item.
+fn is_list_item(line: &str) -> bool {
+    let rest = line.trim_start();
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = if digits > 0 {
+        match rest[digits..].strip_prefix(['.', ')']) {
+            Some(rest) => rest,
+            None => return false,
+        }
+    } else {
+        match rest.strip_prefix(['-', '*', '+']) {
+            Some(rest) => rest,
+            None => return false,
+        }
+    };
+    rest.is_empty() || rest.starts_with([' ', '\t'])
+}
+
+/// Whether a line after a hard break is still part of the same paragraph, so
+/// the break means something.
+fn continues_paragraph(next: &str) -> bool {
+    let next = next.trim();
+    !next.is_empty() && heading(next).is_none() && fence_marker(next).is_none()
+}
+
+/// `#  Title` as `# Title`, or `None` if the line isn't an ATX heading.
+fn heading(line: &str) -> Option<String> {
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    if hashes == 0 || hashes > 6 {
+        return None;
+    }
+    let rest = &line[hashes..];
+    if rest.is_empty() {
+        return Some(line.to_string());
+    }
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some(format!("{} {}", &line[..hashes], rest.trim_start()))
+}
+
+/// `* item` and `+ item` as `- item`, keeping the indent. `* * *` rules are kept.
+fn bullet(line: &str) -> String {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let is_rule = rest.chars().all(|c| c == '*' || c == ' ');
+    if rest.starts_with(['*', '+']) && rest[1..].starts_with([' ', '\t']) && !is_rule {
+        format!("{}-{}", &line[..indent], &rest[1..])
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blank_lines_collapsed() {
+        let content = "\n\n#  Title\nIntro   \n\n\n\n* one\n+ two\n  * nested\n* * *\n#tag stays\n## Next\ntext\n\n\n";
+        assert_eq!(
+            format_markdown(content),
+            "# Title\n\nIntro\n\n- one\n- two\n  - nested\n* * *\n#tag stays\n\n## Next\n\ntext\n"
+        );
+        assert_eq!(format_markdown("\n\n  \n"), "");
+    }
+
+    #[test]
+    fn test_indented_code_untouched() {
+        let content = "Intro\n\n    fn main() {   \n\n\n    * not a bullet\n\t#not a heading\nAfter\n\n- item\n\n    * continued\n";
+        assert_eq!(
+            format_markdown(content),
+            "Intro\n\n    fn main() {   \n\n\n    * not a bullet\n\t#not a heading\n\nAfter\n\n- item\n\n    - continued\n"
+        );
+    }
+
+    #[test]
+    fn test_hard_line_breaks_kept() {
+        let content = "First line   \nsecond line  \n\nEnd of paragraph  \n# Heading  \nlast  \n";
+        assert_eq!(
+            format_markdown(content),
+            "First line  \nsecond line\n\nEnd of paragraph\n\n# Heading\n\nlast\n"
+        );
+    }
+
+    #[test]
+    fn test_code_blocks_untouched() {
+        let content = "Before\n```rust\nfn main() {   \n\n\n\n* not a bullet\n#not a heading\n}\n```\n~~~~\n```\n+ still code\n~~~~\nAfter  \n";
+        assert_eq!(
+            format_markdown(content),
+            "Before\n```rust\nfn main() {   \n\n\n\n* not a bullet\n#not a heading\n}\n```\n~~~~\n```\n+ still code\n~~~~\nAfter\n"
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use crate::markdown_format::{closes_fence, fence_marker};
+use std::collections::HashMap;
+
+/// Footnote markers become links to `footnote:<n>` in the preview, and each
+/// footnote's back-reference a link to `footnote-ref:<n>`, so clicks come back
+/// to the app instead of opening a browser.
+pub const FOOTNOTE_LINK: &str = "footnote:";
+pub const FOOTNOTE_BACK_LINK: &str = "footnote-ref:";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footnote {
+    pub number: usize,
+    pub text: String,
+}
+
+/// A note's `[ref]: url` link definitions and `[^label]: text` footnotes.
+/// Footnotes are numbered in the order they're first referenced; ones never
+/// referenced are left out.
+#[derive(Debug, Default)]
+pub struct References {
+    links: HashMap<String, String>, // Label to destination, as written after the colon
+    numbers: HashMap<String, usize>,
+    footnotes: Vec<Footnote>,
+}
+
+impl References {
+    pub fn collect(content: &str) -> Self {
+        let mut links = HashMap::new();
+        let mut footnote_text: HashMap<String, String> = HashMap::new();
+        let mut referenced: Vec<String> = Vec::new();
+
+        for line in Lines::new(content) {
+            match line {
+                Line::Footnote(label, text) => {
+                    footnote_text.entry(label).or_insert(text);
+                }
+                Line::LinkDefinition(label, destination) => {
+                    links.entry(label).or_insert(destination);
+                }
+                Line::Text(text) => {
+                    for label in footnote_refs(text) {
+                        if !referenced.contains(&label) {
+                            referenced.push(label);
+                        }
+                    }
+                }
+                Line::Code(_) => {}
+            }
+        }
+
+        let mut numbers = HashMap::new();
+        let mut footnotes = Vec::new();
+        for label in referenced {
+            if let Some(text) = footnote_text.remove(&label) {
+                let number = footnotes.len() + 1;
+                numbers.insert(label, number);
+                footnotes.push(Footnote { number, text });
+            }
+        }
+        Self {
+            links,
+            numbers,
+            footnotes,
+        }
+    }
+
+    pub fn footnotes(&self) -> &[Footnote] {
+        &self.footnotes
+    }
+
+    /// `text`, part or all of the note, as plain Markdown the preview can render:
+    /// definitions removed, reference links written inline and footnote markers as
+    /// superscript links to `FOOTNOTE_LINK`. Code is left alone, and so are
+    /// references to labels that aren't defined.
+    pub fn apply(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for line in Lines::new(text) {
+            match line {
+                Line::Text(line) => out.push_str(&self.rewrite_line(line)),
+                Line::Code(line) => out.push_str(line),
+                // Keep the line break so paragraphs around a definition stay apart
+                Line::Footnote(..) | Line::LinkDefinition(..) => out.push('\n'),
+            }
+        }
+        out
+    }
+
+    /// The numbered list shown under the note, each footnote ending in a link back
+    /// to where it's referenced. `None` without footnotes.
+    pub fn footnote_section(&self) -> Option<String> {
+        if self.footnotes.is_empty() {
+            return None;
+        }
+        let mut section = String::from("---\n\n");
+        for footnote in &self.footnotes {
+            section.push_str(&format!(
+                "{}. {} [↩]({}{})\n",
+                footnote.number, footnote.text, FOOTNOTE_BACK_LINK, footnote.number
+            ));
+        }
+        Some(section)
+    }
+
+    fn rewrite_line(&self, line: &str) -> String {
+        let mut out = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(i) = rest.find(['[', '`']) {
+            out.push_str(&rest[..i]);
+            rest = &rest[i..];
+
+            if rest.starts_with('`') {
+                let len = code_span_len(rest);
+                out.push_str(&rest[..len]);
+                rest = &rest[len..];
+                continue;
+            }
+            // Wiki links are the preview's business
+            if rest.starts_with("[[") {
+                let len = rest.find("]]").map_or(2, |end| end + 2);
+                out.push_str(&rest[..len]);
+                rest = &rest[len..];
+                continue;
+            }
+
+            let Some(close) = rest.find(']') else {
+                break;
+            };
+            let inner = &rest[1..close];
+            let after = &rest[close + 1..];
+
+            if let Some(label) = inner.strip_prefix('^') {
+                if let Some(number) = self.numbers.get(&normalize(label)) {
+                    out.push_str(&format!(
+                        "[{}]({}{})",
+                        superscript(*number),
+                        FOOTNOTE_LINK,
+                        number
+                    ));
+                    rest = after;
+                    continue;
+                }
+            } else if !after.starts_with('(') {
+                // [text][label], [text][] or just [label]
+                let (label, consumed) = match after
+                    .strip_prefix('[')
+                    .and_then(|a| a.find(']').map(|end| (a, end)))
+                {
+                    Some((a, end)) if a[..end].is_empty() => (inner, close + 3),
+                    Some((a, end)) => (&a[..end], close + end + 3),
+                    None => (inner, close + 1),
+                };
+                if let Some(destination) = self.links.get(&normalize(label)) {
+                    out.push_str(&format!("[{}]({})", inner, destination));
+                    rest = &rest[consumed..];
+                    continue;
+                }
+            }
+            out.push('[');
+            rest = &rest[1..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// A line of Markdown, by what it means for references.
+enum Line<'a> {
+    Text(&'a str),
+    Code(&'a str),
+    Footnote(String, String),
+    LinkDefinition(String, String),
+}
+
+/// The lines of some Markdown, line breaks included, with each footnote's
+/// continuation lines folded into its text.
+struct Lines<'a> {
+    lines: std::iter::Peekable<std::str::SplitInclusive<'a, char>>,
+    fence: Option<String>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            lines: text.split_inclusive('\n').peekable(),
+            fence: None,
+        }
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = Line<'a>;
+
+    fn next(&mut self) -> Option<Line<'a>> {
+        let line = self.lines.next()?;
+        if let Some(open) = &self.fence {
+            if closes_fence(line.trim_end(), open) {
+                self.fence = None;
+            }
+            return Some(Line::Code(line));
+        }
+        if let Some(open) = fence_marker(line) {
+            self.fence = Some(open.to_string());
+            return Some(Line::Code(line));
+        }
+        let Some((label, rest)) = definition(line) else {
+            return Some(Line::Text(line));
+        };
+        match label.strip_prefix('^') {
+            Some(label) => {
+                let mut text = rest.trim().to_string();
+                while let Some(next) = self.lines.peek().filter(|next| is_indented(next)) {
+                    text.push(' ');
+                    text.push_str(next.trim());
+                    self.lines.next();
+                }
+                Some(Line::Footnote(normalize(label), text))
+            }
+            None if !rest.trim().is_empty() => Some(Line::LinkDefinition(
+                normalize(label),
+                rest.trim().to_string(),
+            )),
+            None => Some(Line::Text(line)),
+        }
+    }
+}
+
+/// `[label]: rest` with at most three spaces before it, split into its label and rest.
+fn definition(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let inner = trimmed.strip_prefix('[')?;
+    let close = inner.find(']')?;
+    let label = &inner[..close];
+    let rest = inner[close + 1..].strip_prefix(':')?;
+    (!label.trim().is_empty() && label.trim() != "^" && !label.contains('['))
+        .then_some((label, rest))
+}
+
+/// The labels of `[^label]` markers in a line of text, outside code spans.
+fn footnote_refs(line: &str) -> Vec<String> {
+    let mut labels = Vec::new();
+    let mut rest = line;
+    while let Some(i) = rest.find(['[', '`']) {
+        rest = &rest[i..];
+        if rest.starts_with('`') {
+            rest = &rest[code_span_len(rest)..];
+            continue;
+        }
+        match (rest.strip_prefix("[^"), rest.find(']')) {
+            (Some(after), Some(close)) if close > 2 => {
+                labels.push(normalize(&after[..close - 2]));
+                rest = &rest[close + 1..];
+            }
+            _ => rest = &rest[1..],
+        }
+    }
+    labels
+}
+
+/// Length of the code span `text` starts with: a run of backticks up to the same
+/// run closing it, or just the opening run if it's never closed.
+fn code_span_len(text: &str) -> usize {
+    let ticks = text.len() - text.trim_start_matches('`').len();
+    let fence = &text[..ticks];
+    text[ticks..]
+        .find(fence)
+        .map_or(ticks, |end| ticks + end + ticks)
+}
+
+fn is_indented(line: &str) -> bool {
+    (line.starts_with("    ") || line.starts_with('\t')) && !line.trim().is_empty()
+}
+
+/// Labels match ignoring case and runs of whitespace.
+fn normalize(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn superscript(number: usize) -> String {
+    const DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+    number
+        .to_string()
+        .chars()
+        .map(|d| DIGITS[d.to_digit(10).unwrap_or(0) as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_footnotes_numbered_in_reference_order() {
+        let content = "Claim one[^b] and claim two[^a].\n\n\
+                       [^a]: Second source,\n    page 4.\n\
+                       [^b]: First source.\n\
+                       [^unused]: Never cited.\n";
+        let references = References::collect(content);
+        assert_eq!(
+            references.footnotes(),
+            &[
+                Footnote {
+                    number: 1,
+                    text: "First source.".to_string()
+                },
+                Footnote {
+                    number: 2,
+                    text: "Second source, page 4.".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            references.apply(content),
+            "Claim one[¹](footnote:1) and claim two[²](footnote:2).\n\n\n\n\n"
+        );
+        assert_eq!(
+            references.footnote_section().unwrap(),
+            "---\n\n\
+             1. First source. [↩](footnote-ref:1)\n\
+             2. Second source, page 4. [↩](footnote-ref:2)\n"
+        );
+    }
+
+    #[test]
+    fn test_reference_links_written_inline() {
+        let content =
+            "See [the book][Rust Book], [rust][] and [Rust], not [this](x) or [nothing].\n\
+                       `[rust]` and [[Rust]] stay.\n\n\
+                       [rust book]: https://doc.rust-lang.org/book \"The Book\"\n\
+                       [Rust]: <https://rust-lang.org>\n\
+                       ```\n[rust]\n```\n";
+        let references = References::collect(content);
+        assert_eq!(
+            references.apply(content),
+            "See [the book](https://doc.rust-lang.org/book \"The Book\"), \
+             [rust](<https://rust-lang.org>) and [Rust](<https://rust-lang.org>), \
+             not [this](x) or [nothing].\n\
+             `[rust]` and [[Rust]] stay.\n\n\n\n\
+             ```\n[rust]\n```\n"
+        );
+        assert!(references.footnote_section().is_none());
+    }
+}
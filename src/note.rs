@@ -1,14 +1,19 @@
-use chrono::Utc;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
 use crate::tags::NoteTags;
 use crate::encryption::EncryptedData;
+use crate::frontmatter::FrontMatter;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Note {
     pub title: String,
     pub content: String,
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(with = "timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    pub updated_at: DateTime<Utc>,
     pub file_path: String,
     
     // New features
@@ -17,15 +22,26 @@ pub struct Note {
     pub encrypted_data: Option<EncryptedData>,
     pub linked_notes: Vec<(usize, usize)>, // (folder_idx, note_idx)
     pub embedded_images: Vec<String>, // Image paths
+    pub front_matter: Option<FrontMatter>, // Re-written on save when present
+    pub order: Option<usize>, // Position in the folder's manual order, once dragged
+    pub properties: BTreeMap<String, String>, // status, priority, author, ...
+    pub icon: Option<String>, // Emoji shown before the title in the sidebar
+    pub color: Option<[u8; 3]>, // RGB swatch shown in the sidebar
+    #[serde(skip)]
+    pub content_loaded: bool, // False until the body is read; see Storage::ensure_loaded
+    #[serde(skip)]
+    pub disk_mtime: Option<SystemTime>, // File's modification time when last read or written
+    #[serde(skip)]
+    pub saved_hash: Option<u64>, // Hash of the file's text when last read or written
 }
 
 impl Note {
     pub fn new(title: String, file_path: String) -> Self {
-        let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let now = Utc::now();
         Self {
             title,
             content: String::new(),
-            created_at: now.clone(),
+            created_at: now,
             updated_at: now,
             file_path,
             tags: NoteTags::new(),
@@ -33,11 +49,37 @@ impl Note {
             encrypted_data: None,
             linked_notes: Vec::new(),
             embedded_images: Vec::new(),
+            front_matter: None,
+            order: None,
+            properties: BTreeMap::new(),
+            icon: None,
+            color: None,
+            content_loaded: true,
+            disk_mtime: None,
+            saved_hash: None,
         }
     }
     
+    /// Take the editor's text as the note body. An encrypted note's `content` is only
+    /// a placeholder, so it refuses; its ciphertext changes only once it is unlocked.
+    pub fn set_content(&mut self, content: &str) -> bool {
+        if self.is_encrypted {
+            return false;
+        }
+        self.content = content.to_string();
+        true
+    }
+    
     pub fn update_timestamp(&mut self) {
-        self.updated_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        self.updated_at = Utc::now();
+    }
+    
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+    
+    pub fn updated(&self) -> DateTime<Utc> {
+        self.updated_at
     }
     
     pub fn from_file(file_path: String, title: String, content: String, metadata: NoteMetadata) -> Self {
@@ -52,6 +94,14 @@ impl Note {
             encrypted_data: metadata.encrypted_data,
             linked_notes: metadata.linked_notes,
             embedded_images: metadata.embedded_images,
+            front_matter: None,
+            order: metadata.order,
+            properties: metadata.properties,
+            icon: metadata.icon,
+            color: metadata.color,
+            content_loaded: true,
+            disk_mtime: None,
+            saved_hash: None,
         }
     }
     
@@ -59,14 +109,17 @@ impl Note {
         self.tags.add_tag(tag_index);
     }
     
+    #[allow(dead_code)]
     pub fn remove_tag(&mut self, tag_index: usize) {
         self.tags.remove_tag(tag_index);
     }
     
+    #[allow(dead_code)]
     pub fn add_image(&mut self, image_path: String) {
         self.embedded_images.push(image_path);
     }
     
+    #[allow(dead_code)]
     pub fn link_to(&mut self, target: (usize, usize)) {
         if !self.linked_notes.contains(&target) {
             self.linked_notes.push(target);
@@ -76,47 +129,74 @@ impl Note {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NoteMetadata {
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(with = "timestamp", default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "timestamp", default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
     pub tags: NoteTags,
     pub is_encrypted: bool,
     pub encrypted_data: Option<EncryptedData>,
     pub linked_notes: Vec<(usize, usize)>,
     pub embedded_images: Vec<String>,
+    #[serde(default)]
+    pub order: Option<usize>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[u8; 3]>,
 }
 
 impl NoteMetadata {
     pub fn new() -> Self {
-        let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let now = Utc::now();
         Self {
-            created_at: now.clone(),
+            created_at: now,
             updated_at: now,
             tags: NoteTags::new(),
             is_encrypted: false,
             encrypted_data: None,
             linked_notes: Vec::new(),
             embedded_images: Vec::new(),
+            order: None,
+            properties: BTreeMap::new(),
+            icon: None,
+            color: None,
         }
     }
     
     pub fn from_note(note: &Note) -> Self {
         Self {
-            created_at: note.created_at.clone(),
-            updated_at: note.updated_at.clone(),
+            created_at: note.created_at,
+            updated_at: note.updated_at,
             tags: note.tags.clone(),
             is_encrypted: note.is_encrypted,
             encrypted_data: note.encrypted_data.clone(),
             linked_notes: note.linked_notes.clone(),
             embedded_images: note.embedded_images.clone(),
+            order: note.order,
+            properties: note.properties.clone(),
+            icon: note.icon.clone(),
+            color: note.color,
         }
     }
 }
 
+/// Settings for a whole folder, kept in a hidden file inside it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FolderMetadata {
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Folder {
     pub name: String,
     pub notes: Vec<Note>,
     pub path: String,
+    #[serde(default)]
+    pub encrypted: bool, // Notes are encrypted with the session password when saved
 }
 
 impl Folder {
@@ -125,11 +205,472 @@ impl Folder {
             name,
             notes: Vec::new(),
             path,
+            encrypted: false,
         }
     }
     
     pub fn add_note(&mut self, note: Note) {
         self.notes.push(note);
     }
+    
+    /// Indices into `notes` in display order. The notes themselves stay where they
+    /// are, so `(folder_idx, note_idx)` pairs held elsewhere keep pointing at the same note.
+    pub fn sorted_note_indices(&self, order: SortOrder) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.notes.len()).collect();
+        indices.sort_by(|&a, &b| {
+            let (a, b) = (&self.notes[a], &self.notes[b]);
+            match order {
+                SortOrder::TitleAsc => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+                SortOrder::TitleDesc => b.title.to_lowercase().cmp(&a.title.to_lowercase()),
+                SortOrder::CreatedNewest => b.created_at.cmp(&a.created_at),
+                SortOrder::UpdatedNewest => b.updated_at.cmp(&a.updated_at),
+                // Notes never dragged go after the arranged ones, alphabetically
+                SortOrder::Manual => match (a.order, b.order) {
+                    (Some(x), Some(y)) => x.cmp(&y),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+                },
+            }
+        });
+        indices
+    }
+}
+
+/// Notes in `folders[folder_idx]`. With `include_nested`, notes in folders whose
+/// directory lies inside it are counted too. Storage only loads top-level folders
+/// today, so the two agree until nested folders are loaded.
+pub fn folder_note_count(folders: &[Folder], folder_idx: usize, include_nested: bool) -> usize {
+    let Some(folder) = folders.get(folder_idx) else {
+        return 0;
+    };
+    if !include_nested {
+        return folder.notes.len();
+    }
+    
+    let root = std::path::Path::new(&folder.path);
+    folders
+        .iter()
+        .filter(|f| std::path::Path::new(&f.path).starts_with(root))
+        .map(|f| f.notes.len())
+        .sum()
+}
+
+/// `(title, content)` of each of `notes`, in the order given, for exporting a
+/// selection. Bodies must already be loaded; encrypted and missing notes are left out.
+pub fn title_content_pairs(folders: &[Folder], notes: &[(usize, usize)]) -> Vec<(String, String)> {
+    notes
+        .iter()
+        .filter_map(|&(folder_idx, note_idx)| folders.get(folder_idx)?.notes.get(note_idx))
+        .filter(|note| !note.is_encrypted)
+        .map(|note| (note.title.clone(), note.content.clone()))
+        .collect()
+}
+
+/// Path from the outermost folder down to a note in `folders[folder_idx]`, for the
+/// editor header. Each folder whose directory contains the note's folder comes first,
+/// shallowest first, as `(name, Some(folder_idx))`; the note's title ends it with `None`.
+pub fn breadcrumb(folders: &[Folder], folder_idx: usize, note_title: &str) -> Vec<(String, Option<usize>)> {
+    let mut crumbs = Vec::new();
+    if let Some(folder) = folders.get(folder_idx) {
+        let path = std::path::Path::new(&folder.path);
+        let mut ancestors: Vec<(usize, usize)> = folders
+            .iter()
+            .enumerate()
+            .map(|(idx, f)| (idx, std::path::Path::new(&f.path)))
+            .filter(|(_, p)| path.starts_with(p))
+            .map(|(idx, p)| (p.components().count(), idx))
+            .collect();
+        ancestors.sort();
+        crumbs.extend(ancestors.into_iter().map(|(_, idx)| (folders[idx].name.clone(), Some(idx))));
+    }
+    crumbs.push((note_title.to_string(), None));
+    crumbs
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    TitleAsc,
+    TitleDesc,
+    CreatedNewest,
+    UpdatedNewest,
+    Manual,
 }
 
+impl SortOrder {
+    pub const ALL: [SortOrder; 5] = [
+        SortOrder::TitleAsc,
+        SortOrder::TitleDesc,
+        SortOrder::CreatedNewest,
+        SortOrder::UpdatedNewest,
+        SortOrder::Manual,
+    ];
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortOrder::TitleAsc => "Title (A-Z)",
+            SortOrder::TitleDesc => "Title (Z-A)",
+            SortOrder::CreatedNewest => "Newest created",
+            SortOrder::UpdatedNewest => "Recently updated",
+            SortOrder::Manual => "Manual (drag to reorder)",
+        }
+    }
+}
+
+// How timestamps were written before metadata switched to RFC 3339, always in UTC
+const LEGACY_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Parse a stored timestamp: RFC 3339, or the older `2024-01-31 09:00:00` form.
+pub fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw)
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(raw, LEGACY_TIMESTAMP_FORMAT)
+                .ok()
+                .map(|time| time.and_utc())
+        })
+}
+
+/// Timestamps are written as RFC 3339 and read in either format, so older
+/// `.meta` files migrate the next time their note is saved. A value that
+/// isn't a timestamp at all reads as now rather than failing the whole file,
+/// which would lose the tags and encryption stored beside it.
+pub(crate) mod timestamp {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+    
+    pub fn serialize<S: Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&time.to_rfc3339())
+    }
+    
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let raw = serde_json::Value::deserialize(deserializer)?;
+        Ok(raw.as_str().and_then(super::parse_timestamp).unwrap_or_else(Utc::now))
+    }
+}
+
+/// "just now", "5 minutes ago", "3 hours ago", "yesterday", "4 days ago",
+/// "3 weeks ago", or the date once it's more than a month back.
+pub fn format_relative(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now.signed_duration_since(time);
+    let plural = |n: i64, unit: &str| format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" });
+    
+    if elapsed.num_minutes() < 1 {
+        "just now".to_string()
+    } else if elapsed.num_hours() < 1 {
+        plural(elapsed.num_minutes(), "minute")
+    } else if elapsed.num_days() < 1 {
+        plural(elapsed.num_hours(), "hour")
+    } else if elapsed.num_days() == 1 {
+        "yesterday".to_string()
+    } else if elapsed.num_days() < 7 {
+        plural(elapsed.num_days(), "day")
+    } else if elapsed.num_days() < 30 {
+        plural(elapsed.num_weeks(), "week")
+    } else {
+        time.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string()
+    }
+}
+
+/// How long ago `since` was, relative to now.
+pub fn humanize(since: DateTime<Utc>) -> String {
+    format_relative(since, Utc::now())
+}
+
+/// A timestamp in local time, for hover text next to a relative one.
+pub fn format_local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// Number of words in `content`. Tokens made only of punctuation (Markdown
+/// markers like `#`, `-`, `**` or `---`) are not counted.
+pub fn word_count(content: &str) -> usize {
+    content
+        .split_whitespace()
+        .filter(|token| token.chars().any(|c| c.is_alphanumeric()))
+        .count()
+}
+
+/// Estimated reading time at 200 words per minute.
+pub fn reading_time_minutes(content: &str) -> u32 {
+    (word_count(content) / 200) as u32
+}
+
+/// ATX headings (`#` to `######`) as `(level, text, byte offset of the line)`, in
+/// document order. Lines inside fenced code blocks are skipped.
+pub fn extract_headings(content: &str) -> Vec<(usize, String, usize)> {
+    let mut headings = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut offset = 0;
+    
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let line_offset = offset;
+        offset += line.len();
+        
+        // Toggle on ``` / ~~~, closing only with the same marker
+        let marker = if trimmed.starts_with("```") {
+            Some("```")
+        } else if trimmed.starts_with("~~~") {
+            Some("~~~")
+        } else {
+            None
+        };
+        match (fence, marker) {
+            (None, Some(m)) => {
+                fence = Some(m);
+                continue;
+            }
+            (Some(open), Some(m)) if open == m => {
+                fence = None;
+                continue;
+            }
+            (Some(_), _) => continue,
+            _ => {}
+        }
+        
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        if level == 0 || level > 6 {
+            continue;
+        }
+        let rest = &trimmed[level..];
+        if !rest.is_empty() && !rest.starts_with([' ', '\t', '\r', '\n']) {
+            continue;
+        }
+        
+        let text = rest.trim().trim_end_matches('#').trim_end();
+        if !text.is_empty() {
+            headings.push((level, text.to_string(), line_offset));
+        }
+    }
+    
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_word_count_empty() {
+        assert_eq!(word_count(""), 0);
+        assert_eq!(word_count("   \n\t "), 0);
+        assert_eq!(reading_time_minutes(""), 0);
+    }
+    
+    #[test]
+    fn test_word_count_paragraph() {
+        let paragraph = "The quick brown fox jumps over the lazy dog.";
+        assert_eq!(word_count(paragraph), 9);
+        
+        let long = "word ".repeat(450);
+        assert_eq!(reading_time_minutes(&long), 2);
+    }
+    
+    #[test]
+    fn test_title_content_pairs_keep_selection_order() {
+        let mut folders = vec![
+            Folder::new("Work".to_string(), "notes/Work".to_string()),
+            Folder::new("Home".to_string(), "notes/Home".to_string()),
+        ];
+        for (folder_idx, title, content) in [(0, "Plan", "Ship it"), (0, "Secret", "shh"), (1, "Groceries", "Milk\nEggs")] {
+            let mut note = Note::new(title.to_string(), format!("{}/{}.md", folders[folder_idx].path, title));
+            note.content = content.to_string();
+            note.is_encrypted = title == "Secret";
+            folders[folder_idx].add_note(note);
+        }
+        
+        let pairs = title_content_pairs(&folders, &[(1, 0), (0, 1), (0, 0), (3, 0)]);
+        assert_eq!(pairs, vec![
+            ("Groceries".to_string(), "Milk\nEggs".to_string()),
+            ("Plan".to_string(), "Ship it".to_string()),
+        ]);
+    }
+    
+    #[test]
+    fn test_folder_note_count() {
+        let mut folders = vec![
+            Folder::new("Projects".to_string(), "notes/Projects".to_string()),
+            Folder::new("Rust".to_string(), "notes/Projects/Rust".to_string()),
+            Folder::new("Projects Archive".to_string(), "notes/Projects Archive".to_string()),
+            Folder::new("Empty".to_string(), "notes/Empty".to_string()),
+        ];
+        for (folder_idx, count) in [(0, 2), (1, 3), (2, 1)] {
+            for i in 0..count {
+                let path = format!("{}/note-{}.md", folders[folder_idx].path, i);
+                folders[folder_idx].add_note(Note::new(format!("note-{}", i), path));
+            }
+        }
+        
+        assert_eq!(folder_note_count(&folders, 0, false), 2);
+        // Sibling with a shared name prefix isn't a child
+        assert_eq!(folder_note_count(&folders, 0, true), 5);
+        assert_eq!(folder_note_count(&folders, 1, true), 3);
+        assert_eq!(folder_note_count(&folders, 3, true), 0);
+        assert_eq!(folder_note_count(&folders, 9, false), 0);
+    }
+    
+    #[test]
+    fn test_breadcrumb() {
+        let folders = vec![
+            Folder::new("Q1".to_string(), "notes/Projects/2024/Q1".to_string()),
+            Folder::new("Projects".to_string(), "notes/Projects".to_string()),
+            Folder::new("Projects Archive".to_string(), "notes/Projects Archive".to_string()),
+            Folder::new("2024".to_string(), "notes/Projects/2024".to_string()),
+        ];
+        
+        let crumbs = breadcrumb(&folders, 0, "Kickoff");
+        assert_eq!(
+            crumbs,
+            vec![
+                ("Projects".to_string(), Some(1)),
+                ("2024".to_string(), Some(3)),
+                ("Q1".to_string(), Some(0)),
+                ("Kickoff".to_string(), None),
+            ]
+        );
+        
+        // A flat folder is just Folder / Note; the sibling sharing a prefix isn't a parent
+        assert_eq!(
+            breadcrumb(&folders, 2, "Old"),
+            vec![("Projects Archive".to_string(), Some(2)), ("Old".to_string(), None)]
+        );
+        assert_eq!(breadcrumb(&folders, 9, "Lost"), vec![("Lost".to_string(), None)]);
+    }
+    
+    fn sort_fixture() -> Folder {
+        let mut folder = Folder::new("Work".to_string(), "Work".to_string());
+        for (title, created, updated) in [
+            ("beta", "2024-01-02 09:00:00", "2024-03-01 09:00:00"),
+            ("Alpha", "2024-01-03 09:00:00", "2024-01-03 09:00:00"),
+            ("gamma", "2024-01-01 09:00:00", "2024-02-01 09:00:00"),
+        ] {
+            let mut note = Note::new(title.to_string(), format!("Work/{}.md", title));
+            note.created_at = parse_timestamp(created).unwrap();
+            note.updated_at = parse_timestamp(updated).unwrap();
+            folder.add_note(note);
+        }
+        folder
+    }
+    
+    #[test]
+    fn test_parse_old_and_new_timestamps() {
+        let legacy = parse_timestamp("2024-01-31 09:15:00").unwrap();
+        let rfc3339 = parse_timestamp("2024-01-31T11:15:00+02:00").unwrap();
+        assert_eq!(legacy, rfc3339);
+        assert_eq!(legacy.to_rfc3339(), "2024-01-31T09:15:00+00:00");
+        assert!(parse_timestamp("last tuesday").is_none());
+        
+        // Old metadata loads and is written back in the new format
+        let old = r#"{"created_at":"2024-01-31 09:15:00","updated_at":"2024-02-01 10:00:00","tags":{"tag_indices":[]},"is_encrypted":false,"encrypted_data":null,"linked_notes":[],"embedded_images":[]}"#;
+        let metadata: NoteMetadata = serde_json::from_str(old).unwrap();
+        assert_eq!(metadata.created_at, legacy);
+        let saved = serde_json::to_string(&metadata).unwrap();
+        assert!(saved.contains(r#""updated_at":"2024-02-01T10:00:00+00:00""#));
+        let reloaded: NoteMetadata = serde_json::from_str(&saved).unwrap();
+        assert_eq!(reloaded.updated_at, metadata.updated_at);
+        assert!(reloaded.properties.is_empty());
+        
+        // One unreadable or missing timestamp keeps the rest of the metadata
+        let damaged = r#"{"created_at":"last tuesday","tags":{"tag_indices":[2]},"is_encrypted":true,"encrypted_data":null,"linked_notes":[],"embedded_images":[]}"#;
+        let before = Utc::now();
+        let metadata: NoteMetadata = serde_json::from_str(damaged).unwrap();
+        assert!(metadata.created_at >= before && metadata.updated_at >= before);
+        assert!(metadata.is_encrypted);
+        assert!(metadata.tags.tag_indices.contains(&2));
+    }
+    
+    #[test]
+    fn test_properties_in_metadata() {
+        let mut note = Note::new("Spec".to_string(), "Spec.md".to_string());
+        note.properties.insert("status".to_string(), "review".to_string());
+        note.properties.insert("author".to_string(), "Sam".to_string());
+        
+        let json = serde_json::to_string(&NoteMetadata::from_note(&note)).unwrap();
+        assert!(json.contains(r#""properties":{"author":"Sam","status":"review"}"#));
+        let metadata: NoteMetadata = serde_json::from_str(&json).unwrap();
+        let loaded = Note::from_file("Spec.md".to_string(), "Spec".to_string(), String::new(), metadata);
+        assert_eq!(loaded.properties, note.properties);
+    }
+    
+    #[test]
+    fn test_icon_and_color_in_metadata() {
+        let mut note = Note::new("Launch".to_string(), "Launch.md".to_string());
+        note.icon = Some("🚀".to_string());
+        note.color = Some([220, 60, 60]);
+        
+        let json = serde_json::to_string(&NoteMetadata::from_note(&note)).unwrap();
+        assert!(json.contains(r#""color":[220,60,60]"#));
+        let metadata: NoteMetadata = serde_json::from_str(&json).unwrap();
+        let loaded = Note::from_file("Launch.md".to_string(), "Launch".to_string(), String::new(), metadata);
+        assert_eq!(loaded.icon.as_deref(), Some("🚀"));
+        assert_eq!(loaded.color, Some([220, 60, 60]));
+        
+        // Notes without either write neither
+        let plain = serde_json::to_string(&NoteMetadata::new()).unwrap();
+        assert!(!plain.contains("icon") && !plain.contains("color"));
+    }
+    
+    #[test]
+    fn test_format_relative_time() {
+        let now = parse_timestamp("2024-06-15T12:00:00Z").unwrap();
+        let ago = |seconds: i64| now - chrono::Duration::seconds(seconds);
+        assert_eq!(format_relative(ago(20), now), "just now");
+        assert_eq!(format_relative(ago(60), now), "1 minute ago");
+        assert_eq!(format_relative(ago(45 * 60), now), "45 minutes ago");
+        assert_eq!(format_relative(ago(3 * 3600), now), "3 hours ago");
+        assert_eq!(format_relative(ago(30 * 3600), now), "yesterday");
+        assert_eq!(format_relative(ago(4 * 86400), now), "4 days ago");
+        assert_eq!(format_relative(ago(7 * 86400), now), "1 week ago");
+        assert_eq!(format_relative(ago(23 * 86400), now), "3 weeks ago");
+        assert_eq!(format_relative(ago(90 * 86400), now).len(), "2024-03-17".len());
+        // Clock skew puts a timestamp slightly in the future
+        assert_eq!(format_relative(ago(-5), now), "just now");
+        
+        assert_eq!(humanize(Utc::now() - chrono::Duration::hours(2)), "2 hours ago");
+    }
+    
+    #[test]
+    fn test_sort_by_title() {
+        let folder = sort_fixture();
+        assert_eq!(folder.sorted_note_indices(SortOrder::TitleAsc), vec![1, 0, 2]);
+        assert_eq!(folder.sorted_note_indices(SortOrder::TitleDesc), vec![2, 0, 1]);
+    }
+    
+    #[test]
+    fn test_sort_by_created() {
+        let folder = sort_fixture();
+        assert_eq!(folder.sorted_note_indices(SortOrder::CreatedNewest), vec![1, 0, 2]);
+    }
+    
+    #[test]
+    fn test_sort_by_updated() {
+        let folder = sort_fixture();
+        assert_eq!(folder.sorted_note_indices(SortOrder::UpdatedNewest), vec![0, 2, 1]);
+    }
+    
+    #[test]
+    fn test_extract_nested_headings() {
+        let content = "# Design\nintro\n## Goals\n### Non-goals ###\n## API\n#hashtag\n";
+        let headings = extract_headings(content);
+        
+        let outline: Vec<_> = headings.iter().map(|(level, text, _)| (*level, text.as_str())).collect();
+        assert_eq!(outline, vec![(1, "Design"), (2, "Goals"), (3, "Non-goals"), (2, "API")]);
+        assert_eq!(&content[headings[1].2..headings[2].2], "## Goals\n");
+    }
+    
+    #[test]
+    fn test_extract_headings_skips_code_fences() {
+        let content = "# Real\n```bash\n# just a comment\n```\n~~~\n## also code\n```\nstill code\n~~~\n## After\n";
+        let headings: Vec<_> = extract_headings(content).into_iter().map(|(_, text, _)| text).collect();
+        assert_eq!(headings, vec!["Real", "After"]);
+    }
+    
+    #[test]
+    fn test_word_count_ignores_markdown_syntax() {
+        let content = "# Title\n\n- first item\n- **second** item\n\n---\n\n> quoted text";
+        assert_eq!(word_count(content), 7);
+    }
+}
@@ -3,6 +3,20 @@ use std::fs::File;
 use std::io::BufWriter;
 use std::path::Path;
 
+// A4, in millimetres
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const TOP: f32 = 270.0;
+const LINE_HEIGHT: f32 = 5.0;
+// Characters of 10pt Helvetica that fit between the margins, roughly
+const WRAP_CHARS: usize = 95;
+// Body lines on a note's first page, below its title, and on the pages after
+const FIRST_PAGE_ROWS: usize = 48;
+const BODY_ROWS: usize = 50;
+const CONTENTS_ROWS: usize = 35;
+const CONTENTS_LINE_HEIGHT: f32 = 7.0;
+
 pub struct PdfExporter;
 
 impl PdfExporter {
@@ -52,47 +66,56 @@ impl PdfExporter {
         Ok(())
     }
     
+    /// Write `notes` (title, content) into one PDF, in order: a contents page listing
+    /// each note with its page number, then every note starting on a page of its own
+    /// and running over as many pages as it needs. Each note is also a bookmark.
     pub fn export_multiple_notes(
         notes: &[(String, String)], // (title, content)
         output_path: &Path,
     ) -> Result<(), String> {
-        let (doc, page1, layer1) = PdfDocument::new(
+        let (doc, contents_page, contents_layer) = PdfDocument::new(
             "Notes Collection",
-            Mm(210.0),
-            Mm(297.0),
+            Mm(PAGE_WIDTH),
+            Mm(PAGE_HEIGHT),
             "Layer 1"
         );
         
         let font = doc.add_builtin_font(BuiltinFont::Helvetica)
             .map_err(|e| format!("Font error: {}", e))?;
         
-        let mut current_page = page1;
-        let mut y_position = 270.0;
+        let bodies: Vec<Vec<String>> = notes.iter().map(|(_, content)| wrap_lines(content)).collect();
+        let first_pages = first_pages(&bodies);
         
-        for (title, content) in notes {
-            let current_layer = doc.get_page(current_page).get_layer(layer1);
-            
-            // Check if we need a new page
-            if y_position < 30.0 {
-                let (new_page, _) = doc.add_page(Mm(210.0), Mm(297.0), "Layer 1");
-                current_page = new_page;
-                y_position = 270.0;
+        // Contents
+        let mut layer = doc.get_page(contents_page).get_layer(contents_layer);
+        layer.use_text("Contents", 20.0, Mm(MARGIN), Mm(TOP), &font);
+        for (i, (title, _)) in notes.iter().enumerate() {
+            let row = i % CONTENTS_ROWS;
+            if i > 0 && row == 0 {
+                let (page, page_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+                layer = doc.get_page(page).get_layer(page_layer);
             }
-            
-            // Write title
-            current_layer.use_text(title, 16.0, Mm(20.0), Mm(y_position), &font);
-            y_position -= 10.0;
-            
-            // Write content (simplified)
-            for line in content.lines().take(20) {
-                if y_position < 20.0 {
-                    break;
+            let y = TOP - 15.0 - row as f32 * CONTENTS_LINE_HEIGHT;
+            layer.use_text(title, 12.0, Mm(MARGIN), Mm(y), &font);
+            layer.use_text(first_pages[i].to_string(), 12.0, Mm(PAGE_WIDTH - MARGIN - 10.0), Mm(y), &font);
+        }
+        
+        // Notes
+        for ((title, _), lines) in notes.iter().zip(&bodies) {
+            for (page_no, page_lines) in paginate(lines).into_iter().enumerate() {
+                let (page, page_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+                let layer = doc.get_page(page).get_layer(page_layer);
+                let mut y = TOP;
+                if page_no == 0 {
+                    doc.add_bookmark(title.clone(), page);
+                    layer.use_text(title, 16.0, Mm(MARGIN), Mm(y), &font);
+                    y -= 10.0;
+                }
+                for line in page_lines {
+                    layer.use_text(line, 10.0, Mm(MARGIN), Mm(y), &font);
+                    y -= LINE_HEIGHT;
                 }
-                current_layer.use_text(line, 10.0, Mm(20.0), Mm(y_position), &font);
-                y_position -= 5.0;
             }
-            
-            y_position -= 10.0; // Space between notes
         }
         
         let file = File::create(output_path)
@@ -104,3 +127,70 @@ impl PdfExporter {
         Ok(())
     }
 }
+
+/// The lines of `content`, with long ones broken at spaces to fit the page width.
+/// A word too long for a line of its own is split.
+fn wrap_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            let mut word = word.to_string();
+            while word.chars().count() > WRAP_CHARS {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                let split = word.char_indices().nth(WRAP_CHARS).map_or(word.len(), |(i, _)| i);
+                lines.push(word[..split].to_string());
+                word = word[split..].to_string();
+            }
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > WRAP_CHARS {
+                lines.push(std::mem::take(&mut current));
+            } else if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&word);
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+/// A note's lines split into pages. Even an empty note gets one page for its title.
+fn paginate(lines: &[String]) -> Vec<&[String]> {
+    let first = lines.len().min(FIRST_PAGE_ROWS);
+    let mut pages = vec![&lines[..first]];
+    pages.extend(lines[first..].chunks(BODY_ROWS));
+    pages
+}
+
+/// The page each note starts on, counting from 1 and after the contents pages.
+fn first_pages(bodies: &[Vec<String>]) -> Vec<usize> {
+    let mut page = bodies.len().div_ceil(CONTENTS_ROWS).max(1) + 1;
+    bodies
+        .iter()
+        .map(|lines| {
+            let first = page;
+            page += paginate(lines).len();
+            first
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_contents_page_numbers() {
+        let lines = |n: usize| vec!["line".to_string(); n];
+        // 120 lines take a first page of 48, then 50 and 22
+        assert_eq!(first_pages(&[lines(0), lines(120), lines(10)]), vec![2, 3, 6]);
+        assert_eq!(first_pages(&vec![lines(1); CONTENTS_ROWS + 1])[0], 3);
+        
+        let long = format!("{} {}", "word ".repeat(30).trim(), "x".repeat(200));
+        let wrapped = wrap_lines(&long);
+        assert!(wrapped.iter().all(|line| line.chars().count() <= WRAP_CHARS));
+        assert_eq!(wrapped.concat().replace(' ', ""), long.replace(' ', ""));
+    }
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const DEFAULT_CAPACITY: usize = 10;
+
+/// Recently opened notes, most recent first. Entries are note file paths rather
+/// than `(folder_idx, note_idx)`, which shift whenever notes are added or removed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentNotes {
+    entries: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Default for RecentNotes {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl RecentNotes {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Move `file_path` to the front, dropping the oldest entry when full.
+    pub fn record(&mut self, file_path: &str) {
+        self.remove(file_path);
+        self.entries.push_front(file_path.to_string());
+        self.entries.truncate(self.capacity);
+    }
+
+    pub fn remove(&mut self, file_path: &str) {
+        self.entries.retain(|p| p != file_path);
+    }
+
+    /// Drop entries whose note no longer exists.
+    pub fn retain(&mut self, keep: impl FnMut(&String) -> bool) {
+        self.entries.retain(keep);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_order_and_dedup() {
+        let mut recent = RecentNotes::new(3);
+        recent.record("a.md");
+        recent.record("b.md");
+        recent.record("c.md");
+        recent.record("a.md");
+        assert_eq!(
+            recent.iter().collect::<Vec<_>>(),
+            vec!["a.md", "c.md", "b.md"]
+        );
+
+        recent.record("d.md");
+        assert_eq!(
+            recent.iter().collect::<Vec<_>>(),
+            vec!["d.md", "a.md", "c.md"]
+        );
+
+        recent.remove("a.md");
+        assert_eq!(recent.iter().collect::<Vec<_>>(), vec!["d.md", "c.md"]);
+    }
+
+    #[test]
+    fn test_recent_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recent.json");
+
+        let mut recent = RecentNotes::default();
+        recent.record("Work/Plan.md");
+        recent.record("Home/List.md");
+        recent.save(&path).unwrap();
+
+        assert_eq!(RecentNotes::load(&path).unwrap(), recent);
+    }
+}
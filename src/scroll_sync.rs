@@ -0,0 +1,180 @@
+/// Where the lines of a note are in a scrolled view of it: pairs of a source line
+/// and the vertical offset it starts at, in the view's content coordinates.
+/// Between two anchors, lines are taken to be spread evenly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrollMap {
+    anchors: Vec<(f32, f32)>, // (line, offset), both increasing
+}
+
+impl ScrollMap {
+    /// Anchors in any order. Where two share a line the first is kept, and one
+    /// that would put a later line above an earlier one is dropped.
+    pub fn new(mut anchors: Vec<(f32, f32)>) -> Self {
+        anchors.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut kept: Vec<(f32, f32)> = Vec::with_capacity(anchors.len());
+        for (line, offset) in anchors {
+            match kept.last() {
+                Some(&(last_line, last_offset)) if line <= last_line || offset < last_offset => {}
+                _ => kept.push((line, offset)),
+            }
+        }
+        Self { anchors: kept }
+    }
+
+    /// The offset `line` starts at; lines past either end are clamped to it.
+    pub fn offset_for_line(&self, line: f32) -> f32 {
+        interpolate(&self.anchors, line, |&(line, offset)| (line, offset))
+    }
+
+    /// The line, fractional, at `offset`.
+    pub fn line_for_offset(&self, offset: f32) -> f32 {
+        interpolate(&self.anchors, offset, |&(line, offset)| (offset, line))
+    }
+}
+
+/// `x` mapped through the piecewise-linear function through `points`, given as
+/// `(x, y)` by `axes`.
+fn interpolate(points: &[(f32, f32)], x: f32, axes: impl Fn(&(f32, f32)) -> (f32, f32)) -> f32 {
+    let Some(first) = points.first().map(&axes) else {
+        return 0.0;
+    };
+    if x <= first.0 {
+        return first.1;
+    }
+    for pair in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (axes(&pair[0]), axes(&pair[1]));
+        if x <= x1 {
+            if x1 - x0 <= f32::EPSILON {
+                return y0;
+            }
+            return y0 + (x - x0) / (x1 - x0) * (y1 - y0);
+        }
+    }
+    points.last().map(&axes).map_or(0.0, |(_, y)| y)
+}
+
+/// One side of a split editor as it was drawn this frame.
+pub struct Pane {
+    pub map: ScrollMap,
+    pub offset: f32,
+    /// Furthest it can scroll: content height less the visible height.
+    pub max_offset: f32,
+}
+
+/// Keeps the editor and preview of a split pane scrolled to the same place.
+/// Whichever side the user scrolls leads, and the other follows on the next frame.
+#[derive(Debug, Default)]
+pub struct ScrollSync {
+    last_offsets: Option<(f32, f32)>, // (editor, preview) as drawn last frame, or as asked for
+    editor_target: Option<f32>,
+    preview_target: Option<f32>,
+}
+
+/// Scrolling by less than this, in points, is rounding rather than the user.
+const SCROLL_TOLERANCE: f32 = 0.5;
+
+impl ScrollSync {
+    /// Where to scroll the editor to this frame, if it should follow the preview.
+    pub fn take_editor_target(&mut self) -> Option<f32> {
+        self.editor_target.take()
+    }
+
+    pub fn take_preview_target(&mut self) -> Option<f32> {
+        self.preview_target.take()
+    }
+
+    /// Compare both sides with last frame and line up the one the user didn't
+    /// scroll. Returns whether a side has to move, so another frame is needed.
+    pub fn update(&mut self, editor: &Pane, preview: &Pane) -> bool {
+        let Some((last_editor, last_preview)) = self.last_offsets else {
+            self.last_offsets = Some((editor.offset, preview.offset));
+            return false;
+        };
+        let follow = |from: &Pane, to: &Pane| {
+            let line = from.map.line_for_offset(from.offset);
+            to.map
+                .offset_for_line(line)
+                .clamp(0.0, to.max_offset.max(0.0))
+        };
+
+        let mut offsets = (editor.offset, preview.offset);
+        if (editor.offset - last_editor).abs() > SCROLL_TOLERANCE {
+            let target = follow(editor, preview);
+            if (target - preview.offset).abs() > SCROLL_TOLERANCE {
+                self.preview_target = Some(target);
+                offsets.1 = target;
+            }
+        } else if (preview.offset - last_preview).abs() > SCROLL_TOLERANCE {
+            let target = follow(preview, editor);
+            if (target - editor.offset).abs() > SCROLL_TOLERANCE {
+                self.editor_target = Some(target);
+                offsets.0 = target;
+            }
+        }
+        self.last_offsets = Some(offsets);
+        self.editor_target.is_some() || self.preview_target.is_some()
+    }
+}
+
+/// Anchors for a text editor's laid-out text: where each line's first row is,
+/// with `top` the offset of the text's top, and the end of the last line.
+pub fn galley_line_anchors(galley: &egui::Galley, top: f32) -> Vec<(f32, f32)> {
+    let mut anchors = Vec::new();
+    let mut line = 0;
+    let mut line_start = true;
+    for row in &galley.rows {
+        if line_start {
+            anchors.push((line as f32, top + row.rect.min.y));
+        }
+        line_start = row.ends_with_newline;
+        if line_start {
+            line += 1;
+        }
+    }
+    anchors.push(((line + 1) as f32, top + galley.rect.max.y));
+    anchors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_position_mapping_between_editor_and_preview() {
+        // 40 lines, 20pt each in the editor; the preview squeezes the first 10
+        // lines into 50pt and spreads the rest over 600pt
+        let editor = ScrollMap::new(
+            (0..=40)
+                .map(|line| (line as f32, line as f32 * 20.0))
+                .collect(),
+        );
+        let preview = ScrollMap::new(vec![(40.0, 650.0), (0.0, 0.0), (10.0, 50.0), (10.0, 80.0)]);
+
+        assert_eq!(editor.line_for_offset(200.0), 10.0);
+        assert_eq!(preview.offset_for_line(10.0), 50.0);
+        assert_eq!(preview.offset_for_line(5.0), 25.0);
+        assert_eq!(preview.offset_for_line(25.0), 350.0);
+        assert_eq!(preview.line_for_offset(350.0), 25.0);
+        // Past either end is clamped
+        assert_eq!(preview.offset_for_line(-3.0), 0.0);
+        assert_eq!(preview.offset_for_line(90.0), 650.0);
+        assert_eq!(editor.line_for_offset(5000.0), 40.0);
+        assert_eq!(ScrollMap::default().offset_for_line(3.0), 0.0);
+
+        // Scrolling the editor to line 25 moves the preview to match, then scrolling
+        // the preview back to the top brings the editor along
+        let pane = |map: &ScrollMap, offset, max_offset| Pane {
+            map: map.clone(),
+            offset,
+            max_offset,
+        };
+        let mut sync = ScrollSync::default();
+        assert!(!sync.update(&pane(&editor, 0.0, 500.0), &pane(&preview, 0.0, 400.0)));
+        assert!(sync.update(&pane(&editor, 500.0, 500.0), &pane(&preview, 0.0, 400.0)));
+        assert_eq!(sync.take_preview_target(), Some(350.0));
+        assert_eq!(sync.take_editor_target(), None);
+        assert!(!sync.update(&pane(&editor, 500.0, 500.0), &pane(&preview, 350.0, 400.0)));
+        assert!(sync.update(&pane(&editor, 500.0, 500.0), &pane(&preview, 0.0, 400.0)));
+        assert_eq!(sync.take_editor_target(), Some(0.0));
+    }
+}
@@ -1,37 +1,891 @@
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 
-use crate::note::Folder;
+use crate::note::{Folder, Note};
+use crate::tags::TagManager;
+
+type NoteId = (usize, usize); // (folder_idx, note_idx)
+
+/// Longest snippet shown under a saved search result, in characters.
+const SNIPPET_CHARS: usize = 80;
+
+/// How much a title match counts against a content match when ranking, unless
+/// configured otherwise: a title match scores double.
+pub const DEFAULT_TITLE_WEIGHT: f64 = 2.0 / 3.0;
+/// Title and content count equally.
+pub const MIN_TITLE_WEIGHT: f64 = 0.5;
+/// Only titles count; content matches are still found but rank last.
+pub const MAX_TITLE_WEIGHT: f64 = 1.0;
+
+/// Inverted index from lowercase words to the notes containing them.
+#[derive(Default)]
+pub struct SearchIndex {
+    // Sorted so a partially typed word can be answered with a prefix range scan
+    postings: BTreeMap<String, HashSet<NoteId>>,
+    note_words: HashMap<NoteId, HashSet<String>>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn build(folders: &[Folder]) -> Self {
+        let mut index = Self::new();
+        for (folder_idx, folder) in folders.iter().enumerate() {
+            for (note_idx, note) in folder.notes.iter().enumerate() {
+                index.index_note((folder_idx, note_idx), note);
+            }
+        }
+        index
+    }
+    
+    /// Add a note, replacing whatever was indexed for it before.
+    pub fn index_note(&mut self, id: NoteId, note: &Note) {
+        self.remove_note(id);
+        
+        let words: HashSet<String> = tokenize(&note.title).chain(tokenize(&note.content)).collect();
+        for word in &words {
+            self.postings.entry(word.clone()).or_default().insert(id);
+        }
+        self.note_words.insert(id, words);
+    }
+    
+    pub fn remove_note(&mut self, id: NoteId) {
+        if let Some(words) = self.note_words.remove(&id) {
+            for word in words {
+                if let Some(notes) = self.postings.get_mut(&word) {
+                    notes.remove(&id);
+                    if notes.is_empty() {
+                        self.postings.remove(&word);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Notes containing a word starting with each word of the query.
+    pub fn candidates(&self, query: &str) -> HashSet<NoteId> {
+        let mut result: Option<HashSet<NoteId>> = None;
+        
+        for term in tokenize(query) {
+            let mut matches = HashSet::new();
+            for (_, notes) in self.postings.range(term.clone()..).take_while(|(word, _)| word.starts_with(&term)) {
+                matches.extend(notes);
+            }
+            
+            result = Some(match result {
+                Some(previous) => previous.intersection(&matches).copied().collect(),
+                None => matches,
+            });
+        }
+        
+        result.unwrap_or_default()
+    }
+}
+
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+/// Search hits, best first, and how many notes matched before the cap was applied.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchResults {
+    pub hits: Vec<(usize, usize)>,
+    pub total: usize,
+}
+
+impl SearchResults {
+    #[allow(dead_code)]
+    pub fn is_truncated(&self) -> bool {
+        self.hits.len() < self.total
+    }
+}
 
 pub struct FuzzySearch {
     matcher: SkimMatcherV2,
+    index: SearchIndex,
+    stale: bool,
+    max_results: Option<usize>,
+    title_weight: f64,
 }
 
 impl FuzzySearch {
+    /// Search with the default smart-case matcher.
     pub fn new() -> Self {
+        Self::with_matcher(SkimMatcherV2::default())
+    }
+    
+    /// Search with a configured matcher, e.g. `SkimMatcherV2::default().ignore_case()`
+    /// where smart case gets in the way. The matcher decides which titles match and how
+    /// results rank; the word index itself is always case-insensitive.
+    pub fn with_matcher(matcher: SkimMatcherV2) -> Self {
         Self {
-            matcher: SkimMatcherV2::default(),
+            matcher,
+            index: SearchIndex::new(),
+            stale: true,
+            max_results: None,
+            title_weight: DEFAULT_TITLE_WEIGHT,
+        }
+    }
+    
+    /// Keep only the best `max` hits of each search; `None` returns every match.
+    pub fn set_max_results(&mut self, max: Option<usize>) {
+        self.max_results = max;
+    }
+    
+    /// How results rank: a note scores the better of its title match times `weight`
+    /// and its content match times `1 - weight`. Clamped to `MIN_TITLE_WEIGHT..=MAX_TITLE_WEIGHT`.
+    pub fn set_title_weight(&mut self, weight: f64) {
+        self.title_weight = weight.clamp(MIN_TITLE_WEIGHT, MAX_TITLE_WEIGHT);
+    }
+    
+    /// Rebuild the index on the next search, e.g. after notes were added, removed or reordered.
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+    
+    /// Re-index a single note after its content changed.
+    pub fn update_note(&mut self, folders: &[Folder], folder_idx: usize, note_idx: usize) {
+        if let Some(note) = folders.get(folder_idx).and_then(|f| f.notes.get(note_idx)) {
+            self.index.index_note((folder_idx, note_idx), note);
+        }
+    }
+    
+    pub fn search(&mut self, folders: &[Folder], query: &str) -> SearchResults {
+        let ranked = self.ranked(folders, query);
+        self.capped(ranked)
+    }
+    
+    /// Search with property filters. Without free text, every note passing the
+    /// filters is a hit, in vault order.
+    pub fn search_query(&mut self, folders: &[Folder], query: &SearchQuery) -> SearchResults {
+        if query.properties.is_empty() {
+            return self.search(folders, &query.text);
         }
+        self.search_filtered(folders, query, |_, _| true)
     }
     
-    pub fn search(&self, folders: &[Folder], query: &str) -> Vec<(usize, usize)> {
-        let mut results = Vec::new();
+    /// Like `search_query`, also keeping only the notes `keep` accepts. Without free
+    /// text, every note passing the filters is a hit, even with no property filters.
+    pub fn search_filtered(
+        &mut self,
+        folders: &[Folder],
+        query: &SearchQuery,
+        keep: impl Fn(&Folder, &Note) -> bool,
+    ) -> SearchResults {
+        let hits: Vec<(usize, usize)> = if query.text.trim().is_empty() {
+            folders.iter().enumerate()
+                .flat_map(|(folder_idx, folder)| {
+                    (0..folder.notes.len()).map(move |note_idx| (folder_idx, note_idx))
+                })
+                .collect()
+        } else {
+            self.ranked(folders, &query.text)
+        };
+        let hits = hits.into_iter()
+            .filter(|&(folder_idx, note_idx)| {
+                let folder = &folders[folder_idx];
+                let note = &folder.notes[note_idx];
+                query.matches(note) && keep(folder, note)
+            })
+            .collect();
+        self.capped(hits)
+    }
+    
+    fn capped(&self, mut hits: Vec<(usize, usize)>) -> SearchResults {
+        let total = hits.len();
+        if let Some(max) = self.max_results {
+            hits.truncate(max);
+        }
+        SearchResults { hits, total }
+    }
+    
+    /// Every matching note, best first.
+    fn ranked(&mut self, folders: &[Folder], query: &str) -> Vec<(usize, usize)> {
+        if self.stale {
+            self.index = SearchIndex::build(folders);
+            self.stale = false;
+        }
         
+        // The index narrows the field; titles are short enough to fuzzy match directly
+        let mut candidates = self.index.candidates(query);
         for (folder_idx, folder) in folders.iter().enumerate() {
             for (note_idx, note) in folder.notes.iter().enumerate() {
-                // Search in title
-                let title_score = self.matcher.fuzzy_match(&note.title, query);
+                if self.matcher.fuzzy_match(&note.title, query).is_some() {
+                    candidates.insert((folder_idx, note_idx));
+                }
+            }
+        }
+        
+        // Fuzzy scores only rank the candidates, best first
+        let mut results: Vec<(f64, (usize, usize))> = candidates
+            .into_iter()
+            .filter_map(|(folder_idx, note_idx)| {
+                let note = folders.get(folder_idx)?.notes.get(note_idx)?;
+                let weighted = |text: &str, weight: f64| {
+                    self.matcher.fuzzy_match(text, query).map_or(0.0, |s| s as f64 * weight)
+                };
+                let score = weighted(&note.title, self.title_weight)
+                    .max(weighted(&note.content, 1.0 - self.title_weight));
+                Some((score, (folder_idx, note_idx)))
+            })
+            .collect();
+        results.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        results.into_iter().map(|(_, id)| id).collect()
+    }
+}
+
+/// Numbers queries in the order they're issued. Shared with the search thread, so a
+/// result is only worth showing while no newer query has been issued since.
+#[derive(Debug, Clone, Default)]
+pub struct QuerySequence(Arc<AtomicU64>);
+
+impl QuerySequence {
+    pub fn next(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::SeqCst) + 1
+    }
+    
+    pub fn is_latest(&self, seq: u64) -> bool {
+        self.0.load(Ordering::SeqCst) == seq
+    }
+}
+
+#[derive(Clone)]
+struct SearchJob {
+    seq: u64,
+    query: SearchQuery,
+    max_results: Option<usize>,
+    title_weight: f64,
+}
+
+enum WorkerMessage {
+    Search(SearchJob),
+    // Notes were added, removed or reordered: reload the whole vault before the next search
+    Reload,
+    // One note's content changed; its index stays the same
+    NoteChanged(NoteId, Box<Note>),
+}
+
+/// Runs searches on a background thread so typing never waits on a large vault.
+/// The thread keeps its own copy of the notes, loaded there on the first search and
+/// after `invalidate`, and patched one note at a time by `update_note`.
+/// Each search replaces the one before it: queued queries are skipped and results
+/// for anything but the newest query are dropped.
+pub struct SearchWorker {
+    messages: Option<Sender<WorkerMessage>>,
+    results: Receiver<(u64, SearchResults)>,
+    handle: Option<JoinHandle<()>>,
+    sequence: QuerySequence,
+    // Bumped whenever the notes change, so results from before can be told apart
+    generation: u64,
+    // Newest query whose results haven't arrived yet, and the generation it searched
+    waiting: Option<(SearchJob, u64)>,
+}
+
+impl SearchWorker {
+    /// Start the search thread. `load` reads every note of the vault, content
+    /// included, and runs on that thread.
+    pub fn spawn(load: impl Fn() -> Vec<Folder> + Send + 'static) -> Self {
+        let (message_tx, message_rx) = mpsc::channel::<WorkerMessage>();
+        let (result_tx, result_rx) = mpsc::channel();
+        let sequence = QuerySequence::default();
+        let latest = sequence.clone();
+        
+        let handle = thread::spawn(move || {
+            let mut search = FuzzySearch::new();
+            let mut folders: Option<Vec<Folder>> = None;
+            while let Ok(message) = message_rx.recv() {
+                // Apply every change queued so far; only the newest queued query matters
+                let mut job = None;
+                for message in std::iter::once(message).chain(message_rx.try_iter()) {
+                    match message {
+                        WorkerMessage::Search(newer) => job = Some(newer),
+                        WorkerMessage::Reload => folders = None,
+                        WorkerMessage::NoteChanged((folder_idx, note_idx), note) => {
+                            let Some(loaded) = folders.as_mut() else {
+                                continue;
+                            };
+                            if let Some(slot) = loaded.get_mut(folder_idx).and_then(|f| f.notes.get_mut(note_idx)) {
+                                *slot = *note;
+                                search.update_note(loaded, folder_idx, note_idx);
+                            }
+                        }
+                    }
+                }
+                let Some(job) = job else {
+                    continue;
+                };
+                if !latest.is_latest(job.seq) {
+                    continue;
+                }
                 
-                // Search in content
-                let content_score = self.matcher.fuzzy_match(&note.content, query);
+                let folders = folders.get_or_insert_with(|| {
+                    search.invalidate();
+                    load()
+                });
+                search.set_max_results(job.max_results);
+                search.set_title_weight(job.title_weight);
+                let results = search.search_query(folders, &job.query);
                 
-                // If either matches, add to results
-                if title_score.is_some() || content_score.is_some() {
-                    results.push((folder_idx, note_idx));
+                // Superseded while it ran
+                if !latest.is_latest(job.seq) {
+                    continue;
+                }
+                if result_tx.send((job.seq, results)).is_err() {
+                    break;
+                }
+            }
+        });
+        
+        Self {
+            messages: Some(message_tx),
+            results: result_rx,
+            handle: Some(handle),
+            sequence,
+            generation: 0,
+            waiting: None,
+        }
+    }
+    
+    /// Start searching, abandoning any search still running. `title_weight` ranks as
+    /// in `FuzzySearch::set_title_weight`.
+    pub fn submit(&mut self, query: SearchQuery, max_results: Option<usize>, title_weight: f64) {
+        let seq = self.sequence.next();
+        let job = SearchJob { seq, query, max_results, title_weight };
+        if self.send(WorkerMessage::Search(job.clone())) {
+            self.waiting = Some((job, self.generation));
+        }
+    }
+    
+    /// Reload every note before the next search, e.g. after notes were added, removed
+    /// or reordered.
+    pub fn invalidate(&mut self) {
+        self.generation += 1;
+        self.send(WorkerMessage::Reload);
+    }
+    
+    /// Re-index a single note after its content changed.
+    pub fn update_note(&mut self, folders: &[Folder], folder_idx: usize, note_idx: usize) {
+        if let Some(note) = folders.get(folder_idx).and_then(|f| f.notes.get(note_idx)) {
+            self.generation += 1;
+            self.send(WorkerMessage::NoteChanged((folder_idx, note_idx), Box::new(note.clone())));
+        }
+    }
+    
+    fn send(&self, message: WorkerMessage) -> bool {
+        self.messages.as_ref().is_some_and(|messages| messages.send(message).is_ok())
+    }
+    
+    /// Forget the search in progress; its results won't be delivered.
+    pub fn cancel(&mut self) {
+        self.sequence.next();
+        self.waiting = None;
+    }
+    
+    /// Whether a search is still running.
+    pub fn is_searching(&self) -> bool {
+        self.waiting.is_some()
+    }
+    
+    /// Results of the newest query if they've arrived, without blocking. Results for
+    /// older queries are discarded, and a query that searched notes which have changed
+    /// since is run again.
+    pub fn poll(&mut self) -> Option<SearchResults> {
+        let mut latest = None;
+        loop {
+            match self.results.try_recv() {
+                Ok((seq, results)) => {
+                    let Some((job, generation)) = self.waiting.take_if(|(job, _)| job.seq == seq) else {
+                        continue;
+                    };
+                    if generation == self.generation {
+                        latest = Some(results);
+                    } else {
+                        // Its indices may point at the wrong notes now
+                        self.submit(job.query, job.max_results, job.title_weight);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.waiting = None;
+                    break;
+                }
+            }
+        }
+        latest
+    }
+}
+
+impl Drop for SearchWorker {
+    fn drop(&mut self) {
+        self.cancel();
+        self.messages.take();
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+/// A search split into `key:value` property filters and the free text around them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub properties: Vec<(String, String)>,
+    pub text: String,
+}
+
+impl SearchQuery {
+    /// `status:done project report` filters on `status` and searches for "project report".
+    #[allow(dead_code)]
+    pub fn parse(query: &str) -> Self {
+        Self::parse_with(query, |_| true)
+    }
+    
+    /// Like `parse`, but `key:value` tokens whose key fails `is_property` stay in the
+    /// text, so a search for `re:meeting` still works when no note has an `re` property.
+    pub fn parse_with(query: &str, is_property: impl Fn(&str) -> bool) -> Self {
+        let mut properties = Vec::new();
+        let mut text = Vec::new();
+        for token in query.split_whitespace() {
+            match token.split_once(':') {
+                Some((key, value)) if !key.is_empty() && !value.is_empty() && is_property(key) => {
+                    properties.push((key.to_string(), value.to_string()));
                 }
+                _ => text.push(token),
+            }
+        }
+        
+        if properties.is_empty() {
+            // Leave plain searches exactly as typed
+            return Self { properties, text: query.to_string() };
+        }
+        Self { properties, text: text.join(" ") }
+    }
+    
+    /// Whether `note` passes every property filter. Keys and values ignore case.
+    pub fn matches(&self, note: &Note) -> bool {
+        self.properties.iter().all(|(key, value)| {
+            note.properties.iter().any(|(k, v)| {
+                k.eq_ignore_ascii_case(key) && v.to_lowercase() == value.to_lowercase()
+            })
+        })
+    }
+}
+
+/// Which folders a saved search looks in.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum SearchScope {
+    #[default]
+    AllFolders,
+    /// Only the folder with this name.
+    Folder(String),
+}
+
+/// A search kept under a name and shown in the sidebar as a smart folder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    /// Search box text, property filters included.
+    pub query: String,
+    #[serde(default)]
+    pub scope: SearchScope,
+    /// Only notes with the tag of this name.
+    #[serde(default)]
+    pub tag_filter: Option<String>,
+}
+
+impl SavedSearch {
+    /// Run the search against the vault as it is now. Note bodies must be loaded.
+    pub fn evaluate(&self, search: &mut FuzzySearch, folders: &[Folder], tags: &TagManager) -> SearchResults {
+        let query = SearchQuery::parse_with(&self.query, |key| has_property(folders, key));
+        // A tag that no longer exists matches nothing
+        let tag = match &self.tag_filter {
+            Some(name) => match tags.all_tags().iter().position(|t| &t.name == name) {
+                Some(idx) => Some(idx),
+                None => return SearchResults { hits: Vec::new(), total: 0 },
+            },
+            None => None,
+        };
+        
+        search.search_filtered(folders, &query, |folder, note| {
+            let in_scope = match &self.scope {
+                SearchScope::AllFolders => true,
+                SearchScope::Folder(name) => &folder.name == name,
+            };
+            in_scope && tag.is_none_or(|idx| note.tags.has_tag(idx))
+        })
+    }
+}
+
+/// Whether any note has a property called `key`.
+pub fn has_property(folders: &[Folder], key: &str) -> bool {
+    folders.iter()
+        .flat_map(|f| f.notes.iter())
+        .any(|n| n.properties.keys().any(|k| k.eq_ignore_ascii_case(key)))
+}
+
+/// Byte ranges `(start, end)` of every occurrence of `term` in `content`, overlapping
+/// ones included, for in-note find. Case-insensitive matching compares char by char,
+/// so ranges always fall on char boundaries of `content`.
+pub fn find_all_ranges(content: &str, term: &str, case_sensitive: bool) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    if term.is_empty() {
+        return ranges;
+    }
+    
+    for (start, _) in content.char_indices() {
+        let rest = &content[start..];
+        let len = if case_sensitive {
+            rest.starts_with(term).then_some(term.len())
+        } else {
+            match_len_ignore_case(rest, term)
+        };
+        if let Some(len) = len {
+            ranges.push((start, start + len));
+        }
+    }
+    ranges
+}
+
+/// The in-note find's last matches, so the find bar only searches again when the
+/// note, the term or case sensitivity changed since the previous frame.
+#[derive(Debug, Default)]
+pub struct FindCache {
+    key: Option<(String, String, bool)>,
+    ranges: Vec<(usize, usize)>,
+}
+
+impl FindCache {
+    /// `find_all_ranges(content, term, case_sensitive)`, from the cache when nothing changed.
+    pub fn ranges(&mut self, content: &str, term: &str, case_sensitive: bool) -> &[(usize, usize)] {
+        let fresh = self.key.as_ref().is_some_and(|(cached_content, cached_term, cached_case)| {
+            cached_content == content && cached_term == term && *cached_case == case_sensitive
+        });
+        if !fresh {
+            self.ranges = find_all_ranges(content, term, case_sensitive);
+            self.key = Some((content.to_string(), term.to_string(), case_sensitive));
+        }
+        &self.ranges
+    }
+}
+
+/// Length in bytes of the prefix of `text` that matches `term` ignoring case.
+fn match_len_ignore_case(text: &str, term: &str) -> Option<usize> {
+    let mut text_chars = text.char_indices();
+    for term_char in term.chars() {
+        let (_, text_char) = text_chars.next()?;
+        if !text_char.to_lowercase().eq(term_char.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(text_chars.next().map_or(text.len(), |(idx, _)| idx))
+}
+
+/// A one-line excerpt of `content` for a search hit: the first line mentioning a
+/// word of `text`, or the first line of prose if none does (e.g. property-only queries).
+pub fn snippet(content: &str, text: &str) -> String {
+    let terms: Vec<String> = tokenize(text).collect();
+    let mut lines = content.lines().map(str::trim).filter(|line| !line.is_empty());
+    let line = lines.clone()
+        .find(|line| {
+            let line = line.to_lowercase();
+            terms.iter().any(|term| line.contains(term.as_str()))
+        })
+        .or_else(|| lines.find(|line| !line.starts_with('#')))
+        .unwrap_or_default();
+    
+    match line.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", line[..end].trim_end()),
+        None => line.to_string(),
+    }
+}
+
+/// Markdown body for a note collecting search results: a heading naming the query,
+/// then each `(title, snippet)` as a `[[wiki link]]` followed by its snippet.
+pub fn results_to_markdown(query: &str, results: &[(String, String)]) -> String {
+    let mut body = format!("# Search: {}\n\n", query.trim());
+    for (title, snippet) in results {
+        if snippet.is_empty() {
+            body.push_str(&format!("- [[{}]]\n", title));
+        } else {
+            body.push_str(&format!("- [[{}]] — {}\n", title, snippet));
+        }
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_find_overlapping_ranges() {
+        assert_eq!(find_all_ranges("aaaa", "aa", true), vec![(0, 2), (1, 3), (2, 4)]);
+        assert_eq!(find_all_ranges("abcabc", "bc", true), vec![(1, 3), (4, 6)]);
+        assert!(find_all_ranges("abc", "", true).is_empty());
+        assert!(find_all_ranges("abc", "abcd", true).is_empty());
+    }
+    
+    #[test]
+    fn test_find_case_insensitive_ranges() {
+        let content = "Rust is fun. rust is fast. RUST!";
+        assert_eq!(find_all_ranges(content, "rust", true), vec![(13, 17)]);
+        assert_eq!(find_all_ranges(content, "rust", false), vec![(0, 4), (13, 17), (27, 31)]);
+        
+        // Ranges index the original text even around multi-byte characters
+        let content = "Café CAFÉ";
+        let ranges = find_all_ranges(content, "café", false);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(&content[ranges[1].0..ranges[1].1], "CAFÉ");
+        
+        // The cache follows edits to the note, the term and the case setting
+        let mut cache = FindCache::default();
+        assert_eq!(cache.ranges(content, "café", false).len(), 2);
+        assert!(cache.ranges(content, "café", true).is_empty());
+        assert_eq!(cache.ranges("Café CAFÉ café", "café", true), &[(12, 17)]);
+        assert!(cache.ranges("Café CAFÉ café", "tea", true).is_empty());
+    }
+    
+    fn folder(name: &str, notes: &[(&str, &str)]) -> Folder {
+        let mut folder = Folder::new(name.to_string(), name.to_string());
+        for (title, content) in notes {
+            let mut note = Note::new(title.to_string(), format!("{}/{}.md", name, title));
+            note.content = content.to_string();
+            folder.add_note(note);
+        }
+        folder
+    }
+    
+    #[test]
+    fn test_index_then_query() {
+        let folders = vec![
+            folder("Work", &[("Standup", "Discussed the release schedule"), ("Budget", "Quarterly numbers")]),
+            folder("Home", &[("Groceries", "Eggs, milk and release coffee")]),
+        ];
+        let index = SearchIndex::build(&folders);
+        
+        let hits = index.candidates("release");
+        assert_eq!(hits, HashSet::from([(0, 0), (1, 0)]));
+        
+        // Prefixes of a word match, and every query term must appear
+        assert_eq!(index.candidates("quart"), HashSet::from([(0, 1)]));
+        assert_eq!(index.candidates("release coffee"), HashSet::from([(1, 0)]));
+        assert!(index.candidates("nothing").is_empty());
+    }
+    
+    #[test]
+    fn test_search_uses_updated_index() {
+        let mut folders = vec![folder("Work", &[("Plan", "first draft"), ("Notes", "unrelated")])];
+        let mut search = FuzzySearch::new();
+        assert_eq!(search.search(&folders, "draft").hits, vec![(0, 0)]);
+        
+        folders[0].notes[1].content = "second draft".to_string();
+        search.update_note(&folders, 0, 1);
+        let mut results = search.search(&folders, "draft").hits;
+        results.sort();
+        assert_eq!(results, vec![(0, 0), (0, 1)]);
+        
+        // Title matches are found even without an indexed word
+        assert_eq!(search.search(&folders, "Pln").hits, vec![(0, 0)]);
+    }
+    
+    #[test]
+    fn test_title_weight_reorders_results() {
+        let folders = vec![folder("Work", &[
+            ("Misc", "budget"),
+            ("The yearly budget plan", "Numbers for next year"),
+        ])];
+        let mut search = FuzzySearch::new();
+        
+        // Equal weight: the exact body match beats the title match mid-title
+        search.set_title_weight(MIN_TITLE_WEIGHT);
+        assert_eq!(search.search(&folders, "budget").hits, vec![(0, 0), (0, 1)]);
+        
+        search.set_title_weight(MAX_TITLE_WEIGHT);
+        assert_eq!(search.search(&folders, "budget").hits, vec![(0, 1), (0, 0)]);
+        
+        // Out of range weights are clamped
+        search.set_title_weight(5.0);
+        assert_eq!(search.title_weight, MAX_TITLE_WEIGHT);
+    }
+    
+    #[test]
+    fn test_stale_query_results_discarded() {
+        let sequence = QuerySequence::default();
+        let first = sequence.next();
+        assert!(sequence.is_latest(first));
+        let second = sequence.next();
+        assert!(!sequence.is_latest(first));
+        assert!(sequence.is_latest(second));
+        
+        let folders = vec![folder("Work", &[("Plan", "release draft"), ("Budget", "quarterly numbers")])];
+        let loaded = folders.clone();
+        let mut worker = SearchWorker::spawn(move || loaded.clone());
+        let wait = |worker: &mut SearchWorker| loop {
+            if let Some(results) = worker.poll() {
+                break results;
             }
+            assert!(worker.is_searching());
+            thread::sleep(std::time::Duration::from_millis(5));
+        };
+        for text in ["rel", "relea", "quarterly"] {
+            worker.submit(SearchQuery::parse(text), None, DEFAULT_TITLE_WEIGHT);
         }
+        // Only the last query's results are delivered
+        assert_eq!(wait(&mut worker).hits, vec![(0, 1)]);
+        assert!(!worker.is_searching());
+        
+        // An edited note is searched without reloading the vault
+        let mut edited = folders.clone();
+        edited[0].notes[0].content = "quarterly review".to_string();
+        worker.update_note(&edited, 0, 0);
+        worker.submit(SearchQuery::parse("quarterly"), None, DEFAULT_TITLE_WEIGHT);
+        let mut hits = wait(&mut worker).hits;
+        hits.sort();
+        assert_eq!(hits, vec![(0, 0), (0, 1)]);
+        
+        worker.submit(SearchQuery::parse("draft"), None, DEFAULT_TITLE_WEIGHT);
+        worker.cancel();
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(worker.poll(), None);
+    }
+    
+    #[test]
+    fn test_result_cap_keeps_best_hits() {
+        let notes: Vec<(String, String)> = (0..20)
+            .map(|i| (format!("Note {}", i), format!("{} meeting notes{}", "filler ".repeat(i), i)))
+            .collect();
+        let notes: Vec<(&str, &str)> = notes.iter().map(|(t, c)| (t.as_str(), c.as_str())).collect();
+        let folders = vec![folder("Work", &notes)];
+        
+        let mut search = FuzzySearch::new();
+        let all = search.search(&folders, "meeting");
+        assert_eq!(all.total, 20);
+        assert!(!all.is_truncated());
+        
+        search.set_max_results(Some(5));
+        let capped = search.search(&folders, "meeting");
+        assert_eq!(capped.hits, all.hits[..5].to_vec());
+        assert_eq!(capped.total, 20);
+        assert!(capped.is_truncated());
+    }
+    
+    #[test]
+    fn test_matcher_case_handling() {
+        let folders = vec![folder("Docs", &[("Read Me", ""), ("read me", ""), ("Other", "")])];
+        
+        // Smart case: an uppercase letter in the query makes the match case-sensitive
+        let mut smart = FuzzySearch::new();
+        assert_eq!(smart.search(&folders, "RdMe").hits, vec![(0, 0)]);
+        
+        let mut plain = FuzzySearch::with_matcher(SkimMatcherV2::default().ignore_case());
+        let mut results = plain.search(&folders, "RdMe").hits;
+        results.sort();
+        assert_eq!(results, vec![(0, 0), (0, 1)]);
+        
+        let mut strict = FuzzySearch::with_matcher(SkimMatcherV2::default().respect_case());
+        assert_eq!(strict.search(&folders, "rdme").hits, vec![(0, 1)]);
+    }
+    
+    #[test]
+    fn test_parse_property_query() {
+        let query = SearchQuery::parse("status:done project report");
+        assert_eq!(query.properties, vec![("status".to_string(), "done".to_string())]);
+        assert_eq!(query.text, "project report");
+        
+        let query = SearchQuery::parse("budget priority:high status:done");
+        assert_eq!(query.properties.len(), 2);
+        assert_eq!(query.text, "budget");
+        
+        // Half-written tokens are text, and plain searches are untouched
+        assert_eq!(SearchQuery::parse("status: done").text, "status: done");
+        assert!(SearchQuery::parse(":done").properties.is_empty());
+        assert_eq!(SearchQuery::parse("  meeting  notes").text, "  meeting  notes");
+        
+        let query = SearchQuery::parse_with("re:meeting status:done", |key| key == "status");
+        assert_eq!(query.text, "re:meeting");
+    }
+    
+    #[test]
+    fn test_filter_by_property() {
+        let mut folders = vec![
+            folder("Work", &[("Spec", "project report"), ("Roadmap", "project plan")]),
+            folder("Home", &[("Taxes", "")]),
+        ];
+        folders[0].notes[0].properties.insert("status".to_string(), "Draft".to_string());
+        folders[0].notes[1].properties.insert("status".to_string(), "done".to_string());
+        folders[1].notes[0].properties.insert("Status".to_string(), "draft".to_string());
+        
+        let mut search = FuzzySearch::new();
+        let hits = |search: &mut FuzzySearch, query: &str| {
+            search.search_query(&folders, &SearchQuery::parse(query)).hits
+        };
+        assert_eq!(hits(&mut search, "status:draft"), vec![(0, 0), (1, 0)]);
+        assert_eq!(hits(&mut search, "status:done"), vec![(0, 1)]);
+        assert_eq!(hits(&mut search, "status:draft project"), vec![(0, 0)]);
+        assert_eq!(hits(&mut search, "project"), vec![(0, 0), (0, 1)]);
+        assert!(hits(&mut search, "priority:high").is_empty());
+        assert!(has_property(&folders, "STATUS"));
+        assert!(!has_property(&folders, "priority"));
+    }
+    
+    #[test]
+    fn test_results_to_markdown() {
+        let content = "# Q3 Plan\n\nIntro paragraph.\nBudget review with the team on Friday.\n";
+        assert_eq!(snippet(content, "budget"), "Budget review with the team on Friday.");
+        assert_eq!(snippet(content, ""), "Intro paragraph.");
+        assert_eq!(snippet(&"a".repeat(100), "a").chars().count(), SNIPPET_CHARS + 1);
+        
+        let results = vec![
+            ("Q3 Plan".to_string(), snippet(content, "budget")),
+            ("Empty".to_string(), String::new()),
+        ];
+        assert_eq!(
+            results_to_markdown(" budget ", &results),
+            "# Search: budget\n\n- [[Q3 Plan]] — Budget review with the team on Friday.\n- [[Empty]]\n"
+        );
+    }
+    
+    #[test]
+    fn test_smart_folder_results() {
+        let mut folders = vec![
+            folder("Work", &[("Spec", "release plan"), ("Retro", "release notes"), ("Budget", "numbers")]),
+            folder("Home", &[("Move", "release the house keys")]),
+        ];
+        let mut tags = TagManager::new();
+        let urgent = tags.add_tag("urgent".to_string());
+        folders[0].notes[1].tags.add_tag(urgent);
+        folders[0].notes[2].tags.add_tag(urgent);
+        folders[1].notes[0].tags.add_tag(urgent);
+        
+        let mut search = FuzzySearch::new();
+        let mut smart = SavedSearch {
+            name: "Work releases".to_string(),
+            query: "release".to_string(),
+            scope: SearchScope::Folder("Work".to_string()),
+            tag_filter: None,
+        };
+        let mut hits = smart.evaluate(&mut search, &folders, &tags).hits;
+        hits.sort();
+        assert_eq!(hits, vec![(0, 0), (0, 1)]);
+        
+        smart.tag_filter = Some("urgent".to_string());
+        assert_eq!(smart.evaluate(&mut search, &folders, &tags).hits, vec![(0, 1)]);
+        
+        // No text: everything tagged, across all folders
+        smart.query.clear();
+        smart.scope = SearchScope::AllFolders;
+        assert_eq!(smart.evaluate(&mut search, &folders, &tags).hits, vec![(0, 1), (0, 2), (1, 0)]);
         
-        results
+        smart.tag_filter = Some("deleted".to_string());
+        assert!(smart.evaluate(&mut search, &folders, &tags).hits.is_empty());
     }
 }
@@ -1,8 +1,36 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long typing has to pause before the editor is checked again.
+pub const SPELLCHECK_DELAY: Duration = Duration::from_millis(400);
+
+/// Byte range and word of each misspelling, as returned by `check_text`.
+pub type Misspelling = (usize, usize, String);
+
+/// Words added with "Add to dictionary", one per line, next to `config.json`.
+pub const USER_DICTIONARY_FILE: &str = "dictionary.txt";
+
+/// Language of the built-in word list.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Extra languages as `<language>.txt` word lists, one word per line.
+pub const DICTIONARIES_DIR: &str = "./dictionaries";
 
 pub struct SpellChecker {
-    dictionary: HashSet<String>,
+    // Word list for each language, keyed by language code
+    dictionaries: BTreeMap<String, HashSet<String>>,
+    language: String,
+    // Words the user added; correct in every language
+    user_words: HashSet<String>,
     enabled: bool,
+    // Hash of the text last checked by `check_text_cached`, and its result
+    cache: Option<(u64, Vec<Misspelling>)>,
+    // How many times `check_text_cached` had to scan the text
+    scans: usize,
 }
 
 impl SpellChecker {
@@ -12,7 +40,7 @@ impl SpellChecker {
         // Basic English dictionary words
         let words = vec![
             // Same words as autocomplete plus more
-            "the", "be", "to", "of", "and", "a", "in", "that", "have", "I",
+            "hello", "the", "be", "to", "of", "and", "a", "in", "that", "have", "I",
             "it", "for", "not", "on", "with", "he", "as", "you", "do", "at",
             "this", "but", "his", "by", "from", "they", "we", "say", "her", "she",
             "or", "an", "will", "my", "one", "all", "would", "there", "their",
@@ -74,8 +102,12 @@ impl SpellChecker {
         }
         
         Self {
-            dictionary,
+            dictionaries: BTreeMap::from([(DEFAULT_LANGUAGE.to_string(), dictionary)]),
+            language: DEFAULT_LANGUAGE.to_string(),
+            user_words: HashSet::new(),
             enabled: true,
+            cache: None,
+            scans: 0,
         }
     }
     
@@ -95,7 +127,9 @@ impl SpellChecker {
         }
         
         // Check if word is in dictionary (case-insensitive)
-        self.dictionary.contains(&word.to_lowercase())
+        let word = word.to_lowercase();
+        self.user_words.contains(&word)
+            || self.dictionaries.get(&self.language).is_some_and(|d| d.contains(&word))
     }
     
     pub fn check_text(&self, text: &str) -> Vec<(usize, usize, String)> {
@@ -126,20 +160,100 @@ impl SpellChecker {
         misspelled
     }
     
+    /// Like `check_text`, but returns the previous result without rescanning if
+    /// `text` is the same as last time.
+    pub fn check_text_cached(&mut self, text: &str) -> &[Misspelling] {
+        let hash = content_hash(text);
+        if self.cache.as_ref().map(|(cached, _)| *cached) != Some(hash) {
+            self.scans += 1;
+            self.cache = Some((hash, self.check_text(text)));
+        }
+        self.cache.as_ref().map_or(&[], |(_, misspelled)| misspelled)
+    }
+    
     pub fn add_to_dictionary(&mut self, word: String) {
-        self.dictionary.insert(word.to_lowercase());
+        self.user_words.insert(word.to_lowercase());
+        self.cache = None;
+    }
+    
+    /// Add one language's words from a file of one word per line; loading the same
+    /// language again extends its list.
+    pub fn load_dictionary(&mut self, language: &str, path: &Path) -> io::Result<()> {
+        let words = fs::read_to_string(path)?;
+        self.cache = None;
+        let dictionary = self.dictionaries.entry(language.to_string()).or_default();
+        for word in words.lines().map(str::trim).filter(|w| !w.is_empty()) {
+            dictionary.insert(word.to_lowercase());
+        }
+        Ok(())
+    }
+    
+    /// Load every `<language>.txt` in `dir`. A missing directory is not an error.
+    pub fn load_dictionaries(&mut self, dir: &Path) -> io::Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "txt") {
+                if let Some(language) = path.file_stem().and_then(|s| s.to_str()) {
+                    self.load_dictionary(language, &path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Languages with a dictionary, sorted.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.dictionaries.keys().map(String::as_str)
     }
     
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    
+    /// Check against `language` from now on. Returns false, leaving the current
+    /// language active, if it has no dictionary.
+    pub fn set_language(&mut self, language: &str) -> bool {
+        if !self.dictionaries.contains_key(language) {
+            return false;
+        }
+        self.language = language.to_string();
+        self.cache = None;
+        true
+    }
+    
+    /// Add the words saved by `save_user_word`. A missing file is not an error.
+    pub fn load_user_dictionary(&mut self, path: &Path) -> io::Result<()> {
+        let words = match fs::read_to_string(path) {
+            Ok(words) => words,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for word in words.lines().map(str::trim).filter(|w| !w.is_empty()) {
+            self.add_to_dictionary(word.to_string());
+        }
+        Ok(())
+    }
+    
+    #[allow(dead_code)]
     pub fn toggle(&mut self) {
         self.enabled = !self.enabled;
+        self.cache = None;
     }
     
+    #[allow(dead_code)]
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
     
+    #[allow(dead_code)]
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
+        self.cache = None;
     }
     
     // Simple suggestion using Levenshtein-like approach
@@ -151,7 +265,8 @@ impl SpellChecker {
         let word_lower = word.to_lowercase();
         let mut suggestions: Vec<(usize, String)> = Vec::new();
         
-        for dict_word in &self.dictionary {
+        let dictionary = self.dictionaries.get(&self.language).into_iter().flatten();
+        for dict_word in dictionary.chain(&self.user_words) {
             // Calculate simple edit distance
             let distance = self.simple_distance(&word_lower, dict_word);
             if distance <= 2 {
@@ -168,7 +283,7 @@ impl SpellChecker {
     
     fn simple_distance(&self, s1: &str, s2: &str) -> usize {
         // Simple character difference count (not true Levenshtein)
-        let len_diff = (s1.len() as i32 - s2.len() as i32).abs() as usize;
+        let len_diff = s1.len().abs_diff(s2.len());
         let mut char_diff = 0;
         
         for (c1, c2) in s1.chars().zip(s2.chars()) {
@@ -181,6 +296,119 @@ impl SpellChecker {
     }
 }
 
+/// The last spell check of the editor, rerun once typing pauses.
+#[derive(Default)]
+pub struct SpellCache {
+    misspelled: Vec<Misspelling>,
+    due: Option<Instant>,
+}
+
+impl SpellCache {
+    pub fn misspelled(&self) -> &[Misspelling] {
+        &self.misspelled
+    }
+    
+    /// The text was edited: check it once `SPELLCHECK_DELAY` has passed.
+    pub fn schedule(&mut self, now: Instant) {
+        self.due = Some(now + SPELLCHECK_DELAY);
+    }
+    
+    /// Time left until a scheduled check, for requesting a repaint.
+    pub fn pending(&self, now: Instant) -> Option<Duration> {
+        self.due.map(|due| due.saturating_duration_since(now))
+    }
+    
+    /// Run a scheduled check if its delay is up.
+    pub fn refresh(&mut self, checker: &mut SpellChecker, text: &str, now: Instant) -> bool {
+        match self.due {
+            Some(due) if now >= due => {
+                self.check_now(checker, text);
+                true
+            }
+            _ => false,
+        }
+    }
+    
+    /// Check `text` right away, e.g. when another note is opened.
+    pub fn check_now(&mut self, checker: &mut SpellChecker, text: &str) {
+        self.due = None;
+        self.misspelled = checker.check_text_cached(text).to_vec();
+    }
+}
+
+/// Append `word` to the user dictionary at `path`.
+pub fn save_user_word(path: &Path, word: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", word.to_lowercase())
+}
+
+/// The misspelling whose span contains byte offset `pos`.
+pub fn misspelling_at(misspelled: &[Misspelling], pos: usize) -> Option<&Misspelling> {
+    misspelled.iter().find(|(start, end, _)| (*start..=*end).contains(&pos))
+}
+
+/// Swap a misspelled word for `suggestion`, keeping the punctuation around it and
+/// a leading capital. Returns false if the word is no longer at that range.
+pub fn replace_misspelling(text: &mut String, misspelling: &Misspelling, suggestion: &str) -> bool {
+    let (start, end, word) = misspelling;
+    let Some(offset) = text.get(*start..*end).and_then(|span| span.find(word.as_str())) else {
+        return false;
+    };
+    let word_start = start + offset;
+    
+    let mut replacement = suggestion.to_string();
+    if word.starts_with(char::is_uppercase) {
+        let mut chars = suggestion.chars();
+        if let Some(first) = chars.next() {
+            replacement = first.to_uppercase().chain(chars).collect();
+        }
+    }
+    text.replace_range(word_start..word_start + word.len(), &replacement);
+    true
+}
+
+fn content_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Lay out `text` as one plain section per run, underlining each misspelling.
+/// Ranges that no longer fit the text (it changed since the last check) are skipped.
+pub fn spelling_layout_job(
+    text: &str,
+    format: egui::TextFormat,
+    underline: egui::Stroke,
+    misspelled: &[Misspelling],
+    wrap_width: f32,
+) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    job.wrap.max_width = wrap_width;
+    
+    let mut pos = 0;
+    for (start, end, word) in misspelled {
+        let still_there = *start >= pos
+            && text.get(*start..*end).is_some_and(|span| span.contains(word.as_str()));
+        if !still_there {
+            continue;
+        }
+        if *start > pos {
+            job.append(&text[pos..*start], 0.0, format.clone());
+        }
+        job.append(
+            &text[*start..*end],
+            0.0,
+            egui::TextFormat { underline, ..format.clone() },
+        );
+        pos = *end;
+    }
+    job.append(&text[pos..], 0.0, format);
+    job
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -199,4 +427,127 @@ mod tests {
         let errors = checker.check_text("This is a tst");
         assert!(!errors.is_empty());
     }
+    
+    #[test]
+    fn test_layout_underlines_misspelling() {
+        let checker = SpellChecker::new();
+        let text = "Update the tst note";
+        let misspelled = checker.check_text(text);
+        assert_eq!(misspelled, vec![(11, 14, "tst".to_string())]);
+        
+        let underline = egui::Stroke::new(1.0, egui::Color32::RED);
+        let job = spelling_layout_job(text, egui::TextFormat::default(), underline, &misspelled, 300.0);
+        let underlined: Vec<_> = job
+            .sections
+            .iter()
+            .filter(|s| s.format.underline == underline)
+            .map(|s| &job.text[s.byte_range.clone()])
+            .collect();
+        assert_eq!(underlined, vec!["tst"]);
+        assert_eq!(job.text, text);
+        
+        // Stale ranges from before an edit are ignored
+        let job = spelling_layout_job("Update", egui::TextFormat::default(), underline, &misspelled, 300.0);
+        assert!(job.sections.iter().all(|s| s.format.underline != underline));
+    }
+    
+    #[test]
+    fn test_cache_waits_for_typing_to_pause() {
+        let mut checker = SpellChecker::new();
+        let mut cache = SpellCache::default();
+        let start = Instant::now();
+        
+        cache.check_now(&mut checker, "the tst");
+        assert_eq!(cache.misspelled().len(), 1);
+        
+        cache.schedule(start);
+        assert!(!cache.refresh(&mut checker, "the test", start));
+        assert_eq!(cache.misspelled().len(), 1);
+        assert!(cache.refresh(&mut checker, "the note", start + SPELLCHECK_DELAY));
+        assert!(cache.misspelled().is_empty());
+        assert_eq!(cache.pending(start), None);
+    }
+    
+    #[test]
+    fn test_replace_misspelling_with_suggestion() {
+        let mut text = "See you (tmorrow) today. Tmorrow!".to_string();
+        let checker = SpellChecker::new();
+        let misspelled = checker.check_text(&text);
+        assert_eq!(misspelled[0], (8, 17, "tmorrow".to_string()));
+        
+        // Clicking anywhere in the span finds it
+        assert_eq!(misspelling_at(&misspelled, 12), Some(&misspelled[0]));
+        assert_eq!(misspelling_at(&misspelled, 2), None);
+        
+        let capitalized = misspelled.iter().find(|m| m.2 == "Tmorrow").unwrap().clone();
+        assert!(replace_misspelling(&mut text, &capitalized, "tomorrow"));
+        assert!(replace_misspelling(&mut text, &misspelled[0], "tomorrow"));
+        assert_eq!(text, "See you (tomorrow) today. Tomorrow!");
+        
+        // The range no longer holds the word
+        assert!(!replace_misspelling(&mut text, &misspelled[0], "tomorrow"));
+    }
+    
+    #[test]
+    fn test_user_dictionary_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config").join(USER_DICTIONARY_FILE);
+        
+        let mut checker = SpellChecker::new();
+        checker.load_user_dictionary(&path).unwrap();
+        assert!(!checker.is_correct("egui"));
+        save_user_word(&path, "egui").unwrap();
+        save_user_word(&path, "Rustacean").unwrap();
+        
+        let mut checker = SpellChecker::new();
+        checker.load_user_dictionary(&path).unwrap();
+        assert!(checker.is_correct("egui"));
+        assert!(checker.is_correct("rustacean"));
+    }
+    
+    #[test]
+    fn test_switch_language() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("de.txt"), "Haus\nbaum\nGröße\n").unwrap();
+        fs::write(dir.path().join("fr.txt"), "maison\narbre\n").unwrap();
+        fs::write(dir.path().join("README.md"), "not a dictionary").unwrap();
+        
+        let mut checker = SpellChecker::new();
+        checker.load_dictionaries(dir.path()).unwrap();
+        assert_eq!(checker.languages().collect::<Vec<_>>(), vec!["de", "en", "fr"]);
+        checker.add_to_dictionary("egui".to_string());
+        
+        assert!(checker.set_language("de"));
+        assert!(checker.is_correct("Haus"));
+        assert!(checker.is_correct("größe"));
+        assert!(!checker.is_correct("maison"));
+        assert!(!checker.is_correct("house"));
+        assert_eq!(checker.suggest("hauss"), vec!["haus"]);
+        
+        assert!(checker.set_language("fr"));
+        assert!(checker.is_correct("maison"));
+        assert!(!checker.is_correct("Haus"));
+        assert_eq!(checker.check_text("une maison tst").len(), 2);
+        
+        // User words count everywhere; unknown languages are refused
+        assert!(checker.is_correct("egui"));
+        assert!(!checker.set_language("es"));
+        assert_eq!(checker.language(), "fr");
+    }
+    
+    #[test]
+    fn test_unchanged_text_not_rechecked() {
+        let mut checker = SpellChecker::new();
+        assert_eq!(checker.check_text_cached("the tst").len(), 1);
+        assert_eq!(checker.check_text_cached("the tst").len(), 1);
+        assert_eq!(checker.scans, 1);
+        
+        assert_eq!(checker.check_text_cached("the test note").len(), 1);
+        assert_eq!(checker.scans, 2);
+        
+        // Changing the dictionary makes the same text worth checking again
+        checker.add_to_dictionary("test".to_string());
+        assert!(checker.check_text_cached("the test note").is_empty());
+        assert_eq!(checker.scans, 3);
+    }
 }
@@ -1,12 +1,260 @@
+use chrono::{Local, SubsecRound};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs;
-use std::io;
-use std::path::Path;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-use crate::note::{Note, Folder, NoteMetadata};
+use crate::encryption::{Encryption, ENCRYPTED_PLACEHOLDER};
+use crate::error::{self, Error};
+use crate::frontmatter::{join_front_matter, split_front_matter, FrontMatter};
+use crate::images::{find_image_refs, is_image_file, resolve_image_path, ImageManager};
+use crate::links::{replace_wiki_links, wiki_link_names, LinkIndex, LinkManager, LinkResolver};
+use crate::note::{parse_timestamp, Note, Folder, FolderMetadata, NoteMetadata, SortOrder};
+use crate::tags::TagManager;
+use crate::tasks::{parse_tasks, Task};
+use crate::templates::{render_placeholders, Template};
+
+/// What an incremental cloud sync changed in the sync folder.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncSummary {
+    pub path: String,
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+/// The files a cloud sync mirrors: every note and its `.meta`, each with where it
+/// goes in the sync folder.
+#[derive(Debug, Clone, Default)]
+pub struct SyncPlan {
+    pub cloud_dir: PathBuf,
+    pub folders: Vec<PathBuf>,
+    pub files: Vec<(PathBuf, PathBuf)>, // (source, destination)
+}
+
+impl SyncPlan {
+    /// Copy new and changed files, so a sync client sees real diffs, then remove
+    /// whatever in the sync folder has no source any more. `progress(done, total)`
+    /// is called after each file; returning `false` stops the sync there, with an
+    /// `Interrupted` error and nothing deleted.
+    pub fn run(&self, mut progress: impl FnMut(usize, usize) -> bool) -> io::Result<SyncSummary> {
+        fs::create_dir_all(&self.cloud_dir)?;
+        let mut summary = SyncSummary {
+            path: self.cloud_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let mut synced: HashSet<PathBuf> = HashSet::new();
+        for folder in &self.folders {
+            fs::create_dir_all(folder)?;
+            synced.insert(folder.clone());
+        }
+        
+        for (done, (source, dest)) in self.files.iter().enumerate() {
+            match sync_file(source, dest)? {
+                SyncAction::Added => summary.added += 1,
+                SyncAction::Updated => summary.updated += 1,
+                SyncAction::Unchanged => {}
+            }
+            synced.insert(dest.clone());
+            if !progress(done + 1, self.files.len()) {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "Sync cancelled"));
+            }
+        }
+        
+        // Remove anything whose source note or folder is gone
+        for entry in walkdir::WalkDir::new(&self.cloud_dir)
+            .min_depth(1)
+            .contents_first(true)
+            .into_iter()
+            .flatten()
+        {
+            let path = entry.path();
+            if synced.contains(path) {
+                continue;
+            }
+            if entry.file_type().is_dir() {
+                fs::remove_dir(path).ok();
+            } else {
+                fs::remove_file(path)?;
+                summary.deleted += 1;
+            }
+        }
+        
+        Ok(summary)
+    }
+}
+
+/// What an Obsidian import brought in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    pub notes: usize,
+    pub attachments: usize,
+    /// `(note title, link target)` for links that didn't match any imported note.
+    pub unresolved_links: Vec<(String, String)>,
+}
+
+/// Most-linked notes listed in the statistics.
+const MOST_LINKED_LIMIT: usize = 10;
+
+/// Notes whose word pairs overlap at least this much count as duplicates.
+const NEAR_DUPLICATE_SIMILARITY: f64 = 0.8;
+
+/// An overview of the vault for the statistics panel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VaultStats {
+    pub total_notes: usize,
+    /// Words in unencrypted notes.
+    pub total_words: usize,
+    /// `(folder name, notes)` in vault order.
+    pub notes_per_folder: Vec<(String, usize)>,
+    /// `(note title, notes linking to it)`, most linked first. Unlinked notes are left out.
+    pub most_linked: Vec<(String, usize)>,
+    /// `(tag name, notes tagged)`, most used first.
+    pub tag_counts: Vec<(String, usize)>,
+    /// `(month as "YYYY-MM", notes created, notes last updated)`, oldest month first.
+    pub activity: Vec<(String, usize, usize)>,
+}
+
+/// A whole vault as JSON, for other tools to read: see `Storage::export_json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VaultExport {
+    pub folders: Vec<FolderExport>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FolderExport {
+    pub name: String,
+    pub notes: Vec<NoteExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoteExport {
+    pub title: String,
+    /// The body, without front-matter.
+    pub content: String,
+    #[serde(with = "crate::note::timestamp")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "crate::note::timestamp")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// Tag names, sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// An Obsidian note waiting to be written into the vault.
+struct PlannedNote {
+    source: PathBuf,
+    folder_name: String,
+    dest: PathBuf,
+    title: String,
+}
+
+/// Line endings notes are written with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineEnding {
+    /// Write whatever the note contains.
+    #[default]
+    AsIs,
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub const ALL: [LineEnding; 3] = [LineEnding::AsIs, LineEnding::Lf, LineEnding::CrLf];
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineEnding::AsIs => "Keep as is",
+            LineEnding::Lf => "LF (Unix, macOS)",
+            LineEnding::CrLf => "CRLF (Windows)",
+        }
+    }
+}
+
+/// Punctuation an ASCII-only policy keeps unless configured otherwise.
+pub const DEFAULT_FILENAME_ALLOWED: &str = "-_ ";
+
+/// Characters Windows refuses in file names, on top of `/` everywhere. Stripped on
+/// every platform, since vaults are synced between them.
+const ILLEGAL_FILENAME_CHARS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Which characters of a title make it into a note's file name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilenamePolicy {
+    /// Keep only ASCII letters and digits plus `allowed`, for tools that choke on anything else.
+    /// Otherwise everything a file name may contain is kept, "Café" and "日記" included.
+    #[serde(default)]
+    pub ascii_only: bool,
+    /// Extra characters kept in ASCII-only mode. Illegal characters are replaced regardless.
+    #[serde(default = "default_filename_allowed")]
+    pub allowed: String,
+}
+
+fn default_filename_allowed() -> String {
+    DEFAULT_FILENAME_ALLOWED.to_string()
+}
+
+impl Default for FilenamePolicy {
+    fn default() -> Self {
+        Self {
+            ascii_only: false,
+            allowed: default_filename_allowed(),
+        }
+    }
+}
+
+impl FilenamePolicy {
+    #[allow(dead_code)]
+    pub fn ascii_only() -> Self {
+        Self {
+            ascii_only: true,
+            ..Self::default()
+        }
+    }
+    
+    fn keeps(&self, c: char) -> bool {
+        if c.is_control() || ILLEGAL_FILENAME_CHARS.contains(&c) {
+            return false;
+        }
+        !self.ascii_only || c.is_ascii_alphanumeric() || self.allowed.contains(c)
+    }
+}
+
+enum SyncAction {
+    Added,
+    Updated,
+    Unchanged,
+}
 
 pub struct Storage {
     base_path: String,
     pub folders: Vec<Folder>,
+    line_ending: LineEnding,
+    trailing_newline: bool, // End every saved note with exactly one newline
+    filename_policy: FilenamePolicy,
+    session_password: Option<String>, // Encrypts notes saved in encrypted folders
+    read_only: bool,
+}
+
+/// Hidden file in each folder holding its `FolderMetadata`.
+const FOLDER_METADATA_FILE: &str = ".folder.meta";
+/// Hidden directory under the vault where archived notes are kept, one
+/// subdirectory per folder they came from. Being hidden keeps them out of
+/// the sidebar and search.
+const ARCHIVE_DIR: &str = ".archive";
+/// Extension of the sidecar holding a note's unsaved edits; see `Storage::write_draft`.
+/// Version control leaves these out of commits.
+pub const DRAFT_EXTENSION: &str = "draft";
+
+/// A note put away with `Storage::archive_note`.
+pub struct ArchivedNote {
+    pub folder: String, // Name of the folder it was archived from
+    pub note: Note,     // Its `file_path` is inside the archive
 }
 
 impl Storage {
@@ -17,6 +265,11 @@ impl Storage {
         let mut storage = Self {
             base_path,
             folders: Vec::new(),
+            line_ending: LineEnding::AsIs,
+            trailing_newline: false,
+            filename_policy: FilenamePolicy::default(),
+            session_password: None,
+            read_only: false,
         };
         
         // Load existing notes
@@ -25,6 +278,47 @@ impl Storage {
         storage
     }
     
+    /// How `save_note` normalizes line endings and the end of the file.
+    pub fn set_save_format(&mut self, line_ending: LineEnding, trailing_newline: bool) {
+        self.line_ending = line_ending;
+        self.trailing_newline = trailing_newline;
+    }
+    
+    /// How titles are turned into file names for notes created from now on.
+    pub fn set_filename_policy(&mut self, policy: FilenamePolicy) {
+        self.filename_policy = policy;
+    }
+    
+    /// The password notes in encrypted folders are sealed with, for this session only.
+    pub fn set_session_password(&mut self, password: Option<String>) {
+        self.session_password = password;
+    }
+    
+    pub fn session_password(&self) -> Option<&str> {
+        self.session_password.as_deref()
+    }
+    
+    /// Refuse every change to the vault, so it can be browsed without risk of
+    /// editing it. Mutating methods return `Error::ReadOnly` until this is turned off.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+    
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+    
+    fn check_writable(&self) -> error::Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+        Ok(())
+    }
+    
+    pub fn base_path(&self) -> &Path {
+        Path::new(&self.base_path)
+    }
+    
     fn load_all_notes(&mut self) {
         self.folders.clear();
         
@@ -39,25 +333,10 @@ impl Storage {
         if let Ok(entries) = fs::read_dir(base) {
             for entry in entries.flatten() {
                 let path = entry.path();
-                if path.is_dir() {
-                    if let Some(folder_name) = path.file_name() {
-                        let folder_name = folder_name.to_string_lossy().to_string();
-                        let folder_path = path.to_string_lossy().to_string();
-                        
-                        let mut folder = Folder::new(folder_name, folder_path.clone());
-                        
-                        // Load notes from this folder
-                        if let Ok(note_entries) = fs::read_dir(&path) {
-                            for note_entry in note_entries.flatten() {
-                                let note_path = note_entry.path();
-                                if note_path.extension().and_then(|s| s.to_str()) == Some("md") {
-                                    if let Ok(note) = self.load_note(&note_path) {
-                                        folder.add_note(note);
-                                    }
-                                }
-                            }
-                        }
-                        
+                // Skip .git and other hidden directories; they aren't note folders
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if path.is_dir() && !hidden {
+                    if let Some(folder) = self.load_folder(&path) {
                         self.folders.push(folder);
                     }
                 }
@@ -65,13 +344,149 @@ impl Storage {
         }
     }
     
+    fn load_folder(&self, path: &Path) -> Option<Folder> {
+        let folder_name = path.file_name()?.to_string_lossy().to_string();
+        let folder_path = path.to_string_lossy().to_string();
+        
+        let mut folder = Folder::new(folder_name, folder_path);
+        folder.encrypted = fs::read_to_string(path.join(FOLDER_METADATA_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str::<FolderMetadata>(&s).ok())
+            .is_some_and(|metadata| metadata.encrypted);
+        
+        // Load notes from this folder
+        if let Ok(note_entries) = fs::read_dir(path) {
+            for note_entry in note_entries.flatten() {
+                let note_path = note_entry.path();
+                if note_path.extension().and_then(|s| s.to_str()) == Some("md") {
+                    if let Ok(note) = self.load_note_header(&note_path) {
+                        folder.add_note(note);
+                    }
+                }
+            }
+        }
+        
+        Some(folder)
+    }
+    
+    /// Bring a single path changed outside the app (another editor, a git pull) back in
+    /// sync with disk. Returns `true` if anything in memory changed, so the app's own
+    /// saves, whose content already matches, don't trigger a reload loop.
+    pub fn reload_path(&mut self, path: &Path) -> bool {
+        let base = Path::new(&self.base_path);
+        let relative = match path.strip_prefix(base) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => match base.canonicalize().ok().and_then(|b| path.strip_prefix(b).ok()) {
+                Some(relative) => relative.to_path_buf(),
+                None => return false,
+            },
+        };
+        let parts: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        // Hidden directories (.git, the archive) aren't note folders
+        if parts.first().is_none_or(|part| part.starts_with('.')) {
+            return false;
+        }
+        let folder_dir = base.join(&parts[0]);
+        let folder_idx = self.folders.iter().position(|f| f.name == parts[0]);
+        
+        match (parts.len(), folder_idx) {
+            // A whole folder appeared or disappeared
+            (1, None) if folder_dir.is_dir() => match self.load_folder(&folder_dir) {
+                Some(folder) => {
+                    self.folders.push(folder);
+                    true
+                }
+                None => false,
+            },
+            (1, Some(idx)) if !folder_dir.exists() => {
+                self.folders.remove(idx);
+                true
+            }
+            (2, _) if relative.extension().and_then(|s| s.to_str()) != Some("md") => false,
+            // A note in a folder we haven't seen yet brings the folder with it
+            (2, None) if folder_dir.is_dir() => match self.load_folder(&folder_dir) {
+                Some(folder) => {
+                    self.folders.push(folder);
+                    true
+                }
+                None => false,
+            },
+            (2, Some(idx)) => {
+                let note_path = folder_dir.join(&parts[1]);
+                let note_idx = self.folders[idx].notes.iter().position(|n| {
+                    Path::new(&n.file_path).file_name() == Some(OsStr::new(&parts[1]))
+                });
+                
+                match (note_idx, self.load_note(&note_path)) {
+                    (Some(note_idx), Ok(note)) => {
+                        let existing = &self.folders[idx].notes[note_idx];
+                        // An unloaded body will be read fresh whenever it's needed
+                        if (!existing.content_loaded || existing.content == note.content)
+                            && existing.title == note.title
+                            && existing.front_matter == note.front_matter
+                        {
+                            return false;
+                        }
+                        self.folders[idx].notes[note_idx] = note;
+                        true
+                    }
+                    (None, Ok(note)) => {
+                        self.folders[idx].add_note(note);
+                        true
+                    }
+                    (Some(note_idx), Err(_)) if !note_path.exists() => {
+                        self.folders[idx].notes.remove(note_idx);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+    
     fn load_note(&self, path: &Path) -> io::Result<Note> {
-        let content = fs::read_to_string(path)?;
-        let title = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("Untitled")
-            .to_string();
+        let raw = fs::read_to_string(path)?;
+        let (front_matter, content) = split_front_matter(&raw);
+        let mut note = self.note_from_parts(path, front_matter, content.to_string());
+        note.saved_hash = Some(content_hash(&raw));
+        Ok(note)
+    }
+    
+    /// Like `load_note`, but reads only the front-matter block. The body is fetched
+    /// later by `ensure_loaded`, which keeps startup fast on large vaults.
+    fn load_note_header(&self, path: &Path) -> io::Result<Note> {
+        let mut header = String::new();
+        let mut lines = BufReader::new(fs::File::open(path)?);
+        if lines.read_line(&mut header)? > 0 && header.trim_end() == "---" {
+            loop {
+                let start = header.len();
+                if lines.read_line(&mut header)? == 0 || header[start..].trim_end() == "---" {
+                    break;
+                }
+            }
+        }
+        let (front_matter, _) = split_front_matter(&header);
+        
+        let mut note = self.note_from_parts(path, front_matter, String::new());
+        note.content_loaded = false;
+        Ok(note)
+    }
+    
+    fn note_from_parts(&self, path: &Path, front_matter: Option<FrontMatter>, content: String) -> Note {
+        // A front-matter title overrides the filename
+        let title = front_matter
+            .as_ref()
+            .and_then(|fm| fm.title.clone())
+            .unwrap_or_else(|| {
+                path.file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("Untitled")
+                    .to_string()
+            });
         
         // Try to load metadata
         let metadata_path = path.with_extension("meta");
@@ -81,18 +496,180 @@ impl Storage {
                 .and_then(|s| serde_json::from_str(&s).ok())
                 .unwrap_or_else(NoteMetadata::new)
         } else {
-            NoteMetadata::new()
+            // An exported note carries its timestamps in the front-matter instead
+            let mut metadata = NoteMetadata::new();
+            if let Some(fm) = &front_matter {
+                if let Some(created) = fm.created.as_deref().and_then(parse_timestamp) {
+                    metadata.created_at = created;
+                }
+                if let Some(updated) = fm.updated.as_deref().and_then(parse_timestamp) {
+                    metadata.updated_at = updated;
+                }
+            }
+            metadata
         };
         
-        Ok(Note::from_file(
+        let mut note = Note::from_file(
             path.to_string_lossy().to_string(),
             title,
             content,
             metadata,
-        ))
+        );
+        // Front-matter properties win over ones only in the metadata
+        if let Some(front_matter) = &front_matter {
+            note.properties.extend(front_matter.properties());
+        }
+        note.front_matter = front_matter;
+        note.disk_mtime = file_mtime(path);
+        note
+    }
+    
+    /// Whether the note's file was modified since `since`, the modification time it had
+    /// when the caller last read or wrote it. A file that has disappeared counts as changed.
+    pub fn changed_on_disk(&self, folder_idx: usize, note_idx: usize, since: Option<SystemTime>) -> bool {
+        self.folders.get(folder_idx)
+            .and_then(|f| f.notes.get(note_idx))
+            .is_some_and(|note| file_mtime(Path::new(&note.file_path)) != since)
+    }
+    
+    /// The note as it is on disk right now, without touching the copy in memory.
+    pub fn disk_version(&self, folder_idx: usize, note_idx: usize) -> io::Result<Note> {
+        let note = self.folders.get(folder_idx)
+            .and_then(|f| f.notes.get(note_idx))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Note not found"))?;
+        self.load_note(Path::new(&note.file_path))
+    }
+    
+    /// Current `(folder_idx, note_idx)` of the note stored at `file_path`.
+    pub fn find_note_by_path(&self, file_path: &str) -> Option<(usize, usize)> {
+        self.folders.iter().enumerate().find_map(|(folder_idx, folder)| {
+            folder.notes.iter()
+                .position(|n| n.file_path == file_path)
+                .map(|note_idx| (folder_idx, note_idx))
+        })
+    }
+    
+    /// Read a note's body from disk if it hasn't been yet.
+    pub fn ensure_loaded(&mut self, folder_idx: usize, note_idx: usize) -> io::Result<()> {
+        let note = self.folders.get_mut(folder_idx)
+            .and_then(|f| f.notes.get_mut(note_idx))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Note not found"))?;
+        load_body(note)
+    }
+    
+    /// Load every note's body, for features that need all content (search, statistics).
+    pub fn ensure_all_loaded(&mut self) {
+        load_all_bodies(&mut self.folders);
+    }
+    
+    /// Links between every note in the vault. Only notes modified since `index` last
+    /// saw them are read from disk; their links are recorded in it, and entries for
+    /// notes that are gone are dropped. Encrypted notes' links can't be read.
+    pub fn link_graph(&mut self, index: &mut LinkIndex) -> LinkManager {
+        let mut names = Vec::new();
+        for (folder_idx, folder) in self.folders.iter_mut().enumerate() {
+            for (note_idx, note) in folder.notes.iter_mut().enumerate() {
+                if note.is_encrypted {
+                    continue;
+                }
+                let links = match index.get(&note.file_path, note.disk_mtime) {
+                    Some(links) => links.to_vec(),
+                    None => {
+                        if let Err(e) = load_body(note) {
+                            eprintln!("Failed to load {}: {}", note.file_path, e);
+                            continue;
+                        }
+                        let links = wiki_link_names(&note.content);
+                        index.insert(&note.file_path, note.disk_mtime, links.clone());
+                        links
+                    }
+                };
+                names.push(((folder_idx, note_idx), links));
+            }
+        }
+        
+        let paths: HashSet<&str> = self.folders.iter()
+            .flat_map(|f| f.notes.iter().map(|n| n.file_path.as_str()))
+            .collect();
+        index.retain(|file_path| paths.contains(file_path));
+        LinkManager::from_link_names(&self.folders, &names)
+    }
+    
+    /// Every unchecked task in the vault with the note it came from. Loads any
+    /// bodies not read yet; encrypted notes are skipped.
+    pub fn collect_open_tasks(&mut self) -> Vec<(usize, usize, Task)> {
+        self.ensure_all_loaded();
+        
+        let mut open = Vec::new();
+        for (folder_idx, folder) in self.folders.iter().enumerate() {
+            for (note_idx, note) in folder.notes.iter().enumerate() {
+                if note.is_encrypted {
+                    continue;
+                }
+                for task in parse_tasks(&note.content).into_iter().filter(|t| !t.done) {
+                    open.push((folder_idx, note_idx, task));
+                }
+            }
+        }
+        open
+    }
+    
+    /// Totals, links, tags and activity across the vault. Loads any bodies not read yet.
+    pub fn compute_stats(&mut self, tags: &TagManager) -> VaultStats {
+        self.ensure_all_loaded();
+        let notes = || self.folders.iter().enumerate()
+            .flat_map(|(folder_idx, folder)| {
+                folder.notes.iter().enumerate().map(move |(note_idx, note)| ((folder_idx, note_idx), note))
+            });
+        
+        let mut stats = VaultStats {
+            total_notes: notes().count(),
+            total_words: notes()
+                .filter(|(_, note)| !note.is_encrypted)
+                .map(|(_, note)| note.content.split_whitespace().count())
+                .sum(),
+            notes_per_folder: self.folders.iter().map(|f| (f.name.clone(), f.notes.len())).collect(),
+            ..VaultStats::default()
+        };
+        
+        // Links from every note, not just the ones edited this session
+        let links = LinkManager::build(&self.folders);
+        let mut most_linked: Vec<(String, usize)> = notes()
+            .filter_map(|(id, note)| {
+                let sources: HashSet<_> = links.get_backlinks(id).into_iter().filter(|&s| s != id).collect();
+                (!sources.is_empty()).then(|| (note.title.clone(), sources.len()))
+            })
+            .collect();
+        most_linked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        most_linked.truncate(MOST_LINKED_LIMIT);
+        stats.most_linked = most_linked;
+        
+        let usage = tags.usage_counts(&self.folders);
+        let mut tag_counts: Vec<(String, usize)> = tags.all_tags().iter().enumerate()
+            .map(|(idx, tag)| (tag.name.clone(), usage.get(&idx).copied().unwrap_or(0)))
+            .collect();
+        tag_counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        stats.tag_counts = tag_counts;
+        
+        let mut activity: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for (_, note) in notes() {
+            let month = |time: chrono::DateTime<chrono::Utc>| time.with_timezone(&Local).format("%Y-%m").to_string();
+            activity.entry(month(note.created())).or_default().0 += 1;
+            activity.entry(month(note.updated())).or_default().1 += 1;
+        }
+        stats.activity = activity.into_iter().map(|(month, (created, updated))| (month, created, updated)).collect();
+        
+        stats
     }
     
-    pub fn create_folder(&mut self, name: &str) -> io::Result<()> {
+    pub fn create_folder(&mut self, name: &str) -> error::Result<()> {
+        self.check_writable()?;
+        if !is_valid_name(name) {
+            return Err(Error::InvalidName(name.to_string()));
+        }
+        if self.folders.iter().any(|f| f.name == name) {
+            return Err(Error::FolderExists(name.to_string()));
+        }
         let folder_path = Path::new(&self.base_path).join(name);
         fs::create_dir_all(&folder_path)?;
         
@@ -105,13 +682,55 @@ impl Storage {
         Ok(())
     }
     
-    pub fn create_note(&mut self, folder_idx: usize, title: &str) -> io::Result<usize> {
+    /// Index of the folder called `name`, creating it if there isn't one.
+    pub fn find_or_create_folder(&mut self, name: &str) -> error::Result<usize> {
+        if let Some(idx) = self.folders.iter().position(|f| f.name == name) {
+            return Ok(idx);
+        }
+        self.create_folder(name)?;
+        Ok(self.folders.len() - 1)
+    }
+    
+    /// Today's journal note in `folder_idx`, titled by the local date. It is created
+    /// from `template` the first time and reused on every later call that day.
+    pub fn get_or_create_daily_note(&mut self, folder_idx: usize, template: &Template) -> error::Result<usize> {
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        self.get_or_create_dated_note(folder_idx, &date, template)
+    }
+    
+    fn get_or_create_dated_note(&mut self, folder_idx: usize, date: &str, template: &Template) -> error::Result<usize> {
+        let folder = self.folders.get(folder_idx).ok_or(Error::FolderNotFound(folder_idx))?;
+        if let Some(note_idx) = folder.notes.iter().position(|n| n.title == date) {
+            return Ok(note_idx);
+        }
+        
+        self.create_note(folder_idx, date, &render_placeholders(&template.body, date, date))
+    }
+    
+    pub fn create_note(&mut self, folder_idx: usize, title: &str, initial_content: &str) -> error::Result<usize> {
+        self.check_writable()?;
+        if title.trim().is_empty() {
+            return Err(Error::InvalidName(title.to_string()));
+        }
         if let Some(folder) = self.folders.get_mut(folder_idx) {
-            let file_name = format!("{}.md", sanitize_filename(title));
-            let file_path = Path::new(&folder.path).join(&file_name);
+            let sealed = folder.encrypted;
+            if sealed && self.session_password.is_none() {
+                return Err(Error::FolderLocked(folder.name.clone()));
+            }
+            let file_stem = sanitize_filename(title, &self.filename_policy);
+            let file_path = unique_note_path(Path::new(&folder.path), &file_stem);
+            // A duplicate title gets the numbered name its file was given, "Ideas (2)",
+            // as it would when the vault is next loaded
+            let used_stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+            let title = if used_stem == file_stem { title.to_string() } else { used_stem.to_string() };
             
-            // Create empty file
-            fs::write(&file_path, "")?;
+            // Create file with its initial content (empty for a blank note). create_new
+            // guarantees an existing note is never truncated, even if one appeared meanwhile.
+            // In an encrypted folder the text only reaches disk once sealed, below.
+            let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&file_path)?;
+            if !sealed {
+                file.write_all(initial_content.as_bytes())?;
+            }
             
             // Create metadata file
             let metadata = NoteMetadata::new();
@@ -119,82 +738,2269 @@ impl Storage {
             let metadata_json = serde_json::to_string_pretty(&metadata)?;
             fs::write(&metadata_path, metadata_json)?;
             
-            let note = Note::new(title.to_string(), file_path.to_string_lossy().to_string());
+            let mut note = Note::new(title, file_path.to_string_lossy().to_string());
+            note.content = initial_content.to_string();
+            note.disk_mtime = file_mtime(&file_path);
             folder.add_note(note);
             
             // Return the index of the newly created note
             let note_idx = folder.notes.len() - 1;
+            if sealed {
+                self.save_note(folder_idx, note_idx)?;
+            }
             Ok(note_idx)
         } else {
-            Err(io::Error::new(io::ErrorKind::NotFound, "Folder not found"))
+            Err(Error::FolderNotFound(folder_idx))
+        }
+    }
+    
+    /// Whether a note in `folder_idx` is already called `title`. A `[[title]]` link
+    /// written in that folder would reach that note rather than a new one.
+    pub fn title_taken(&self, folder_idx: usize, title: &str) -> bool {
+        let title = title.trim();
+        self.folders.get(folder_idx).is_some_and(|f| f.notes.iter().any(|n| n.title == title))
+    }
+    
+    /// `title`, or `title (2)`, `title (3)`, ... if that is `title_taken` in `folder_idx`.
+    pub fn unique_title(&self, folder_idx: usize, title: &str) -> String {
+        let title = title.trim();
+        let mut candidate = title.to_string();
+        let mut counter = 2;
+        while self.title_taken(folder_idx, &candidate) {
+            candidate = format!("{} ({})", title, counter);
+            counter += 1;
+        }
+        candidate
+    }
+    
+    /// The note a `[[link]]` points to, created blank in `folder_idx` if no note has
+    /// that title yet. A `[[Folder/Title]]` link creates it in the folder it names.
+    /// Returns its `(folder_idx, note_idx)`.
+    pub fn create_note_for_link(&mut self, folder_idx: usize, link: &str) -> error::Result<(usize, usize)> {
+        let resolver = LinkResolver::new(&self.folders);
+        if let Some(found) = resolver.resolve(link, folder_idx) {
+            return Ok(found);
         }
+        let (folder_idx, title) = resolver.split_qualified(link).unwrap_or((folder_idx, link.trim()));
+        let note_idx = self.create_note(folder_idx, title, "")?;
+        Ok((folder_idx, note_idx))
     }
     
-    pub fn save_note(&mut self, folder_idx: usize, note_idx: usize) -> io::Result<()> {
+    /// Write a note's body and metadata. A plain note in an encrypted folder is
+    /// encrypted with the session password first, leaving a placeholder as its content.
+    pub fn save_note(&mut self, folder_idx: usize, note_idx: usize) -> error::Result<()> {
+        self.write_note(folder_idx, note_idx).map(|_| ())
+    }
+    
+    /// Save a note, returning whether its file was written. It isn't when the text
+    /// is the same as when the file was last read or written and the file hasn't
+    /// been touched since, so saving without edits leaves nothing to commit. The
+    /// `.meta` sidecar is likewise only written when it changed.
+    pub fn write_note(&mut self, folder_idx: usize, note_idx: usize) -> error::Result<bool> {
+        self.check_writable()?;
         if let Some(folder) = self.folders.get_mut(folder_idx) {
+            let folder_encrypted = folder.encrypted;
+            let folder_name = folder.name.clone();
             if let Some(note) = folder.notes.get_mut(note_idx) {
-                // Save content
-                fs::write(&note.file_path, &note.content)?;
+                // Never write an empty body over content we haven't read yet
+                load_body(note)?;
+                
+                if folder_encrypted && !note.is_encrypted {
+                    let password = self.session_password.as_deref()
+                        .ok_or(Error::FolderLocked(folder_name))?;
+                    let sealed = Encryption::new()
+                        .encrypt(&note.content, password)
+                        .map_err(Error::Encryption)?;
+                    note.encrypted_data = Some(sealed);
+                    note.is_encrypted = true;
+                    note.content = ENCRYPTED_PLACEHOLDER.to_string();
+                }
+                
+                // Save content, re-attaching any front-matter the file was loaded with
+                if let Some(front_matter) = note.front_matter.as_mut() {
+                    front_matter.set_properties(&note.properties);
+                    // Timestamps written by an export are kept current
+                    if front_matter.updated.is_some() {
+                        front_matter.updated = Some(note.updated_at.to_rfc3339());
+                    }
+                }
+                let raw = match &note.front_matter {
+                    Some(front_matter) => join_front_matter(front_matter, &note.content)
+                        .map_err(Error::FrontMatter)?,
+                    None => note.content.clone(),
+                };
+                // Ciphertext is written exactly as produced
+                let raw = if note.is_encrypted {
+                    raw
+                } else {
+                    normalize_text(&raw, self.line_ending, self.trailing_newline)
+                };
+                let path = Path::new(&note.file_path);
+                let hash = content_hash(&raw);
+                let unchanged = note.saved_hash == Some(hash)
+                    && note.disk_mtime.is_some()
+                    && note.disk_mtime == file_mtime(path);
+                if !unchanged {
+                    fs::write(path, raw)?;
+                    note.disk_mtime = file_mtime(path);
+                    note.saved_hash = Some(hash);
+                }
                 
                 // Save metadata with all new fields
                 let metadata = NoteMetadata::from_note(note);
-                let metadata_path = Path::new(&note.file_path).with_extension("meta");
+                let metadata_path = path.with_extension("meta");
                 let metadata_json = serde_json::to_string_pretty(&metadata)?;
-                fs::write(&metadata_path, metadata_json)?;
+                if fs::read_to_string(&metadata_path).ok().as_ref() != Some(&metadata_json) {
+                    fs::write(&metadata_path, metadata_json)?;
+                }
                 
-                return Ok(());
+                return Ok(!unchanged);
+            }
+        }
+        Err(Error::NoteNotFound(folder_idx, note_idx))
+    }
+    
+    /// Keep a note's unsaved text in a `.draft` sidecar next to it, so the edits
+    /// survive a crash. Nothing is written for a note that is, or is about to be,
+    /// encrypted, as the draft would hold its plaintext.
+    pub fn write_draft(&self, folder_idx: usize, note_idx: usize, content: &str) -> error::Result<()> {
+        self.check_writable()?;
+        let folder = self.folders.get(folder_idx).ok_or(Error::FolderNotFound(folder_idx))?;
+        let note = folder.notes.get(note_idx).ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
+        if folder.encrypted || note.is_encrypted {
+            return Ok(());
+        }
+        fs::write(draft_path(note), content)?;
+        Ok(())
+    }
+    
+    /// The text a note's draft holds, if it has one that differs from the saved note.
+    pub fn recover_draft(&mut self, folder_idx: usize, note_idx: usize) -> error::Result<Option<String>> {
+        let note = self.folders.get_mut(folder_idx)
+            .and_then(|f| f.notes.get_mut(note_idx))
+            .ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
+        let draft = match fs::read_to_string(draft_path(note)) {
+            Ok(draft) => draft,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        load_body(note)?;
+        Ok((draft != note.content).then_some(draft))
+    }
+    
+    /// Remove a note's draft, once it's saved or its edits are thrown away.
+    pub fn clear_draft(&self, folder_idx: usize, note_idx: usize) -> error::Result<()> {
+        self.check_writable()?;
+        let note = self.folders.get(folder_idx)
+            .and_then(|f| f.notes.get(note_idx))
+            .ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
+        remove_if_exists(&draft_path(note))?;
+        Ok(())
+    }
+    
+    /// Notes left with a draft that was never saved, e.g. by a crash, to offer for
+    /// recovery when the vault opens. Drafts that match their note are removed.
+    pub fn notes_with_drafts(&mut self) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+        for folder_idx in 0..self.folders.len() {
+            for note_idx in 0..self.folders[folder_idx].notes.len() {
+                if !draft_path(&self.folders[folder_idx].notes[note_idx]).exists() {
+                    continue;
+                }
+                match self.recover_draft(folder_idx, note_idx) {
+                    Ok(Some(_)) => found.push((folder_idx, note_idx)),
+                    Ok(None) if !self.read_only => {
+                        self.clear_draft(folder_idx, note_idx).ok();
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Failed to read draft of note {} in folder {}: {}", note_idx, folder_idx, e),
+                }
             }
         }
-        Err(io::Error::new(io::ErrorKind::NotFound, "Note not found"))
+        found
+    }
+    
+    /// Folder holding a note's file; its embedded images live under it.
+    fn note_dir(&self, folder_idx: usize, note_idx: usize) -> error::Result<PathBuf> {
+        let note = self.folders.get(folder_idx)
+            .and_then(|f| f.notes.get(note_idx))
+            .ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
+        Ok(Path::new(&note.file_path).parent().map(Path::to_path_buf).unwrap_or_default())
+    }
+    
+    /// Copy an image file into the note's `images/` folder and return the Markdown
+    /// reference to it.
+    pub fn import_image(&self, folder_idx: usize, note_idx: usize, source: &Path) -> error::Result<String> {
+        self.check_writable()?;
+        ImageManager::new().import_image(source, &self.note_dir(folder_idx, note_idx)?)
+            .map_err(Error::Image)
+    }
+    
+    /// Save RGBA pixels (e.g. from the clipboard) as a PNG in the note's `images/`
+    /// folder and return the Markdown reference to it.
+    pub fn save_pasted_image(&self, folder_idx: usize, note_idx: usize, rgba: &[u8], width: u32, height: u32) -> error::Result<String> {
+        self.check_writable()?;
+        ImageManager::new().save_pasted_image(rgba, width, height, &self.note_dir(folder_idx, note_idx)?)
+            .map_err(Error::Image)
+    }
+    
+    /// Delete a note's file and metadata and drop it from its folder. Notes after it
+    /// in the folder move up one index.
+    pub fn delete_note(&mut self, folder_idx: usize, note_idx: usize) -> error::Result<Note> {
+        self.check_writable()?;
+        let note = self.folders.get(folder_idx)
+            .and_then(|f| f.notes.get(note_idx))
+            .ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
+        let path = Path::new(&note.file_path);
+        remove_if_exists(path)?;
+        remove_if_exists(&path.with_extension("meta"))?;
+        remove_if_exists(&path.with_extension(DRAFT_EXTENSION))?;
+        Ok(self.folders[folder_idx].notes.remove(note_idx))
+    }
+    
+    /// Delete a folder's directory with everything in it. Folders after it move up one index.
+    pub fn delete_folder(&mut self, folder_idx: usize) -> error::Result<Folder> {
+        self.check_writable()?;
+        let folder = self.folders.get(folder_idx).ok_or(Error::FolderNotFound(folder_idx))?;
+        match fs::remove_dir_all(&folder.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(self.folders.remove(folder_idx))
     }
     
-    pub fn export_to_cloud(&self) -> io::Result<String> {
-        // This creates a backup/sync folder that user can manually upload to cloud
-        let cloud_path = format!("{}_cloud_sync", self.base_path);
+    /// Give a note a new title, renaming its files to match. A front-matter `title`
+    /// is updated too.
+    #[allow(dead_code)]
+    pub fn rename_note(&mut self, folder_idx: usize, note_idx: usize, title: &str) -> error::Result<()> {
+        self.check_writable()?;
+        let title = title.trim();
+        if title.is_empty() {
+            return Err(Error::InvalidName(title.to_string()));
+        }
+        let policy = self.filename_policy.clone();
+        let folder = self.folders.get_mut(folder_idx).ok_or(Error::FolderNotFound(folder_idx))?;
+        let note = folder.notes.get_mut(note_idx).ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
         
-        // Remove old sync folder if exists
-        if Path::new(&cloud_path).exists() {
-            fs::remove_dir_all(&cloud_path)?;
+        let old_path = PathBuf::from(&note.file_path);
+        let stem = sanitize_filename(title, &policy);
+        if old_path.file_stem() != Some(OsStr::new(&stem)) {
+            let new_path = unique_note_path(Path::new(&folder.path), &stem);
+            move_note_files(&old_path, &new_path)?;
+            note.file_path = new_path.to_string_lossy().to_string();
         }
+        note.title = title.to_string();
         
-        // Create new sync folder
-        fs::create_dir_all(&cloud_path)?;
+        match note.front_matter.as_mut() {
+            Some(front_matter) if front_matter.title.is_some() => {
+                front_matter.title = Some(title.to_string());
+                self.save_note(folder_idx, note_idx)
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    /// Mark a folder as encrypted or not. Turning it on needs the session password and
+    /// encrypts every plain note already in it; returns their indices. Turning it off
+    /// leaves notes that are encrypted as they are.
+    pub fn set_folder_encrypted(&mut self, folder_idx: usize, encrypted: bool) -> error::Result<Vec<usize>> {
+        self.check_writable()?;
+        let folder = self.folders.get_mut(folder_idx).ok_or(Error::FolderNotFound(folder_idx))?;
+        if encrypted && self.session_password.is_none() {
+            return Err(Error::FolderLocked(folder.name.clone()));
+        }
+        let metadata_json = serde_json::to_string_pretty(&FolderMetadata { encrypted })?;
+        fs::write(Path::new(&folder.path).join(FOLDER_METADATA_FILE), metadata_json)?;
+        folder.encrypted = encrypted;
         
-        // Copy all folders and notes
-        for folder in &self.folders {
-            let folder_sync_path = Path::new(&cloud_path).join(&folder.name);
-            fs::create_dir_all(&folder_sync_path)?;
-            
-            for note in &folder.notes {
-                let note_path = Path::new(&note.file_path);
-                let note_name = note_path.file_name().unwrap();
-                let dest_path = folder_sync_path.join(note_name);
-                
-                fs::copy(&note.file_path, &dest_path)?;
-                
-                // Copy metadata
-                let metadata_path = note_path.with_extension("meta");
-                if metadata_path.exists() {
-                    let dest_meta = dest_path.with_extension("meta");
-                    fs::copy(&metadata_path, &dest_meta)?;
+        let plain: Vec<usize> = if encrypted {
+            (0..folder.notes.len()).filter(|&n| !folder.notes[n].is_encrypted).collect()
+        } else {
+            Vec::new()
+        };
+        for &note_idx in &plain {
+            self.save_note(folder_idx, note_idx)?;
+        }
+        Ok(plain)
+    }
+    
+    /// Move a note into another folder, returning its index there. It goes to the
+    /// end of the folder's manual order.
+    #[allow(dead_code)]
+    pub fn move_note(&mut self, folder_idx: usize, note_idx: usize, dest_folder: usize) -> error::Result<usize> {
+        self.check_writable()?;
+        if folder_idx == dest_folder {
+            return Err(Error::SameFolder);
+        }
+        let dest_dir = PathBuf::from(&self.folders.get(dest_folder).ok_or(Error::FolderNotFound(dest_folder))?.path);
+        let note = self.folders.get(folder_idx)
+            .and_then(|f| f.notes.get(note_idx))
+            .ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
+        
+        let old_path = PathBuf::from(&note.file_path);
+        let stem = old_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let new_path = unique_note_path(&dest_dir, &stem);
+        move_note_files(&old_path, &new_path)?;
+        
+        let mut note = self.folders[folder_idx].notes.remove(note_idx);
+        note.file_path = new_path.to_string_lossy().to_string();
+        note.order = None;
+        let dest = &mut self.folders[dest_folder];
+        dest.add_note(note);
+        let moved = dest.notes.len() - 1;
+        save_metadata(&dest.notes[moved])?;
+        Ok(moved)
+    }
+    
+    /// Move a note into the archive, out of its folder but not deleted. Notes after
+    /// it in the folder move up one index.
+    pub fn archive_note(&mut self, folder_idx: usize, note_idx: usize) -> error::Result<Note> {
+        self.check_writable()?;
+        let folder = self.folders.get(folder_idx).ok_or(Error::FolderNotFound(folder_idx))?;
+        let note = folder.notes.get(note_idx).ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
+        let archive_dir = Path::new(&self.base_path).join(ARCHIVE_DIR).join(&folder.name);
+        fs::create_dir_all(&archive_dir)?;
+        
+        let old_path = PathBuf::from(&note.file_path);
+        let stem = old_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let new_path = unique_note_path(&archive_dir, &stem);
+        move_note_files(&old_path, &new_path)?;
+        
+        let mut note = self.folders[folder_idx].notes.remove(note_idx);
+        note.file_path = new_path.to_string_lossy().to_string();
+        Ok(note)
+    }
+    
+    /// Every archived note, by folder and then title.
+    pub fn list_archived(&self) -> Vec<ArchivedNote> {
+        let mut archived = Vec::new();
+        let Ok(folders) = fs::read_dir(Path::new(&self.base_path).join(ARCHIVE_DIR)) else {
+            return archived;
+        };
+        for folder_entry in folders.flatten().filter(|e| e.path().is_dir()) {
+            let folder = folder_entry.file_name().to_string_lossy().to_string();
+            for entry in fs::read_dir(folder_entry.path()).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|s| s.to_str()) != Some("md") {
+                    continue;
+                }
+                if let Ok(note) = self.load_note(&path) {
+                    archived.push(ArchivedNote { folder: folder.clone(), note });
                 }
             }
         }
+        archived.sort_by(|a, b| a.folder.cmp(&b.folder).then_with(|| a.note.title.cmp(&b.note.title)));
+        archived
+    }
+    
+    /// Bring an archived note back into the folder it came from, re-creating the
+    /// folder if it has since been deleted. Returns where it ended up; it goes to
+    /// the end of the folder's manual order.
+    pub fn unarchive(&mut self, archived_path: &Path) -> error::Result<(usize, usize)> {
+        self.check_writable()?;
+        let archive_root = Path::new(&self.base_path).join(ARCHIVE_DIR);
+        let folder_name = archived_path
+            .parent()
+            .filter(|dir| dir.parent() == Some(archive_root.as_path()) && archived_path.is_file())
+            .and_then(|dir| dir.file_name())
+            .ok_or_else(|| Error::NotArchived(archived_path.to_path_buf()))?
+            .to_string_lossy()
+            .to_string();
+        let folder_idx = self.find_or_create_folder(&folder_name)?;
         
-        Ok(cloud_path)
+        let stem = archived_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let new_path = unique_note_path(Path::new(&self.folders[folder_idx].path), &stem);
+        move_note_files(archived_path, &new_path)?;
+        
+        let mut note = self.load_note_header(&new_path)?;
+        note.order = None;
+        let folder = &mut self.folders[folder_idx];
+        folder.add_note(note);
+        let note_idx = folder.notes.len() - 1;
+        save_metadata(&folder.notes[note_idx])?;
+        Ok((folder_idx, note_idx))
     }
-}
-
-fn sanitize_filename(name: &str) -> String {
-    name.chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' {
-                c
-            } else {
-                '_'
+    
+    /// Groups of notes with the same content, or close enough to it: sharing at least
+    /// `NEAR_DUPLICATE_SIMILARITY` of their word pairs. Each group has at least two
+    /// notes, in vault order.
+    /// Encrypted and blank notes are left out.
+    pub fn find_duplicates(&mut self) -> Vec<Vec<(usize, usize)>> {
+        self.ensure_all_loaded();
+        
+        // Identical content, ignoring trailing whitespace and line endings
+        let mut exact: Vec<(String, Vec<(usize, usize)>)> = Vec::new();
+        let mut by_content: HashMap<String, usize> = HashMap::new();
+        for (folder_idx, folder) in self.folders.iter().enumerate() {
+            for (note_idx, note) in folder.notes.iter().enumerate() {
+                if note.is_encrypted {
+                    continue;
+                }
+                let text = note.content.lines().map(str::trim_end).collect::<Vec<_>>().join("\n");
+                let text = text.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                match by_content.get(text) {
+                    Some(&group) => exact[group].1.push((folder_idx, note_idx)),
+                    None => {
+                        by_content.insert(text.to_string(), exact.len());
+                        exact.push((text.to_string(), vec![(folder_idx, note_idx)]));
+                    }
+                }
+            }
+        }
+        
+        // Then join groups whose content is nearly the same
+        let shingles: Vec<HashSet<String>> = exact.iter().map(|(text, _)| word_pairs(text)).collect();
+        let mut parent: Vec<usize> = (0..exact.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for (i, j) in similar_candidates(&shingles, NEAR_DUPLICATE_SIMILARITY) {
+            if jaccard(&shingles[i], &shingles[j]) >= NEAR_DUPLICATE_SIMILARITY {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[b.max(a)] = a.min(b);
+            }
+        }
+        
+        let mut groups: Vec<Vec<(usize, usize)>> = vec![Vec::new(); exact.len()];
+        for (i, (_, notes)) in exact.into_iter().enumerate() {
+            groups[root(&mut parent, i)].extend(notes);
+        }
+        let mut groups: Vec<_> = groups.into_iter().filter(|g| g.len() > 1).collect();
+        for group in &mut groups {
+            group.sort();
+        }
+        groups.sort();
+        groups
+    }
+    
+    /// Fold `duplicates` into `keep` and delete them. The kept note gains their tags
+    /// and any properties it doesn't have, and paragraphs it doesn't already contain
+    /// are appended after a rule. `[[links]]` to the deleted notes are pointed at the
+    /// kept one. Returns where `keep` ends up, since deleting notes ahead of it in its
+    /// folder moves it up.
+    pub fn merge_notes(&mut self, keep: (usize, usize), duplicates: &[(usize, usize)]) -> error::Result<(usize, usize)> {
+        self.check_writable()?;
+        let mut duplicates: Vec<(usize, usize)> = duplicates.iter().copied().filter(|&d| d != keep).collect();
+        duplicates.sort();
+        duplicates.dedup();
+        for &(folder_idx, note_idx) in std::iter::once(&keep).chain(&duplicates) {
+            self.ensure_loaded(folder_idx, note_idx)
+                .map_err(|_| Error::NoteNotFound(folder_idx, note_idx))?;
+        }
+        self.ensure_all_loaded();
+        self.relink_notes(&duplicates, keep)?;
+        
+        let merged: Vec<Note> = duplicates.iter()
+            .map(|&(f, n)| self.folders[f].notes[n].clone())
+            .collect();
+        let kept = &mut self.folders[keep.0].notes[keep.1];
+        for other in &merged {
+            for &tag in &other.tags.tag_indices {
+                kept.add_tag(tag);
+            }
+            if let Some(other_fm) = other.front_matter.as_ref().filter(|fm| !fm.tags.is_empty()) {
+                let kept_fm = kept.front_matter.get_or_insert_with(Default::default);
+                for tag in &other_fm.tags {
+                    if !kept_fm.tags.contains(tag) {
+                        kept_fm.tags.push(tag.clone());
+                    }
+                }
+            }
+            for (key, value) in &other.properties {
+                kept.properties.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+            // Only paragraphs the kept note lacks, so shared text isn't repeated
+            let extra: Vec<&str> = other.content
+                .split("\n\n")
+                .map(str::trim)
+                .filter(|paragraph| !paragraph.is_empty() && !kept.content.contains(paragraph))
+                .collect();
+            if !extra.is_empty() {
+                kept.content = format!("{}\n\n---\n\n{}\n", kept.content.trim_end(), extra.join("\n\n"));
+            }
+            kept.updated_at = kept.updated_at.max(other.updated_at);
+            kept.created_at = kept.created_at.min(other.created_at);
+        }
+        let kept_path = kept.file_path.clone();
+        self.save_note(keep.0, keep.1)?;
+        
+        // Highest indices first so the rest stay valid
+        for &(folder_idx, note_idx) in duplicates.iter().rev() {
+            self.delete_note(folder_idx, note_idx)?;
+        }
+        self.find_note_by_path(&kept_path).ok_or(Error::NoteNotFound(keep.0, keep.1))
+    }
+    
+    /// Point `[[links]]` that resolve to one of `removed` at `keep` instead, saving
+    /// each note that changes. The new link names `keep`'s folder only when its title
+    /// alone would lead somewhere else once `removed` are gone. Encrypted notes are
+    /// left alone.
+    fn relink_notes(&mut self, removed: &[(usize, usize)], keep: (usize, usize)) -> error::Result<()> {
+        let resolver = LinkResolver::new(&self.folders);
+        let title = self.folders[keep.0].notes[keep.1].title.clone();
+        let qualified = format!("{}/{}", self.folders[keep.0].name, title);
+        let remaining: Vec<(usize, usize)> = resolver.candidates(&title)
+            .into_iter()
+            .filter(|note| !removed.contains(note))
+            .collect();
+        
+        let mut changed = Vec::new();
+        for (folder_idx, folder) in self.folders.iter_mut().enumerate() {
+            for (note_idx, note) in folder.notes.iter_mut().enumerate() {
+                if note.is_encrypted || removed.contains(&(folder_idx, note_idx)) {
+                    continue;
+                }
+                let relinked = replace_wiki_links(&note.content, |name| {
+                    if !resolver.resolve(name, folder_idx).is_some_and(|target| removed.contains(&target)) {
+                        return Some(format!("[[{}]]", name));
+                    }
+                    let by_title = remaining.iter()
+                        .find(|&&(f, _)| f == folder_idx)
+                        .or(remaining.first());
+                    Some(format!("[[{}]]", if by_title == Some(&keep) { &title } else { &qualified }))
+                });
+                if relinked != note.content {
+                    note.set_content(&relinked);
+                    note.update_timestamp();
+                    changed.push((folder_idx, note_idx));
+                }
+            }
+        }
+        for (folder_idx, note_idx) in changed {
+            self.save_note(folder_idx, note_idx)?;
+        }
+        Ok(())
+    }
+    
+    /// Move the note at position `from` of the folder's manual order to position `to`,
+    /// then number every note in the folder and save their metadata. Positions are
+    /// in `SortOrder::Manual` display order, not indices into `notes`.
+    pub fn reorder_note(&mut self, folder_idx: usize, from: usize, to: usize) -> error::Result<()> {
+        self.check_writable()?;
+        let folder = self.folders.get_mut(folder_idx)
+            .ok_or(Error::FolderNotFound(folder_idx))?;
+        let mut order = folder.sorted_note_indices(SortOrder::Manual);
+        if from >= order.len() || to >= order.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Note position out of range").into());
+        }
+        
+        let moved = order.remove(from);
+        order.insert(to, moved);
+        for (position, note_idx) in order.into_iter().enumerate() {
+            let note = &mut folder.notes[note_idx];
+            if note.order == Some(position) {
+                continue;
+            }
+            // Only the metadata changes; the body may not even be loaded
+            note.order = Some(position);
+            save_metadata(note)?;
+        }
+        Ok(())
+    }
+    
+    /// Set the emoji and color swatch the sidebar shows for a note. Only its metadata
+    /// is rewritten.
+    pub fn set_note_appearance(&mut self, folder_idx: usize, note_idx: usize, icon: Option<String>, color: Option<[u8; 3]>) -> error::Result<()> {
+        self.check_writable()?;
+        let note = self.folders.get_mut(folder_idx)
+            .and_then(|f| f.notes.get_mut(note_idx))
+            .ok_or(Error::NoteNotFound(folder_idx, note_idx))?;
+        note.icon = icon;
+        note.color = color;
+        save_metadata(note)?;
+        Ok(())
+    }
+    
+    /// Where startup backups go unless configured otherwise: `<vault>_backups` next to
+    /// the vault, like the cloud sync folder. Built from the vault's parent and name,
+    /// so a path given as `notes/` or `.` doesn't put the backups inside the vault.
+    pub fn default_backup_root(&self) -> PathBuf {
+        let vault = self.base_path();
+        let vault = match vault.file_name() {
+            Some(_) => vault.to_path_buf(),
+            None => vault.canonicalize().unwrap_or_else(|_| vault.to_path_buf()),
+        };
+        let name = vault.file_name().map_or("vault".into(), |n| n.to_string_lossy());
+        vault.parent().unwrap_or(Path::new("")).join(format!("{}_backups", name))
+    }
+    
+    /// Copy the vault into a new timestamped directory under `backup_root`, then delete
+    /// the oldest backups there so at most `keep_n` remain. The `.git` history is left
+    /// out; it's already a backup of its own. Returns the new backup's path.
+    pub fn create_backup(&self, backup_root: &Path, keep_n: usize) -> io::Result<PathBuf> {
+        backup_vault(self.base_path(), backup_root, keep_n)
+    }
+    
+    /// Mirror the vault into a sync folder the user can upload to the cloud.
+    #[allow(dead_code)]
+    pub fn export_to_cloud(&self) -> io::Result<SyncSummary> {
+        self.cloud_sync_plan().run(|_, _| true)
+    }
+    
+    /// What `export_to_cloud` would copy. Only paths are gathered, so this is cheap
+    /// to take under the storage lock and run elsewhere.
+    pub fn cloud_sync_plan(&self) -> SyncPlan {
+        let cloud_dir = PathBuf::from(format!("{}_cloud_sync", self.base_path));
+        let mut plan = SyncPlan {
+            cloud_dir: cloud_dir.clone(),
+            ..Default::default()
+        };
+        for folder in &self.folders {
+            let folder_sync_path = cloud_dir.join(&folder.name);
+            for note in &folder.notes {
+                let note_path = Path::new(&note.file_path);
+                let Some(note_name) = note_path.file_name() else {
+                    continue;
+                };
+                let dest_path = folder_sync_path.join(note_name);
+                plan.files.push((note_path.to_path_buf(), dest_path.clone()));
+                
+                let metadata_path = note_path.with_extension("meta");
+                if metadata_path.exists() {
+                    plan.files.push((metadata_path, dest_path.with_extension("meta")));
+                }
+            }
+            plan.folders.push(folder_sync_path);
+        }
+        plan
+    }
+    
+    /// Import an Obsidian vault. Notes at its root go into a folder named after the
+    /// vault; nested directories become one folder each, named by their path
+    /// ("Projects - Rust"). `[[links]]` are rewritten to the plain `[[Title]]` form
+    /// this app resolves, dropping paths, headings and aliases. Embedded attachments,
+    /// both `![[file.png]]` and relative `![](...)`, are copied into the folder's
+    /// `images/`, renamed if another file already has the name; ones that aren't
+    /// images become plain links. Hidden directories such as `.obsidian` are skipped.
+    pub fn import_obsidian(&mut self, vault_path: &Path) -> error::Result<ImportReport> {
+        self.check_writable()?;
+        // A leading dot would hide the folder from load_all_notes
+        let vault_name = vault_path
+            .file_name()
+            .map(|n| sanitize_filename(&n.to_string_lossy(), &self.filename_policy))
+            .unwrap_or_else(|| "Obsidian".to_string());
+        
+        // By path in the vault, which links may give in full or leave at the file name
+        let mut attachments: BTreeMap<String, PathBuf> = BTreeMap::new();
+        let mut sources = Vec::new();
+        let walker = walkdir::WalkDir::new(vault_path)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'));
+        for entry in walker {
+            let entry = entry.map_err(io::Error::other)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path().to_path_buf();
+            if path.extension() == Some(OsStr::new("md")) {
+                sources.push(path);
+            } else if let Ok(relative) = path.strip_prefix(vault_path) {
+                attachments.insert(vault_key(relative), path);
+            }
+        }
+        
+        // Decide every destination first, so links can use the titles notes end up with
+        let mut planned = Vec::new();
+        let mut reserved: HashSet<PathBuf> = HashSet::new();
+        let mut titles: HashMap<String, String> = HashMap::new();
+        for source in sources {
+            let relative_dir = source
+                .parent()
+                .and_then(|dir| dir.strip_prefix(vault_path).ok())
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let folder_name = if relative_dir.as_os_str().is_empty() {
+                vault_name.clone()
+            } else {
+                relative_dir
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" - ")
+            };
+            
+            let stem = source.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let dir = Path::new(&self.base_path).join(&folder_name);
+            let file_stem = sanitize_filename(&stem, &self.filename_policy);
+            let mut dest = unique_note_path(&dir, &file_stem);
+            let mut counter = 2;
+            while reserved.contains(&dest) {
+                dest = unique_note_path(&dir, &format!("{} ({})", file_stem, counter));
+                counter += 1;
+            }
+            reserved.insert(dest.clone());
+            
+            let title = dest.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            titles.entry(stem.to_lowercase()).or_insert_with(|| title.clone());
+            planned.push(PlannedNote { source, folder_name, dest, title });
+        }
+        
+        let mut report = ImportReport::default();
+        // The name each attachment got in each images/ folder, so one embedded twice
+        // is copied once, and the destinations handed out so far
+        let mut placed: HashMap<(PathBuf, PathBuf), String> = HashMap::new();
+        let mut taken: HashSet<PathBuf> = HashSet::new();
+        for note in planned {
+            if !self.folders.iter().any(|f| f.name == note.folder_name) {
+                self.create_folder(&note.folder_name)?;
+            }
+            let images_dir = note.dest.parent().unwrap_or(Path::new(&self.base_path)).join("images");
+            let note_dir = note.source
+                .parent()
+                .and_then(|dir| dir.strip_prefix(vault_path).ok())
+                .map(vault_key)
+                .unwrap_or_default();
+            
+            let mut copies = Vec::new();
+            let mut place = |source: &Path| {
+                placed
+                    .entry((images_dir.clone(), source.to_path_buf()))
+                    .or_insert_with(|| {
+                        let name = unique_attachment_name(&images_dir, &attachment_name(source), &taken);
+                        taken.insert(images_dir.join(&name));
+                        copies.push((source.to_path_buf(), name.clone()));
+                        name
+                    })
+                    .clone()
+            };
+            let content = fs::read_to_string(&note.source)?;
+            let content = import_markdown_images(&content, &note.source, &mut place);
+            let (content, unresolved) = convert_wiki_links(&content, &titles, &attachments, &note_dir, &mut place);
+            
+            for (source, name) in copies {
+                fs::create_dir_all(&images_dir)?;
+                fs::copy(&source, images_dir.join(&name))?;
+                report.attachments += 1;
+            }
+            fs::write(&note.dest, content)?;
+            report.notes += 1;
+            report.unresolved_links.extend(unresolved.into_iter().map(|link| (note.title.clone(), link)));
+        }
+        
+        self.load_all_notes();
+        Ok(report)
+    }
+    
+    /// Every folder and note as pretty-printed JSON: titles, bodies, timestamps and
+    /// tag names from `tags`. Encrypted notes are left out, since only their
+    /// ciphertext is readable. Loads any bodies not read yet.
+    pub fn export_json(&mut self, tags: &TagManager) -> error::Result<String> {
+        self.ensure_all_loaded();
+        let folders = self.folders.iter()
+            .map(|folder| FolderExport {
+                name: folder.name.clone(),
+                notes: folder.notes.iter()
+                    .filter(|note| !note.is_encrypted)
+                    .map(|note| {
+                        let mut tag_names: Vec<String> = note.tags.get_tags(tags).into_iter().map(|t| t.name.clone()).collect();
+                        tag_names.sort();
+                        NoteExport {
+                            title: note.title.clone(),
+                            content: note.content.clone(),
+                            created_at: note.created(),
+                            updated_at: note.updated(),
+                            tags: tag_names,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(serde_json::to_string_pretty(&VaultExport { folders })?)
+    }
+    
+    /// Write `export_json` to `path`, returning how many notes it holds.
+    pub fn export_json_to(&mut self, tags: &TagManager, path: &Path) -> error::Result<usize> {
+        let json = self.export_json(tags)?;
+        fs::write(path, json)?;
+        Ok(self.folders.iter().flat_map(|f| &f.notes).filter(|n| !n.is_encrypted).count())
+    }
+    
+    /// Add the folders and notes of a vault exported with `export_json`, keeping
+    /// their timestamps. Folders are matched by name and created when missing;
+    /// notes are always added, so importing twice gives two copies. Tags are
+    /// registered in `tags`, which the caller saves. Returns the number of notes.
+    pub fn import_json(&mut self, json: &str, tags: &mut TagManager) -> error::Result<usize> {
+        self.check_writable()?;
+        let vault: VaultExport = serde_json::from_str(json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        
+        let mut imported = 0;
+        for folder in vault.folders {
+            let folder_idx = self.find_or_create_folder(&folder.name)?;
+            for exported in folder.notes {
+                let note_idx = self.create_note(folder_idx, &exported.title, &exported.content)?;
+                let note = &mut self.folders[folder_idx].notes[note_idx];
+                note.created_at = exported.created_at;
+                note.updated_at = exported.updated_at;
+                for name in exported.tags {
+                    note.add_tag(tags.add_tag(name));
+                }
+                self.save_note(folder_idx, note_idx)?;
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
+}
+
+/// Copy a whole vault, version history included, into `dest` so it can be reopened
+/// from there. `dest` must be empty or not exist yet; the original is left as is.
+pub fn copy_vault(source: &Path, dest: &Path) -> io::Result<usize> {
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "Destination is not empty"));
+    }
+    fs::create_dir_all(dest)?;
+    
+    let mut copied = 0;
+    for entry in walkdir::WalkDir::new(source).min_depth(1) {
+        let entry = entry.map_err(io::Error::other)?;
+        let relative = entry.path().strip_prefix(source).map_err(io::Error::other)?;
+        let target = dest.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+/// `Storage::create_backup` for the vault at `source`, without needing its `Storage`,
+/// so a scheduled backup can run on a background thread. The copy is made under a
+/// temporary name and only renamed into place once complete, so a backup cut short
+/// is never mistaken for a good one; its leftovers are removed by the next backup.
+pub fn backup_vault(source: &Path, backup_root: &Path, keep_n: usize) -> io::Result<PathBuf> {
+    // Keeping none would delete the backup just made
+    let keep_n = keep_n.max(1);
+    fs::create_dir_all(backup_root)?;
+    // Copies left behind by a backup that was cut short
+    for dir in list_backups(backup_root)? {
+        if dir.extension().is_some_and(|ext| ext == "partial") {
+            fs::remove_dir_all(dir)?;
+        }
+    }
+    // Each backup must be newer than the last, or pruning would keep the wrong ones
+    let latest = list_backups(backup_root)?.iter().filter_map(|b| backup_time(b)).max();
+    let mut time = Local::now();
+    let mut name = time.format(BACKUP_TIMESTAMP_FORMAT).to_string();
+    while latest.is_some_and(|latest| backup_time(Path::new(&name)).is_some_and(|t| t <= latest)) {
+        time += chrono::Duration::milliseconds(1);
+        name = time.format(BACKUP_TIMESTAMP_FORMAT).to_string();
+    }
+    let backup = backup_root.join(&name);
+    let partial = backup_root.join(format!("{}.partial", name));
+    fs::create_dir_all(&partial)?;
+    
+    for entry in vault_entries(source, backup_root).skip(1) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(source).map_err(io::Error::other)?;
+        let target = partial.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    fs::rename(&partial, &backup)?;
+    
+    for old in backups_to_prune(&list_backups(backup_root)?, keep_n) {
+        fs::remove_dir_all(old)?;
+    }
+    Ok(backup)
+}
+
+/// Like `backup_vault`, but only if something in the vault was modified, added or
+/// removed since the newest backup in `backup_root`. `None` when nothing was.
+pub fn backup_vault_if_changed(source: &Path, backup_root: &Path, keep_n: usize) -> io::Result<Option<PathBuf>> {
+    let latest = match list_backups(backup_root) {
+        Ok(backups) => backups.iter().filter_map(|b| backup_time(b)).max(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if let Some(latest) = latest {
+        let mut changed = false;
+        for entry in vault_entries(source, backup_root) {
+            // A folder's mtime moves when something in it is added or removed
+            let modified = entry?.metadata()?.modified()?;
+            // Backup names only go down to the millisecond
+            let modified = chrono::DateTime::<Local>::from(modified).naive_local().trunc_subsecs(3);
+            if modified > latest {
+                changed = true;
+                break;
             }
+        }
+        if !changed {
+            return Ok(None);
+        }
+    }
+    backup_vault(source, backup_root, keep_n).map(Some)
+}
+
+/// Everything a backup of `source` covers, starting with `source` itself: not the
+/// `.git` history, nor `backup_root` if it's inside the vault.
+fn vault_entries<'a>(source: &Path, backup_root: &'a Path) -> impl Iterator<Item = io::Result<walkdir::DirEntry>> + 'a {
+    walkdir::WalkDir::new(source).into_iter()
+        .filter_entry(move |e| e.file_name() != ".git" && !e.path().starts_with(backup_root))
+        .map(|entry| entry.map_err(io::Error::other))
+}
+
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H-%M-%S%.3f";
+
+/// Directories in `backup_root`, backups or not.
+fn list_backups(backup_root: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(fs::read_dir(backup_root)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| entry.path())
+        .collect())
+}
+
+/// When a backup was made, from its directory name. `None` for anything not named
+/// like a backup.
+fn backup_time(path: &Path) -> Option<chrono::NaiveDateTime> {
+    let name = path.file_name()?.to_str()?;
+    chrono::NaiveDateTime::parse_from_str(name, BACKUP_TIMESTAMP_FORMAT).ok()
+}
+
+/// Which of `backups` to delete so only the `keep_n` newest remain, going by the
+/// time in each name. Directories not named like a backup are never picked.
+fn backups_to_prune(backups: &[PathBuf], keep_n: usize) -> Vec<PathBuf> {
+    let mut dated: Vec<_> = backups.iter()
+        .filter_map(|path| Some((backup_time(path)?, path)))
+        .collect();
+    dated.sort();
+    let excess = dated.len().saturating_sub(keep_n);
+    dated[..excess].iter().map(|(_, path)| path.to_path_buf()).collect()
+}
+
+/// File name for an imported attachment in `images/`. Spaces would break the
+/// Markdown image syntax, so they become underscores.
+fn attachment_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().replace(' ', "_"))
+        .unwrap_or_default()
+}
+
+/// `name`, or `stem-1.ext`, `stem-2.ext`, ... if `images_dir` already has a file
+/// by that name or the import has `taken` it for another attachment.
+fn unique_attachment_name(images_dir: &Path, name: &str, taken: &HashSet<PathBuf>) -> String {
+    let path = Path::new(name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut name = name.to_string();
+    let mut counter = 1;
+    while taken.contains(&images_dir.join(&name)) || images_dir.join(&name).exists() {
+        name = format!("{}-{}{}", stem, counter, extension);
+        counter += 1;
+    }
+    name
+}
+
+/// A path inside an Obsidian vault as links write it: `/`-separated and lowercased,
+/// since Obsidian matches links without regard to case.
+fn vault_key(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The attachment an Obsidian link `target` means from a note in `note_dir` (a
+/// `vault_key`). A file beside the note wins, then one at that path from the
+/// vault root, then the first in vault order whose path ends with `target`, which
+/// may be just a file name.
+fn find_attachment<'a>(
+    attachments: &'a BTreeMap<String, PathBuf>,
+    target: &str,
+    note_dir: &str,
+) -> Option<&'a PathBuf> {
+    let target = target.replace('\\', "/").to_lowercase();
+    let beside = if note_dir.is_empty() {
+        target.clone()
+    } else {
+        format!("{}/{}", note_dir, target)
+    };
+    let suffix = format!("/{}", target);
+    attachments.get(&beside)
+        .or_else(|| attachments.get(&target))
+        .or_else(|| {
+            attachments.iter()
+                .find(|(key, _)| key.ends_with(&suffix))
+                .map(|(_, path)| path)
         })
-        .collect::<String>()
-        .trim()
-        .to_string()
+}
+
+/// Point relative `![alt](path)` embeds of files that exist next to `note_path` at
+/// `images/`, under the name `place` gives each file.
+fn import_markdown_images(content: &str, note_path: &Path, place: &mut impl FnMut(&Path) -> String) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut pos = 0;
+    
+    for image in find_image_refs(content) {
+        if image.path.contains("://") {
+            continue;
+        }
+        let source = resolve_image_path(note_path, &image.path);
+        if !source.is_file() {
+            continue;
+        }
+        out.push_str(&content[pos..image.range.start]);
+        out.push_str(&format!("![{}](images/{})", image.alt, place(&source)));
+        pos = image.range.end;
+    }
+    out.push_str(&content[pos..]);
+    
+    out
+}
+
+/// Rewrite Obsidian `[[path/Note#heading|alias]]` links to `[[Note]]` using `titles`
+/// (lowercased Obsidian name -> imported title), and links to files in `attachments`
+/// to Markdown pointing into `images/`, under the name `place` gives each file:
+/// images for images, plain links for anything else. Returns the new content and
+/// the link targets that matched nothing, which are left as written.
+fn convert_wiki_links(
+    content: &str,
+    titles: &HashMap<String, String>,
+    attachments: &BTreeMap<String, PathBuf>,
+    note_dir: &str,
+    place: &mut impl FnMut(&Path) -> String,
+) -> (String, Vec<String>) {
+    let mut unresolved = Vec::new();
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]").filter(|&end| !after[..end].contains('\n')) else {
+            out.push_str(&rest[..start + 2]);
+            rest = after;
+            continue;
+        };
+        let is_embed = rest[..start].ends_with('!');
+        out.push_str(&rest[..if is_embed { start - 1 } else { start }]);
+        
+        let inner = &after[..end];
+        let target = inner.split('|').next().unwrap_or_default();
+        let target = target.split('#').next().unwrap_or_default().trim();
+        let name = target.rsplit('/').next().unwrap_or_default();
+        
+        if let Some(source) = find_attachment(attachments, target, note_dir) {
+            let marker = if is_image_file(source) { "!" } else { "" };
+            out.push_str(&format!("{}[{}](images/{})", marker, name, place(source)));
+        } else if let Some(title) = titles.get(&name.trim_end_matches(".md").to_lowercase()) {
+            out.push_str(&format!("[[{}]]", title));
+        } else {
+            if is_embed {
+                out.push('!');
+            }
+            out.push_str(&rest[start..start + 2 + end + 2]);
+            if !target.is_empty() {
+                unresolved.push(target.to_string());
+            }
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    
+    (out, unresolved)
+}
+
+/// Titles and folder names become file names, so they can't be blank or a path.
+fn is_valid_name(name: &str) -> bool {
+    let name = name.trim();
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Rename a note's file and its `.meta` and `.draft` sidecars, if it has them.
+fn move_note_files(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)?;
+    for extension in ["meta", DRAFT_EXTENSION] {
+        let sidecar = from.with_extension(extension);
+        if sidecar.exists() {
+            fs::rename(&sidecar, to.with_extension(extension))?;
+        }
+    }
+    Ok(())
+}
+
+fn draft_path(note: &Note) -> PathBuf {
+    Path::new(&note.file_path).with_extension(DRAFT_EXTENSION)
+}
+
+/// Write a note's `.meta` sidecar, leaving its body file alone.
+fn save_metadata(note: &Note) -> io::Result<()> {
+    let metadata_json = serde_json::to_string_pretty(&NoteMetadata::from_note(note))?;
+    fs::write(Path::new(&note.file_path).with_extension("meta"), metadata_json)
+}
+
+/// Each pair of neighbouring words in `text`, lowercased. A one-word note is just
+/// that word.
+fn word_pairs(text: &str) -> HashSet<String> {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    if words.len() == 1 {
+        return words.into_iter().collect();
+    }
+    words.windows(2).map(|pair| pair.join(" ")).collect()
+}
+
+/// Pairs `(i, j)`, `i < j`, of `sets` that might overlap by `threshold` or more, so
+/// only those need comparing. Sets that similar share one of their rarest words
+/// (prefix filtering): with words ordered by how few sets use them, two sets
+/// overlapping in at least `threshold` of either one's size must share a word among
+/// each one's first `len - ceil(threshold * len) + 1`. Pairs too different in size
+/// are dropped too. Every pair that does reach `threshold` is returned.
+fn similar_candidates(sets: &[HashSet<String>], threshold: f64) -> Vec<(usize, usize)> {
+    let mut frequency: HashMap<&str, usize> = HashMap::new();
+    for word in sets.iter().flatten() {
+        *frequency.entry(word).or_default() += 1;
+    }
+    
+    let mut by_prefix_word: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, set) in sets.iter().enumerate() {
+        let mut words: Vec<&str> = set.iter().map(String::as_str).collect();
+        words.sort_by_key(|word| (frequency[word], *word));
+        // Rounded down a hair so float error can only lengthen the prefix
+        let overlap = (threshold * words.len() as f64 - 1e-9).ceil().max(0.0) as usize;
+        for word in &words[..words.len() + 1 - overlap.clamp(1, words.len().max(1))] {
+            by_prefix_word.entry(word).or_default().push(i);
+        }
+    }
+    
+    let mut pairs = HashSet::new();
+    for ids in by_prefix_word.values() {
+        for (n, &i) in ids.iter().enumerate() {
+            for &j in &ids[n + 1..] {
+                let (small, large) = (sets[i].len().min(sets[j].len()), sets[i].len().max(sets[j].len()));
+                if small as f64 >= threshold * large as f64 - 1e-9 {
+                    pairs.insert((i.min(j), i.max(j)));
+                }
+            }
+        }
+    }
+    let mut pairs: Vec<_> = pairs.into_iter().collect();
+    pairs.sort();
+    pairs
+}
+
+/// How much two sets overlap, from 0.0 (nothing shared) to 1.0 (the same).
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Load the body of every note in `folders` not loaded yet, e.g. in a copy of the
+/// vault taken off the UI thread.
+pub fn load_all_bodies(folders: &mut [Folder]) {
+    for note in folders.iter_mut().flat_map(|f| f.notes.iter_mut()) {
+        if let Err(e) = load_body(note) {
+            eprintln!("Failed to load {}: {}", note.file_path, e);
+        }
+    }
+}
+
+fn load_body(note: &mut Note) -> io::Result<()> {
+    if note.content_loaded {
+        return Ok(());
+    }
+    // Front-matter was already read with the header and may have been edited since
+    let raw = fs::read_to_string(&note.file_path)?;
+    let (_, content) = split_front_matter(&raw);
+    note.content = content.to_string();
+    note.content_loaded = true;
+    note.saved_hash = Some(content_hash(&raw));
+    Ok(())
+}
+
+fn content_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Copy `source` over `dest` unless `dest` already has identical content.
+fn sync_file(source: &Path, dest: &Path) -> io::Result<SyncAction> {
+    if !dest.exists() {
+        fs::copy(source, dest)?;
+        return Ok(SyncAction::Added);
+    }
+    
+    let same_size = fs::metadata(source)?.len() == fs::metadata(dest)?.len();
+    if same_size && fs::read(source)? == fs::read(dest)? {
+        return Ok(SyncAction::Unchanged);
+    }
+    
+    fs::copy(source, dest)?;
+    Ok(SyncAction::Updated)
+}
+
+/// Convert every line break in `text` (`\r\n`, `\n` or a lone `\r`) to `line_ending`,
+/// and with `trailing_newline`, end it with exactly one. Empty text stays empty.
+pub fn normalize_text(text: &str, line_ending: LineEnding, trailing_newline: bool) -> String {
+    let newline = match line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::CrLf => "\r\n",
+        // Trailing newlines match the file's own style
+        LineEnding::AsIs if text.contains("\r\n") => "\r\n",
+        LineEnding::AsIs => "\n",
+    };
+    
+    let mut out = match line_ending {
+        LineEnding::AsIs => text.to_string(),
+        _ => text.replace("\r\n", "\n").replace('\r', "\n").replace('\n', newline),
+    };
+    if trailing_newline && !out.is_empty() {
+        out.truncate(out.trim_end_matches(['\r', '\n']).len());
+        out.push_str(newline);
+    }
+    out
+}
+
+pub fn file_mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// `dir/stem.md`, or `dir/stem (2).md`, `dir/stem (3).md`, ... if that's taken.
+fn unique_note_path(dir: &Path, stem: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.md", stem));
+    let mut counter = 2;
+    while path.exists() {
+        path = dir.join(format!("{} ({}).md", stem, counter));
+        counter += 1;
+    }
+    path
+}
+
+// Well under the usual 255-byte limit, leaving room for a " (N)" suffix and extension
+const MAX_FILENAME_BYTES: usize = 120;
+
+pub fn sanitize_filename(name: &str, policy: &FilenamePolicy) -> String {
+    let mut sanitized = String::new();
+    for c in name.chars() {
+        let c = if policy.keeps(c) { c } else { '_' };
+        // Collapse runs of replaced characters into a single '_'
+        if c == '_' && sanitized.ends_with('_') {
+            continue;
+        }
+        sanitized.push(c);
+    }
+    
+    let mut sanitized = sanitized
+        .trim_matches(|c: char| c.is_whitespace() || c == '_' || c == '.')
+        .to_string();
+    
+    if sanitized.len() > MAX_FILENAME_BYTES {
+        let mut end = MAX_FILENAME_BYTES;
+        while !sanitized.is_char_boundary(end) {
+            end -= 1;
+        }
+        sanitized.truncate(end);
+        sanitized = sanitized.trim_end().to_string();
+    }
+    
+    if sanitized.is_empty() {
+        "untitled".to_string()
+    } else {
+        sanitized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn temp_storage() -> (tempfile::TempDir, Storage) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().to_string_lossy().to_string());
+        (dir, storage)
+    }
+    
+    #[test]
+    fn test_load_note_without_front_matter() {
+        let (dir, _) = temp_storage();
+        fs::create_dir_all(dir.path().join("Inbox")).unwrap();
+        fs::write(dir.path().join("Inbox/Plain.md"), "Just content").unwrap();
+        
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        storage.ensure_loaded(0, 0).unwrap();
+        let note = &storage.folders[0].notes[0];
+        assert_eq!(note.title, "Plain");
+        assert_eq!(note.content, "Just content");
+        assert!(note.front_matter.is_none());
+    }
+    
+    #[test]
+    fn test_front_matter_parsed_and_preserved_on_save() {
+        let (dir, _) = temp_storage();
+        fs::create_dir_all(dir.path().join("Inbox")).unwrap();
+        let note_path = dir.path().join("Inbox/file-name.md");
+        fs::write(&note_path, "---\ntitle: Real Title\ntags:\n- rust\n---\nBody\n").unwrap();
+        
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        storage.ensure_loaded(0, 0).unwrap();
+        {
+            let note = &storage.folders[0].notes[0];
+            assert_eq!(note.title, "Real Title");
+            assert_eq!(note.content, "Body\n");
+            assert_eq!(note.front_matter.as_ref().unwrap().tags, vec!["rust"]);
+        }
+        
+        storage.folders[0].notes[0].content = "Edited\n".to_string();
+        storage.save_note(0, 0).unwrap();
+        
+        let raw = fs::read_to_string(&note_path).unwrap();
+        assert!(raw.starts_with("---\n"));
+        assert!(raw.contains("title: Real Title"));
+        assert!(raw.ends_with("---\nEdited\n"));
+    }
+    
+    #[test]
+    fn test_content_loaded_on_demand() {
+        let (dir, _) = temp_storage();
+        fs::create_dir_all(dir.path().join("Inbox")).unwrap();
+        fs::write(dir.path().join("Inbox/Big.md"), "---\ntitle: Header Only\ntags:\n- lazy\n---\nThe body\n").unwrap();
+        
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        {
+            let note = &storage.folders[0].notes[0];
+            assert!(!note.content_loaded);
+            assert!(note.content.is_empty());
+            // Front-matter is still available up front for titles and tags
+            assert_eq!(note.title, "Header Only");
+            assert_eq!(note.front_matter.as_ref().unwrap().tags, vec!["lazy"]);
+        }
+        
+        storage.ensure_loaded(0, 0).unwrap();
+        let note = &storage.folders[0].notes[0];
+        assert!(note.content_loaded);
+        assert_eq!(note.content, "The body\n");
+    }
+    
+    #[test]
+    fn test_saving_unloaded_note_keeps_body() {
+        let (dir, _) = temp_storage();
+        fs::create_dir_all(dir.path().join("Inbox")).unwrap();
+        let note_path = dir.path().join("Inbox/Keep.md");
+        fs::write(&note_path, "precious").unwrap();
+        
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        storage.save_note(0, 0).unwrap();
+        assert_eq!(fs::read_to_string(&note_path).unwrap(), "precious");
+        
+        // Front-matter edited before the body was ever loaded survives the save
+        let tagged_path = dir.path().join("Inbox/Tagged.md");
+        fs::write(&tagged_path, "---\ntags:\n- old\n---\nbody\n").unwrap();
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        let idx = storage.folders[0].notes.iter().position(|n| n.title == "Tagged").unwrap();
+        storage.folders[0].notes[idx].front_matter.as_mut().unwrap().tags = vec!["new".to_string()];
+        storage.save_note(0, idx).unwrap();
+        let raw = fs::read_to_string(&tagged_path).unwrap();
+        assert!(raw.contains("- new") && raw.ends_with("---\nbody\n"));
+    }
+    
+    #[test]
+    fn test_sanitize_all_symbols_title() {
+        let strict = FilenamePolicy::ascii_only();
+        assert_eq!(sanitize_filename("!!!???", &strict), "untitled");
+        assert_eq!(sanitize_filename("   ", &strict), "untitled");
+        assert_eq!(sanitize_filename("a // b", &strict), "a _ b");
+        assert_eq!(sanitize_filename("what?!", &strict), "what");
+        
+        let relaxed = FilenamePolicy::default();
+        assert_eq!(sanitize_filename("???", &relaxed), "untitled");
+        assert_eq!(sanitize_filename("what?!", &relaxed), "what_!");
+        assert_eq!(sanitize_filename("a:b*c|d", &relaxed), "a_b_c_d");
+    }
+    
+    #[test]
+    fn test_sanitize_keeps_unicode() {
+        let relaxed = FilenamePolicy::default();
+        assert_eq!(sanitize_filename("Café", &relaxed), "Café");
+        assert_eq!(sanitize_filename("日記", &relaxed), "日記");
+        assert_eq!(sanitize_filename("a/b", &relaxed), "a_b");
+        assert_eq!(sanitize_filename("Q&A (draft), v1.2", &relaxed), "Q&A (draft), v1.2");
+        
+        let strict = FilenamePolicy::ascii_only();
+        assert_eq!(sanitize_filename("Café", &strict), "Caf");
+        assert_eq!(sanitize_filename("日記", &strict), "untitled");
+        assert_eq!(sanitize_filename("a/b", &strict), "a_b");
+        
+        // The allowlist can't let illegal characters through
+        let custom = FilenamePolicy {
+            ascii_only: true,
+            allowed: " &/".to_string(),
+        };
+        assert_eq!(sanitize_filename("Q&A a/b", &custom), "Q&A a_b");
+        
+        let (_dir, mut storage) = temp_storage();
+        storage.create_folder("Journal").unwrap();
+        storage.create_note(0, "日記 Café", "").unwrap();
+        assert!(storage.folders[0].notes[0].file_path.ends_with("日記 Café.md"));
+    }
+    
+    #[test]
+    fn test_sanitize_leading_dot_title() {
+        for policy in [FilenamePolicy::default(), FilenamePolicy::ascii_only()] {
+            assert_eq!(sanitize_filename(".hidden", &policy), "hidden");
+            assert_eq!(sanitize_filename("...", &policy), "untitled");
+        }
+    }
+    
+    #[test]
+    fn test_sanitize_very_long_title() {
+        let sanitized = sanitize_filename(&"ü".repeat(500), &FilenamePolicy::default());
+        assert!(sanitized.len() <= MAX_FILENAME_BYTES);
+        assert!(sanitized.chars().all(|c| c == 'ü'));
+        
+        let (_dir, mut storage) = temp_storage();
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, &"long title ".repeat(50), "body").unwrap();
+        assert!(Path::new(&storage.folders[0].notes[0].file_path).exists());
+    }
+    
+    #[test]
+    fn test_duplicate_title_keeps_first_note() {
+        let (_dir, mut storage) = temp_storage();
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "Ideas", "original").unwrap();
+        storage.create_note(0, "Ideas", "second").unwrap();
+        storage.create_note(0, "Ideas", "").unwrap();
+        
+        let notes = &storage.folders[0].notes;
+        assert_eq!(fs::read_to_string(&notes[0].file_path).unwrap(), "original");
+        assert_eq!(fs::read_to_string(&notes[1].file_path).unwrap(), "second");
+        assert!(notes[1].file_path.ends_with("Ideas (2).md"));
+        assert!(notes[2].file_path.ends_with("Ideas (3).md"));
+        assert_eq!(notes[1].title, "Ideas (2)");
+        assert_eq!(notes[2].title, "Ideas (3)");
+        
+        // Callers that link to the new note by title ask for one of its own
+        assert!(storage.title_taken(0, " Ideas "));
+        assert_eq!(storage.unique_title(0, "Ideas"), "Ideas (4)");
+        assert_eq!(storage.unique_title(0, "Plans"), "Plans");
+        assert!(!storage.title_taken(1, "Ideas"));
+    }
+    
+    #[test]
+    fn test_reload_picks_up_external_changes() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "Plan", "draft").unwrap();
+        let note_path = PathBuf::from(&storage.folders[0].notes[0].file_path);
+        
+        // Our own save leaves disk and memory in agreement
+        storage.save_note(0, 0).unwrap();
+        assert!(!storage.reload_path(&note_path));
+        
+        fs::write(&note_path, "edited elsewhere").unwrap();
+        assert!(storage.reload_path(&note_path));
+        assert_eq!(storage.folders[0].notes[0].content, "edited elsewhere");
+        
+        let new_note = dir.path().join("Work").join("Pulled.md");
+        fs::write(&new_note, "from git").unwrap();
+        assert!(storage.reload_path(&new_note));
+        assert_eq!(storage.folders[0].notes.len(), 2);
+        
+        fs::remove_file(&note_path).unwrap();
+        assert!(storage.reload_path(&note_path));
+        assert_eq!(storage.folders[0].notes.len(), 1);
+        assert_eq!(storage.folders[0].notes[0].title, "Pulled");
+    }
+    
+    #[test]
+    fn test_cloud_sync_copies_only_changes() {
+        let (dir, _) = temp_storage();
+        let vault = dir.path().join("vault");
+        let mut storage = Storage::new(vault.to_string_lossy().to_string());
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "One", "first").unwrap();
+        storage.create_note(0, "Two", "second").unwrap();
+        
+        // Each note is synced along with its .meta file
+        let summary = storage.export_to_cloud().unwrap();
+        assert_eq!(summary.added, 4);
+        assert_eq!((summary.updated, summary.deleted), (0, 0));
+        
+        let resync = storage.export_to_cloud().unwrap();
+        assert_eq!((resync.added, resync.updated, resync.deleted), (0, 0, 0));
+        
+        storage.folders[0].notes[0].content = "first, edited".to_string();
+        fs::write(&storage.folders[0].notes[0].file_path, "first, edited").unwrap();
+        let summary = storage.export_to_cloud().unwrap();
+        assert_eq!((summary.added, summary.updated, summary.deleted), (0, 1, 0));
+        
+        let synced_one = Path::new(&summary.path).join("Work").join("One.md");
+        assert_eq!(fs::read_to_string(synced_one).unwrap(), "first, edited");
+        
+        let removed = storage.folders[0].notes.remove(1);
+        fs::remove_file(&removed.file_path).unwrap();
+        let summary = storage.export_to_cloud().unwrap();
+        assert_eq!(summary.deleted, 2);
+        assert!(!Path::new(&summary.path).join("Work").join("Two.md").exists());
+    }
+    
+    #[test]
+    fn test_locked_encrypted_note_survives_save() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Private").unwrap();
+        storage.create_note(0, "Diary", "secret plans").unwrap();
+        
+        let encryption = crate::encryption::Encryption::new();
+        let sealed = encryption.encrypt("secret plans", "hunter2").unwrap();
+        {
+            let note = &mut storage.folders[0].notes[0];
+            note.encrypted_data = Some(sealed.clone());
+            note.is_encrypted = true;
+            note.content = "[ENCRYPTED]".to_string();
+        }
+        storage.save_note(0, 0).unwrap();
+        
+        // The editor's placeholder text must not replace anything while locked
+        assert!(!storage.folders[0].notes[0].set_content("[ENCRYPTED] oops, typed here"));
+        storage.save_note(0, 0).unwrap();
+        
+        let reopened = Storage::new(dir.path().to_string_lossy().to_string());
+        let note = &reopened.folders[0].notes[0];
+        assert!(note.is_encrypted);
+        assert_eq!(note.encrypted_data.as_ref(), Some(&sealed));
+        assert_eq!(encryption.decrypt(&sealed, "hunter2").unwrap(), "secret plans");
+        assert!(!fs::read_to_string(&note.file_path).unwrap().contains("oops"));
+    }
+    
+    #[test]
+    fn test_custom_vault_path_created_and_loaded() {
+        let (dir, _) = temp_storage();
+        let vault = dir.path().join("Dropbox").join("Notes");
+        assert!(!vault.exists());
+        
+        let mut storage = Storage::new(vault.to_string_lossy().to_string());
+        assert!(vault.is_dir());
+        assert_eq!(storage.base_path(), vault.as_path());
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "Plan", "ship it").unwrap();
+        assert!(vault.join("Work").join("Plan.md").exists());
+        
+        let mut reopened = Storage::new(vault.to_string_lossy().to_string());
+        reopened.ensure_all_loaded();
+        assert_eq!(reopened.folders.len(), 1);
+        assert_eq!(reopened.folders[0].notes[0].content, "ship it");
+    }
+    
+    #[test]
+    fn test_import_obsidian_vault() {
+        let vault = tempfile::tempdir().unwrap();
+        let root = &vault.path().join("Second Brain");
+        fs::create_dir_all(root.join(".obsidian")).unwrap();
+        fs::write(root.join(".obsidian/app.json"), "{}").unwrap();
+        fs::create_dir_all(root.join("Projects/Rust")).unwrap();
+        fs::create_dir_all(root.join("attachments")).unwrap();
+        fs::write(root.join("attachments/Pasted image.png"), b"png").unwrap();
+        fs::write(root.join("attachments/chart.png"), b"png").unwrap();
+        fs::write(root.join("attachments/Spec.pdf"), b"pdf").unwrap();
+        fs::write(root.join("Projects/Rust/chart.png"), b"rust chart").unwrap();
+        
+        fs::write(root.join("Home.md"), "Start at [[Projects/Rust/Borrow Checker#Rules|the rules]].\n\n![[Pasted image.png]]\n\nAlso [[Nowhere]].\n\n![[attachments/chart.png]] ![[Spec.pdf]]\n").unwrap();
+        fs::write(root.join("Projects/Rust/Borrow Checker.md"), "Back to [[home]]\n\n![Chart](../../attachments/chart.png)\n\n![[chart.png]] ![[Chart.png]]\n").unwrap();
+        
+        let (_dir, mut storage) = temp_storage();
+        let report = storage.import_obsidian(root).unwrap();
+        assert_eq!(report.notes, 2);
+        assert_eq!(report.attachments, 5);
+        assert_eq!(report.unresolved_links, vec![("Home".to_string(), "Nowhere".to_string())]);
+        
+        storage.ensure_all_loaded();
+        let home_folder = storage.folders.iter().find(|f| f.name == "Second Brain").unwrap();
+        let rust_folder = storage.folders.iter().find(|f| f.name == "Projects - Rust").unwrap();
+        assert!(!storage.folders.iter().any(|f| f.name.starts_with('.')));
+        
+        let home = &home_folder.notes[0];
+        assert_eq!(home.content, "Start at [[Borrow Checker]].\n\n![Pasted image.png](images/Pasted_image.png)\n\nAlso [[Nowhere]].\n\n![chart.png](images/chart.png) [Spec.pdf](images/Spec.pdf)\n");
+        assert!(Path::new(&home_folder.path).join("images/Pasted_image.png").exists());
+        assert!(Path::new(&home_folder.path).join("images/Spec.pdf").exists());
+        
+        // The chart beside the note is a different file from the one in attachments/
+        let borrow = &rust_folder.notes[0];
+        assert_eq!(borrow.content, "Back to [[Home]]\n\n![Chart](images/chart.png)\n\n![chart.png](images/chart-1.png) ![Chart.png](images/chart-1.png)\n");
+        let rust_images = Path::new(&rust_folder.path).join("images");
+        assert_eq!(fs::read(rust_images.join("chart.png")).unwrap(), b"png");
+        assert_eq!(fs::read(rust_images.join("chart-1.png")).unwrap(), b"rust chart");
+        
+        // The rewritten link resolves the way the app's link index does: by exact title
+        let titles: HashSet<&str> = storage.folders.iter()
+            .flat_map(|f| f.notes.iter().map(|n| n.title.as_str()))
+            .collect();
+        let mut links = crate::links::LinkManager::new();
+        for link in links.scan_note_for_links(&home.content, (0, 0)) {
+            assert_eq!(titles.contains(link.as_str()), link != "Nowhere");
+        }
+        assert!(titles.contains("Borrow Checker"));
+    }
+    
+    #[test]
+    fn test_copy_vault_to_new_location() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "Plan", "ship it").unwrap();
+        
+        let dest = tempfile::tempdir().unwrap();
+        let new_vault = dest.path().join("vault");
+        // Note and its .meta file
+        assert_eq!(copy_vault(dir.path(), &new_vault).unwrap(), 2);
+        
+        let mut moved = Storage::new(new_vault.to_string_lossy().to_string());
+        moved.ensure_all_loaded();
+        assert_eq!(moved.folders[0].notes[0].content, "ship it");
+        assert!(copy_vault(dir.path(), &new_vault).is_err());
+    }
+    
+    #[test]
+    fn test_daily_note_created_once_per_day() {
+        let (_dir, mut storage) = temp_storage();
+        storage.create_folder("Journal").unwrap();
+        let template = Template::new("Daily Journal".to_string(), "# ${date}\n\n## Today\n".to_string());
+        
+        let first = storage.get_or_create_dated_note(0, "2024-05-06", &template).unwrap();
+        storage.folders[0].notes[first].content.push_str("- wrote tests\n");
+        let second = storage.get_or_create_dated_note(0, "2024-05-06", &template).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(storage.folders[0].notes.len(), 1);
+        assert_eq!(storage.folders[0].notes[first].content, "# 2024-05-06\n\n## Today\n- wrote tests\n");
+        
+        let next_day = storage.get_or_create_dated_note(0, "2024-05-07", &template).unwrap();
+        assert_ne!(next_day, first);
+        assert_eq!(storage.folders[0].notes.len(), 2);
+        
+        let today = storage.get_or_create_daily_note(0, &template).unwrap();
+        assert_eq!(storage.get_or_create_daily_note(0, &template).unwrap(), today);
+    }
+    
+    #[test]
+    fn test_collect_open_tasks_across_notes() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "Sprint", "- [ ] Ship it\n- [x] Plan it\n").unwrap();
+        storage.create_note(0, "Ideas", "No tasks here").unwrap();
+        storage.create_note(0, "Errands", "1. [ ] Call bank\n- [ ] Buy milk\n").unwrap();
+        
+        // Freshly loaded, so bodies have to be read on the way
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        let open = storage.collect_open_tasks();
+        
+        let mut found: Vec<_> = open.iter()
+            .map(|(f, n, task)| (storage.folders[*f].notes[*n].title.as_str(), task.text.as_str(), task.line))
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![
+            ("Errands", "Buy milk", 1),
+            ("Errands", "Call bank", 0),
+            ("Sprint", "Ship it", 0),
+        ]);
+    }
+    
+    #[test]
+    fn test_reorder_notes_manually() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Book").unwrap();
+        for title in ["Chapter 3", "Chapter 1", "Chapter 2", "Notes"] {
+            storage.create_note(0, title, "").unwrap();
+        }
+        let titles = |storage: &Storage| -> Vec<String> {
+            storage.folders[0].sorted_note_indices(SortOrder::Manual).into_iter()
+                .map(|idx| storage.folders[0].notes[idx].title.clone())
+                .collect()
+        };
+        // Never arranged, so alphabetical to start with
+        assert_eq!(titles(&storage), vec!["Chapter 1", "Chapter 2", "Chapter 3", "Notes"]);
+        
+        storage.reorder_note(0, 3, 0).unwrap();
+        storage.reorder_note(0, 1, 2).unwrap();
+        assert_eq!(titles(&storage), vec!["Notes", "Chapter 2", "Chapter 1", "Chapter 3"]);
+        assert!(storage.reorder_note(0, 4, 0).is_err());
+        
+        // The order is kept in metadata, so it survives a reload
+        let reloaded = Storage::new(dir.path().to_string_lossy().to_string());
+        assert_eq!(titles(&reloaded), vec!["Notes", "Chapter 2", "Chapter 1", "Chapter 3"]);
+    }
+    
+    #[test]
+    fn test_backups_pruned_to_keep_n() {
+        let (dir, mut storage) = temp_storage();
+        let backups = tempfile::tempdir().unwrap();
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "Plan", "irreplaceable").unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git/HEAD"), "ref").unwrap();
+        
+        let mut created = Vec::new();
+        for _ in 0..5 {
+            created.push(storage.create_backup(backups.path(), 3).unwrap());
+        }
+        
+        let mut remaining: Vec<PathBuf> = fs::read_dir(backups.path()).unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        remaining.sort();
+        let mut newest = created[2..].to_vec();
+        newest.sort();
+        assert_eq!(remaining, newest);
+        
+        let latest = created.last().unwrap();
+        assert_eq!(fs::read_to_string(latest.join("Work/Plan.md")).unwrap(), "irreplaceable");
+        assert!(latest.join("Work/Plan.meta").exists());
+        assert!(!latest.join(".git").exists());
+        
+        let kept = storage.create_backup(backups.path(), 0).unwrap();
+        assert_eq!(fs::read_dir(backups.path()).unwrap().count(), 1);
+        assert!(kept.join("Work/Plan.md").exists());
+    }
+    
+    #[test]
+    fn test_default_backup_root_is_beside_the_vault() {
+        let parent = tempfile::tempdir().unwrap();
+        let vault = parent.path().join("notes");
+        fs::create_dir_all(&vault).unwrap();
+        for base in [vault.clone(), vault.join(""), vault.join(".")] {
+            let storage = Storage::new(base.to_string_lossy().to_string());
+            let root = storage.default_backup_root();
+            assert_eq!(root.file_name().unwrap(), "notes_backups");
+            assert!(!root.starts_with(&vault), "{} is inside the vault", root.display());
+        }
+    }
+    
+    #[test]
+    fn test_scheduled_backup_skipped_when_vault_unchanged() {
+        let (dir, mut storage) = temp_storage();
+        let backups = tempfile::tempdir().unwrap();
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "Plan", "v1").unwrap();
+        // Left behind by a backup that was interrupted
+        fs::create_dir_all(backups.path().join("2024-01-01_00-00-00.000.partial/Work")).unwrap();
+        
+        let first = backup_vault_if_changed(dir.path(), backups.path(), 3).unwrap();
+        assert!(first.is_some());
+        assert!(!backups.path().join("2024-01-01_00-00-00.000.partial").exists());
+        assert_eq!(backup_vault_if_changed(dir.path(), backups.path(), 3).unwrap(), None);
+        
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        storage.folders[0].notes[0].set_content("v2");
+        storage.save_note(0, 0).unwrap();
+        let second = backup_vault_if_changed(dir.path(), backups.path(), 3).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(second.join("Work/Plan.md")).unwrap(), "v2");
+    }
+    
+    #[test]
+    fn test_prune_keeps_newest_backups_by_timestamp() {
+        let root = Path::new("/backups");
+        let backups: Vec<PathBuf> = [
+            "2024-05-01_09-00-00.000",
+            "2024-04-30_23-59-59.999",
+            "Keep me",
+            "2024-05-01_09-30-00.000",
+            "2023-12-31_12-00-00.500",
+            "2024-05-01_10-00-00.000.partial",
+        ]
+        .iter()
+        .map(|name| root.join(name))
+        .collect();
+        
+        assert_eq!(
+            backups_to_prune(&backups, 2),
+            vec![root.join("2023-12-31_12-00-00.500"), root.join("2024-04-30_23-59-59.999")]
+        );
+        assert!(backups_to_prune(&backups, 4).is_empty());
+        assert_eq!(backups_to_prune(&backups, 0).len(), 4);
+    }
+    
+    #[test]
+    fn test_external_change_detected_by_mtime() {
+        let (_dir, mut storage) = temp_storage();
+        storage.create_folder("Work").unwrap();
+        storage.create_note(0, "Plan", "mine").unwrap();
+        let loaded_at = storage.folders[0].notes[0].disk_mtime;
+        assert!(loaded_at.is_some());
+        assert!(!storage.changed_on_disk(0, 0, loaded_at));
+        
+        // Another program (a git pull, Dropbox) rewrites the file a little later
+        let path = PathBuf::from(&storage.folders[0].notes[0].file_path);
+        fs::write(&path, "theirs").unwrap();
+        let later = loaded_at.unwrap() + std::time::Duration::from_secs(5);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert!(storage.changed_on_disk(0, 0, loaded_at));
+        assert_eq!(storage.disk_version(0, 0).unwrap().content, "theirs");
+        assert_eq!(storage.folders[0].notes[0].content, "mine");
+        
+        // Our own save becomes the new baseline
+        storage.save_note(0, 0).unwrap();
+        let saved_at = storage.folders[0].notes[0].disk_mtime;
+        assert!(!storage.changed_on_disk(0, 0, saved_at));
+        
+        fs::remove_file(&path).unwrap();
+        assert!(storage.changed_on_disk(0, 0, saved_at));
+    }
+    
+    #[test]
+    fn test_compute_stats() {
+        let (_dir, mut storage) = temp_storage();
+        storage.create_folder("Work").unwrap();
+        storage.create_folder("Home").unwrap();
+        let work = storage.folders.iter().position(|f| f.name == "Work").unwrap();
+        let home = storage.folders.iter().position(|f| f.name == "Home").unwrap();
+        storage.create_note(work, "Roadmap", "Quarterly plan for the team").unwrap();
+        storage.create_note(work, "Standup", "See [[Roadmap]] and [[Roadmap]] again").unwrap();
+        storage.create_note(home, "Groceries", "Milk, see [[Roadmap]] and [[Standup]]").unwrap();
+        
+        let march = chrono::DateTime::parse_from_rfc3339("2024-03-15T12:00:00Z").unwrap().to_utc();
+        let april = chrono::DateTime::parse_from_rfc3339("2024-04-15T12:00:00Z").unwrap().to_utc();
+        for note in storage.folders.iter_mut().flat_map(|f| f.notes.iter_mut()) {
+            note.created_at = march;
+            note.updated_at = if note.title == "Groceries" { april } else { march };
+        }
+        
+        let mut tags = TagManager::new();
+        let urgent = tags.add_tag("urgent".to_string());
+        let later = tags.add_tag("later".to_string());
+        tags.add_tag("unused".to_string());
+        for note in storage.folders.iter_mut().flat_map(|f| f.notes.iter_mut()) {
+            note.tags.add_tag(later);
+            if note.title == "Standup" {
+                note.tags.add_tag(urgent);
+            }
+        }
+        
+        let stats = storage.compute_stats(&tags);
+        assert_eq!(stats.total_notes, 3);
+        assert_eq!(stats.total_words, 15);
+        assert!(stats.notes_per_folder.contains(&("Work".to_string(), 2)));
+        assert!(stats.notes_per_folder.contains(&("Home".to_string(), 1)));
+        assert_eq!(stats.most_linked, vec![("Roadmap".to_string(), 2), ("Standup".to_string(), 1)]);
+        assert_eq!(
+            stats.tag_counts,
+            vec![("later".to_string(), 3), ("urgent".to_string(), 1), ("unused".to_string(), 0)]
+        );
+        assert_eq!(
+            stats.activity,
+            vec![("2024-03".to_string(), 3, 2), ("2024-04".to_string(), 0, 1)]
+        );
+    }
+    
+    #[test]
+    fn test_normalize_line_endings() {
+        let mixed = "one\r\ntwo\nthree\rfour";
+        assert_eq!(normalize_text(mixed, LineEnding::Lf, false), "one\ntwo\nthree\nfour");
+        assert_eq!(normalize_text(mixed, LineEnding::CrLf, false), "one\r\ntwo\r\nthree\r\nfour");
+        assert_eq!(normalize_text(mixed, LineEnding::AsIs, false), mixed);
+        
+        assert_eq!(normalize_text("a\nb", LineEnding::Lf, true), "a\nb\n");
+        assert_eq!(normalize_text("a\r\nb\r\n\r\n\n", LineEnding::Lf, true), "a\nb\n");
+        assert_eq!(normalize_text("a\nb\n\n", LineEnding::CrLf, true), "a\r\nb\r\n");
+        assert_eq!(normalize_text("a\r\nb", LineEnding::AsIs, true), "a\r\nb\r\n");
+        assert_eq!(normalize_text("", LineEnding::CrLf, true), "");
+    }
+    
+    #[test]
+    fn test_save_normalizes_line_endings() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Inbox").unwrap();
+        storage.create_note(0, "Mixed", "").unwrap();
+        storage.set_save_format(LineEnding::Lf, true);
+        storage.folders[0].notes[0].content = "first\r\nsecond\n\n".to_string();
+        storage.save_note(0, 0).unwrap();
+        
+        let written = fs::read_to_string(dir.path().join("Inbox/Mixed.md")).unwrap();
+        assert_eq!(written, "first\nsecond\n");
+        // The editor's copy is left alone
+        assert_eq!(storage.folders[0].notes[0].content, "first\r\nsecond\n\n");
+    }
+    
+    #[test]
+    fn test_note_in_encrypted_folder_is_stored_as_ciphertext() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Private").unwrap();
+        assert!(matches!(storage.set_folder_encrypted(0, true), Err(Error::FolderLocked(_))));
+        
+        storage.set_session_password(Some("hunter2".to_string()));
+        storage.set_folder_encrypted(0, true).unwrap();
+        storage.create_note(0, "Diary", "secret plans").unwrap();
+        
+        let note = &storage.folders[0].notes[0];
+        assert!(note.is_encrypted);
+        assert_eq!(note.content, ENCRYPTED_PLACEHOLDER);
+        assert!(!fs::read_to_string(&note.file_path).unwrap().contains("secret"));
+        assert!(!fs::read_to_string(Path::new(&note.file_path).with_extension("meta")).unwrap().contains("secret"));
+        
+        let mut reopened = Storage::new(dir.path().to_string_lossy().to_string());
+        assert!(reopened.folders[0].encrypted);
+        let sealed = reopened.folders[0].notes[0].encrypted_data.as_ref().unwrap();
+        assert_eq!(Encryption::new().decrypt(sealed, "hunter2").unwrap(), "secret plans");
+        
+        // Without the password nothing new can be written into the folder
+        assert!(matches!(reopened.create_note(0, "Later", "more"), Err(Error::FolderLocked(_))));
+        assert!(!dir.path().join("Private/Later.md").exists());
+    }
+    
+    #[test]
+    fn test_storage_error_variants() {
+        let (dir, mut storage) = temp_storage();
+        assert!(matches!(storage.create_note(0, "Orphan", ""), Err(Error::FolderNotFound(0))));
+        assert!(matches!(storage.create_folder(""), Err(Error::InvalidName(_))));
+        assert!(matches!(storage.create_folder("a/b"), Err(Error::InvalidName(_))));
+        
+        storage.create_folder("Inbox").unwrap();
+        storage.create_folder("Archive").unwrap();
+        assert!(matches!(storage.create_folder("Inbox"), Err(Error::FolderExists(name)) if name == "Inbox"));
+        assert!(matches!(storage.create_note(0, "  ", ""), Err(Error::InvalidName(_))));
+        
+        storage.create_note(0, "Draft", "text").unwrap();
+        assert!(matches!(storage.save_note(0, 9), Err(Error::NoteNotFound(0, 9))));
+        assert!(matches!(storage.rename_note(0, 0, " "), Err(Error::InvalidName(_))));
+        assert!(matches!(storage.move_note(0, 0, 0), Err(Error::SameFolder)));
+        assert!(matches!(storage.move_note(0, 0, 5), Err(Error::FolderNotFound(5))));
+        assert!(matches!(storage.delete_note(1, 0), Err(Error::NoteNotFound(1, 0))));
+        assert!(matches!(storage.delete_folder(7), Err(Error::FolderNotFound(7))));
+        
+        // The folder vanished from under the app
+        fs::remove_dir_all(dir.path().join("Inbox")).unwrap();
+        match storage.save_note(0, 0) {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            other => panic!("expected an io error, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_link_graph_reads_only_changed_notes() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Zettel").unwrap();
+        storage.create_note(0, "A", "Links to [[B]]").unwrap();
+        storage.create_note(0, "B", "Back to [[A]]").unwrap();
+        storage.create_note(0, "C", "Alone").unwrap();
+        
+        let mut index = LinkIndex::default();
+        let links = storage.link_graph(&mut index);
+        assert_eq!(links.get_backlinks((0, 1)), vec![(0, 0)]);
+        assert_eq!(links.get_backlinks((0, 0)), vec![(0, 1)]);
+        let index_path = dir.path().join("links.json");
+        index.save(&index_path).unwrap();
+        
+        // A fresh session gets the same graph without reading any note
+        let mut reopened = Storage::new(dir.path().to_string_lossy().to_string());
+        let mut index = LinkIndex::load(&index_path).unwrap();
+        let links = reopened.link_graph(&mut index);
+        assert_eq!(links.get_backlinks((0, 1)), vec![(0, 0)]);
+        assert!(reopened.folders[0].notes.iter().all(|n| !n.content_loaded));
+        
+        // C now links to B; its newer mtime makes it stale, so it is reread
+        let c_path = dir.path().join("Zettel/C.md");
+        fs::write(&c_path, "Now about [[B]]").unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        fs::File::options().write(true).open(&c_path).unwrap().set_modified(later).unwrap();
+        fs::remove_file(dir.path().join("Zettel/A.md")).unwrap();
+        
+        let mut reopened = Storage::new(dir.path().to_string_lossy().to_string());
+        let links = reopened.link_graph(&mut index);
+        let b = reopened.find_note_by_path(&dir.path().join("Zettel/B.md").to_string_lossy()).unwrap();
+        let c = reopened.find_note_by_path(&c_path.to_string_lossy()).unwrap();
+        assert_eq!(links.get_backlinks(b), vec![c]);
+        assert!(!serde_json::to_string(&index).unwrap().contains("A.md"));
+        assert_eq!(reopened.folders[b.0].notes.iter().filter(|n| n.content_loaded).count(), 1);
+    }
+    
+    #[test]
+    fn test_create_note_from_link_text() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Inbox").unwrap();
+        storage.create_folder("Ideas").unwrap();
+        storage.create_note(0, "Source", "Builds on [[New Idea]].").unwrap();
+        
+        let created = storage.create_note_for_link(1, " New Idea ").unwrap();
+        assert_eq!(created, (1, 0));
+        assert_eq!(storage.folders[1].notes[0].title, "New Idea");
+        assert!(dir.path().join("Ideas/New Idea.md").exists());
+        
+        let links = LinkManager::build(&storage.folders);
+        assert_eq!(links.get_backlinks(created), vec![(0, 0)]);
+        
+        // Following the link again finds the note instead of making another
+        assert_eq!(storage.create_note_for_link(0, "New Idea").unwrap(), created);
+        assert_eq!(storage.folders[0].notes.len(), 1);
+        
+        // A qualified link lands in the folder it names, even beside a same-titled note
+        assert_eq!(storage.create_note_for_link(1, "Inbox/New Idea").unwrap(), (0, 1));
+        assert_eq!(storage.folders[0].notes[1].title, "New Idea");
+        assert!(matches!(storage.create_note_for_link(0, "  "), Err(Error::InvalidName(_))));
+    }
+    
+    #[test]
+    fn test_rename_move_and_delete_note() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Inbox").unwrap();
+        storage.create_folder("Archive").unwrap();
+        storage.create_note(0, "Draft", "text").unwrap();
+        
+        storage.rename_note(0, 0, "Final").unwrap();
+        assert_eq!(storage.folders[0].notes[0].title, "Final");
+        assert!(dir.path().join("Inbox/Final.md").exists());
+        assert!(dir.path().join("Inbox/Final.meta").exists());
+        assert!(!dir.path().join("Inbox/Draft.md").exists());
+        
+        let moved = storage.move_note(0, 0, 1).unwrap();
+        assert!(storage.folders[0].notes.is_empty());
+        assert_eq!(storage.folders[1].notes[moved].title, "Final");
+        assert_eq!(fs::read_to_string(dir.path().join("Archive/Final.md")).unwrap(), "text");
+        
+        let deleted = storage.delete_note(1, moved).unwrap();
+        assert_eq!(deleted.title, "Final");
+        assert!(!dir.path().join("Archive/Final.md").exists());
+        assert!(!dir.path().join("Archive/Final.meta").exists());
+        
+        storage.delete_folder(0).unwrap();
+        assert!(!dir.path().join("Inbox").exists());
+        assert_eq!(storage.folders[0].name, "Archive");
+    }
+    
+    #[test]
+    fn test_saving_unchanged_note_skips_the_write() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Inbox").unwrap();
+        storage.create_note(0, "Draft", "first").unwrap();
+        let path = dir.path().join("Inbox/Draft.md");
+        
+        storage.folders[0].notes[0].content = "second".to_string();
+        assert!(storage.write_note(0, 0).unwrap());
+        assert!(!storage.write_note(0, 0).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        
+        // Only the sidecar changes when only the metadata did
+        storage.folders[0].notes[0].icon = Some("📌".to_string());
+        assert!(!storage.write_note(0, 0).unwrap());
+        assert!(fs::read_to_string(dir.path().join("Inbox/Draft.meta")).unwrap().contains("📌"));
+        
+        // A freshly loaded note knows what's on disk too
+        let mut reloaded = Storage::new(dir.path().to_string_lossy().to_string());
+        reloaded.ensure_loaded(0, 0).unwrap();
+        assert!(!reloaded.write_note(0, 0).unwrap());
+    }
+    
+    #[test]
+    fn test_archive_list_and_unarchive_note() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Projects").unwrap();
+        storage.create_note(0, "Old Plan", "# Old Plan\nShip it").unwrap();
+        
+        let archived = storage.archive_note(0, 0).unwrap();
+        assert!(storage.folders[0].notes.is_empty());
+        assert!(!dir.path().join("Projects/Old Plan.md").exists());
+        assert!(Path::new(&archived.file_path).exists());
+        // Archived notes don't come back as a folder when the vault is reloaded
+        let reloaded = Storage::new(dir.path().to_string_lossy().to_string());
+        assert_eq!(reloaded.folders.len(), 1);
+        assert!(reloaded.folders[0].notes.is_empty());
+        
+        let listed = storage.list_archived();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].folder, "Projects");
+        assert_eq!(listed[0].note.title, "Old Plan");
+        
+        storage.delete_folder(0).unwrap();
+        let restored = storage.unarchive(Path::new(&listed[0].note.file_path)).unwrap();
+        assert_eq!(restored, (0, 0));
+        assert_eq!(storage.folders[0].name, "Projects");
+        assert_eq!(fs::read_to_string(dir.path().join("Projects/Old Plan.md")).unwrap(), "# Old Plan\nShip it");
+        assert!(storage.list_archived().is_empty());
+        assert!(matches!(
+            storage.unarchive(&dir.path().join("Projects/Old Plan.md")),
+            Err(Error::NotArchived(_))
+        ));
+    }
+    
+    #[test]
+    fn test_read_only_storage_refuses_changes() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Work").unwrap();
+        storage.create_folder("Home").unwrap();
+        storage.create_note(0, "Plan", "Original").unwrap();
+        let note_path = dir.path().join("Work/Plan.md");
+        
+        storage.set_read_only(true);
+        assert!(matches!(storage.create_folder("Later"), Err(Error::ReadOnly)));
+        assert!(matches!(storage.create_note(0, "New", ""), Err(Error::ReadOnly)));
+        storage.folders[0].notes[0].content = "Edited".to_string();
+        assert!(matches!(storage.save_note(0, 0), Err(Error::ReadOnly)));
+        assert!(matches!(storage.rename_note(0, 0, "Renamed"), Err(Error::ReadOnly)));
+        assert!(matches!(storage.move_note(0, 0, 1), Err(Error::ReadOnly)));
+        assert!(matches!(storage.archive_note(0, 0), Err(Error::ReadOnly)));
+        assert!(matches!(storage.delete_note(0, 0), Err(Error::ReadOnly)));
+        assert!(matches!(storage.delete_folder(0), Err(Error::ReadOnly)));
+        assert!(matches!(storage.set_note_appearance(0, 0, Some("📌".to_string()), None), Err(Error::ReadOnly)));
+        assert!(matches!(storage.reorder_note(0, 0, 0), Err(Error::ReadOnly)));
+        let photo = dir.path().join("photo.png");
+        image::RgbaImage::new(1, 1).save(&photo).unwrap();
+        assert!(matches!(storage.import_image(0, 0, &photo), Err(Error::ReadOnly)));
+        assert!(matches!(storage.save_pasted_image(0, 0, &[0; 4], 1, 1), Err(Error::ReadOnly)));
+        assert!(!dir.path().join("Work/images").exists());
+        
+        assert_eq!(fs::read_to_string(&note_path).unwrap(), "Original");
+        assert!(!dir.path().join("Later").exists());
+        assert_eq!(storage.folders.len(), 2);
+        assert_eq!(storage.folders[0].notes.len(), 1);
+        
+        storage.set_read_only(false);
+        storage.save_note(0, 0).unwrap();
+        assert_eq!(fs::read_to_string(&note_path).unwrap(), "Edited");
+    }
+    
+    #[test]
+    fn test_find_duplicates_groups_identical_and_similar_notes() {
+        let (_dir, mut storage) = temp_storage();
+        storage.create_folder("Inbox").unwrap();
+        storage.create_folder("Imported").unwrap();
+        let recipe = "Pancakes\n\nMix flour, milk and two eggs, then fry in butter until golden on both sides.";
+        storage.create_note(0, "Pancakes", recipe).unwrap();
+        storage.create_note(0, "Shopping", "Buy milk").unwrap();
+        storage.create_note(1, "Pancakes copy", &format!("{}\r\n", recipe.replace('\n', "\r\n"))).unwrap();
+        storage.create_note(1, "Pancakes edited", &format!("{} Serve warm.", recipe)).unwrap();
+        storage.create_note(1, "Blank", "").unwrap();
+        storage.create_note(1, "Blank too", "").unwrap();
+        
+        assert_eq!(storage.find_duplicates(), vec![vec![(0, 0), (1, 0), (1, 1)]]);
+    }
+    
+    #[test]
+    fn test_similar_candidates_cover_every_similar_pair() {
+        let texts: Vec<String> = (0..40)
+            .map(|i| (0..(5 + i % 7)).map(|w| format!("w{}", (w * (i % 5 + 1) + i / 8) % 12)).collect::<Vec<_>>().join(" "))
+            .collect();
+        let sets: Vec<HashSet<String>> = texts.iter().map(|t| word_pairs(t)).collect();
+        let candidates: HashSet<(usize, usize)> = similar_candidates(&sets, 0.5).into_iter().collect();
+        let mut similar = 0;
+        for i in 0..sets.len() {
+            for j in i + 1..sets.len() {
+                if jaccard(&sets[i], &sets[j]) >= 0.5 {
+                    assert!(candidates.contains(&(i, j)), "missed {} and {}", texts[i], texts[j]);
+                    similar += 1;
+                }
+            }
+        }
+        assert!(similar > 0);
+        assert!(candidates.len() < sets.len() * (sets.len() - 1) / 2);
+    }
+    
+    #[test]
+    fn test_merge_notes_keeps_tags_and_extra_content() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Inbox").unwrap();
+        storage.create_note(0, "Copy", "Same text").unwrap();
+        storage.create_note(0, "Original", "Same text").unwrap();
+        storage.create_note(0, "Longer", "Same text\n\nPlus a bit more").unwrap();
+        storage.create_folder("Archive").unwrap();
+        storage.create_note(1, "Index", "See [[Copy]], [[Longer]] and [[Elsewhere]]").unwrap();
+        storage.create_note(1, "Original", "Unrelated").unwrap();
+        storage.folders[0].notes[0].add_tag(3);
+        storage.folders[0].notes[0].properties.insert("status".to_string(), "draft".to_string());
+        storage.folders[0].notes[2].front_matter = Some(FrontMatter {
+            tags: vec!["recipes".to_string()],
+            ..Default::default()
+        });
+        
+        let kept = storage.merge_notes((0, 1), &[(0, 0), (0, 1), (0, 2)]).unwrap();
+        assert_eq!(kept, (0, 0));
+        assert_eq!(storage.folders[0].notes.len(), 1);
+        let note = &storage.folders[0].notes[0];
+        assert_eq!(note.title, "Original");
+        assert!(note.tags.has_tag(3));
+        assert_eq!(note.properties["status"], "draft");
+        assert_eq!(note.front_matter.as_ref().unwrap().tags, vec!["recipes"]);
+        assert_eq!(note.content, "Same text\n\n---\n\nPlus a bit more\n");
+        assert!(!dir.path().join("Inbox/Copy.md").exists());
+        assert!(!dir.path().join("Inbox/Longer.md").exists());
+        
+        // "Original" alone would mean the Archive note from there, so the folder is named
+        let index = &storage.folders[1].notes[0];
+        assert_eq!(index.content, "See [[Inbox/Original]], [[Inbox/Original]] and [[Elsewhere]]");
+        assert!(fs::read_to_string(&index.file_path).unwrap().contains("[[Inbox/Original]]"));
+    }
+    
+    #[test]
+    fn test_json_export_then_import_round_trips() {
+        let (dir, mut storage) = temp_storage();
+        let mut tags = TagManager::new();
+        storage.create_folder("Work").unwrap();
+        storage.create_folder("Home").unwrap();
+        storage.create_folder("Empty").unwrap();
+        storage.create_note(0, "Plan", "# Plan\n\nBudget for Q3.\n").unwrap();
+        storage.create_note(0, "Ideas", "").unwrap();
+        storage.create_note(1, "Groceries", "- milk\n- eggs\n").unwrap();
+        let note = &mut storage.folders[0].notes[0];
+        note.created_at = chrono::DateTime::parse_from_rfc3339("2024-03-01T09:30:00Z").unwrap().into();
+        note.add_tag(tags.add_tag("budget".to_string()));
+        note.add_tag(tags.add_tag("work".to_string()));
+        storage.save_note(0, 0).unwrap();
+        
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        let json = storage.export_json(&tags).unwrap();
+        let exported: VaultExport = serde_json::from_str(&json).unwrap();
+        let plan = exported.folders.iter().flat_map(|f| &f.notes).find(|n| n.title == "Plan").unwrap();
+        assert_eq!(plan.tags, vec!["budget", "work"]);
+        assert_eq!(plan.created_at.to_rfc3339(), "2024-03-01T09:30:00+00:00");
+        
+        let other = tempfile::tempdir().unwrap();
+        let mut imported = Storage::new(other.path().to_string_lossy().to_string());
+        let mut imported_tags = TagManager::new();
+        assert_eq!(imported.import_json(&json, &mut imported_tags).unwrap(), 3);
+        
+        let mut reopened = Storage::new(other.path().to_string_lossy().to_string());
+        let round_trip: VaultExport = serde_json::from_str(&reopened.export_json(&imported_tags).unwrap()).unwrap();
+        assert_eq!(round_trip, exported);
+        
+        assert!(imported.import_json("not json", &mut imported_tags).is_err());
+    }
+    
+    #[test]
+    fn test_draft_recovered_after_restart() {
+        let (dir, mut storage) = temp_storage();
+        storage.create_folder("Inbox").unwrap();
+        storage.create_note(0, "Plan", "Saved text").unwrap();
+        storage.create_note(0, "Other", "Unchanged").unwrap();
+        storage.write_draft(0, 0, "Saved text, then edits that never got saved").unwrap();
+        storage.write_draft(0, 1, "Unchanged").unwrap();
+        drop(storage);
+        
+        // As if the app crashed and was started again
+        let mut storage = Storage::new(dir.path().to_string_lossy().to_string());
+        let plan = storage.folders[0].notes.iter().position(|n| n.title == "Plan").unwrap();
+        let other = 1 - plan;
+        assert_eq!(storage.notes_with_drafts(), vec![(0, plan)]);
+        assert!(!dir.path().join("Inbox/Other.draft").exists());
+        assert_eq!(
+            storage.recover_draft(0, plan).unwrap().as_deref(),
+            Some("Saved text, then edits that never got saved")
+        );
+        assert_eq!(storage.recover_draft(0, other).unwrap(), None);
+        
+        storage.clear_draft(0, plan).unwrap();
+        assert_eq!(storage.recover_draft(0, plan).unwrap(), None);
+        assert!(storage.notes_with_drafts().is_empty());
+    }
 }
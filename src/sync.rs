@@ -0,0 +1,120 @@
+use crate::storage::{SyncPlan, SyncSummary};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+enum SyncEvent {
+    Progress(usize, usize),
+    Finished(Result<SyncSummary, String>),
+}
+
+/// Runs a cloud sync on a background thread so copying a large vault never
+/// freezes the window. Progress and the result come back through `poll`.
+pub struct SyncWorker {
+    events: Receiver<SyncEvent>,
+    cancelled: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    progress: (usize, usize), // (files done, total)
+}
+
+impl SyncWorker {
+    pub fn spawn(plan: SyncPlan) -> Self {
+        let (event_tx, event_rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let stop = cancelled.clone();
+
+        let handle = thread::spawn(move || {
+            let result = plan.run(|done, total| {
+                event_tx.send(SyncEvent::Progress(done, total)).ok();
+                !stop.load(Ordering::SeqCst)
+            });
+            event_tx
+                .send(SyncEvent::Finished(result.map_err(|e| e.to_string())))
+                .ok();
+        });
+
+        Self {
+            events: event_rx,
+            cancelled,
+            handle: Some(handle),
+            progress: (0, 0),
+        }
+    }
+
+    /// Stop after the file being copied; the sync then finishes with an error.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Files copied so far and the total, as of the last `poll`.
+    pub fn progress(&self) -> (usize, usize) {
+        self.progress
+    }
+
+    /// Take in progress reports without blocking. Returns the sync's result once
+    /// it has finished.
+    pub fn poll(&mut self) -> Option<Result<SyncSummary, String>> {
+        loop {
+            match self.events.try_recv() {
+                Ok(SyncEvent::Progress(done, total)) => self.progress = (done, total),
+                Ok(SyncEvent::Finished(result)) => return Some(result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err("Sync worker stopped".to_string()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SyncWorker {
+    fn drop(&mut self) {
+        self.cancel();
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_sync_worker_copies_files_and_reports_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join("vault");
+        let mut storage = Storage::new(vault.to_string_lossy().to_string());
+        storage.create_folder("Work").unwrap();
+        for title in ["One", "Two", "Three"] {
+            storage.create_note(0, title, title).unwrap();
+        }
+
+        let mut worker = SyncWorker::spawn(storage.cloud_sync_plan());
+        let started = Instant::now();
+        let summary = loop {
+            if let Some(result) = worker.poll() {
+                break result.unwrap();
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "sync never finished"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+        };
+
+        // Each note goes with its .meta file
+        assert_eq!((summary.added, summary.updated, summary.deleted), (6, 0, 0));
+        assert_eq!(worker.progress(), (6, 6));
+        let synced = Path::new(&summary.path).join("Work").join("Two.md");
+        assert_eq!(std::fs::read_to_string(synced).unwrap(), "Two");
+    }
+}
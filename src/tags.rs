@@ -1,5 +1,16 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::autocomplete::STOPWORDS;
+use crate::note::{Folder, Note};
+
+/// How many tags `suggest_tags` proposes at most.
+const MAX_SUGGESTED_TAGS: usize = 5;
+/// A word has to come up this often in a note before it's worth suggesting.
+const MIN_WORD_COUNT: usize = 3;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tag {
@@ -15,6 +26,7 @@ impl Tag {
         }
     }
     
+    #[allow(dead_code)]
     pub fn with_color(name: String, color: [u8; 3]) -> Self {
         Self { name, color }
     }
@@ -32,6 +44,7 @@ pub struct TagManager {
 }
 
 impl TagManager {
+    #[allow(dead_code)]
     pub fn new() -> Self {
         Self {
             tags: Vec::new(),
@@ -49,6 +62,7 @@ impl TagManager {
         self.tags.len() - 1
     }
     
+    #[allow(dead_code)]
     pub fn get_tag(&self, name: &str) -> Option<&Tag> {
         self.tags.iter().find(|t| t.name == name)
     }
@@ -57,13 +71,111 @@ impl TagManager {
         self.tags.get(index)
     }
     
-    pub fn remove_tag(&mut self, name: &str) {
-        self.tags.retain(|t| t.name != name);
+    /// Remove a tag by name, returning the index it occupied.
+    ///
+    /// Every later tag shifts down by one, so callers must pass the returned
+    /// index to [`reindex_notes`] to keep stored `NoteTags` pointing at the right tags.
+    pub fn remove_tag(&mut self, name: &str) -> Option<usize> {
+        let idx = self.tags.iter().position(|t| t.name == name)?;
+        self.tags.remove(idx);
+        Some(idx)
     }
     
     pub fn all_tags(&self) -> &[Tag] {
         &self.tags
     }
+    
+    /// Rename a tag in place. Its index is unchanged, so `NoteTags` stay valid.
+    /// Fails if another tag already has `new_name`; use [`TagManager::merge_tag`] for that case.
+    pub fn rename_tag(&mut self, index: usize, new_name: &str) -> Result<(), String> {
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            return Err("Tag name is empty".to_string());
+        }
+        if let Some(existing) = self.tags.iter().position(|t| t.name == new_name) {
+            if existing != index {
+                return Err(format!("A tag named '{}' already exists", new_name));
+            }
+        }
+        
+        let tag = self.tags.get_mut(index).ok_or("Tag not found")?;
+        tag.name = new_name.to_string();
+        Ok(())
+    }
+    
+    /// Move every note tagged `from` onto `into`, then delete `from`.
+    /// Returns the notes whose tags changed.
+    pub fn merge_tag(&mut self, folders: &mut [Folder], from: usize, into: usize) -> Result<Vec<(usize, usize)>, String> {
+        if from == into {
+            return Err("Cannot merge a tag into itself".to_string());
+        }
+        if from >= self.tags.len() || into >= self.tags.len() {
+            return Err("Tag not found".to_string());
+        }
+        
+        let mut changed = Vec::new();
+        for (folder_idx, folder) in folders.iter_mut().enumerate() {
+            for (note_idx, note) in folder.notes.iter_mut().enumerate() {
+                if note.tags.has_tag(from) {
+                    note.tags.remove_tag(from);
+                    note.tags.add_tag(into);
+                    changed.push((folder_idx, note_idx));
+                }
+            }
+        }
+        
+        self.tags.remove(from);
+        for id in reindex_notes(folders, from) {
+            if !changed.contains(&id) {
+                changed.push(id);
+            }
+        }
+        Ok(changed)
+    }
+    
+    /// Number of notes referencing each tag index. Every tag has an entry, so unused tags map to 0.
+    pub fn usage_counts(&self, folders: &[Folder]) -> HashMap<usize, usize> {
+        let mut counts: HashMap<usize, usize> = (0..self.tags.len()).map(|idx| (idx, 0)).collect();
+        for note in folders.iter().flat_map(|f| &f.notes) {
+            for idx in &note.tags.tag_indices {
+                if let Some(count) = counts.get_mut(idx) {
+                    *count += 1;
+                }
+            }
+        }
+        counts
+    }
+    
+    /// Write the tag list to disk.
+    ///
+    /// `NoteTags` refers to tags by their position in this list, so the order
+    /// written here is the order every note's indices are resolved against.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+    
+    /// Load a tag list saved by [`TagManager::save`]. The order is kept exactly
+    /// as stored (never sorted or deduplicated) so saved tag indices stay valid.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let manager = serde_json::from_str(&json)?;
+        Ok(manager)
+    }
+    
+    /// Register the tag names declared in a note's front-matter and assign them to the note.
+    pub fn register_front_matter_tags(&mut self, note: &mut Note) {
+        let names = note
+            .front_matter
+            .as_ref()
+            .map(|fm| fm.tags.clone())
+            .unwrap_or_default();
+        
+        for name in names {
+            let idx = self.add_tag(name);
+            note.add_tag(idx);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -97,11 +209,62 @@ impl NoteTags {
             .collect()
     }
     
+    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.tag_indices.clear();
     }
+    
+    /// Drop `removed` and shift every higher index down to match the shrunk tag list.
+    /// Returns true if anything changed.
+    pub fn reindex_after_removal(&mut self, removed: usize) -> bool {
+        if !self.tag_indices.iter().any(|&idx| idx >= removed) {
+            return false;
+        }
+        
+        self.tag_indices = self
+            .tag_indices
+            .iter()
+            .filter(|&&idx| idx != removed)
+            .map(|&idx| if idx > removed { idx - 1 } else { idx })
+            .collect();
+        true
+    }
+}
+
+/// Rename a tag inside every note's front-matter list. Returns the notes that changed.
+pub fn rename_front_matter_tag(folders: &mut [Folder], old_name: &str, new_name: &str) -> Vec<(usize, usize)> {
+    let mut changed = Vec::new();
+    for (folder_idx, folder) in folders.iter_mut().enumerate() {
+        for (note_idx, note) in folder.notes.iter_mut().enumerate() {
+            if let Some(front_matter) = note.front_matter.as_mut() {
+                if front_matter.tags.iter().any(|t| t == old_name) {
+                    front_matter.tags.retain(|t| t != old_name);
+                    if !front_matter.tags.iter().any(|t| t == new_name) {
+                        front_matter.tags.push(new_name.to_string());
+                    }
+                    changed.push((folder_idx, note_idx));
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// Fix up every note's tag indices after the tag at `removed` was deleted.
+/// Returns the `(folder_idx, note_idx)` of each note whose tags changed, so they can be saved.
+pub fn reindex_notes(folders: &mut [Folder], removed: usize) -> Vec<(usize, usize)> {
+    let mut changed = Vec::new();
+    for (folder_idx, folder) in folders.iter_mut().enumerate() {
+        for (note_idx, note) in folder.notes.iter_mut().enumerate() {
+            if note.tags.reindex_after_removal(removed) {
+                changed.push((folder_idx, note_idx));
+            }
+        }
+    }
+    changed
 }
 
+#[allow(dead_code)]
 pub fn filter_notes_by_tag(notes: &[(usize, usize, NoteTags)], tag_index: usize) -> Vec<(usize, usize)> {
     notes
         .iter()
@@ -109,3 +272,160 @@ pub fn filter_notes_by_tag(notes: &[(usize, usize, NoteTags)], tag_index: usize)
         .map(|(folder_idx, note_idx, _)| (*folder_idx, *note_idx))
         .collect()
 }
+
+/// Tags for a note with `content`, from the words it uses most: lowercase, at
+/// least three letters (hyphens allowed), not a stopword and not already one of
+/// `existing_tags`. Most frequent first, ties in alphabetical order. Code blocks
+/// are skipped.
+pub fn suggest_tags(content: &str, existing_tags: &[Tag]) -> Vec<String> {
+    let stopwords: HashSet<&str> = STOPWORDS.iter().copied().collect();
+    let existing: HashSet<String> = existing_tags.iter().map(|t| t.name.to_lowercase()).collect();
+    
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut in_code = false;
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        for word in line.split(|c: char| !c.is_alphanumeric() && c != '-' && c != '\'') {
+            let word = word.trim_matches(|c: char| c == '-' || c == '\'').to_lowercase();
+            if word.chars().count() < 3
+                || !word.chars().all(|c| c.is_alphabetic() || c == '-')
+                || stopwords.contains(word.as_str())
+                || existing.contains(&word)
+            {
+                continue;
+            }
+            *counts.entry(word).or_insert(0) += 1;
+        }
+    }
+    
+    let mut words: Vec<(String, usize)> = counts
+        .into_iter()
+        .filter(|(_, count)| *count >= MIN_WORD_COUNT)
+        .collect();
+    words.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    words.into_iter().take(MAX_SUGGESTED_TAGS).map(|(word, _)| word).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_save_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tags.json");
+        
+        let mut manager = TagManager::new();
+        manager.tags.push(Tag::with_color("work".to_string(), [10, 20, 30]));
+        manager.tags.push(Tag::with_color("alpha".to_string(), [40, 50, 60]));
+        manager.save(&path).unwrap();
+        
+        let loaded = TagManager::load(&path).unwrap();
+        let names: Vec<_> = loaded.tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["work", "alpha"]);
+        assert_eq!(loaded.tags[0].color, [10, 20, 30]);
+        assert_eq!(loaded.tags[1].color, [40, 50, 60]);
+    }
+    
+    #[test]
+    fn test_remove_tag_keeps_note_tags_valid() {
+        let mut manager = TagManager::new();
+        manager.add_tag("first".to_string());
+        manager.add_tag("second".to_string());
+        let third = manager.add_tag("third".to_string());
+        
+        let mut folder = Folder::new("Inbox".to_string(), String::new());
+        let mut note = Note::new("Note".to_string(), String::new());
+        note.add_tag(third);
+        folder.add_note(note);
+        let mut folders = vec![folder];
+        
+        let removed = manager.remove_tag("first").unwrap();
+        let changed = reindex_notes(&mut folders, removed);
+        assert_eq!(changed, vec![(0, 0)]);
+        
+        let tags = folders[0].notes[0].tags.get_tags(&manager);
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].name, "third");
+    }
+    
+    #[test]
+    fn test_usage_counts() {
+        let mut manager = TagManager::new();
+        let rust = manager.add_tag("rust".to_string());
+        let notes_tag = manager.add_tag("notes".to_string());
+        let unused = manager.add_tag("unused".to_string());
+        
+        let mut folder = Folder::new("Inbox".to_string(), String::new());
+        for title in ["A", "B", "C"] {
+            let mut note = Note::new(title.to_string(), String::new());
+            note.add_tag(rust);
+            if title == "A" {
+                note.add_tag(notes_tag);
+            }
+            folder.add_note(note);
+        }
+        
+        let counts = manager.usage_counts(&[folder]);
+        assert_eq!(counts[&rust], 3);
+        assert_eq!(counts[&notes_tag], 1);
+        assert_eq!(counts[&unused], 0);
+    }
+    
+    #[test]
+    fn test_rename_tag() {
+        let mut manager = TagManager::new();
+        let wip = manager.add_tag("wip".to_string());
+        manager.add_tag("done".to_string());
+        
+        manager.rename_tag(wip, "in-progress").unwrap();
+        assert_eq!(manager.tags[wip].name, "in-progress");
+        assert!(manager.rename_tag(wip, "done").is_err());
+        assert_eq!(manager.tags[wip].name, "in-progress");
+    }
+    
+    #[test]
+    fn test_merge_tag_on_collision() {
+        let mut manager = TagManager::new();
+        let wip = manager.add_tag("wip".to_string());
+        let in_progress = manager.add_tag("in-progress".to_string());
+        let other = manager.add_tag("other".to_string());
+        
+        let mut folder = Folder::new("Inbox".to_string(), String::new());
+        let mut first = Note::new("First".to_string(), String::new());
+        first.add_tag(wip);
+        let mut second = Note::new("Second".to_string(), String::new());
+        second.add_tag(other);
+        folder.add_note(first);
+        folder.add_note(second);
+        let mut folders = vec![folder];
+        
+        let changed = manager.merge_tag(&mut folders, wip, in_progress).unwrap();
+        assert_eq!(changed, vec![(0, 0), (0, 1)]);
+        assert_eq!(manager.tags.len(), 2);
+        
+        let first_tags = folders[0].notes[0].tags.get_tags(&manager);
+        assert_eq!(first_tags.len(), 1);
+        assert_eq!(first_tags[0].name, "in-progress");
+        assert_eq!(folders[0].notes[1].tags.get_tags(&manager)[0].name, "other");
+    }
+    
+    #[test]
+    fn test_suggest_tags_from_frequent_words() {
+        let content = "Budget review for the quarter.\n\n\
+                       The budget is over: travel budget, office budget and a new budget line.\n\
+                       Travel costs went up. Travel was the biggest cost.\n\n\
+                       ```\nlet budget_code = code + code + code;\n```\n";
+        assert_eq!(suggest_tags(content, &[]), vec!["budget", "travel"]);
+        
+        let existing = [Tag::new("Budget".to_string())];
+        assert_eq!(suggest_tags(content, &existing), vec!["travel"]);
+        assert!(suggest_tags("", &[]).is_empty());
+    }
+}
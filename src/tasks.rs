@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+/// A Markdown task list item (`- [ ] text` / `- [x] text`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    pub text: String,
+    pub done: bool,
+    pub line: usize, // 0-based line in the note
+}
+
+/// Every task list item in `content`, in document order. Items inside fenced code
+/// blocks are ignored.
+pub fn parse_tasks(content: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut in_fence = false;
+
+    for (line_idx, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        if let Some((done, marker)) = checkbox(line) {
+            tasks.push(Task {
+                text: line[marker.end..].trim().to_string(),
+                done,
+                line: line_idx,
+            });
+        }
+    }
+
+    tasks
+}
+
+/// Split tasks into `(open, done)` counts.
+pub fn task_counts(tasks: &[Task]) -> (usize, usize) {
+    let done = tasks.iter().filter(|t| t.done).count();
+    (tasks.len() - done, done)
+}
+
+/// Flip `[ ]` <-> `[x]` on line `line`. Returns false if that line isn't a task.
+pub fn toggle_task(content: &mut String, line: usize) -> bool {
+    let Some(line_start) = line_offset(content, line) else {
+        return false;
+    };
+    let line_end = content[line_start..]
+        .find('\n')
+        .map_or(content.len(), |i| line_start + i);
+
+    match checkbox(&content[line_start..line_end]) {
+        Some((done, marker)) => {
+            let range = line_start + marker.start..line_start + marker.end;
+            content.replace_range(range, if done { "[ ]" } else { "[x]" });
+            true
+        }
+        None => false,
+    }
+}
+
+/// Whether `line` is a list item starting with a checkbox, and the byte range of
+/// the `[ ]`/`[x]` marker within it.
+fn checkbox(line: &str) -> Option<(bool, Range<usize>)> {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+
+    let bullet_len = if rest.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        // Ordered lists: "1." or "1)"
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || !rest[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+
+    let after_bullet = &rest[bullet_len..];
+    let spaces = after_bullet.len() - after_bullet.trim_start_matches([' ', '\t']).len();
+    if spaces == 0 {
+        return None;
+    }
+
+    let start = indent + bullet_len + spaces;
+    let done = match line.get(start..start + 3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    // "- [x]text" is not a task
+    if !line[start + 3..].is_empty() && !line[start + 3..].starts_with([' ', '\t']) {
+        return None;
+    }
+
+    Some((done, start..start + 3))
+}
+
+/// Byte offset where 0-based line `line` starts.
+pub fn line_offset(content: &str, line: usize) -> Option<usize> {
+    if line == 0 {
+        return Some(0);
+    }
+    content
+        .match_indices('\n')
+        .nth(line - 1)
+        .map(|(i, _)| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mixed_tasks() {
+        let content = "# Todo\n- [ ] Write docs\n- [x] Fix bug\n  * [X] Nested\n1. [ ] Ordered\n- plain item\n- [x]no space\n```\n- [ ] in code\n```\n";
+        let tasks = parse_tasks(content);
+
+        let summary: Vec<_> = tasks
+            .iter()
+            .map(|t| (t.text.as_str(), t.done, t.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Write docs", false, 1),
+                ("Fix bug", true, 2),
+                ("Nested", true, 3),
+                ("Ordered", false, 4),
+            ]
+        );
+        assert_eq!(task_counts(&tasks), (2, 2));
+    }
+
+    #[test]
+    fn test_toggle_task() {
+        let mut content = "- [ ] First\n- [x] Second\nNot a task\n".to_string();
+
+        assert!(toggle_task(&mut content, 0));
+        assert!(toggle_task(&mut content, 1));
+        assert_eq!(content, "- [x] First\n- [ ] Second\nNot a task\n");
+
+        assert!(!toggle_task(&mut content, 2));
+        assert!(!toggle_task(&mut content, 10));
+        assert_eq!(content, "- [x] First\n- [ ] Second\nNot a task\n");
+    }
+}
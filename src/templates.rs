@@ -0,0 +1,121 @@
+use chrono::Local;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub body: String,
+}
+
+impl Template {
+    pub fn new(name: String, body: String) -> Self {
+        Self { name, body }
+    }
+
+    /// Substitute `${title}` and `${date}` (today, local time) into the template body.
+    pub fn render(&self, title: &str) -> String {
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        render_placeholders(&self.body, title, &date)
+    }
+}
+
+pub fn render_placeholders(body: &str, title: &str, date: &str) -> String {
+    body.replace("${title}", title).replace("${date}", date)
+}
+
+/// Template used for daily notes when the user has one by this name.
+pub const DAILY_TEMPLATE: &str = "Daily Journal";
+
+pub struct TemplateManager {
+    pub templates: Vec<Template>,
+}
+
+impl TemplateManager {
+    /// Load every `.md` template in `dir`. The directory is created and seeded
+    /// with the built-in templates the first time.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        if !dir.exists() {
+            fs::create_dir_all(dir)?;
+            for template in Self::default_templates() {
+                fs::write(dir.join(format!("{}.md", template.name)), &template.body)?;
+            }
+        }
+
+        let mut templates = Vec::new();
+        for entry in fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("md") {
+                if let (Some(name), Ok(body)) = (
+                    path.file_stem().and_then(|s| s.to_str()),
+                    fs::read_to_string(&path),
+                ) {
+                    templates.push(Template::new(name.to_string(), body));
+                }
+            }
+        }
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self { templates })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.iter().find(|t| t.name == name)
+    }
+
+    /// The daily-note template, or a bare date heading if it was deleted.
+    pub fn daily_template(&self) -> Template {
+        self.get(DAILY_TEMPLATE)
+            .cloned()
+            .unwrap_or_else(|| Template::new(DAILY_TEMPLATE.to_string(), "# ${date}\n".to_string()))
+    }
+
+    fn default_templates() -> Vec<Template> {
+        vec![
+            Template::new(
+                "Meeting".to_string(),
+                "# ${title}\n\nDate: ${date}\n\n## Attendees\n\n- \n\n## Agenda\n\n- \n\n## Action Items\n\n- [ ] \n".to_string(),
+            ),
+            Template::new(
+                DAILY_TEMPLATE.to_string(),
+                "# ${date}\n\n## Today\n\n\n## Gratitude\n\n- \n\n## Tomorrow\n\n- [ ] \n".to_string(),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn test_placeholders_substituted() {
+        let rendered =
+            render_placeholders("# ${title}\n${date} - ${title}", "Standup", "2024-01-02");
+        assert_eq!(rendered, "# Standup\n2024-01-02 - Standup");
+    }
+
+    #[test]
+    fn test_create_note_from_template() {
+        let vault = tempfile::tempdir().unwrap();
+        let template_dir = tempfile::tempdir().unwrap();
+        let template_path = template_dir.path().join("templates");
+
+        let manager = TemplateManager::load(&template_path).unwrap();
+        assert!(manager.get("Meeting").is_some());
+        assert!(manager.get("Daily Journal").is_some());
+
+        let template = Template::new("Simple".to_string(), "# ${title}\n".to_string());
+        let mut storage = Storage::new(vault.path().to_string_lossy().to_string());
+        storage.create_folder("Work").unwrap();
+        let note_idx = storage
+            .create_note(0, "Standup", &template.render("Standup"))
+            .unwrap();
+
+        let note = &storage.folders[0].notes[note_idx];
+        assert_eq!(note.content, "# Standup\n");
+        assert_eq!(fs::read_to_string(&note.file_path).unwrap(), "# Standup\n");
+    }
+}
@@ -1,7 +1,8 @@
 use egui::{Color32, Visuals};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Theme {
@@ -116,6 +117,8 @@ impl Theme {
         visuals.window_fill = Color32::from_rgb(self.background[0], self.background[1], self.background[2]);
         visuals.panel_fill = Color32::from_rgb(self.sidebar_bg[0], self.sidebar_bg[1], self.sidebar_bg[2]);
         visuals.extreme_bg_color = Color32::from_rgb(self.editor_bg[0], self.editor_bg[1], self.editor_bg[2]);
+        visuals.widgets.noninteractive.fg_stroke.color = Color32::from_rgb(self.foreground[0], self.foreground[1], self.foreground[2]);
+        visuals.hyperlink_color = Color32::from_rgb(self.accent[0], self.accent[1], self.accent[2]);
         
         ctx.set_visuals(visuals);
     }
@@ -143,11 +146,72 @@ impl Theme {
         let theme = serde_json::from_str(&json)?;
         Ok(theme)
     }
+    
+    /// Load every `*.json` theme in `dir`, sorted by name. Unreadable files are skipped.
+    pub fn load_user_themes(dir: &Path) -> Vec<Theme> {
+        let mut themes: Vec<Theme> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+                    .filter_map(|path| Self::load(&path).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        
+        themes.sort_by(|a, b| a.name.cmp(&b.name));
+        themes
+    }
+    
+    fn file_stem(&self) -> String {
+        self.name
+            .trim()
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+            .collect()
+    }
+    
+    /// The file in `dir` this theme saves to. Different names can sanitise to the same
+    /// stem ("Ocean Blue" and "Ocean_Blue"), so a file holding another theme gets a
+    /// numeric suffix rather than being overwritten.
+    fn path_in(&self, dir: &Path) -> PathBuf {
+        let stem = self.file_stem();
+        let mut path = dir.join(format!("{}.json", stem));
+        let mut n = 2;
+        while path.exists() && Self::load(&path).map_or(true, |t| t.name != self.name) {
+            path = dir.join(format!("{}-{}.json", stem, n));
+            n += 1;
+        }
+        path
+    }
+}
+
+/// Theme choice persisted between sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ThemePreferences {
+    theme: Theme,
+    #[serde(default)]
+    follow_system: bool,
+}
+
+/// Map a detected OS appearance to the matching built-in theme.
+/// `None` means detection failed or the OS expressed no preference.
+pub fn theme_for_mode(mode: Option<dark_light::Mode>) -> Option<Theme> {
+    match mode? {
+        dark_light::Mode::Dark => Some(Theme::dark()),
+        dark_light::Mode::Light => Some(Theme::light()),
+        dark_light::Mode::Unspecified => None,
+    }
 }
 
 pub struct ThemeManager {
     pub current_theme: Theme,
     pub available_themes: Vec<Theme>,
+    pub follow_system: bool,
+    // The user's explicit choice, used whenever OS detection fails
+    saved_theme: Theme,
+    user_themes_dir: Option<PathBuf>,
 }
 
 impl ThemeManager {
@@ -155,26 +219,150 @@ impl ThemeManager {
         Self {
             current_theme: Theme::default(),
             available_themes: Theme::available_themes(),
+            follow_system: false,
+            saved_theme: Theme::default(),
+            user_themes_dir: None,
         }
     }
     
+    #[allow(dead_code)]
     pub fn load_or_default(config_path: &Path) -> Self {
         let theme = Theme::load(config_path).unwrap_or_default();
         Self {
-            current_theme: theme,
+            current_theme: theme.clone(),
             available_themes: Theme::available_themes(),
+            follow_system: false,
+            saved_theme: theme,
+            user_themes_dir: None,
+        }
+    }
+    
+    /// Built-in themes followed by any custom themes saved in `dir`.
+    pub fn with_user_themes(dir: &Path) -> Self {
+        let mut manager = Self::new();
+        manager.available_themes.extend(Theme::load_user_themes(dir));
+        manager.user_themes_dir = Some(dir.to_path_buf());
+        manager
+    }
+    
+    /// Persist a custom theme into the user-themes directory and make it available.
+    /// A theme with the same name replaces the existing entry.
+    pub fn save_user_theme(&mut self, theme: Theme) -> io::Result<PathBuf> {
+        if theme.name.trim().is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Theme name is empty"));
+        }
+        
+        let dir = self
+            .user_themes_dir
+            .clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No user themes directory"))?;
+        fs::create_dir_all(&dir)?;
+        
+        let path = theme.path_in(&dir);
+        theme.save(&path)?;
+        
+        if let Some(existing) = self.available_themes.iter_mut().find(|t| t.name == theme.name) {
+            *existing = theme;
+        } else {
+            self.available_themes.push(theme);
         }
+        
+        Ok(path)
     }
     
     pub fn set_theme(&mut self, theme: Theme) {
+        self.saved_theme = theme.clone();
         self.current_theme = theme;
     }
     
     pub fn toggle_dark_mode(&mut self) {
-        self.current_theme = if self.current_theme.is_dark {
+        let theme = if self.current_theme.is_dark {
             Theme::light()
         } else {
             Theme::dark()
         };
+        self.set_theme(theme);
+    }
+    
+    /// Switch to the light or dark theme matching the OS appearance,
+    /// falling back to the saved theme when detection fails.
+    pub fn apply_system_preference(&mut self) {
+        let detected = dark_light::detect().ok();
+        self.current_theme = theme_for_mode(detected).unwrap_or_else(|| self.saved_theme.clone());
+    }
+    
+    pub fn set_follow_system(&mut self, follow: bool) {
+        self.follow_system = follow;
+        if follow {
+            self.apply_system_preference();
+        } else {
+            self.current_theme = self.saved_theme.clone();
+        }
+    }
+    
+    /// Restore the saved theme and the follow-system flag. Missing or invalid files leave defaults.
+    pub fn load_preferences(&mut self, path: &Path) {
+        let prefs: Option<ThemePreferences> = fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok());
+        
+        if let Some(prefs) = prefs {
+            self.set_theme(prefs.theme);
+            self.follow_system = prefs.follow_system;
+        }
+    }
+    
+    pub fn save_preferences(&self, path: &Path) -> io::Result<()> {
+        let prefs = ThemePreferences {
+            theme: self.saved_theme.clone(),
+            follow_system: self.follow_system,
+        };
+        let json = serde_json::to_string_pretty(&prefs)?;
+        fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_save_and_reload_custom_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = ThemeManager::with_user_themes(dir.path());
+        
+        let mut theme = Theme::dark();
+        theme.name = "My Theme".to_string();
+        theme.accent = [12, 34, 56];
+        let path = manager.save_user_theme(theme).unwrap();
+        assert!(path.exists());
+        assert!(manager.available_themes.iter().any(|t| t.name == "My Theme"));
+        
+        let reloaded = ThemeManager::with_user_themes(dir.path());
+        let custom = reloaded
+            .available_themes
+            .iter()
+            .find(|t| t.name == "My Theme")
+            .unwrap();
+        assert_eq!(custom.accent, [12, 34, 56]);
+        assert_eq!(reloaded.available_themes.len(), Theme::available_themes().len() + 1);
+        
+        // A name that sanitises to the same file gets its own; re-saving reuses it
+        let mut clash = Theme::light();
+        clash.name = "My_Theme".to_string();
+        let clash_path = manager.save_user_theme(clash.clone()).unwrap();
+        assert_ne!(clash_path, path);
+        assert_eq!(manager.save_user_theme(clash).unwrap(), clash_path);
+        assert_eq!(Theme::load(&path).unwrap().name, "My Theme");
+    }
+    
+    #[test]
+    fn test_system_mode_maps_to_theme() {
+        use dark_light::Mode;
+        
+        assert_eq!(theme_for_mode(Some(Mode::Dark)).unwrap().name, "Dark");
+        assert_eq!(theme_for_mode(Some(Mode::Light)).unwrap().name, "Light");
+        assert!(theme_for_mode(Some(Mode::Unspecified)).is_none());
+        assert!(theme_for_mode(None).is_none());
     }
 }
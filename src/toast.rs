@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant};
+
+/// How long a toast stays on screen, including its fade.
+pub const TOAST_DURATION: Duration = Duration::from_secs(4);
+/// The last part of `TOAST_DURATION`, over which a toast fades out.
+const FADE_DURATION: Duration = Duration::from_millis(600);
+/// Older toasts are dropped once this many are showing.
+const MAX_TOASTS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToastLevel {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl ToastLevel {
+    fn icon(self) -> &'static str {
+        match self {
+            ToastLevel::Info => "ℹ",
+            ToastLevel::Success => "✓",
+            ToastLevel::Warning => "⚠",
+            ToastLevel::Error => "✗",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub message: String,
+    pub level: ToastLevel,
+    pub created: Instant,
+}
+
+impl Toast {
+    /// 1.0 until the fade starts, then down to 0.0 when the toast expires.
+    pub fn opacity(&self, now: Instant) -> f32 {
+        let left = TOAST_DURATION.saturating_sub(now.duration_since(self.created));
+        (left.as_secs_f32() / FADE_DURATION.as_secs_f32()).min(1.0)
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.duration_since(self.created) >= TOAST_DURATION
+    }
+}
+
+/// Short messages stacked in the bottom-right corner, each fading after a few
+/// seconds.
+#[derive(Debug, Default)]
+pub struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn notify(&mut self, level: ToastLevel, message: impl Into<String>) {
+        self.push(level, message.into(), Instant::now());
+    }
+
+    #[allow(dead_code)]
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.notify(ToastLevel::Error, message);
+    }
+
+    fn push(&mut self, level: ToastLevel, message: String, created: Instant) {
+        self.toasts.push(Toast {
+            message,
+            level,
+            created,
+        });
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+    }
+
+    /// Drop toasts that have been showing for `TOAST_DURATION`.
+    pub fn prune(&mut self, now: Instant) {
+        self.toasts.retain(|t| !t.is_expired(now));
+    }
+
+    #[allow(dead_code)]
+    pub fn messages(&self) -> impl Iterator<Item = &Toast> {
+        self.toasts.iter()
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let now = Instant::now();
+        self.prune(now);
+        if self.toasts.is_empty() {
+            return;
+        }
+
+        let mut dismissed = None;
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                for (idx, toast) in self.toasts.iter().enumerate() {
+                    let color = match toast.level {
+                        ToastLevel::Info => ui.visuals().text_color(),
+                        ToastLevel::Success => egui::Color32::from_rgb(80, 180, 80),
+                        ToastLevel::Warning => ui.visuals().warn_fg_color,
+                        ToastLevel::Error => ui.visuals().error_fg_color,
+                    };
+                    let frame = ui
+                        .scope(|ui| {
+                            ui.set_opacity(toast.opacity(now));
+                            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                                ui.set_max_width(320.0);
+                                ui.colored_label(
+                                    color,
+                                    format!("{} {}", toast.level.icon(), toast.message),
+                                );
+                            })
+                        })
+                        .inner;
+                    if frame.response.interact(egui::Sense::click()).clicked() {
+                        dismissed = Some(idx);
+                    }
+                }
+            });
+        if let Some(idx) = dismissed {
+            self.toasts.remove(idx);
+        }
+
+        // Repaint through fades, and otherwise wake up when the next one starts
+        let next_fade = self
+            .toasts
+            .iter()
+            .map(|t| (TOAST_DURATION - FADE_DURATION).saturating_sub(now.duration_since(t.created)))
+            .min();
+        if let Some(wait) = next_fade {
+            ctx.request_repaint_after(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toasts_expire_and_cap() {
+        let start = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.push(ToastLevel::Error, "first".to_string(), start);
+        toasts.push(
+            ToastLevel::Info,
+            "second".to_string(),
+            start + Duration::from_secs(2),
+        );
+
+        toasts.prune(start + Duration::from_secs(3));
+        assert_eq!(toasts.messages().count(), 2);
+        toasts.prune(start + TOAST_DURATION);
+        let left: Vec<_> = toasts.messages().map(|t| t.message.as_str()).collect();
+        assert_eq!(left, vec!["second"]);
+
+        for i in 0..MAX_TOASTS + 2 {
+            toasts.push(ToastLevel::Info, i.to_string(), start);
+        }
+        assert_eq!(toasts.messages().count(), MAX_TOASTS);
+        assert_eq!(toasts.messages().last().unwrap().message, "5");
+    }
+
+    #[test]
+    fn test_toast_fades_before_expiring() {
+        let start = Instant::now();
+        let toast = Toast {
+            message: "Synced".to_string(),
+            level: ToastLevel::Success,
+            created: start,
+        };
+        assert_eq!(toast.opacity(start), 1.0);
+        assert_eq!(toast.opacity(start + TOAST_DURATION - FADE_DURATION), 1.0);
+        let halfway = start + TOAST_DURATION - FADE_DURATION / 2;
+        assert!((toast.opacity(halfway) - 0.5).abs() < 0.01);
+        assert!(!toast.is_expired(halfway));
+        assert_eq!(toast.opacity(start + TOAST_DURATION), 0.0);
+        assert!(toast.is_expired(start + TOAST_DURATION));
+    }
+}
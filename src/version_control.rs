@@ -1,7 +1,16 @@
-use git2::{Repository, Signature, Oid};
+use git2::{Diff, DiffFormat, DiffOptions, Index, IndexAddOption, IndexEntry, IndexTime, Oid, Patch, Repository, Signature};
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use chrono::DateTime;
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::frontmatter::split_front_matter;
+use crate::note::Note;
+use crate::storage::DRAFT_EXTENSION;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Version {
@@ -15,6 +24,36 @@ pub struct VersionControl {
     repo_path: PathBuf,
 }
 
+/// Interval offered when switching to `CommitPolicy::OnInterval`.
+pub const DEFAULT_COMMIT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// When saved notes get committed to history.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum CommitPolicy {
+    /// One commit per save.
+    #[default]
+    EveryChange,
+    /// Saves accumulate and are squashed into one commit once the oldest
+    /// uncommitted change is this old.
+    OnInterval(Duration),
+    /// Only explicit snapshots commit.
+    Manual,
+}
+
+impl CommitPolicy {
+    /// Whether accumulated changes should be committed now. `pending_for` is how
+    /// long the oldest uncommitted change has been waiting, or `None` if there is
+    /// nothing to commit.
+    pub fn should_commit(&self, pending_for: Option<Duration>) -> bool {
+        match (self, pending_for) {
+            (_, None) => false,
+            (CommitPolicy::EveryChange, Some(_)) => true,
+            (CommitPolicy::OnInterval(interval), Some(waited)) => waited >= *interval,
+            (CommitPolicy::Manual, Some(_)) => false,
+        }
+    }
+}
+
 impl VersionControl {
     pub fn new(repo_path: PathBuf) -> Result<Self, String> {
         Ok(Self { repo_path })
@@ -45,6 +84,78 @@ impl VersionControl {
         index.add_path(relative_path)
             .map_err(|e| format!("Failed to add file: {}", e))?;
         
+        self.commit_index(&repo, &mut index, message)
+    }
+    
+    /// Commit notes together as they were when saved, from their bytes rather than
+    /// the files, so a save that lands before the commit runs can't change what's
+    /// committed or make reading the file fail. Notes without content are committed
+    /// as deleted.
+    pub fn commit_snapshots(&self, notes: &[NoteSnapshot], message: &str) -> Result<String, String> {
+        let repo = Repository::open(&self.repo_path)
+            .map_err(|e| format!("Failed to open repo: {}", e))?;
+        
+        let mut index = repo.index()
+            .map_err(|e| format!("Failed to get index: {}", e))?;
+        
+        for (file_path, content) in notes {
+            let relative_path = file_path
+                .strip_prefix(&self.repo_path)
+                .map_err(|e| format!("Path error: {}", e))?;
+            
+            match content {
+                Some(bytes) => {
+                    let entry = IndexEntry {
+                        ctime: IndexTime::new(0, 0),
+                        mtime: IndexTime::new(0, 0),
+                        dev: 0,
+                        ino: 0,
+                        mode: 0o100644,
+                        uid: 0,
+                        gid: 0,
+                        file_size: bytes.len() as u32,
+                        id: Oid::zero(),
+                        flags: 0,
+                        flags_extended: 0,
+                        // Git paths always use forward slashes
+                        path: relative_path.to_string_lossy().replace('\\', "/").into_bytes(),
+                    };
+                    index.add_frombuffer(&entry, bytes)
+                        .map_err(|e| format!("Failed to add file: {}", e))?;
+                }
+                None => {
+                    index.remove_path(relative_path)
+                        .map_err(|e| format!("Failed to remove file: {}", e))?;
+                }
+            }
+        }
+        
+        self.commit_index(&repo, &mut index, message)
+    }
+    
+    /// Stage every new, modified and deleted file under the repo (honouring
+    /// `.gitignore`, and leaving out unsaved drafts) and record them in a single commit.
+    pub fn commit_all(&self, message: &str) -> Result<String, String> {
+        let repo = Repository::open(&self.repo_path)
+            .map_err(|e| format!("Failed to open repo: {}", e))?;
+        
+        let mut index = repo.index()
+            .map_err(|e| format!("Failed to get index: {}", e))?;
+        
+        let mut skip_drafts = |path: &Path, _: &[u8]| {
+            i32::from(path.extension() == Some(OsStr::new(DRAFT_EXTENSION)))
+        };
+        index.add_all(["*"], IndexAddOption::DEFAULT, Some(&mut skip_drafts))
+            .map_err(|e| format!("Failed to add files: {}", e))?;
+        
+        // add_all doesn't pick up removals
+        index.update_all(["*"], None)
+            .map_err(|e| format!("Failed to stage deletions: {}", e))?;
+        
+        self.commit_index(&repo, &mut index, message)
+    }
+    
+    fn commit_index(&self, repo: &Repository, index: &mut Index, message: &str) -> Result<String, String> {
         index.write()
             .map_err(|e| format!("Failed to write index: {}", e))?;
         
@@ -162,7 +273,27 @@ impl VersionControl {
         Ok(content.to_string())
     }
     
-    pub fn get_diff(&self, commit_id1: &str, commit_id2: &str) -> Result<String, String> {
+    /// Roll a note back to the file as it was at `commit_id`: the old file is written to disk,
+    /// the in-memory note is updated, and the restoration is committed as a new version.
+    /// Returns the new commit's id.
+    pub fn restore_note(&self, note: &mut Note, commit_id: &str) -> Result<String, String> {
+        let file_path = PathBuf::from(&note.file_path);
+        let raw = self.restore_version(&file_path, commit_id)?;
+        
+        fs::write(&file_path, &raw)
+            .map_err(|e| format!("Failed to write restored note: {}", e))?;
+        
+        let (front_matter, content) = split_front_matter(&raw);
+        note.content = content.to_string();
+        note.front_matter = front_matter;
+        note.update_timestamp();
+        
+        let short_id = &commit_id[..commit_id.len().min(7)];
+        self.commit_note(&file_path, &format!("Restored: {} to {}", note.title, short_id))
+    }
+    
+    /// Unified diff of `file_path` between two commits (older first).
+    pub fn get_diff(&self, file_path: &Path, commit_id1: &str, commit_id2: &str) -> Result<String, String> {
         let repo = Repository::open(&self.repo_path)
             .map_err(|e| format!("Failed to open repo: {}", e))?;
         
@@ -181,18 +312,439 @@ impl VersionControl {
         let tree2 = commit2.tree()
             .map_err(|e| format!("Failed to get tree 2: {}", e))?;
         
-        let diff = repo.diff_tree_to_tree(Some(&tree1), Some(&tree2), None)
+        let relative_path = file_path
+            .strip_prefix(&self.repo_path)
+            .map_err(|e| format!("Path error: {}", e))?;
+        
+        let mut options = DiffOptions::new();
+        options.pathspec(relative_path);
+        
+        let diff = repo.diff_tree_to_tree(Some(&tree1), Some(&tree2), Some(&mut options))
             .map_err(|e| format!("Failed to create diff: {}", e))?;
         
-        // Convert diff to string (simplified)
-        let stats = diff.stats()
-            .map_err(|e| format!("Failed to get stats: {}", e))?;
+        patch_text(&diff)
+    }
+    
+    /// Unified diff from the file as committed at `commit_id` to an in-memory buffer,
+    /// e.g. the editor's unsaved content. Lets the UI preview a revert before committing it.
+    pub fn diff_against_working(&self, file_path: &Path, commit_id: &str, current_content: &str) -> Result<String, String> {
+        let repo = Repository::open(&self.repo_path)
+            .map_err(|e| format!("Failed to open repo: {}", e))?;
+        
+        let oid = Oid::from_str(commit_id)
+            .map_err(|e| format!("Invalid commit ID: {}", e))?;
+        
+        let commit = repo.find_commit(oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+        
+        let tree = commit.tree()
+            .map_err(|e| format!("Failed to get tree: {}", e))?;
+        
+        let relative_path = file_path
+            .strip_prefix(&self.repo_path)
+            .map_err(|e| format!("Path error: {}", e))?;
+        
+        let entry = tree.get_path(relative_path)
+            .map_err(|e| format!("File not found in commit: {}", e))?;
+        
+        let blob = repo.find_blob(entry.id())
+            .map_err(|e| format!("Failed to get blob: {}", e))?;
+        
+        let path = relative_path.to_string_lossy();
+        let mut patch = Patch::from_blob_and_buffer(
+            &blob,
+            Some(Path::new(path.as_ref())),
+            current_content.as_bytes(),
+            Some(Path::new(path.as_ref())),
+            None,
+        )
+        .map_err(|e| format!("Failed to create diff: {}", e))?;
+        
+        let buf = patch.to_buf()
+            .map_err(|e| format!("Failed to print diff: {}", e))?;
+        
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+    
+    /// A Markdown changelog of `file_path`: one entry per commit that changed it,
+    /// newest first, with its date, message, author and how many lines changed.
+    pub fn export_history_markdown(&self, file_path: &Path) -> Result<String, String> {
+        let versions = self.get_file_history(file_path)?;
+        let title = file_path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        
+        // Walk oldest first so each version is compared with the one before it
+        let mut entries = Vec::new();
+        let mut previous: Option<String> = None;
+        for version in versions.iter().rev() {
+            let content = self.restore_version(file_path, &version.commit_id)?;
+            let summary = match &previous {
+                // Commits that only touched other notes
+                Some(old) if *old == content => continue,
+                Some(old) => change_summary(file_path, old, &content)?,
+                None => format!("Created with {}.", plural(content.lines().count(), "line")),
+            };
+            
+            let short_id = &version.commit_id[..version.commit_id.len().min(7)];
+            entries.push(format!(
+                "## {} — {}\n\n*{} · {}*\n\n{}\n",
+                version.timestamp,
+                version.message.trim(),
+                version.author,
+                short_id,
+                summary
+            ));
+            previous = Some(content);
+        }
+        entries.reverse();
+        
+        Ok(format!("# History of {}\n\n{}", title, entries.join("\n")))
+    }
+}
+
+/// "2 lines added, 1 removed." for the change from `old` to `new`.
+fn change_summary(file_path: &Path, old: &str, new: &str) -> Result<String, String> {
+    let patch = Patch::from_buffers(old.as_bytes(), Some(file_path), new.as_bytes(), Some(file_path), None)
+        .map_err(|e| format!("Failed to create diff: {}", e))?;
+    let (_, added, removed) = patch.line_stats()
+        .map_err(|e| format!("Failed to count changes: {}", e))?;
+    
+    Ok(match (added, removed) {
+        (0, 0) => "Only whitespace or line endings changed.".to_string(),
+        (added, 0) => format!("{} added.", plural(added, "line")),
+        (0, removed) => format!("{} removed.", plural(removed, "line")),
+        (added, removed) => format!("{} added, {} removed.", plural(added, "line"), removed),
+    })
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("1 {}", word)
+    } else {
+        format!("{} {}s", count, word)
+    }
+}
+
+/// A note to commit and the bytes it was saved with, or `None` if it was deleted.
+pub type NoteSnapshot = (PathBuf, Option<Vec<u8>>);
+
+/// Notes carry the content they were saved with: the worker may run a job well
+/// after it was queued, when the file has been saved again.
+pub enum CommitJob {
+    Note { file_path: PathBuf, content: Vec<u8>, message: String },
+    Notes { notes: Vec<NoteSnapshot>, message: String },
+    All { message: String },
+}
+
+/// Performs commits on a background thread so git I/O never blocks a frame.
+/// Jobs run one at a time in the order they were queued; each produces one
+/// result on the results channel.
+pub struct CommitWorker {
+    jobs: Option<Sender<CommitJob>>,
+    results: Receiver<Result<String, String>>,
+    handle: Option<JoinHandle<()>>,
+    pending: usize,
+}
+
+impl CommitWorker {
+    pub fn spawn(vc: VersionControl) -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<CommitJob>();
+        let (result_tx, result_rx) = mpsc::channel();
+        
+        let handle = thread::spawn(move || {
+            for job in job_rx {
+                let result = match job {
+                    CommitJob::Note { file_path, content, message } => {
+                        vc.commit_snapshots(&[(file_path, Some(content))], &message)
+                    }
+                    CommitJob::Notes { notes, message } => vc.commit_snapshots(&notes, &message),
+                    CommitJob::All { message } => vc.commit_all(&message),
+                };
+                if result_tx.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+        
+        Self {
+            jobs: Some(job_tx),
+            results: result_rx,
+            handle: Some(handle),
+            pending: 0,
+        }
+    }
+    
+    pub fn queue(&mut self, job: CommitJob) {
+        if let Some(jobs) = &self.jobs {
+            if jobs.send(job).is_ok() {
+                self.pending += 1;
+            }
+        }
+    }
+    
+    /// Number of queued commits that haven't reported back yet.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+    
+    /// Collect the results of any commits finished since the last call, without blocking.
+    pub fn poll(&mut self) -> Vec<Result<String, String>> {
+        let mut finished = Vec::new();
+        loop {
+            match self.results.try_recv() {
+                Ok(result) => finished.push(result),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.pending > finished.len() {
+                        finished.push(Err("Commit worker stopped".to_string()));
+                    }
+                    self.pending = 0;
+                    return finished;
+                }
+            }
+        }
+        self.pending -= finished.len();
+        finished
+    }
+    
+    /// Finish every queued commit, then stop the worker.
+    #[allow(dead_code)]
+    pub fn shutdown(mut self) -> Vec<Result<String, String>> {
+        self.stop();
+        self.results.try_iter().collect()
+    }
+    
+    fn stop(&mut self) {
+        // Closing the channel lets the worker drain its queue and exit
+        self.jobs.take();
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl Drop for CommitWorker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Unified diff between two in-memory versions of `file_path`, e.g. what's on disk
+/// and the editor's unsaved text. No repository is involved.
+pub fn diff_text(file_path: &Path, old: &str, new: &str) -> Result<String, String> {
+    let mut patch = Patch::from_buffers(old.as_bytes(), Some(file_path), new.as_bytes(), Some(file_path), None)
+        .map_err(|e| format!("Failed to create diff: {}", e))?;
+    
+    let buf = patch.to_buf()
+        .map_err(|e| format!("Failed to print diff: {}", e))?;
+    
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Render a diff as unified patch text, prefixing each line with its `+`/`-`/` ` origin.
+fn patch_text(diff: &Diff) -> Result<String, String> {
+    let mut text = String::new();
+    diff.print(DiffFormat::Patch, |_delta, _hunk, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            text.push(line.origin());
+        }
+        text.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })
+    .map_err(|e| format!("Failed to print diff: {}", e))?;
+    
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    
+    fn temp_repo() -> (tempfile::TempDir, VersionControl) {
+        let dir = tempfile::tempdir().unwrap();
+        let vc = VersionControl::new(dir.path().to_path_buf()).unwrap();
+        vc.init().unwrap();
+        (dir, vc)
+    }
+    
+    #[test]
+    fn test_diff_contains_changed_lines() {
+        let (dir, vc) = temp_repo();
+        let file = dir.path().join("note.md");
+        
+        fs::write(&file, "unchanged\nold line\n").unwrap();
+        let first = vc.commit_note(&file, "first").unwrap();
+        fs::write(&file, "unchanged\nnew line\n").unwrap();
+        let second = vc.commit_note(&file, "second").unwrap();
+        
+        let diff = vc.get_diff(&file, &first, &second).unwrap();
+        assert!(diff.contains("-old line"));
+        assert!(diff.contains("+new line"));
+        assert!(diff.contains(" unchanged"));
+    }
+    
+    #[test]
+    fn test_export_history_timeline() {
+        let (dir, vc) = temp_repo();
+        let file = dir.path().join("Plan.md");
+        let other = dir.path().join("Other.md");
+        
+        fs::write(&file, "# Plan\nStep one\n").unwrap();
+        vc.commit_note(&file, "Draft plan").unwrap();
+        fs::write(&other, "unrelated").unwrap();
+        vc.commit_note(&other, "Other note").unwrap();
+        fs::write(&file, "# Plan\nStep one, revised\nStep two\n").unwrap();
+        let second = vc.commit_note(&file, "Add step two").unwrap();
+        
+        let timeline = vc.export_history_markdown(&file).unwrap();
+        assert!(timeline.starts_with("# History of Plan\n"));
+        assert_eq!(timeline.matches("\n## ").count(), 2);
+        
+        let newest = timeline.find("Add step two").unwrap();
+        let oldest = timeline.find("Draft plan").unwrap();
+        assert!(newest < oldest);
+        assert!(timeline.contains("2 lines added, 1 removed."));
+        assert!(timeline.contains("Created with 2 lines."));
+        assert!(timeline.contains(&second[..7]));
+        assert!(!timeline.contains("Other note"));
+    }
+    
+    #[test]
+    fn test_commit_all_includes_every_note() {
+        let (dir, vc) = temp_repo();
+        fs::create_dir(dir.path().join("Work")).unwrap();
+        fs::write(dir.path().join("a.md"), "a").unwrap();
+        fs::write(dir.path().join("b.md"), "b").unwrap();
+        fs::write(dir.path().join("Work").join("c.md"), "c").unwrap();
+        fs::write(dir.path().join("Work").join("c.draft"), "c, unsaved").unwrap();
+        
+        let commit_id = vc.commit_all("Snapshot").unwrap();
+        
+        let repo = Repository::open(dir.path()).unwrap();
+        let commit = repo.find_commit(Oid::from_str(&commit_id).unwrap()).unwrap();
+        let tree = commit.tree().unwrap();
+        assert!(tree.get_path(Path::new("a.md")).is_ok());
+        assert!(tree.get_path(Path::new("b.md")).is_ok());
+        assert!(tree.get_path(Path::new("Work/c.md")).is_ok());
+        assert!(tree.get_path(Path::new("Work/c.draft")).is_err());
+        assert_eq!(commit.message(), Some("Snapshot"));
+        
+        fs::remove_file(dir.path().join("b.md")).unwrap();
+        let commit_id = vc.commit_all("Removed b").unwrap();
+        let tree = repo.find_commit(Oid::from_str(&commit_id).unwrap()).unwrap().tree().unwrap();
+        assert!(tree.get_path(Path::new("b.md")).is_err());
+    }
+    
+    #[test]
+    fn test_interval_policy() {
+        let policy = CommitPolicy::OnInterval(Duration::from_secs(300));
+        assert!(!policy.should_commit(None));
+        assert!(!policy.should_commit(Some(Duration::from_secs(10))));
+        assert!(!policy.should_commit(Some(Duration::from_secs(299))));
+        assert!(policy.should_commit(Some(Duration::from_secs(300))));
+        assert!(policy.should_commit(Some(Duration::from_secs(3600))));
+        
+        assert!(CommitPolicy::EveryChange.should_commit(Some(Duration::ZERO)));
+        assert!(!CommitPolicy::EveryChange.should_commit(None));
+        assert!(!CommitPolicy::Manual.should_commit(Some(Duration::from_secs(3600))));
+    }
+    
+    #[test]
+    fn test_commit_notes_squashes_changes() {
+        let (dir, vc) = temp_repo();
+        let a = dir.path().join("a.md");
+        let b = dir.path().join("b.md");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        fs::write(dir.path().join("untouched.md"), "c").unwrap();
+        vc.commit_snapshots(&[(a.clone(), Some(b"a".to_vec())), (b.clone(), Some(b"b".to_vec()))], "first").unwrap();
+        
+        // The file has moved on since the save being committed
+        fs::write(&a, "a, saved again").unwrap();
+        fs::remove_file(&b).unwrap();
+        let commit_id = vc.commit_snapshots(&[(a.clone(), Some(b"a again".to_vec())), (b.clone(), None)], "Updated 2 notes").unwrap();
+        
+        let repo = Repository::open(dir.path()).unwrap();
+        let commit = repo.find_commit(Oid::from_str(&commit_id).unwrap()).unwrap();
+        let tree = commit.tree().unwrap();
+        assert!(tree.get_path(Path::new("a.md")).is_ok());
+        assert!(tree.get_path(Path::new("b.md")).is_err());
+        assert!(tree.get_path(Path::new("untouched.md")).is_err());
+        assert_eq!(commit.parent_count(), 1);
+    }
+    
+    #[test]
+    fn test_commit_worker_keeps_queue_order() {
+        let (dir, vc) = temp_repo();
+        let file = dir.path().join("note.md");
+        let mut worker = CommitWorker::spawn(VersionControl::new(dir.path().to_path_buf()).unwrap());
+        
+        // Saving the same note over and over while the worker commits earlier saves
+        for i in 0..5 {
+            let content = format!("revision {}", i);
+            fs::write(&file, &content).unwrap();
+            worker.queue(CommitJob::Note {
+                file_path: file.clone(),
+                content: content.into_bytes(),
+                message: format!("save {}", i),
+            });
+        }
+        assert_eq!(worker.pending(), 5);
+        
+        let results = worker.shutdown();
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|r| r.is_ok()));
+        
+        // Each commit has the content of its own save
+        let history = vc.get_file_history(&file).unwrap();
+        let messages: Vec<_> = history.iter().map(|v| v.message.as_str()).collect();
+        assert_eq!(messages, vec!["save 4", "save 3", "save 2", "save 1", "save 0"]);
+        for (i, version) in history.iter().rev().enumerate() {
+            assert_eq!(vc.restore_version(&file, &version.commit_id).unwrap(), format!("revision {}", i));
+        }
+    }
+    
+    #[test]
+    fn test_diff_against_working_buffer() {
+        let (dir, vc) = temp_repo();
+        let file = dir.path().join("note.md");
+        
+        fs::write(&file, "line one\nline two\n").unwrap();
+        let commit = vc.commit_note(&file, "first").unwrap();
+        
+        let diff = vc
+            .diff_against_working(&file, &commit, "line one\nline 2 edited\n")
+            .unwrap();
+        assert!(diff.contains("-line two"));
+        assert!(diff.contains("+line 2 edited"));
+        
+        let unchanged = vc
+            .diff_against_working(&file, &commit, "line one\nline two\n")
+            .unwrap();
+        assert!(!unchanged.contains("+line"));
+    }
+    
+    #[test]
+    fn test_restore_note_to_older_version() {
+        let (dir, vc) = temp_repo();
+        let file = dir.path().join("note.md");
+        let mut note = Note::new("note".to_string(), file.to_string_lossy().to_string());
+        
+        fs::write(&file, "original text").unwrap();
+        let first = vc.commit_note(&file, "first").unwrap();
+        fs::write(&file, "rewritten text").unwrap();
+        vc.commit_note(&file, "second").unwrap();
+        note.content = "rewritten text".to_string();
+        
+        vc.restore_note(&mut note, &first).unwrap();
+        
+        assert_eq!(fs::read_to_string(&file).unwrap(), "original text");
+        assert_eq!(note.content, "original text");
         
-        Ok(format!(
-            "Files changed: {}, Insertions: {}, Deletions: {}",
-            stats.files_changed(),
-            stats.insertions(),
-            stats.deletions()
-        ))
+        let history = vc.get_file_history(&file).unwrap();
+        assert_eq!(history.len(), 3);
+        assert!(history[0].message.starts_with("Restored: note"));
     }
 }
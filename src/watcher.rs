@@ -0,0 +1,72 @@
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// How long the vault has to stay quiet before queued changes are handed out.
+/// Editors and git write a file in several steps; this folds them into one reload.
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the notes directory for changes made outside the app.
+pub struct NoteWatcher {
+    // Kept alive for as long as we want events
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    pending: HashSet<PathBuf>,
+    last_event: Option<Instant>,
+}
+
+impl NoteWatcher {
+    /// Start watching `dir` recursively. `on_event` runs on the watcher's thread after
+    /// each raw event, e.g. to wake the UI so it can poll.
+    pub fn new(dir: &Path, on_event: impl Fn() + Send + 'static) -> Result<Self, String> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            if tx.send(event).is_ok() {
+                on_event();
+            }
+        })
+        .map_err(|e| format!("Failed to create watcher: {}", e))?;
+
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+
+        Ok(Self {
+            _watcher: watcher,
+            events: rx,
+            pending: HashSet::new(),
+            last_event: None,
+        })
+    }
+
+    /// Paths touched since the last batch, once no new event has arrived for
+    /// [`DEBOUNCE`]. Returns an empty list while changes are still coming in.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        for event in self.events.try_iter().flatten() {
+            // The version-control repo lives inside the vault and churns on every commit
+            let paths = event
+                .paths
+                .into_iter()
+                .filter(|p| !p.components().any(|c| c.as_os_str() == ".git"));
+            for path in paths {
+                self.pending.insert(path);
+                self.last_event = Some(Instant::now());
+            }
+        }
+
+        match self.last_event {
+            Some(last) if last.elapsed() >= DEBOUNCE => {
+                self.last_event = None;
+                self.pending.drain().collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether changes are queued and waiting out the debounce.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}